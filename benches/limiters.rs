@@ -0,0 +1,186 @@
+//! Compares the `try_consume` throughput of the limiters, on one thread and contended by
+//! several tasks on a multi-threaded runtime.
+//!
+//! Run it with `cargo bench --bench limiters`. The limits are never reached, so every call is
+//! an admission. `try_consume` never sleeps, so the calls are driven straight from an async
+//! loop without any timers involved.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use limitr::bucket::{LeakyBucket, TokenBucket};
+use limitr::window::{FixedWindowCounter, SlidingWindowCounter};
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::runtime::{Builder, Runtime};
+use tokio::time::Duration;
+
+/// Tasks sharing a limiter in the contended benchmarks
+const TASKS: u64 = 4;
+
+/// Measures `iters` calls of `$consume`, split evenly across `$tasks` tasks that each use their
+/// own clone `$l` of the limiter built by `$limiter`.
+macro_rules! measure {
+    ($runtime:expr, $tasks:expr, $iters:expr, $limiter:expr, |$l:ident| $consume:expr) => {
+        $runtime.block_on(async {
+            // Built on the runtime, so limiters with a coarse clock can spawn its refresh task
+            let limiter = $limiter;
+            let start = Instant::now();
+            let tasks: Vec<_> = (0..$tasks)
+                .map(|_| {
+                    #[allow(unused_mut)]
+                    let mut $l = limiter.clone();
+                    tokio::spawn(async move {
+                        for _ in 0..$iters / $tasks {
+                            black_box($consume);
+                        }
+                    })
+                })
+                .collect();
+            for task in tasks {
+                task.await.unwrap();
+            }
+            start.elapsed()
+        })
+    };
+}
+
+fn single_threaded(c: &mut Criterion) {
+    let runtime = Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("failed to build runtime");
+    let window = Duration::from_secs(60);
+    let mut group = c.benchmark_group("single_threaded");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("token_bucket", |b| {
+        b.iter_custom(|iters| {
+            measure!(runtime, 1, iters, TokenBucket::new(u64::MAX, 1), |l| l
+                .try_consume(1)
+                .await)
+        })
+    });
+    group.bench_function("leaky_bucket", |b| {
+        b.iter_custom(|iters| {
+            measure!(runtime, 1, iters, LeakyBucket::new(usize::MAX, 1), |l| l
+                .try_consume()
+                .await)
+        })
+    });
+    group.bench_function("fixed_window", |b| {
+        b.iter_custom(|iters| {
+            measure!(
+                runtime,
+                1,
+                iters,
+                FixedWindowCounter::shared(u32::MAX, window),
+                |l| l.try_consume().await
+            )
+        })
+    });
+    group.bench_function("fixed_window_coarse_clock", |b| {
+        b.iter_custom(|iters| {
+            measure!(
+                runtime,
+                1,
+                iters,
+                Arc::new(
+                    FixedWindowCounter::new(u32::MAX, window)
+                        .with_coarse_clock(Duration::from_millis(1))
+                ),
+                |l| l.try_consume().await
+            )
+        })
+    });
+    group.bench_function("sliding_window", |b| {
+        b.iter_custom(|iters| {
+            measure!(
+                runtime,
+                1,
+                iters,
+                Arc::new(SlidingWindowCounter::new(u32::MAX, window).with_max_entries(1024)),
+                |l| l.try_consume().await
+            )
+        })
+    });
+    group.bench_function("sliding_window_coarse_clock", |b| {
+        b.iter_custom(|iters| {
+            measure!(
+                runtime,
+                1,
+                iters,
+                Arc::new(
+                    SlidingWindowCounter::new(u32::MAX, window)
+                        .with_max_entries(1024)
+                        .with_coarse_clock(Duration::from_millis(1))
+                ),
+                |l| l.try_consume().await
+            )
+        })
+    });
+    group.finish();
+}
+
+fn contended(c: &mut Criterion) {
+    let runtime = multi_threaded();
+    let window = Duration::from_secs(60);
+    let mut group = c.benchmark_group("contended");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("token_bucket", |b| {
+        b.iter_custom(|iters| {
+            measure!(
+                runtime,
+                TASKS,
+                iters,
+                TokenBucket::shared(u64::MAX, 1),
+                |l| l.try_consume(1).await
+            )
+        })
+    });
+    group.bench_function("leaky_bucket", |b| {
+        b.iter_custom(|iters| {
+            measure!(
+                runtime,
+                TASKS,
+                iters,
+                LeakyBucket::shared(usize::MAX, 1),
+                |l| l.try_consume().await
+            )
+        })
+    });
+    group.bench_function("fixed_window", |b| {
+        b.iter_custom(|iters| {
+            measure!(
+                runtime,
+                TASKS,
+                iters,
+                FixedWindowCounter::shared(u32::MAX, window),
+                |l| l.try_consume().await
+            )
+        })
+    });
+    group.bench_function("sliding_window", |b| {
+        b.iter_custom(|iters| {
+            measure!(
+                runtime,
+                TASKS,
+                iters,
+                Arc::new(SlidingWindowCounter::new(u32::MAX, window).with_max_entries(1024)),
+                |l| l.try_consume().await
+            )
+        })
+    });
+    group.finish();
+}
+
+fn multi_threaded() -> Runtime {
+    Builder::new_multi_thread()
+        .worker_threads(TASKS as usize)
+        .enable_time()
+        .build()
+        .expect("failed to build runtime")
+}
+
+criterion_group!(benches, single_threaded, contended);
+criterion_main!(benches);
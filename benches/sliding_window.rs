@@ -0,0 +1,87 @@
+//! Measures the hot path of `SlidingWindowCounter`.
+//!
+//! Run it with `cargo bench --bench sliding_window`.
+
+use limitr::window::SlidingWindowCounter;
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::time::Duration;
+
+const ITERATIONS: u32 = 1_000_000;
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("failed to build runtime");
+
+    // Built outside of the runtime, the clock has no refresh task and reads the precise clock on
+    // every call, which counts the reads of a counter without a coarse clock
+    let precise = SlidingWindowCounter::new(u32::MAX, Duration::from_secs(60))
+        .with_coarse_clock(Duration::from_millis(1));
+
+    runtime.block_on(async {
+        // A limit that is never reached measures admissions, a reached one denials
+        for (name, limit) in [("admitted", u32::MAX), ("denied", 1)] {
+            let limiter =
+                SlidingWindowCounter::new(limit, Duration::from_secs(60)).with_max_entries(1024);
+            report(name, &limiter).await;
+        }
+
+        // Sharing the counter through an `Arc` must not add any cost per call
+        let shared = Arc::new(SlidingWindowCounter::new(u32::MAX, Duration::from_secs(60)));
+        report("shared", &shared).await;
+
+        report_eviction().await;
+        report_coarse_clock(&precise).await;
+    });
+}
+
+/// Compares the precise clock to a coarse clock refreshed every 1ms.
+async fn report_coarse_clock(precise: &SlidingWindowCounter) {
+    report("precise clock", precise).await;
+    let coarse = SlidingWindowCounter::new(u32::MAX, Duration::from_secs(60))
+        .with_coarse_clock(Duration::from_millis(1));
+    report("coarse clock", &coarse).await;
+
+    let reads = |limiter: &SlidingWindowCounter| limiter.coarse_clock().map_or(0, |c| c.reads());
+    println!(
+        "clock reads: {} precise vs {} coarse for {} calls",
+        reads(precise),
+        reads(&coarse),
+        ITERATIONS
+    );
+}
+
+/// Measures the worst call, the first one after a backlog of 100k requests has expired.
+async fn report_eviction() {
+    let window = Duration::from_millis(10);
+    let mut worst = Duration::ZERO;
+    for _ in 0..10 {
+        let limiter = SlidingWindowCounter::new(u32::MAX, window);
+        for _ in 0..100_000 {
+            black_box(limiter.try_consume().await);
+        }
+        tokio::time::sleep(window * 2).await;
+
+        let start = Instant::now();
+        black_box(limiter.try_consume().await);
+        worst = worst.max(start.elapsed());
+    }
+    println!("try_consume (after 100k expired): {:?} worst call", worst);
+}
+
+async fn report(name: &str, limiter: &SlidingWindowCounter) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(limiter.try_consume().await);
+    }
+
+    let elapsed = start.elapsed();
+    println!(
+        "try_consume ({}): {:?} per call",
+        name,
+        elapsed / ITERATIONS
+    );
+}
@@ -0,0 +1,89 @@
+//! Example of limiting an axum server per client IP with a `KeyedLimiter`.
+//!
+//! Every client may send 5 requests per 10 seconds. Further requests are answered with
+//! `429 Too Many Requests` and a `Retry-After` header, and every response carries the
+//! `X-RateLimit-*` headers of the client's limiter.
+//!
+//! Run it with `cargo run --example axum_keyed` and try `curl -i http://127.0.0.1:3000`.
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use limitr::combinators::KeyedLimiter;
+use limitr::window::FixedWindowCounter;
+use limitr::Decision;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::time::Duration;
+
+const LIMIT: u32 = 5;
+const WINDOW: Duration = Duration::from_secs(10);
+
+type Limiter = KeyedLimiter<IpAddr, FixedWindowCounter>;
+
+#[tokio::main]
+async fn main() {
+    let limiter: Arc<Limiter> =
+        Arc::new(KeyedLimiter::new(|| FixedWindowCounter::new(LIMIT, WINDOW)));
+
+    // Forget clients that have been quiet for a whole window, they would start over anyway
+    let _gc = limiter.spawn_gc(Duration::from_secs(60), WINDOW);
+
+    let app = Router::new()
+        .route("/", get(|| async { "Hello, world!" }))
+        .layer(middleware::from_fn_with_state(limiter, rate_limit));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
+        .await
+        .expect("failed to bind");
+    println!("Listening on http://{}", listener.local_addr().unwrap());
+
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .expect("server error");
+}
+
+async fn rate_limit(
+    State(limiter): State<Arc<Limiter>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let decision = limiter.check(addr.ip(), 1).await;
+
+    let mut response = if decision.is_allowed() {
+        next.run(request).await
+    } else {
+        let mut response = StatusCode::TOO_MANY_REQUESTS.into_response();
+        if let Some(retry_after) = decision.retry_after() {
+            response
+                .headers_mut()
+                .insert("retry-after", seconds(retry_after));
+        }
+        response
+    };
+
+    insert_rate_limit_headers(response.headers_mut(), &decision);
+    response
+}
+
+fn insert_rate_limit_headers(headers: &mut HeaderMap, decision: &Decision) {
+    headers.insert("x-ratelimit-limit", decision.limit().into());
+    headers.insert("x-ratelimit-remaining", decision.remaining().into());
+    headers.insert("x-ratelimit-reset", seconds(decision.reset_in()));
+}
+
+/// Formats a duration as whole seconds, rounded up so clients never retry too early.
+fn seconds(duration: Duration) -> HeaderValue {
+    let mut seconds = duration.as_secs();
+    if duration.subsec_nanos() > 0 {
+        seconds += 1;
+    }
+    seconds.into()
+}
@@ -0,0 +1,43 @@
+//! Example of throttling writes to a socket with the `ByteRateLimiter`.
+
+use limitr::bucket::ByteRateLimiter;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::Instant;
+
+/// Bandwidth granted to the connection
+const BYTES_PER_SECOND: u64 = 16 * 1024;
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    // A receiver that reads until the connection is closed
+    let receiver = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await?;
+        let mut received = Vec::new();
+        socket.read_to_end(&mut received).await?;
+        Ok::<_, std::io::Error>(received.len())
+    });
+
+    let mut socket = TcpStream::connect(addr).await?;
+    let mut limiter = ByteRateLimiter::bytes_per_second(BYTES_PER_SECOND);
+    let start = Instant::now();
+
+    // 64 KiB in 4 KiB writes. The first second's worth goes out at once, the rest is paced.
+    let chunk = [0u8; 4 * 1024];
+    for i in 0..16 {
+        limiter
+            .send(&chunk)
+            .await
+            .expect("the burst size is not zero");
+        socket.write_all(&chunk).await?;
+        println!("Write {} sent after {:?}", i + 1, start.elapsed());
+    }
+    socket.shutdown().await?;
+
+    let received = receiver.await.expect("the receiver does not panic")?;
+    println!("Received {} bytes in {:?}", received, start.elapsed());
+    Ok(())
+}
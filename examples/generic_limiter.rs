@@ -0,0 +1,73 @@
+//! Example of driving every limiter of the crate through the common `RateLimiter` trait, and
+//! of sharing a limiter picked at runtime through `SharedRateLimiter`
+
+use limitr::bucket::{LeakyBucket, SharedLeakyBucket, TokenBucket};
+use limitr::window::{FixedWindowCounter, SlidingWindowCounter};
+use limitr::{RateLimiter, SharedRateLimiter};
+use std::sync::Arc;
+use tokio::time::{Duration, Instant};
+
+/// Sends a batch of requests, waiting for the limiter whenever it is full.
+async fn send_batch(name: &str, limiter: &mut impl RateLimiter) {
+    let start = Instant::now();
+    for i in 0..8 {
+        if limiter.try_acquire(1).await {
+            println!("{}: request {} sent right away", name, i + 1);
+            continue;
+        }
+        match limiter.acquire(1).await {
+            Ok(()) => println!(
+                "{}: request {} sent after {:?}",
+                name,
+                i + 1,
+                start.elapsed()
+            ),
+            Err(e) => println!("{}: request {} dropped: {}", name, i + 1, e),
+        }
+    }
+}
+
+/// Picks the algorithm by name, e.g. from a configuration file, as one limiter for all tasks.
+fn limiter_from_config(algorithm: &str) -> Arc<dyn SharedRateLimiter> {
+    match algorithm {
+        "token" => Arc::new(TokenBucket::shared(5, 2)),
+        "leaky" => Arc::new(SharedLeakyBucket::new(5, 2)),
+        "fixed" => Arc::new(FixedWindowCounter::new(5, Duration::from_secs(1))),
+        _ => Arc::new(SlidingWindowCounter::new(5, Duration::from_secs(1))),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    send_batch("Token Bucket", &mut TokenBucket::new(5, 2)).await;
+    send_batch("Leaky Bucket", &mut LeakyBucket::new(5, 2)).await;
+    send_batch(
+        "Fixed Window",
+        &mut FixedWindowCounter::new(5, Duration::from_secs(1)),
+    )
+    .await;
+    send_batch(
+        "Sliding Window",
+        &mut SlidingWindowCounter::new(5, Duration::from_secs(1)),
+    )
+    .await;
+
+    // A shared limiter tells tasks how long to back off without charging them
+    let limiter = limiter_from_config("token");
+    let tasks: Vec<_> = (0..4)
+        .map(|task| {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move {
+                if !limiter.try_acquire(2).await {
+                    let wait = limiter.retry_after(2).await.unwrap_or(Duration::MAX);
+                    println!("Shared limiter: task {} backs off for {:?}", task, wait);
+                    return;
+                }
+                println!("Shared limiter: task {} admitted", task);
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
@@ -7,10 +7,10 @@ use tokio::time::{sleep, Duration};
 #[tokio::main]
 async fn main() {
     let mut bucket = LeakyBucket::new(10, 2); // Capacity of 10, leak rate of 2 tokens per second
-    let mut rng = rand::thread_rng();
+    let mut rng = rand::rng();
 
     for i in 0..60 {
-        let sleep_duration = rng.gen_range(10..=400);
+        let sleep_duration = rng.random_range(10..=400);
         if bucket.try_consume().await {
             println!("Leaky Bucket Example: Request {} succeeded.", i + 1);
         } else {
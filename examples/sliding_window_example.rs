@@ -1,22 +1,18 @@
 use limitr::window::SlidingWindowCounter;
 use std::sync::Arc;
-use tokio::sync::Mutex;
 use tokio::time::Duration;
 
 #[tokio::main]
 async fn main() {
     // Create a rate limiter that allows 5 requests per 10-second window
-    let limiter = Arc::new(Mutex::new(SlidingWindowCounter::new(
-        5,
-        Duration::from_secs(10),
-    )));
+    let limiter = SlidingWindowCounter::shared(5, Duration::from_secs(10));
 
     // Simulate multiple requests being made
     for i in 1..=10 {
+        // The counter locks internally, so the `Arc` needs no `Mutex` around it
         let limiter_clone = Arc::clone(&limiter);
         let result = tokio::spawn(async move {
-            let mut limiter = limiter_clone.lock().await;
-            if limiter.try_consume().await {
+            if limiter_clone.try_consume().await {
                 println!("Request {} allowed", i);
             } else {
                 println!("Request {} rate-limited", i);
@@ -30,7 +26,6 @@ async fn main() {
     tokio::time::sleep(Duration::from_secs(11)).await;
 
     // Try again after the window has expired
-    let mut limiter = limiter.lock().await;
     if limiter.try_consume().await {
         println!("New request allowed after window expiration");
     } else {
@@ -8,10 +8,10 @@ use tokio::time::{sleep, Duration};
 async fn main() {
     // Capacity of 10, refill rate of 2 tokens per second
     let mut bucket = TokenBucket::new(20, 2);
-    let mut rng = rand::thread_rng();
+    let mut rng = rand::rng();
 
     for i in 0..60 {
-        let tokens_required = rng.gen_range(1..=6);
+        let tokens_required = rng.random_range(1..=6);
 
         if bucket.try_consume(tokens_required).await {
             println!(
@@ -27,7 +27,7 @@ async fn main() {
             );
         }
 
-        let sleep_duration = rng.gen_range(100..=1000);
+        let sleep_duration = rng.random_range(100..=1000);
         sleep(Duration::from_millis(sleep_duration)).await;
     }
 }
@@ -0,0 +1,231 @@
+use crate::bucket::{OverCapacityPolicy, TokenBucket};
+use crate::decision::Decision;
+use crate::error::LimitrError;
+use crate::limiter::{BoxFuture, RateLimiter};
+use crate::quota::Quota;
+use std::time::Duration;
+
+/// A token bucket counting bytes instead of requests, e.g. to shape the bandwidth of a socket.
+///
+/// Each byte takes one token, so the capacity is the largest burst in bytes and the refill rate
+/// is the sustained bandwidth in bytes per second. This is a thin wrapper around
+/// [`TokenBucket`] whose methods take buffers, so the unit shows in the code using it.
+///
+/// A buffer larger than the capacity can never fit into the bucket at once.
+/// [`ByteRateLimiter::try_send`] follows the [`OverCapacityPolicy`] for such buffers and denies
+/// them by default, while [`ByteRateLimiter::send`] charges them in chunks of at most the
+/// capacity, waiting for each chunk in turn.
+///
+/// # Example
+///
+/// ```rust
+/// use limitr::bucket::ByteRateLimiter;
+///
+/// # tokio_test::block_on(async {
+/// let mut limiter = ByteRateLimiter::bytes_per_second(1024);
+///
+/// assert!(limiter.try_send(&[0; 1000]).await);
+/// assert!(!limiter.try_send(&[0; 100]).await);
+/// # })
+/// ```
+#[derive(Clone)]
+pub struct ByteRateLimiter {
+    bucket: TokenBucket,
+}
+
+impl ByteRateLimiter {
+    /// Creates a new `ByteRateLimiter` allowing bursts of up to `burst` bytes and refilling
+    /// `bytes_per_second` bytes every second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `burst` or `bytes_per_second` is zero, see [`TokenBucket::try_new`].
+    pub fn new(burst: u64, bytes_per_second: u64) -> Self {
+        ByteRateLimiter {
+            bucket: TokenBucket::new(burst, bytes_per_second),
+        }
+    }
+
+    /// Creates a new `ByteRateLimiter` sustaining `bytes_per_second`, allowing bursts of one
+    /// second's worth of bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_second` is zero.
+    pub fn bytes_per_second(bytes_per_second: u64) -> Self {
+        ByteRateLimiter::new(bytes_per_second, bytes_per_second)
+    }
+
+    /// Creates a new `ByteRateLimiter` from a quota of bytes, such as
+    /// [`Quota::bytes_per_second`], allowing bursts of the quota's burst.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`LimitrError::InvalidConfig`] like [`TokenBucket::from_quota`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::ByteRateLimiter;
+    /// use limitr::Quota;
+    ///
+    /// let quota = Quota::bytes_per_second(64 * 1024).allow_burst(256 * 1024);
+    /// let limiter = ByteRateLimiter::from_quota(quota).unwrap();
+    /// assert_eq!(limiter.burst(), 256 * 1024);
+    /// ```
+    pub fn from_quota(quota: Quota) -> Result<Self, LimitrError> {
+        Ok(ByteRateLimiter {
+            bucket: TokenBucket::from_quota(quota)?,
+        })
+    }
+
+    /// Sets how [`ByteRateLimiter::try_send`] treats buffers larger than the burst size.
+    pub fn with_over_capacity_policy(mut self, policy: OverCapacityPolicy) -> Self {
+        self.bucket = self.bucket.with_over_capacity_policy(policy);
+        self
+    }
+
+    /// Attempts to charge the length of `buf` without waiting.
+    ///
+    /// Returns `true` if the buffer may be sent now. Empty buffers are always admitted.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_send(&mut self, buf: &[u8]) -> bool {
+        self.try_consume_bytes(buf.len() as u64).await
+    }
+
+    /// Attempts to charge `bytes` bytes without waiting.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume_bytes(&mut self, bytes: u64) -> bool {
+        self.bucket.try_consume(bytes).await
+    }
+
+    /// Waits until the length of `buf` may be sent, and charges it.
+    ///
+    /// See [`ByteRateLimiter::consume_bytes`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`TokenBucket::acquire`], which cannot happen for chunks of at most the burst.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::ByteRateLimiter;
+    /// use tokio::time::{self, Duration, Instant};
+    ///
+    /// # tokio_test::block_on(async {
+    /// # time::pause();
+    /// let mut limiter = ByteRateLimiter::bytes_per_second(100);
+    /// let start = Instant::now();
+    ///
+    /// // Three times the burst size, charged 100 bytes at a time
+    /// limiter.send(&[0; 300]).await.unwrap();
+    /// assert!(start.elapsed() >= Duration::from_secs(2));
+    /// # })
+    /// ```
+    pub async fn send(&mut self, buf: &[u8]) -> Result<(), LimitrError> {
+        self.consume_bytes(buf.len() as u64).await
+    }
+
+    /// Waits until `bytes` bytes may be sent, and charges them.
+    ///
+    /// Amounts larger than the burst size are charged in chunks of the burst size, so a large
+    /// buffer is admitted at the sustained rate instead of being denied forever.
+    ///
+    /// # Cancel safety
+    ///
+    /// Amounts up to the burst size are charged at once, so dropping the future while it waits
+    /// charges nothing. Of larger amounts, the chunks charged before the future was dropped stay
+    /// charged.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`TokenBucket::acquire`], which cannot happen for chunks of at most the burst.
+    pub async fn consume_bytes(&mut self, mut bytes: u64) -> Result<(), LimitrError> {
+        let chunk_size = self.bucket.capacity();
+        while bytes > 0 {
+            let chunk = bytes.min(chunk_size);
+            self.bucket.acquire(chunk).await?;
+            bytes -= chunk;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of bytes that may currently be sent without waiting.
+    pub async fn available_bytes(&self) -> u64 {
+        self.bucket.available_tokens().await
+    }
+
+    /// Returns the largest burst in bytes.
+    pub fn burst(&self) -> u64 {
+        self.bucket.capacity()
+    }
+
+    /// Returns the sustained rate in bytes per second, rounded down.
+    pub fn rate(&self) -> u64 {
+        let per_second = self.bucket.refill_rate() as u128 * 1_000_000_000;
+        u64::try_from(per_second / self.bucket.refill_period().as_nanos()).unwrap_or(u64::MAX)
+    }
+
+    /// Returns the underlying token bucket, with one token per byte.
+    pub fn into_inner(self) -> TokenBucket {
+        self.bucket
+    }
+}
+
+impl RateLimiter for ByteRateLimiter {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        self.bucket.try_acquire(cost)
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        RateLimiter::check(&mut self.bucket, cost)
+    }
+
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        self.bucket.refund(cost)
+    }
+
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        RateLimiter::retry_after(&mut self.bucket, cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bucket::{ByteRateLimiter, OverCapacityPolicy};
+    use tokio::time::{self, Duration, Instant};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_charges_buffer_length() {
+        let mut limiter = ByteRateLimiter::new(10, 4);
+
+        assert!(limiter.try_send(b"hello").await);
+        assert!(limiter.try_send(&[]).await);
+        assert!(!limiter.try_send(b"world!").await);
+        assert_eq!(limiter.available_bytes().await, 5);
+
+        time::advance(Duration::from_secs(1)).await;
+        assert!(limiter.try_send(b"world!").await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_oversized_buffer() {
+        let buf = [0; 25];
+
+        let mut limiter = ByteRateLimiter::new(10, 5);
+        assert!(!limiter.try_send(&buf).await);
+
+        let mut limiter = ByteRateLimiter::new(10, 5)
+            .with_over_capacity_policy(OverCapacityPolicy::AllowWhenFull);
+        assert!(limiter.try_send(&buf).await);
+        assert_eq!(limiter.available_bytes().await, 0);
+
+        // Sending waits for every chunk in turn: 10 at once, then 10 and 5 at 5 bytes per second
+        let mut limiter = ByteRateLimiter::new(10, 5);
+        let start = Instant::now();
+        limiter.send(&buf).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+        assert_eq!(limiter.available_bytes().await, 0);
+    }
+}
@@ -0,0 +1,252 @@
+use crate::decision::Decision;
+use crate::limiter::{BoxFuture, RateLimiter};
+use tokio::time::{Duration, Instant};
+
+/// A Token Bucket whose capacity and refill rate are fixed at compile time.
+///
+/// This behaves like [`TokenBucket`](crate::bucket::TokenBucket) with the default
+/// [`OverCapacityPolicy`](crate::bucket::OverCapacityPolicy), but the configuration is part of
+/// the type. Limits that never change cannot be misconfigured at runtime, different limits are
+/// distinct types, and the bucket only stores its current state.
+///
+/// * `CAPACITY`: The maximum number of tokens the bucket can hold, must not be zero.
+/// * `RATE`: Number of tokens added to the bucket every second, must not be zero.
+///
+/// # Example
+///
+/// ```rust
+/// use limitr::bucket::ConstTokenBucket;
+///
+/// # tokio_test::block_on(async {
+/// // 10 tokens capacity, 5 tokens per second refill rate
+/// let mut bucket = ConstTokenBucket::<10, 5>::new();
+///
+/// assert!(bucket.try_consume(10).await);
+/// assert!(!bucket.try_consume(1).await);
+/// # })
+/// ```
+///
+/// A zero capacity or rate is rejected at compile time:
+///
+/// ```compile_fail
+/// use limitr::bucket::ConstTokenBucket;
+///
+/// let bucket = ConstTokenBucket::<0, 5>::new();
+/// ```
+///
+/// ```compile_fail
+/// use limitr::bucket::ConstTokenBucket;
+///
+/// let bucket = ConstTokenBucket::<10, 0>::new();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConstTokenBucket<const CAPACITY: u64, const RATE: u64> {
+    /// Current number of tokens
+    tokens: u64,
+    /// Time of last token refill
+    last_refill: Instant,
+}
+
+impl<const CAPACITY: u64, const RATE: u64> ConstTokenBucket<CAPACITY, RATE> {
+    /// Creates a new, full `ConstTokenBucket`.
+    pub fn new() -> Self {
+        const { assert!(CAPACITY > 0, "capacity must be greater than zero") };
+        const { assert!(RATE > 0, "refill rate must be greater than zero") };
+
+        ConstTokenBucket {
+            tokens: CAPACITY,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Returns the maximum number of tokens the bucket can hold.
+    pub const fn capacity(&self) -> u64 {
+        CAPACITY
+    }
+
+    /// Returns the number of tokens added to the bucket every second.
+    pub const fn refill_rate(&self) -> u64 {
+        RATE
+    }
+
+    /// Refills the bucket based on the whole seconds elapsed since the last refill.
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs();
+
+        if elapsed > 0 {
+            self.tokens = self
+                .tokens
+                .saturating_add(elapsed.saturating_mul(RATE))
+                .min(CAPACITY);
+            // Time short of a whole second counts towards the next refill, like for a
+            // `TokenBucket`, unless the bucket is full
+            self.last_refill = if self.tokens == CAPACITY {
+                now
+            } else {
+                self.last_refill + Duration::from_secs(elapsed)
+            };
+        }
+    }
+
+    /// Attempts to consume the specified `amount` of tokens.
+    ///
+    /// Returns `true` if the tokens were consumed, otherwise `false`. Requests for more tokens
+    /// than the capacity are always denied.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume(&mut self, amount: u64) -> bool {
+        self.refill();
+
+        if self.tokens >= amount {
+            self.tokens -= amount;
+            trace!(
+                "Consumed {} tokens, {} tokens left in the bucket.",
+                amount,
+                self.tokens
+            );
+            true
+        } else {
+            trace!(
+                "Failed to consume {} tokens. Only {} tokens left in the bucket.",
+                amount,
+                self.tokens
+            );
+            false
+        }
+    }
+
+    /// Attempts to consume the specified `amount` of tokens, reporting the state of the bucket
+    /// along with the outcome.
+    ///
+    /// See [`TokenBucket::check`](crate::bucket::TokenBucket::check).
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&mut self, amount: u64) -> Decision {
+        let allowed = self.try_consume(amount).await;
+        let now = Instant::now();
+        let reset_in = self
+            .available_at(CAPACITY)
+            .map_or(Duration::MAX, |at| at.saturating_duration_since(now));
+
+        if allowed {
+            Decision::Allowed {
+                limit: CAPACITY,
+                remaining: self.tokens,
+                reset_in,
+            }
+        } else {
+            Decision::Denied {
+                limit: CAPACITY,
+                remaining: self.tokens,
+                reset_in,
+                retry_after: self
+                    .available_at(amount)
+                    .map(|at| at.saturating_duration_since(now)),
+            }
+        }
+    }
+
+    /// Returns the number of tokens currently available.
+    pub async fn available_tokens(&mut self) -> u64 {
+        self.refill();
+        self.tokens
+    }
+
+    /// Returns the instant from which `amount` tokens are available, or `None` if that never
+    /// happens.
+    fn available_at(&self, amount: u64) -> Option<Instant> {
+        if amount > CAPACITY {
+            return None;
+        }
+
+        let missing = amount.saturating_sub(self.tokens);
+        if missing == 0 {
+            return Some(self.last_refill);
+        }
+        if RATE == 0 {
+            return None;
+        }
+
+        Some(self.last_refill + Duration::from_secs(missing.div_ceil(RATE)))
+    }
+}
+
+impl<const CAPACITY: u64, const RATE: u64> Default for ConstTokenBucket<CAPACITY, RATE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const CAPACITY: u64, const RATE: u64> RateLimiter for ConstTokenBucket<CAPACITY, RATE> {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        Box::pin(self.try_consume(cost))
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        Box::pin(ConstTokenBucket::check(self, cost))
+    }
+
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            self.tokens = self.tokens.saturating_add(cost).min(CAPACITY);
+        })
+    }
+
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        Box::pin(async move {
+            let now = Instant::now();
+            self.available_at(cost)
+                .map(|at| at.saturating_duration_since(now))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bucket::{ConstTokenBucket, TokenBucket};
+    use crate::RateLimiter;
+    use tokio::time::{advance, Duration};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_matches_dynamic_bucket() {
+        let mut fixed = ConstTokenBucket::<4, 2>::new();
+        let mut dynamic = TokenBucket::new(4, 2);
+
+        for (cost, wait) in [
+            (3, 0),
+            (2, 500),
+            (2, 500),
+            (4, 1500),
+            (5, 3000),
+            (1, 0),
+            (4, 3000),
+            (1, 1500),
+            (3, 500),
+        ] {
+            advance(Duration::from_millis(wait)).await;
+            assert_eq!(
+                fixed.try_consume(cost).await,
+                dynamic.try_consume(cost).await
+            );
+            assert_eq!(
+                fixed.available_tokens().await,
+                dynamic.available_tokens().await
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_check_and_refund() {
+        let mut bucket = ConstTokenBucket::<4, 2>::default();
+        assert_eq!(bucket.capacity(), 4);
+        assert_eq!(bucket.refill_rate(), 2);
+
+        assert!(bucket.try_acquire(3).await);
+        let decision = RateLimiter::check(&mut bucket, 3).await;
+        assert_eq!(decision.remaining(), 1);
+        assert_eq!(decision.retry_after(), Some(Duration::from_secs(1)));
+        assert_eq!(bucket.check(5).await.retry_after(), None);
+
+        bucket.refund(3).await;
+        assert_eq!(bucket.available_tokens().await, 4);
+    }
+}
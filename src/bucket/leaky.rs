@@ -28,8 +28,15 @@
 //! # })
 //! ```
 
-use tokio::time::Instant;
-use tracing::trace;
+use crate::decision::Decision;
+use crate::error::{InvalidConfigReason, LimitrError};
+use crate::limiter::{BoxFuture, RateLimiter, SharedRateLimiter};
+use crate::quota::Quota;
+use crate::stats::RateRecorder;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+use tokio::task;
+use tokio::time::{self, Duration, Instant};
 
 /// The `LeakyBucket` struct manages rate-limiting by allowing a steady rate of requests.
 pub struct LeakyBucket {
@@ -37,10 +44,16 @@ pub struct LeakyBucket {
     capacity: usize,
     /// How many requests are left
     remaining: usize,
-    /// How many tokens to leak per second
+    /// How many tokens to leak per leak period
     leak_rate: usize,
+    /// The period `leak_rate` applies to
+    leak_period: Duration,
+    /// Fraction of a token leaked since the last whole one, in nanoseconds times `leak_rate`
+    leak_carry: u128,
     /// Last time the bucket was checked
     last_checked: Instant,
+    /// Recently admitted requests
+    admissions: RateRecorder,
 }
 
 impl LeakyBucket {
@@ -58,13 +71,119 @@ impl LeakyBucket {
     /// let bucket = LeakyBucket::new(10, 2);
     /// # assert!(true);
     /// ```
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `capacity` or `leak_rate` is zero, see [`LeakyBucket::try_new`].
     pub fn new(capacity: usize, leak_rate: usize) -> Self {
-        LeakyBucket {
+        match LeakyBucket::try_new(capacity, leak_rate) {
+            Ok(bucket) => bucket,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Creates a new `LeakyBucket` with the given capacity and leak rate, or fails if the bucket
+    /// could never admit a request or never leak.
+    ///
+    /// ## Errors
+    ///
+    /// Fails with [`LimitrError::InvalidConfig`] if `capacity` or `leak_rate` is zero.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    ///
+    /// assert!(LeakyBucket::try_new(10, 2).is_ok());
+    /// assert!(LeakyBucket::try_new(0, 2).is_err());
+    /// assert!(LeakyBucket::try_new(10, 0).is_err());
+    /// ```
+    pub fn try_new(capacity: usize, leak_rate: usize) -> Result<Self, LimitrError> {
+        if capacity == 0 {
+            return Err(LimitrError::InvalidConfig(
+                InvalidConfigReason::ZeroCapacity,
+            ));
+        }
+        if leak_rate == 0 {
+            return Err(LimitrError::InvalidConfig(InvalidConfigReason::ZeroRate));
+        }
+        Ok(LeakyBucket {
             capacity,
             remaining: capacity,
             leak_rate,
+            leak_period: Duration::from_secs(1),
+            leak_carry: 0,
             last_checked: Instant::now(),
-        }
+            admissions: RateRecorder::default(),
+        })
+    }
+
+    /// Creates a new [`SharedLeakyBucket`] with the given capacity and leak rate, a handle whose
+    /// clones all use the same bucket.
+    ///
+    /// Unlike [`LeakyBucket::clone`], which creates an independent copy of the bucket, cloning
+    /// the handle shares the remaining capacity.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let bucket = LeakyBucket::shared(2, 1);
+    /// let handle = bucket.clone();
+    ///
+    /// assert!(handle.try_consume_n(2).await);
+    /// assert!(!bucket.try_consume().await);
+    /// # })
+    /// ```
+    pub fn shared(capacity: usize, leak_rate: usize) -> SharedLeakyBucket {
+        SharedLeakyBucket::new(capacity, leak_rate)
+    }
+
+    /// Creates a new `LeakyBucket` holding the burst of `quota` and leaking the amount over the
+    /// quota's period.
+    ///
+    /// ## Errors
+    ///
+    /// Fails with [`LimitrError::InvalidConfig`] if the period or the burst is zero.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    /// use limitr::Quota;
+    /// use tokio::time::Duration;
+    ///
+    /// let bucket = LeakyBucket::from_quota(Quota::per(3, Duration::from_secs(10))).unwrap();
+    /// assert_eq!(bucket.capacity(), 3);
+    /// assert_eq!(bucket.leak_period(), Duration::from_secs(10));
+    /// ```
+    pub fn from_quota(quota: Quota) -> Result<Self, LimitrError> {
+        let period = quota.nonzero_period()?;
+        let capacity = usize::try_from(quota.burst()).unwrap_or(usize::MAX);
+        let leak_rate = usize::try_from(quota.amount()).unwrap_or(usize::MAX);
+        Ok(LeakyBucket::try_new(capacity, leak_rate)?.with_leak_period(period))
+    }
+
+    /// Leaks `leak_rate` tokens every `period` instead of every second.
+    ///
+    /// Tokens leak back continuously rather than in steps, so e.g. a rate of 2 per second makes
+    /// one token available every 500ms. A zero period is treated as one nanosecond.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    /// use tokio::time::Duration;
+    ///
+    /// // 3 requests per 10 seconds, one becoming available every 3.33 seconds
+    /// let bucket = LeakyBucket::new(3, 3).with_leak_period(Duration::from_secs(10));
+    /// ```
+    pub fn with_leak_period(mut self, period: Duration) -> Self {
+        self.leak_period = period.max(Duration::from_nanos(1));
+        self.leak_carry = 0;
+        self
     }
 
     /// Tries to consume one token from the bucket.
@@ -86,27 +205,381 @@ impl LeakyBucket {
     ///  }
     /// # })
     /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
     pub async fn try_consume(&mut self) -> bool {
-        self.leak().await;
-        if self.remaining > 0 {
-            self.remaining -= 1;
+        self.try_consume_n(1).await
+    }
+
+    /// Tries to consume `n` tokens from the bucket at once.
+    ///
+    /// Either all `n` tokens are consumed or none are. Returns `true` if successful, otherwise
+    /// returns `false` if the bucket does not hold enough tokens.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    /// # tokio_test::block_on(async {
+    ///  let mut bucket = LeakyBucket::new(10, 2);
+    ///
+    ///  assert!(bucket.try_consume_n(8).await);
+    ///  assert!(!bucket.try_consume_n(3).await);
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume_n(&mut self, n: usize) -> bool {
+        self.try_consume_at(Instant::now(), n).await
+    }
+
+    /// Tries to consume `n` tokens at the instant `now` instead of the current one.
+    ///
+    /// Tokens are consumed exactly like with [`LeakyBucket::try_consume_n`], e.g. to test
+    /// deterministically or to decide a batch of requests against several limiters at the same
+    /// instant. Time never moves backwards, so an instant before the last check is treated as
+    /// the time of that check.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    /// use tokio::time::{Duration, Instant};
+    /// # tokio_test::block_on(async {
+    ///  let mut bucket = LeakyBucket::new(2, 2);
+    ///  let now = Instant::now();
+    ///
+    ///  assert!(bucket.try_consume_at(now, 2).await);
+    ///  assert!(!bucket.try_consume_at(now + Duration::from_millis(499), 1).await);
+    ///  assert!(bucket.try_consume_at(now + Duration::from_millis(500), 1).await);
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume_at(&mut self, now: Instant, n: usize) -> bool {
+        self.consume_at(now, n)
+    }
+
+    /// Leaks tokens up to `now` and consumes `n` of them if the bucket holds enough.
+    fn consume_at(&mut self, now: Instant, n: usize) -> bool {
+        let now = now.max(self.last_checked);
+        self.leak(now);
+        if self.remaining >= n {
+            self.remaining -= n;
+            self.admissions.record(now, n as u64);
             trace!("Request processed, remaining tokens: {}", self.remaining);
             true
         } else {
-            trace!("Request denied, bucket is empty.");
+            trace!(
+                "Request for {} tokens denied, only {} tokens left.",
+                n,
+                self.remaining
+            );
             false
         }
     }
 
+    /// Tries to consume `n` tokens from the bucket, reporting the state of the bucket along with
+    /// the outcome.
+    ///
+    /// Tokens are consumed exactly like with [`LeakyBucket::try_consume_n`]. The returned
+    /// [`Decision`] uses the capacity as limit and the tokens left as remaining, and resets once
+    /// all tokens have leaked back into the bucket.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    /// use tokio::time::Duration;
+    /// # tokio_test::block_on(async {
+    ///  let mut bucket = LeakyBucket::new(10, 2);
+    ///
+    ///  assert_eq!(bucket.check(8).await.remaining(), 2);
+    ///
+    ///  let decision = bucket.check(3).await;
+    ///  assert!(!decision.is_allowed());
+    ///  assert!(decision.retry_after().unwrap() <= Duration::from_secs(1));
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&mut self, n: usize) -> Decision {
+        self.decide(Instant::now(), n)
+    }
+
+    /// Consumes `n` tokens at `now` like [`LeakyBucket::consume_at`], reporting the outcome as a
+    /// [`Decision`].
+    fn decide(&mut self, now: Instant, n: usize) -> Decision {
+        let allowed = self.consume_at(now, n);
+        let reset_in = self.full_in(now);
+
+        if allowed {
+            Decision::Allowed {
+                limit: self.capacity as u64,
+                remaining: self.remaining as u64,
+                reset_in,
+            }
+        } else {
+            Decision::Denied {
+                limit: self.capacity as u64,
+                remaining: self.remaining as u64,
+                reset_in,
+                retry_after: self
+                    .available_at(n)
+                    .map(|at| at.saturating_duration_since(now)),
+            }
+        }
+    }
+
+    /// Waits until `n` tokens are available and consumes them.
+    ///
+    /// Denied requests are retried once enough tokens have leaked back into the bucket, so the
+    /// bucket is not polled while it is known to be full.
+    ///
+    /// ## Cancel safety
+    ///
+    /// Tokens are only consumed right before the call returns, so dropping the future while it
+    /// waits leaves the bucket untouched.
+    ///
+    /// ## Errors
+    ///
+    /// Fails immediately, without waiting, with [`LimitrError::AmountExceedsCapacity`] if `n`
+    /// exceeds the capacity.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    /// # tokio_test::block_on(async {
+    ///  let mut bucket = LeakyBucket::new(10, 2);
+    ///
+    ///  assert!(bucket.acquire(10).await.is_ok());
+    ///  assert!(bucket.acquire(11).await.is_err());
+    /// # })
+    /// ```
+    pub async fn acquire(&mut self, n: usize) -> Result<(), LimitrError> {
+        loop {
+            if self.try_consume_n(n).await {
+                return Ok(());
+            }
+            match self.available_at(n) {
+                Some(at) => time::sleep_until(at).await,
+                None if n > self.capacity => {
+                    return Err(LimitrError::AmountExceedsCapacity {
+                        requested: n as u64,
+                        capacity: self.capacity as u64,
+                    })
+                }
+                None => return Err(LimitrError::Exhausted),
+            }
+        }
+    }
+
+    /// Tries to consume `n` tokens, returning whether they were consumed along with the number
+    /// of tokens left afterwards, like
+    /// [`TokenBucket::consume_reporting`](crate::bucket::TokenBucket::consume_reporting).
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    /// # tokio_test::block_on(async {
+    ///  let mut bucket = LeakyBucket::new(10, 2);
+    ///
+    ///  assert_eq!(bucket.consume_reporting(3).await, (true, 7));
+    ///  assert_eq!(bucket.consume_reporting(8).await, (false, 7));
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn consume_reporting(&mut self, n: usize) -> (bool, usize) {
+        let allowed = self.try_consume_n(n).await;
+        (allowed, self.remaining)
+    }
+
+    /// Tries to consume `n` tokens, returning how many more tokens were needed if they were not
+    /// consumed.
+    ///
+    /// Tokens are consumed exactly like with [`LeakyBucket::try_consume_n`]. The deficit lets
+    /// adaptive clients shrink their next request or decide between waiting and shedding load,
+    /// without computing wait times like [`LeakyBucket::check`].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    /// # tokio_test::block_on(async {
+    ///  let mut bucket = LeakyBucket::new(10, 2);
+    ///
+    ///  assert_eq!(bucket.consume_or_deficit(8).await, Ok(()));
+    ///  assert_eq!(bucket.consume_or_deficit(5).await, Err(3));
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn consume_or_deficit(&mut self, n: usize) -> Result<(), usize> {
+        if self.try_consume_n(n).await {
+            Ok(())
+        } else {
+            Err(n - self.remaining)
+        }
+    }
+
+    /// Returns the observed number of requests admitted per second over the trailing `over` period.
+    ///
+    /// The rate is estimated from recent admissions, see [the `stats`
+    /// module](crate::stats#observed-rate).
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    /// use tokio::time::Duration;
+    /// # tokio_test::block_on(async {
+    ///  let mut bucket = LeakyBucket::new(10, 2);
+    ///  bucket.try_consume().await;
+    ///
+    ///  println!("{} requests/s", bucket.observed_rate(Duration::from_secs(1)).await);
+    /// # })
+    /// ```
+    pub async fn observed_rate(&self, over: Duration) -> f64 {
+        self.admissions.rate(Instant::now(), over)
+    }
+
+    /// Returns the maximum number of requests the bucket can hold.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of tokens leaked back every [leak period](LeakyBucket::leak_period).
+    pub fn leak_rate(&self) -> usize {
+        self.leak_rate
+    }
+
+    /// Returns the period the leak rate applies to, one second unless configured otherwise.
+    pub fn leak_period(&self) -> Duration {
+        self.leak_period
+    }
+
+    /// Checks whether a single request would currently be admitted, without consuming it.
+    ///
+    /// Pending leaks are projected from the elapsed time, but neither the remaining
+    /// capacity nor the last check time is modified, so the answer agrees with a
+    /// `try_consume` issued immediately afterwards.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = LeakyBucket::new(1, 1);
+    ///
+    /// assert!(bucket.would_allow());
+    /// assert!(bucket.try_consume().await);
+    /// assert!(!bucket.would_allow());
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub fn would_allow(&self) -> bool {
+        self.would_allow_n(1)
+    }
+
+    /// Checks whether `n` requests would currently be admitted, without consuming them.
+    ///
+    /// See [`LeakyBucket::would_allow`] for details.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub fn would_allow_n(&self, n: usize) -> bool {
+        self.remaining_at(Instant::now()) >= n
+    }
+
+    /// Returns the tokens left at `now`, including those leaked back since the last check.
+    fn remaining_at(&self, now: Instant) -> usize {
+        self.remaining
+            .saturating_add(self.pending_leak(now))
+            .min(self.capacity)
+    }
+
+    /// Returns how long until all tokens have leaked back into the bucket at the current leak
+    /// rate, e.g. for a "your limit resets in" hint.
+    ///
+    /// This is zero for a full bucket. The bucket is left unchanged.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::LeakyBucket;
+    /// use tokio::time::Duration;
+    /// # tokio_test::block_on(async {
+    ///  let mut bucket = LeakyBucket::new(10, 2);
+    ///  assert_eq!(bucket.time_to_full().await, Duration::ZERO);
+    ///
+    ///  assert!(bucket.try_consume_n(4).await);
+    ///  assert!(bucket.time_to_full().await <= Duration::from_secs(2));
+    /// # })
+    /// ```
+    pub async fn time_to_full(&self) -> Duration {
+        self.full_in(Instant::now())
+    }
+
+    /// Returns how long from `now` until the bucket is full again.
+    fn full_in(&self, now: Instant) -> Duration {
+        self.available_at(self.capacity)
+            .map_or(Duration::MAX, |at| at.saturating_duration_since(now))
+    }
+
+    /// Returns `cost` tokens to the bucket, never exceeding its capacity, and takes them back
+    /// out of the admission statistics.
+    fn give_back(&mut self, cost: u64) {
+        let n = usize::try_from(cost).unwrap_or(usize::MAX);
+        self.remaining = self.remaining.saturating_add(n).min(self.capacity);
+        self.admissions.retract(cost);
+    }
+
+    /// Returns the instant from which `n` tokens are available, or `None` if that never happens.
+    fn available_at(&self, n: usize) -> Option<Instant> {
+        if n > self.capacity {
+            return None;
+        }
+
+        let missing = n.saturating_sub(self.remaining);
+        if missing == 0 {
+            return Some(self.last_checked);
+        }
+        let needed =
+            (missing as u128 * self.leak_period.as_nanos()).saturating_sub(self.leak_carry);
+        let nanos = u64::try_from(needed.div_ceil(self.leak_rate as u128)).ok()?;
+        self.last_checked.checked_add(Duration::from_nanos(nanos))
+    }
+
+    /// Returns the leak accumulated since the last check, in nanoseconds times `leak_rate`.
+    ///
+    /// Integer arithmetic keeps fractional leaks exact, so no token is lost or gained to
+    /// rounding no matter how often the bucket is checked.
+    fn pending_credit(&self, now: Instant) -> u128 {
+        let elapsed = now.duration_since(self.last_checked).as_nanos();
+        elapsed
+            .saturating_mul(self.leak_rate as u128)
+            .saturating_add(self.leak_carry)
+    }
+
+    /// Returns the number of tokens that have leaked back since the last check.
+    fn pending_leak(&self, now: Instant) -> usize {
+        let tokens = self.pending_credit(now) / self.leak_period.as_nanos();
+        usize::try_from(tokens).unwrap_or(usize::MAX)
+    }
+
     /// Leaks tokens based on the elapsed time since the last check.
-    async fn leak(&mut self) {
-        let now = Instant::now();
-        let elapsed = now.duration_since(self.last_checked).as_secs() as usize;
-        let leak_amount = elapsed * self.leak_rate;
+    fn leak(&mut self, now: Instant) {
+        let credit = self.pending_credit(now);
+        let leak_amount = self.pending_leak(now);
+        self.last_checked = now;
+
+        self.leak_carry = if self.remaining.saturating_add(leak_amount) >= self.capacity {
+            // A full bucket does not bank partial tokens
+            0
+        } else {
+            credit % self.leak_period.as_nanos()
+        };
 
         if leak_amount > 0 {
-            self.remaining = (self.remaining + leak_amount).min(self.capacity);
-            self.last_checked = now;
+            self.remaining = self
+                .remaining
+                .saturating_add(leak_amount)
+                .min(self.capacity);
             trace!(
                 "Leaked {} tokens, current capacity: {}",
                 leak_amount,
@@ -116,10 +589,279 @@ impl LeakyBucket {
     }
 }
 
+/// Creates a sibling bucket with the same configuration and remaining capacity.
+///
+/// The clone has completely independent state: consuming from one bucket does not affect the
+/// other. Its leak clock starts at the moment of cloning.
+impl Clone for LeakyBucket {
+    fn clone(&self) -> Self {
+        LeakyBucket {
+            capacity: self.capacity,
+            remaining: self.remaining,
+            leak_rate: self.leak_rate,
+            leak_period: self.leak_period,
+            leak_carry: 0,
+            last_checked: Instant::now(),
+            admissions: RateRecorder::default(),
+        }
+    }
+}
+
+/// Creates a bucket of capacity 60 leaking 60 tokens per second, a reasonable starting point
+/// for prototypes and tests.
+impl Default for LeakyBucket {
+    fn default() -> Self {
+        LeakyBucket::new(60, 60)
+    }
+}
+
+/// Shows the configuration along with the tokens left right now, including those leaked back
+/// since the last call.
+impl fmt::Debug for LeakyBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LeakyBucket")
+            .field("capacity", &self.capacity)
+            .field("leak_rate", &self.leak_rate)
+            .field("leak_period", &self.leak_period)
+            .field("remaining", &self.remaining_at(Instant::now()))
+            .finish_non_exhaustive()
+    }
+}
+
+impl RateLimiter for LeakyBucket {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            match usize::try_from(cost) {
+                Ok(n) => self.try_consume_n(n).await,
+                Err(_) => false,
+            }
+        })
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        Box::pin(async move {
+            let n = usize::try_from(cost).unwrap_or(usize::MAX);
+            LeakyBucket::check(self, n).await
+        })
+    }
+
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        Box::pin(async move { self.give_back(cost) })
+    }
+
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        Box::pin(async move {
+            let n = usize::try_from(cost).ok()?;
+            let now = Instant::now();
+            self.available_at(n)
+                .map(|at| at.saturating_duration_since(now))
+        })
+    }
+
+    fn acquire(&mut self, cost: u64) -> BoxFuture<'_, Result<(), LimitrError>> {
+        Box::pin(async move {
+            match usize::try_from(cost) {
+                Ok(n) => LeakyBucket::acquire(self, n).await,
+                Err(_) => Err(LimitrError::AmountExceedsCapacity {
+                    requested: cost,
+                    capacity: self.capacity as u64,
+                }),
+            }
+        })
+    }
+}
+
+/// A [`LeakyBucket`] that many tasks can use at once.
+///
+/// All methods take `&self` and all clones of the handle use the same bucket, so it can be
+/// cloned into every task that needs to be limited, or shared through an `Arc`. Each request
+/// leaks and consumes tokens as one step under a short, synchronous lock that is never held
+/// across an `.await`, so concurrent requests never admit more than a single bucket would, and
+/// tasks are not queued behind each other the way they are behind an async mutex.
+///
+/// ## Example
+///
+/// ```rust
+/// use limitr::bucket::SharedLeakyBucket;
+///
+/// # tokio_test::block_on(async {
+/// let bucket = SharedLeakyBucket::new(10, 2);
+///
+/// let tasks: Vec<_> = (0..4)
+///     .map(|_| {
+///         let bucket = bucket.clone();
+///         tokio::spawn(async move { bucket.try_consume_n(3).await })
+///     })
+///     .collect();
+///
+/// let mut admitted = 0;
+/// for task in tasks {
+///     admitted += task.await.unwrap() as usize;
+/// }
+/// assert_eq!(admitted, 3);
+/// # })
+/// ```
+#[derive(Clone)]
+pub struct SharedLeakyBucket {
+    bucket: Arc<Mutex<LeakyBucket>>,
+}
+
+impl SharedLeakyBucket {
+    /// Creates a new `SharedLeakyBucket` with the given capacity and leak rate, see
+    /// [`LeakyBucket::new`].
+    ///
+    /// Buckets with further settings, such as a custom leak period, are shared with
+    /// [`SharedLeakyBucket::from`].
+    pub fn new(capacity: usize, leak_rate: usize) -> Self {
+        SharedLeakyBucket::from(LeakyBucket::new(capacity, leak_rate))
+    }
+
+    /// Tries to consume one token from the bucket, see [`LeakyBucket::try_consume`].
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume(&self) -> bool {
+        self.try_consume_n(1).await
+    }
+
+    /// Tries to consume `n` tokens from the bucket at once, see [`LeakyBucket::try_consume_n`].
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume_n(&self, n: usize) -> bool {
+        task::coop::consume_budget().await;
+        self.lock().consume_at(Instant::now(), n)
+    }
+
+    /// Tries to consume `n` tokens, reporting the state of the bucket along with the outcome,
+    /// see [`LeakyBucket::check`].
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&self, n: usize) -> Decision {
+        task::coop::consume_budget().await;
+        self.lock().decide(Instant::now(), n)
+    }
+
+    /// Checks whether `n` requests would currently be admitted, without consuming them, see
+    /// [`LeakyBucket::would_allow_n`].
+    ///
+    /// Other tasks may consume tokens in the meantime, so a following request can still be
+    /// denied.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub fn would_allow_n(&self, n: usize) -> bool {
+        self.lock().would_allow_n(n)
+    }
+
+    /// Returns how long until all tokens have leaked back into the bucket, see
+    /// [`LeakyBucket::time_to_full`].
+    pub async fn time_to_full(&self) -> Duration {
+        self.lock().full_in(Instant::now())
+    }
+
+    /// Returns the maximum number of requests the bucket can hold.
+    pub fn capacity(&self) -> usize {
+        self.lock().capacity
+    }
+
+    /// Returns the number of tokens leaked back every leak period, see
+    /// [`LeakyBucket::leak_rate`].
+    pub fn leak_rate(&self) -> usize {
+        self.lock().leak_rate
+    }
+
+    /// Returns the period the leak rate applies to, see [`LeakyBucket::leak_period`].
+    pub fn leak_period(&self) -> Duration {
+        self.lock().leak_period
+    }
+
+    /// Returns the observed number of requests admitted per second over the trailing `over`
+    /// period, see [`LeakyBucket::observed_rate`].
+    pub async fn observed_rate(&self, over: Duration) -> f64 {
+        self.lock().admissions.rate(Instant::now(), over)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LeakyBucket> {
+        self.bucket.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Shows the shared bucket, or `<locked>` while another task holds it.
+impl fmt::Debug for SharedLeakyBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("SharedLeakyBucket");
+        match self.bucket.try_lock() {
+            Ok(bucket) => debug.field("bucket", &*bucket),
+            Err(TryLockError::Poisoned(poisoned)) => debug.field("bucket", &*poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => debug.field("bucket", &format_args!("<locked>")),
+        };
+        debug.finish()
+    }
+}
+
+/// Shares an existing bucket, keeping its configuration and remaining capacity.
+impl From<LeakyBucket> for SharedLeakyBucket {
+    fn from(bucket: LeakyBucket) -> Self {
+        SharedLeakyBucket {
+            bucket: Arc::new(Mutex::new(bucket)),
+        }
+    }
+}
+
+impl RateLimiter for SharedLeakyBucket {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        SharedRateLimiter::try_acquire(self, cost)
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        SharedRateLimiter::check(self, cost)
+    }
+
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        SharedRateLimiter::refund(self, cost)
+    }
+
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        SharedRateLimiter::retry_after(self, cost)
+    }
+}
+
+impl SharedRateLimiter for SharedLeakyBucket {
+    fn try_acquire(&self, cost: u64) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            match usize::try_from(cost) {
+                Ok(n) => self.try_consume_n(n).await,
+                Err(_) => false,
+            }
+        })
+    }
+
+    fn check(&self, cost: u64) -> BoxFuture<'_, Decision> {
+        Box::pin(async move {
+            let n = usize::try_from(cost).unwrap_or(usize::MAX);
+            SharedLeakyBucket::check(self, n).await
+        })
+    }
+
+    fn refund(&self, cost: u64) -> BoxFuture<'_, ()> {
+        Box::pin(async move { self.lock().give_back(cost) })
+    }
+
+    fn retry_after(&self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        Box::pin(async move {
+            let n = usize::try_from(cost).ok()?;
+            let now = Instant::now();
+            self.lock()
+                .available_at(n)
+                .map(|at| at.saturating_duration_since(now))
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::bucket::LeakyBucket;
-    use tokio::time::{sleep, Duration};
+    use crate::bucket::{LeakyBucket, SharedLeakyBucket};
+    use crate::test_util::paused_runtime;
+    use crate::{Decision, InvalidConfigReason, LimitrError};
+    use proptest::prelude::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::time::Instant;
+    use tokio::time::{advance, sleep, Duration};
 
     #[tokio::test]
     async fn test_new_bucket() {
@@ -188,4 +930,350 @@ mod tests {
         assert!(bucket.try_consume().await);
         assert!(!bucket.try_consume().await);
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_would_allow_agrees_with_try_consume() {
+        let mut bucket = LeakyBucket::new(2, 1);
+
+        assert!(bucket.would_allow_n(2));
+        assert!(!bucket.would_allow_n(3));
+
+        for _ in 0..2 {
+            assert!(bucket.would_allow());
+            assert!(bucket.try_consume().await);
+        }
+        assert!(!bucket.would_allow());
+        assert!(!bucket.try_consume().await);
+
+        advance(Duration::from_millis(999)).await;
+        assert!(!bucket.would_allow());
+        assert!(!bucket.try_consume().await);
+
+        advance(Duration::from_millis(1)).await;
+        assert!(bucket.would_allow());
+        assert!(bucket.would_allow());
+        assert!(!bucket.would_allow_n(2));
+        assert!(bucket.try_consume().await);
+        assert!(!bucket.would_allow());
+        assert!(!bucket.try_consume().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_would_allow_does_not_modify_state() {
+        let mut bucket = LeakyBucket::new(3, 1);
+        for _ in 0..3 {
+            assert!(bucket.try_consume().await);
+        }
+
+        advance(Duration::from_secs(2)).await;
+        let remaining = bucket.remaining;
+        let last_checked = bucket.last_checked;
+        assert!(bucket.would_allow_n(2));
+        assert!(!bucket.would_allow_n(3));
+        assert_eq!(bucket.remaining, remaining);
+        assert_eq!(bucket.last_checked, last_checked);
+
+        assert!(bucket.try_consume().await);
+        assert!(bucket.try_consume().await);
+        assert!(!bucket.try_consume().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_clone_has_independent_state() {
+        let mut bucket = LeakyBucket::new(3, 1);
+        assert!(bucket.try_consume().await);
+
+        let mut sibling = bucket.clone();
+        assert!(sibling.would_allow_n(2));
+        assert!(!sibling.would_allow_n(3));
+
+        assert!(sibling.try_consume().await);
+        assert!(sibling.try_consume().await);
+        assert!(!sibling.try_consume().await);
+
+        assert!(bucket.would_allow_n(2));
+        assert!(bucket.try_consume().await);
+        assert!(bucket.try_consume().await);
+        assert!(!bucket.try_consume().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_consume_n_is_all_or_nothing() {
+        let mut bucket = LeakyBucket::new(5, 1);
+        assert!(bucket.try_consume_n(3).await);
+        assert!(!bucket.try_consume_n(3).await);
+        assert!(bucket.try_consume_n(2).await);
+        assert!(!bucket.try_consume().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_consume_or_deficit_reports_missing_tokens() {
+        let mut bucket = LeakyBucket::new(5, 1);
+        assert_eq!(bucket.consume_or_deficit(4).await, Ok(()));
+        assert_eq!(bucket.consume_or_deficit(3).await, Err(2));
+        assert_eq!(bucket.consume_or_deficit(1).await, Ok(()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_debug_shows_projected_remaining() {
+        let mut bucket = LeakyBucket::new(6, 2);
+        assert!(bucket.try_consume_n(5).await);
+        sleep(Duration::from_secs(1)).await;
+
+        let debug = format!("{:?}", bucket);
+        assert!(debug.starts_with("LeakyBucket {"), "{}", debug);
+        assert!(debug.contains("capacity: 6"), "{}", debug);
+        assert!(debug.contains("leak_rate: 2"), "{}", debug);
+        assert!(debug.contains("remaining: 3"), "{}", debug);
+
+        let shared = SharedLeakyBucket::from(bucket);
+        assert!(format!("{:?}", shared).contains("remaining: 3"));
+        let _guard = shared.lock();
+        assert_eq!(
+            format!("{:?}", shared),
+            "SharedLeakyBucket { bucket: <locked> }"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_time_to_full() {
+        let mut bucket = LeakyBucket::new(6, 2);
+        assert_eq!(bucket.time_to_full().await, Duration::ZERO);
+
+        assert!(bucket.try_consume_n(5).await);
+        assert_eq!(bucket.time_to_full().await, Duration::from_millis(2_500));
+        sleep(Duration::from_secs(1)).await;
+        assert_eq!(bucket.time_to_full().await, Duration::from_millis(1_500));
+        sleep(Duration::from_millis(1_500)).await;
+        assert_eq!(bucket.time_to_full().await, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_observed_rate() {
+        let mut bucket = LeakyBucket::new(5, 2);
+        for _ in 0..8 {
+            sleep(Duration::from_millis(250)).await;
+            assert!(bucket.try_consume().await);
+        }
+        assert!(!bucket.try_consume_n(5).await);
+
+        assert_eq!(bucket.observed_rate(Duration::from_secs(2)).await, 4.0);
+        assert_eq!(bucket.observed_rate(Duration::from_secs(1)).await, 4.0);
+        assert_eq!(bucket.observed_rate(Duration::from_secs(4)).await, 2.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_check_reports_bucket_state() {
+        let mut bucket = LeakyBucket::new(5, 2);
+
+        assert_eq!(
+            bucket.check(4).await,
+            Decision::Allowed {
+                limit: 5,
+                remaining: 1,
+                reset_in: Duration::from_secs(2),
+            }
+        );
+        assert_eq!(
+            bucket.check(4).await,
+            Decision::Denied {
+                limit: 5,
+                remaining: 1,
+                reset_in: Duration::from_secs(2),
+                retry_after: Some(Duration::from_millis(1_500)),
+            }
+        );
+        assert_eq!(bucket.check(6).await.retry_after(), None);
+
+        advance(Duration::from_secs(2)).await;
+        assert!(bucket.check(4).await.is_allowed());
+    }
+
+    #[test]
+    fn test_getters_return_configuration() {
+        let bucket = LeakyBucket::new(10, 2);
+        assert_eq!(bucket.capacity(), 10);
+        assert_eq!(bucket.leak_rate(), 2);
+        assert_eq!(bucket.leak_period(), Duration::from_secs(1));
+
+        let bucket = SharedLeakyBucket::from(
+            LeakyBucket::new(3, 1).with_leak_period(Duration::from_millis(250)),
+        );
+        assert_eq!(bucket.capacity(), 3);
+        assert_eq!(bucket.leak_rate(), 1);
+        assert_eq!(bucket.leak_period(), Duration::from_millis(250));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_leaks_smoothly() {
+        let mut bucket = LeakyBucket::new(4, 2);
+        assert!(bucket.try_consume_n(4).await);
+
+        // Half a token leaks every 250ms, so a whole one is back every other tick
+        let mut admitted = Vec::new();
+        for _ in 0..8 {
+            advance(Duration::from_millis(250)).await;
+            admitted.push(bucket.try_consume().await);
+        }
+        assert_eq!(
+            admitted,
+            [false, true, false, true, false, true, false, true]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_leak_period() {
+        let mut bucket = LeakyBucket::new(3, 3).with_leak_period(Duration::from_secs(10));
+        assert!(bucket.try_consume_n(3).await);
+
+        let decision = bucket.check(1).await;
+        assert_eq!(
+            decision.retry_after(),
+            Some(Duration::from_nanos(3_333_333_334))
+        );
+
+        advance(Duration::from_millis(3_333)).await;
+        assert!(!bucket.try_consume().await);
+        advance(Duration::from_millis(1)).await;
+        assert!(bucket.try_consume().await);
+
+        // No single check leaks a whole token, but the fractions add up
+        for _ in 0..21 {
+            advance(Duration::from_millis(333)).await;
+            assert!(bucket.try_consume_n(0).await);
+        }
+        assert!(bucket.try_consume_n(2).await);
+    }
+
+    #[test]
+    fn test_try_new_rejects_zero_capacity() {
+        for leak_rate in [0, 5] {
+            assert_eq!(
+                LeakyBucket::try_new(0, leak_rate).err(),
+                Some(LimitrError::InvalidConfig(
+                    InvalidConfigReason::ZeroCapacity
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_try_new_rejects_zero_rate() {
+        assert_eq!(
+            LeakyBucket::try_new(10, 0).err(),
+            Some(LimitrError::InvalidConfig(InvalidConfigReason::ZeroRate))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shared_bucket_spinning_tasks_take_turns() {
+        let bucket = Arc::new(SharedLeakyBucket::new(10, 1));
+        let started = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..50)
+            .map(|_| {
+                let bucket = Arc::clone(&bucket);
+                let started = Arc::clone(&started);
+                tokio::spawn(async move {
+                    started.fetch_add(1, Ordering::SeqCst);
+                    // Spin on the bucket without ever yielding explicitly
+                    for _ in 0..5_000 {
+                        let _ = bucket.try_consume().await;
+                    }
+                    started.load(Ordering::SeqCst)
+                })
+            })
+            .collect();
+
+        // On a single thread, every task only gets going if the spinning ones yield
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), 50);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_shared_bucket_conserves_tokens_under_contention() {
+        // Without leaking, exactly the capacity is admitted no matter how requests interleave
+        let bucket = Arc::new(SharedLeakyBucket::from(
+            LeakyBucket::new(1_000, 1).with_leak_period(Duration::from_secs(3_600)),
+        ));
+        let tasks: Vec<_> = (0..100)
+            .map(|_| {
+                let bucket = Arc::clone(&bucket);
+                tokio::spawn(async move {
+                    let mut admitted = 0;
+                    for _ in 0..50 {
+                        admitted += bucket.try_consume().await as usize;
+                        tokio::task::yield_now().await;
+                    }
+                    admitted
+                })
+            })
+            .collect();
+
+        let mut admitted = 0;
+        for task in tasks {
+            admitted += task.await.unwrap();
+        }
+        assert_eq!(admitted, 1_000);
+        assert!(!bucket.would_allow_n(1));
+
+        // With leaking, at most the capacity plus what leaked is admitted
+        let bucket = Arc::new(SharedLeakyBucket::new(100, 1_000));
+        let start = Instant::now();
+        let tasks: Vec<_> = (0..100)
+            .map(|_| {
+                let bucket = Arc::clone(&bucket);
+                tokio::spawn(async move {
+                    let mut admitted = 0;
+                    for _ in 0..20 {
+                        admitted += bucket.try_consume().await as usize;
+                        tokio::task::yield_now().await;
+                    }
+                    admitted
+                })
+            })
+            .collect();
+
+        let mut admitted = 0;
+        for task in tasks {
+            admitted += task.await.unwrap();
+        }
+        let leaked = (1_000.0 * start.elapsed().as_secs_f64()).ceil() as usize;
+        assert!(admitted >= 100);
+        assert!(admitted <= 100 + leaked, "{} > 100 + {}", admitted, leaked);
+    }
+
+    proptest! {
+        /// Over any interval, the bucket admits at most its capacity plus what leaked out.
+        #[test]
+        fn prop_admits_at_most_capacity_plus_leak(
+            capacity in 1usize..20,
+            leak_rate in 1usize..10,
+            steps in prop::collection::vec((0usize..25, 0u64..2_500), 1..60),
+        ) {
+            let admitted = paused_runtime()
+                .block_on(async {
+                    let start = Instant::now();
+                    let mut bucket = LeakyBucket::new(capacity, leak_rate);
+                    let mut admitted = Vec::new();
+                    for (amount, wait) in steps {
+                        if bucket.try_consume_n(amount).await {
+                            admitted.push((start.elapsed(), amount));
+                        }
+                        advance(Duration::from_millis(wait)).await;
+                    }
+                    admitted
+                });
+
+            for (i, (from, _)) in admitted.iter().enumerate() {
+                let mut total = 0;
+                for (at, amount) in &admitted[i..] {
+                    total += amount;
+                    // A fraction of a token may have leaked before `from`
+                    let leaked = (leak_rate as f64 * (*at - *from).as_secs_f64()).ceil();
+                    prop_assert!(total <= capacity + leaked as usize);
+                }
+            }
+        }
+    }
 }
@@ -8,11 +8,22 @@
 //!
 //! - **Token Bucket**: This algorithm allows a burst of requests up to a specified limit, and then
 //!   processes requests at a steady rate. It can accommodate sudden bursts of traffic but will
-//!   throttle the rate if the burst capacity is exceeded.
+//!   throttle the rate if the burst capacity is exceeded. A [`SharedTokenBucket`] can be used by
+//!   many tasks at once.
 //!
 //! - **Leaky Bucket**: This algorithm ensures a steady rate of processing requests, leaking them at
 //!   a constant rate. It smooths out burstiness in traffic and maintains a consistent processing rate,
-//!   dropping requests if the bucket is full.
+//!   dropping requests if the bucket is full. A [`SharedLeakyBucket`] can be used by many tasks at
+//!   once.
+//!
+//! - **Const Token Bucket**: A token bucket whose capacity and refill rate are const generic
+//!   parameters, for limits that are fixed at compile time.
+//!
+//! - **Byte Rate Limiter**: A token bucket counting bytes, taking buffers instead of amounts, to
+//!   shape bandwidth rather than request rates.
+//!
+//! - **Pacing Leaky Bucket**: A queueing variant of the leaky bucket that buffers submitted items and
+//!   releases them at the leak rate instead of rejecting bursts outright.
 //!
 //! ## Usage
 //!
@@ -47,8 +58,25 @@
 //! }
 //! ```
 
+mod bytes;
+mod const_token;
 mod leaky;
+mod pacing;
 mod token;
 
+pub use bytes::*;
+pub use const_token::*;
 pub use leaky::*;
+pub use pacing::*;
 pub use token::*;
+
+const _: () = {
+    crate::assert_send_sync::<TokenBucket>();
+    crate::assert_send_sync::<SharedTokenBucket>();
+    crate::assert_send_sync::<OwnedTokenPermit>();
+    crate::assert_send_sync::<ByteRateLimiter>();
+    crate::assert_send_sync::<LeakyBucket>();
+    crate::assert_send_sync::<SharedLeakyBucket>();
+    crate::assert_send_sync::<ConstTokenBucket<1, 1>>();
+    crate::assert_send_sync::<PacingLeakyBucket<String>>();
+};
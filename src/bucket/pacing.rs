@@ -0,0 +1,171 @@
+//! Pacing Leaky Bucket Implementation
+//!
+//! Instead of admitting or rejecting requests, a pacing leaky bucket queues submitted items and
+//! releases them at the leak rate. This smooths bursts of outgoing work into an evenly spaced
+//! stream, e.g. to respect strict QPS rules of a partner API.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use limitr::bucket::PacingLeakyBucket;
+//!
+//! # tokio_test::block_on(async {
+//! // Buffer up to 10 calls and release them at 5 per second
+//! let pacer = PacingLeakyBucket::new(10, 5);
+//!
+//! for call in 0..3 {
+//!     pacer.submit(call).await.expect("buffer has room");
+//! }
+//!
+//! for _ in 0..3 {
+//!     let call = pacer.recv().await;
+//!     println!("Sending call {}", call);
+//! }
+//! # })
+//! ```
+
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Duration, Instant};
+
+/// A leaky bucket that paces items through a bounded queue.
+///
+/// Up to `capacity` items can be buffered. `recv` hands them out in submission order, no faster
+/// than `leak_rate` items per second. This is effectively a rate-limited mpsc channel whose
+/// sending side rejects items once the buffer is full.
+pub struct PacingLeakyBucket<T> {
+    sender: Sender<T>,
+    receiver: Mutex<PacedReceiver<T>>,
+    /// Minimum spacing between two released items
+    interval: Duration,
+}
+
+struct PacedReceiver<T> {
+    receiver: Receiver<T>,
+    /// Earliest time the next item may be released
+    next_release: Instant,
+}
+
+impl<T> PacingLeakyBucket<T> {
+    /// Creates a new `PacingLeakyBucket` with the given capacity and leak rate.
+    ///
+    /// ## Parameters
+    /// - `capacity`: The maximum number of items that can be buffered.
+    /// - `leak_rate`: The number of items released per second.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `capacity` or `leak_rate` is zero.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::PacingLeakyBucket;
+    ///
+    /// let pacer: PacingLeakyBucket<String> = PacingLeakyBucket::new(10, 2);
+    /// ```
+    pub fn new(capacity: usize, leak_rate: usize) -> Self {
+        assert!(leak_rate > 0, "leak rate must be greater than zero");
+        let (sender, receiver) = mpsc::channel(capacity);
+
+        PacingLeakyBucket {
+            sender,
+            receiver: Mutex::new(PacedReceiver {
+                receiver,
+                next_release: Instant::now(),
+            }),
+            interval: Duration::from_nanos(1_000_000_000 / leak_rate as u64),
+        }
+    }
+
+    /// Submits an item to the bucket.
+    ///
+    /// Returns the item back as an error if the buffer is full.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::PacingLeakyBucket;
+    /// # tokio_test::block_on(async {
+    /// let pacer = PacingLeakyBucket::new(1, 2);
+    ///
+    /// assert!(pacer.submit("first").await.is_ok());
+    /// assert_eq!(pacer.submit("second").await, Err("second"));
+    /// # })
+    /// ```
+    pub async fn submit(&self, item: T) -> Result<(), T> {
+        match self.sender.try_send(item) {
+            Ok(()) => {
+                trace!("Item submitted, {} slots left.", self.sender.capacity());
+                Ok(())
+            }
+            Err(err) => {
+                trace!("Item rejected, bucket is full.");
+                Err(err.into_inner())
+            }
+        }
+    }
+
+    /// Receives the next item, waiting until it may be released according to the leak rate.
+    ///
+    /// Concurrent callers are served one at a time. The future is cancel safe: dropping it never
+    /// loses an item.
+    pub async fn recv(&self) -> T {
+        let mut paced = self.receiver.lock().await;
+        sleep_until(paced.next_release).await;
+
+        // The bucket owns a sender, so the channel can never be closed
+        let Some(item) = paced.receiver.recv().await else {
+            unreachable!("pacing channel closed while the bucket owns a sender")
+        };
+
+        paced.next_release = Instant::now() + self.interval;
+        trace!("Item released, next release at {:?}", paced.next_release);
+        item
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bucket::PacingLeakyBucket;
+    use tokio::time::{Duration, Instant};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rejects_when_full() {
+        let pacer = PacingLeakyBucket::new(2, 1);
+        assert_eq!(pacer.submit(1).await, Ok(()));
+        assert_eq!(pacer.submit(2).await, Ok(()));
+        assert_eq!(pacer.submit(3).await, Err(3));
+
+        assert_eq!(pacer.recv().await, 1);
+        assert_eq!(pacer.submit(3).await, Ok(()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_releases_at_leak_rate() {
+        let pacer = PacingLeakyBucket::new(3, 2);
+        let start = Instant::now();
+
+        for i in 0..3 {
+            assert_eq!(pacer.submit(i).await, Ok(()));
+        }
+
+        for (i, expected) in [0, 500, 1000].into_iter().enumerate() {
+            assert_eq!(pacer.recv().await, i);
+            assert_eq!(start.elapsed(), Duration::from_millis(expected));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_idle_bucket_releases_immediately() {
+        let pacer = PacingLeakyBucket::new(3, 1);
+        assert_eq!(pacer.submit(1).await, Ok(()));
+        assert_eq!(pacer.recv().await, 1);
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        let start = Instant::now();
+        assert_eq!(pacer.submit(2).await, Ok(()));
+        assert_eq!(pacer.recv().await, 2);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+}
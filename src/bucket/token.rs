@@ -1,5 +1,15 @@
-use std::time::Instant;
-use tracing::trace;
+use crate::decision::Decision;
+use crate::error::{InvalidConfigReason, LimitrError};
+use crate::limiter::{BoxFuture, RateLimiter, SharedRateLimiter};
+use crate::quota::Quota;
+use crate::stats::{RateRecorder, WaitRecorder, WaitStats};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
+use std::task::{Context, Poll};
+use tokio::task;
+use tokio::time::{sleep_until, Duration, Instant};
 
 /// An asynchronous Token Bucket rate limiter.
 ///
@@ -52,17 +62,55 @@ pub struct TokenBucket {
     capacity: u64,
     /// Current number of tokens
     tokens: u64,
-    /// Tokens added per second
+    /// Tokens added per refill period
     refill_rate: u64,
+    /// Time over which `refill_rate` tokens are added, never zero
+    refill_period: Duration,
     /// Time of last token refill
     last_refill: Instant,
+    /// How requests larger than the capacity are handled
+    over_capacity: OverCapacityPolicy,
+    /// Recent wait durations of `acquire` calls
+    wait_stats: WaitRecorder,
+    /// Recently admitted requests
+    admissions: RateRecorder,
+    /// Ramp-up of the refill rate, if configured
+    warmup: Option<Warmup>,
+    /// Tokens in use beyond which `check` flags admissions, if configured
+    soft_limit: Option<u64>,
+}
+
+/// Linear ramp of the refill rate from `start_rate` to the configured rate.
+#[derive(Debug, Clone, Copy)]
+struct Warmup {
+    /// Tokens added per refill period when the ramp starts
+    start_rate: u64,
+    /// How long the ramp takes, never zero
+    duration: Duration,
+    /// Time the ramp started
+    started: Instant,
+}
+
+/// Determines how a [`TokenBucket`] treats requests for more tokens than its capacity.
+///
+/// Such a request can never be fully covered by the bucket, so without an explicit policy
+/// it would be denied forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverCapacityPolicy {
+    /// Requests larger than the capacity are always denied. This is the default.
+    #[default]
+    Reject,
+    /// A single request larger than the capacity is admitted when the bucket is full,
+    /// draining it to zero tokens.
+    AllowWhenFull,
 }
 
 impl TokenBucket {
     /// Creates a new `TokenBucket` with the specified `capacity` and `refill_rate`.
     ///
     /// * `capacity`: The maximum number of tokens the bucket can hold.
-    /// * `refill_rate`: Number of tokens added to the bucket every second.
+    /// * `refill_rate`: Number of tokens added to the bucket every second, or every period set
+    ///   with [`TokenBucket::with_refill_period`].
     ///
     /// # Example
     ///
@@ -70,51 +118,295 @@ impl TokenBucket {
     /// use limitr::bucket::TokenBucket;
     /// let bucket = TokenBucket::new(10, 5); // 10 tokens capacity, 5 tokens per second refill rate
     /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` or `refill_rate` is zero, see [`TokenBucket::try_new`].
     pub fn new(capacity: u64, refill_rate: u64) -> Self {
+        match TokenBucket::try_new(capacity, refill_rate) {
+            Ok(bucket) => bucket,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Creates a new `TokenBucket` with the specified `capacity` and `refill_rate`, or fails if
+    /// the bucket could never admit a request or never be refilled.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`LimitrError::InvalidConfig`] if `capacity` or `refill_rate` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    ///
+    /// assert!(TokenBucket::try_new(10, 5).is_ok());
+    /// assert!(TokenBucket::try_new(0, 5).is_err());
+    /// assert!(TokenBucket::try_new(10, 0).is_err());
+    /// ```
+    pub fn try_new(capacity: u64, refill_rate: u64) -> Result<Self, LimitrError> {
+        if capacity == 0 {
+            return Err(LimitrError::InvalidConfig(
+                InvalidConfigReason::ZeroCapacity,
+            ));
+        }
+        if refill_rate == 0 {
+            return Err(LimitrError::InvalidConfig(InvalidConfigReason::ZeroRate));
+        }
         trace!(
             "Creating a new TokenBucket with capacity: {} and refill rate: {}",
             capacity,
             refill_rate
         );
-        Self {
+        Ok(Self {
             capacity,
             tokens: capacity, // Start with a full bucket
             refill_rate,
+            refill_period: Duration::from_secs(1),
             last_refill: Instant::now(),
+            over_capacity: OverCapacityPolicy::default(),
+            wait_stats: WaitRecorder::default(),
+            admissions: RateRecorder::default(),
+            warmup: None,
+            soft_limit: None,
+        })
+    }
+
+    /// Creates a new [`SharedTokenBucket`] with the specified `capacity` and `refill_rate`, a
+    /// handle whose clones all use the same bucket.
+    ///
+    /// This is the usual way to limit requests handled by many tasks, instead of wrapping the
+    /// bucket in an `Arc` and a `Mutex` by hand. Unlike [`TokenBucket::clone`], which creates
+    /// an independent sibling bucket, cloning the handle shares the tokens.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let bucket = TokenBucket::shared(10, 5);
+    /// let handle = bucket.clone();
+    ///
+    /// assert!(handle.try_consume(10).await);
+    /// assert!(!bucket.try_consume(1).await);
+    /// # })
+    /// ```
+    pub fn shared(capacity: u64, refill_rate: u64) -> SharedTokenBucket {
+        SharedTokenBucket::from(TokenBucket::new(capacity, refill_rate))
+    }
+
+    /// Creates a new, full `TokenBucket` holding the burst of `quota` and refilling the amount
+    /// over the quota's period.
+    ///
+    /// The amount is refilled in the smallest whole steps it divides into, e.g. one token every
+    /// 600ms for `Quota::per_minute(100)`, see [`TokenBucket::with_refill_period`].
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`LimitrError::InvalidConfig`] if the period or the burst is zero.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// use limitr::Quota;
+    /// use std::time::Duration;
+    ///
+    /// let bucket = TokenBucket::from_quota(Quota::per_minute(600).allow_burst(20)).unwrap();
+    /// assert_eq!(bucket.capacity(), 20);
+    /// assert_eq!(bucket.refill_rate(), 1);
+    /// assert_eq!(bucket.refill_period(), Duration::from_millis(100));
+    ///
+    /// let bucket = TokenBucket::from_quota(Quota::per_minute(100)).unwrap();
+    /// assert_eq!(bucket.refill_period(), Duration::from_millis(600));
+    /// ```
+    pub fn from_quota(quota: Quota) -> Result<Self, LimitrError> {
+        let (refill_rate, period) = quota.refill_step()?;
+        Ok(TokenBucket::try_new(quota.burst(), refill_rate)?.with_refill_period(period))
+    }
+
+    /// Adds `refill_rate` tokens every `period` instead of every second.
+    ///
+    /// Tokens are added in steps of `refill_rate` once per period, and time short of a whole
+    /// period counts towards the next step. A zero period is treated as one nanosecond.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// use std::time::Duration;
+    ///
+    /// // 100 requests per minute, one token every 600ms
+    /// let bucket = TokenBucket::new(100, 1).with_refill_period(Duration::from_millis(600));
+    /// ```
+    pub fn with_refill_period(mut self, period: Duration) -> Self {
+        self.refill_period = period.max(Duration::from_nanos(1));
+        self
+    }
+
+    /// Ramps the refill rate up from `start_rate` to the configured rate over `warmup`.
+    ///
+    /// The refill rate grows linearly, starting when the bucket is created, similar to Guava's
+    /// `SmoothWarmingUp` limiter. This protects a cold cache or backend from being hit at full
+    /// rate right away. A `start_rate` above the configured rate is clamped to it, and a zero
+    /// `warmup` disables the ramp.
+    ///
+    /// Only the refill rate ramps up: the bucket still starts full, so choose a capacity that the
+    /// cold backend can take as a single burst. [`TokenBucket::reset`] restarts the ramp.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// use std::time::Duration;
+    ///
+    /// // Refill 10 tokens per second at first, ramping up to 100 per second within a minute
+    /// let bucket = TokenBucket::new(20, 100).with_warmup(10, Duration::from_secs(60));
+    /// ```
+    pub fn with_warmup(mut self, start_rate: u64, warmup: Duration) -> Self {
+        self.warmup = (!warmup.is_zero()).then(|| Warmup {
+            start_rate: start_rate.min(self.refill_rate),
+            duration: warmup,
+            started: Instant::now(),
+        });
+        self
+    }
+
+    /// Refills the bucket completely and restarts the warmup, if one is configured.
+    ///
+    /// Use this after the protected backend was restarted or its cache flushed, so it gets
+    /// the same ramp as after creating the bucket.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(10, 5);
+    /// assert!(bucket.try_consume(10).await);
+    ///
+    /// bucket.reset().await;
+    /// assert_eq!(bucket.available_tokens().await, 10);
+    /// # })
+    /// ```
+    pub async fn reset(&mut self) {
+        let now = Instant::now();
+        self.tokens = self.capacity;
+        self.last_refill = now;
+        if let Some(warmup) = &mut self.warmup {
+            warmup.started = now;
         }
     }
 
+    /// Sets how requests for more tokens than the capacity are handled.
+    ///
+    /// By default such requests are rejected. With [`OverCapacityPolicy::AllowWhenFull`]
+    /// one oversized request passes whenever the bucket is completely full, leaving it empty.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::{OverCapacityPolicy, TokenBucket};
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(10, 5)
+    ///     .with_over_capacity_policy(OverCapacityPolicy::AllowWhenFull);
+    ///
+    /// assert!(bucket.try_consume(25).await);
+    /// assert_eq!(bucket.available_tokens().await, 0);
+    /// # })
+    /// ```
+    pub fn with_over_capacity_policy(mut self, policy: OverCapacityPolicy) -> Self {
+        self.over_capacity = policy;
+        self
+    }
+
+    /// Sets a soft limit of `tokens` in use, beyond which [`TokenBucket::check`] still admits
+    /// requests but reports them as [`Decision::AllowedSoftExceeded`].
+    ///
+    /// The tokens in use are the capacity minus the tokens left, so clients can be warned while
+    /// they drain the bucket, before they are cut off.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(10, 5).with_soft_limit(8);
+    ///
+    /// assert!(!bucket.check(8).await.is_soft_exceeded());
+    /// let decision = bucket.check(1).await;
+    /// assert!(decision.is_allowed());
+    /// assert!(decision.is_soft_exceeded());
+    /// # })
+    /// ```
+    pub fn with_soft_limit(mut self, tokens: u64) -> Self {
+        self.soft_limit = Some(tokens);
+        self
+    }
+
+    /// Returns the soft limit of tokens in use, if one is set.
+    pub fn soft_limit(&self) -> Option<u64> {
+        self.soft_limit
+    }
+
     /// Refills the bucket based on the elapsed time since the last refill.
     ///
     /// Adds tokens to the bucket based on the `refill_rate` and the amount of
     /// time that has passed since the last refill. It ensures the bucket does
     /// not exceed the defined `capacity`.
-    ///
-    /// This function runs synchronously, but is called asynchronously in the context of `try_consume`.
-    async fn refill(&mut self) {
-        let now = Instant::now();
-        let time_since_last_refill = now.duration_since(self.last_refill).as_secs();
+    fn refill(&mut self, now: Instant) {
+        let periods = self.periods_since_refill(now);
 
-        if time_since_last_refill > 0 {
-            let tokens_to_add = time_since_last_refill * self.refill_rate;
-            trace!(
-                "Refilling bucket: adding {} tokens after {} seconds",
-                tokens_to_add,
-                time_since_last_refill
-            );
+        if periods > 0 {
+            trace!("Refilling bucket after {} refill periods", periods);
 
-            self.tokens = (self.tokens + tokens_to_add).min(self.capacity);
-            self.last_refill = now;
+            self.tokens = self.tokens_at(now);
+            // Time short of a whole period counts towards the next refill, unless the bucket
+            // is full and has nowhere to put the tokens
+            self.last_refill = if self.tokens == self.capacity {
+                now
+            } else {
+                self.last_refill + self.periods(periods)
+            };
         } else {
-            trace!("No need to refill, less than 1 second has passed.");
+            trace!("No need to refill, less than one refill period has passed.");
         }
     }
 
+    /// Returns the number of tokens the bucket holds at `now` once refilled, without refilling
+    /// it.
+    fn tokens_at(&self, now: Instant) -> u64 {
+        let periods = self.periods_since_refill(now);
+        if periods == 0 {
+            return self.tokens;
+        }
+        let tokens_to_add = self.refilled_within(periods);
+        self.tokens.saturating_add(tokens_to_add).min(self.capacity)
+    }
+
+    /// Returns the number of whole refill periods between the last refill and `now`.
+    fn periods_since_refill(&self, now: Instant) -> u64 {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_nanos();
+        u64::try_from(elapsed / self.refill_period.as_nanos()).unwrap_or(u64::MAX)
+    }
+
+    /// Returns the duration of `count` refill periods, saturating at about 584 years.
+    fn periods(&self, count: u64) -> Duration {
+        let nanos = self.refill_period.as_nanos().saturating_mul(count as u128);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+
     /// Attempts to consume the specified `amount` of tokens asynchronously.
     ///
     /// Refills tokens if necessary before consumption. If there are enough tokens, the request succeeds,
     /// otherwise it fails. Provides detailed tracing of the token state for debugging.
     ///
+    /// Requests for more tokens than the capacity follow the bucket's [`OverCapacityPolicy`].
+    /// Under the default policy they are never admitted, which [`TokenBucket::checked_consume`]
+    /// reports as an error instead of `false`.
+    ///
     /// # Returns
     ///
     /// `true` if tokens were successfully consumed, otherwise `false`.
@@ -130,17 +422,60 @@ impl TokenBucket {
     /// }
     /// # })
     /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
     pub async fn try_consume(&mut self, amount: u64) -> bool {
-        self.refill().await;
+        self.try_consume_at(Instant::now(), amount).await
+    }
+
+    /// Attempts to consume `amount` tokens at the instant `now` instead of the current one.
+    ///
+    /// Tokens are consumed exactly like with [`TokenBucket::try_consume`], e.g. to test
+    /// deterministically or to decide a batch of requests against several limiters at the same
+    /// instant. An instant before the last refill refills nothing.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// use tokio::time::{Duration, Instant};
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(2, 1);
+    /// let now = Instant::now();
+    ///
+    /// assert!(bucket.try_consume_at(now, 2).await);
+    /// assert!(!bucket.try_consume_at(now, 1).await);
+    /// assert!(bucket.try_consume_at(now + Duration::from_secs(1), 1).await);
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume_at(&mut self, now: Instant, amount: u64) -> bool {
+        self.consume_at(now, amount)
+    }
+
+    /// Refills the bucket up to `now` and consumes `amount` tokens if they are available.
+    fn consume_at(&mut self, now: Instant, amount: u64) -> bool {
+        self.refill(now);
 
         if self.tokens >= amount {
             self.tokens -= amount;
+            self.admissions.record(now, amount);
             trace!(
                 "Consumed {} tokens, {} tokens left in the bucket.",
                 amount,
                 self.tokens
             );
             true
+        } else if amount > self.capacity
+            && self.over_capacity == OverCapacityPolicy::AllowWhenFull
+            && self.tokens == self.capacity
+        {
+            self.tokens = 0;
+            self.admissions.record(now, amount);
+            trace!(
+                "Consumed over-capacity request of {} tokens, bucket drained.",
+                amount
+            );
+            true
         } else {
             trace!(
                 "Failed to consume {} tokens. Only {} tokens left in the bucket.",
@@ -151,84 +486,948 @@ impl TokenBucket {
         }
     }
 
-    /// Returns the current number of tokens available in the bucket.
+    /// Attempts to consume the specified `amount` of tokens, rejecting amounts that can only be
+    /// programming mistakes.
+    ///
+    /// Unlike [`TokenBucket::try_consume`], which admits a zero amount without consuming
+    /// anything, this returns an error for amounts that can never be meaningfully admitted.
+    /// A legitimate denial because the bucket is short on tokens is still `Ok(false)`.
+    ///
+    /// # Errors
     ///
-    /// This is useful for monitoring or logging the current token state.
+    /// - [`LimitrError::ZeroAmount`] if `amount` is zero.
+    /// - [`LimitrError::AmountExceedsCapacity`] if `amount` exceeds the capacity and the
+    ///   [`OverCapacityPolicy`] rejects such requests.
     ///
     /// # Example
     ///
     /// ```rust
     /// use limitr::bucket::TokenBucket;
+    /// use limitr::LimitrError;
     /// # tokio_test::block_on(async {
     /// let mut bucket = TokenBucket::new(10, 5);
-    /// println!("Available tokens: {}", bucket.available_tokens().await);
+    ///
+    /// assert_eq!(bucket.checked_consume(8).await, Ok(true));
+    /// assert_eq!(bucket.checked_consume(8).await, Ok(false));
+    /// assert_eq!(bucket.checked_consume(0).await, Err(LimitrError::ZeroAmount));
     /// # })
     /// ```
-    pub async fn available_tokens(&mut self) -> u64 {
-        self.refill().await;
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn checked_consume(&mut self, amount: u64) -> Result<bool, LimitrError> {
+        if amount == 0 {
+            return Err(LimitrError::ZeroAmount);
+        }
+        if amount > self.capacity && self.over_capacity == OverCapacityPolicy::Reject {
+            return Err(LimitrError::AmountExceedsCapacity {
+                requested: amount,
+                capacity: self.capacity,
+            });
+        }
 
-        self.tokens
+        Ok(self.try_consume(amount).await)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::bucket::TokenBucket;
-    use tokio::time::{sleep, Duration};
 
-    #[tokio::test]
-    async fn test_new_token_bucket() {
-        let mut bucket = TokenBucket::new(10, 5);
-        assert_eq!(bucket.available_tokens().await, 10);
+    /// Attempts to consume the specified `amount` of tokens, reporting the state of the bucket
+    /// along with the outcome.
+    ///
+    /// Tokens are consumed exactly like with [`TokenBucket::try_consume`]. The returned
+    /// [`Decision`] uses the capacity as limit and the tokens left as remaining, and resets once
+    /// the bucket is full again. Admissions beyond the soft limit, if one is set, are reported
+    /// as [`Decision::AllowedSoftExceeded`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// use std::time::Duration;
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(10, 5);
+    ///
+    /// let decision = bucket.check(8).await;
+    /// assert!(decision.is_allowed());
+    /// assert_eq!(decision.remaining(), 2);
+    ///
+    /// let decision = bucket.check(8).await;
+    /// assert!(!decision.is_allowed());
+    /// assert!(decision.retry_after().unwrap() <= Duration::from_secs(2));
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&mut self, amount: u64) -> Decision {
+        self.decide(Instant::now(), amount)
     }
 
-    #[tokio::test]
-    async fn test_consume_success() {
-        let mut bucket = TokenBucket::new(10, 5);
-        assert!(bucket.try_consume(3).await);
-        assert_eq!(bucket.available_tokens().await, 7);
+    /// Consumes `amount` tokens at `now` if they are available, reporting the outcome as a
+    /// [`Decision`].
+    fn decide(&mut self, now: Instant, amount: u64) -> Decision {
+        let allowed = self.consume_at(now, amount);
+        let reset_in = self.full_in(now);
+
+        if allowed {
+            Decision::Allowed {
+                limit: self.capacity,
+                remaining: self.tokens,
+                reset_in,
+            }
+            .flag_soft_limit(self.soft_limit)
+        } else {
+            Decision::Denied {
+                limit: self.capacity,
+                remaining: self.tokens,
+                reset_in,
+                retry_after: self
+                    .next_available(amount)
+                    .ok()
+                    .map(|at| at.saturating_duration_since(now)),
+            }
+        }
     }
 
-    #[tokio::test]
-    async fn test_consume_failure() {
-        let mut bucket = TokenBucket::new(10, 5);
-        assert!(!bucket.try_consume(11).await);
-        assert_eq!(bucket.available_tokens().await, 10);
+    /// Attempts to consume `amount` tokens, returning whether they were consumed along with
+    /// the number of tokens left afterwards.
+    ///
+    /// Both are taken in the same call, so the count cannot be skewed by other consumers, e.g.
+    /// for an `X-RateLimit-Remaining` header. Unlike [`TokenBucket::check`], this skips
+    /// computing wait times.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(10, 5);
+    ///
+    /// assert_eq!(bucket.consume_reporting(3).await, (true, 7));
+    /// assert_eq!(bucket.consume_reporting(8).await, (false, 7));
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn consume_reporting(&mut self, amount: u64) -> (bool, u64) {
+        let allowed = self.try_consume(amount).await;
+        (allowed, self.tokens)
     }
 
-    #[tokio::test]
-    async fn test_refill() {
-        let mut bucket = TokenBucket::new(10, 5);
-        assert!(bucket.try_consume(10).await);
-        assert_eq!(bucket.available_tokens().await, 0);
+    /// Attempts to consume `amount` tokens, returning how many more tokens were needed if they
+    /// were not consumed.
+    ///
+    /// Tokens are consumed exactly like with [`TokenBucket::try_consume`]. The deficit lets
+    /// adaptive clients shrink their next request or decide between waiting and shedding load,
+    /// without computing wait times like [`TokenBucket::check`]. An over-capacity request under
+    /// [`OverCapacityPolicy::AllowWhenFull`] only needs a full bucket, so its deficit is the
+    /// number of tokens missing to the capacity.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(10, 5);
+    ///
+    /// assert_eq!(bucket.consume_or_deficit(7).await, Ok(()));
+    /// assert_eq!(bucket.consume_or_deficit(5).await, Err(2));
+    /// assert_eq!(bucket.consume_or_deficit(3).await, Ok(()));
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn consume_or_deficit(&mut self, amount: u64) -> Result<(), u64> {
+        if self.try_consume(amount).await {
+            return Ok(());
+        }
 
-        sleep(Duration::from_secs(1)).await;
-        assert!(bucket.try_consume(5).await);
-        assert_eq!(bucket.available_tokens().await, 0);
+        let needed =
+            if amount > self.capacity && self.over_capacity == OverCapacityPolicy::AllowWhenFull {
+                self.capacity
+            } else {
+                amount
+            };
+        Err(needed.saturating_sub(self.tokens))
     }
 
-    #[tokio::test]
-    async fn test_refill_up_to_capacity() {
-        let mut bucket = TokenBucket::new(10, 5);
-        assert!(bucket.try_consume(10).await);
-
-        sleep(Duration::from_secs(2)).await;
-        assert!(bucket.try_consume(10).await);
+    /// Waits until the specified `amount` of tokens is available and consumes it.
+    ///
+    /// Unlike `try_consume`, a request that cannot be served right away is delayed until enough
+    /// tokens have been refilled. How long each successful call waited is recorded and can be
+    /// inspected through [`TokenBucket::wait_time_stats`].
+    ///
+    /// # Errors
+    ///
+    /// Fails immediately, without waiting, with [`LimitrError::AmountExceedsCapacity`] if
+    /// `amount` exceeds the capacity and the [`OverCapacityPolicy`] rejects such requests.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// use limitr::LimitrError;
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(10, 5);
+    /// assert_eq!(bucket.acquire(2).await, Ok(()));
+    /// assert_eq!(
+    ///     bucket.acquire(11).await,
+    ///     Err(LimitrError::AmountExceedsCapacity { requested: 11, capacity: 10 })
+    /// );
+    /// # })
+    /// ```
+    pub async fn acquire(&mut self, amount: u64) -> Result<(), LimitrError> {
+        self.acquire_until(amount, None).await
+    }
 
-        sleep(Duration::from_secs(2)).await;
-        assert_eq!(bucket.available_tokens().await, 10);
+    /// Waits until the specified `amount` of tokens is available and consumes it, giving up at
+    /// `deadline`.
+    ///
+    /// This behaves like [`TokenBucket::acquire`], but fails with
+    /// [`LimitrError::DeadlineExceeded`] if the tokens are not available by `deadline`. A
+    /// deadline in the past still makes one immediate attempt. If the refill schedule shows
+    /// that the tokens cannot arrive in time, the call fails right away instead of sleeping
+    /// until the deadline.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe, e.g. when used as a branch of `tokio::select!`. Tokens are
+    /// only ever consumed right before the call returns, so dropping the future while it waits
+    /// leaves the bucket untouched.
+    ///
+    /// # Errors
+    ///
+    /// Besides [`LimitrError::DeadlineExceeded`], this fails with the same errors as
+    /// [`TokenBucket::acquire`] for requests that can never be satisfied.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// use limitr::LimitrError;
+    /// use tokio::time::{Duration, Instant};
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(10, 5);
+    /// let deadline = Instant::now() + Duration::from_millis(100);
+    ///
+    /// assert_eq!(bucket.acquire_by(10, deadline).await, Ok(()));
+    /// assert_eq!(
+    ///     bucket.acquire_by(1, deadline).await,
+    ///     Err(LimitrError::DeadlineExceeded)
+    /// );
+    /// # })
+    /// ```
+    pub async fn acquire_by(&mut self, amount: u64, deadline: Instant) -> Result<(), LimitrError> {
+        self.acquire_until(amount, Some(deadline)).await
     }
 
-    #[tokio::test]
-    async fn test_multiple_consume_and_refill() {
-        let mut bucket = TokenBucket::new(10, 2);
+    /// Waits until `amount` tokens are consumed, giving up at `deadline` if there is one.
+    async fn acquire_until(
+        &mut self,
+        amount: u64,
+        deadline: Option<Instant>,
+    ) -> Result<(), LimitrError> {
+        let start = Instant::now();
 
-        for _ in 0..5 {
-            assert!(bucket.try_consume(2).await);
-            sleep(Duration::from_millis(500)).await;
+        loop {
+            if self.try_consume(amount).await {
+                self.wait_stats.record(start.elapsed());
+                return Ok(());
+            }
+
+            match self.next_available(amount) {
+                Ok(at) if deadline.is_some_and(|deadline| at > deadline) => {
+                    trace!("Request for {} tokens cannot be satisfied in time.", amount);
+                    return Err(LimitrError::DeadlineExceeded);
+                }
+                Ok(at) => {
+                    trace!("Waiting for {} tokens until {:?}", amount, at);
+                    sleep_until(at).await;
+                }
+                Err(err) => {
+                    trace!("Request for {} tokens can never be satisfied.", amount);
+                    return Err(err);
+                }
+            }
         }
+    }
 
-        assert!(!bucket.try_consume(5).await);
+    /// Returns statistics about how long recent `acquire` calls had to wait.
+    ///
+    /// Only the most recent successful calls are taken into account, see [`WaitStats`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(10, 5);
+    /// bucket.acquire(1).await.unwrap();
+    ///
+    /// let stats = bucket.wait_time_stats().await;
+    /// println!("p95 wait: {:?}", stats.p95);
+    /// # })
+    /// ```
+    pub async fn wait_time_stats(&self) -> WaitStats {
+        self.wait_stats.stats()
+    }
+
+    /// Returns the observed number of tokens consumed per second over the trailing `over` period.
+    ///
+    /// The rate is estimated from recent admissions, see [the `stats`
+    /// module](crate::stats#observed-rate).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// use std::time::Duration;
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(10, 5);
+    /// bucket.try_consume(4).await;
+    ///
+    /// println!("{} tokens/s", bucket.observed_rate(Duration::from_secs(1)).await);
+    /// # })
+    /// ```
+    pub async fn observed_rate(&self, over: Duration) -> f64 {
+        self.admissions.rate(Instant::now(), over)
+    }
+
+    /// Returns the instant at which `amount` tokens will next be consumable, or an error if
+    /// that will never happen.
+    fn next_available(&self, amount: u64) -> Result<Instant, LimitrError> {
+        let target = if amount <= self.capacity {
+            amount
+        } else if self.over_capacity == OverCapacityPolicy::AllowWhenFull {
+            self.capacity
+        } else {
+            return Err(LimitrError::AmountExceedsCapacity {
+                requested: amount,
+                capacity: self.capacity,
+            });
+        };
+
+        let missing = target.saturating_sub(self.tokens);
+        let periods = match self.warmup {
+            None => missing.div_ceil(self.refill_rate).max(1),
+            Some(warmup) => {
+                // The rate only grows, so the full rate bounds the wait once the ramp is over
+                let ramp = warmup.duration.as_nanos() / self.refill_period.as_nanos();
+                let ramp = u64::try_from(ramp).unwrap_or(u64::MAX);
+                let (mut low, mut high) = (1, ramp.saturating_add(1));
+                high = high.saturating_add(missing.div_ceil(self.refill_rate));
+                while low < high {
+                    let mid = low + (high - low) / 2;
+                    if self.refilled_within(mid) >= missing {
+                        high = mid;
+                    } else {
+                        low = mid + 1;
+                    }
+                }
+                low
+            }
+        };
+        Ok(self.last_refill + self.periods(periods))
+    }
+
+    /// Returns the number of tokens refilled within the given whole number of `periods` after
+    /// the last refill, taking the warmup into account.
+    fn refilled_within(&self, periods: u64) -> u64 {
+        let Some(warmup) = self.warmup else {
+            return periods.saturating_mul(self.refill_rate);
+        };
+
+        // Tokens refilled since the start of the ramp, scaled by nanoseconds per refill period
+        let refilled = |elapsed: Duration| -> u128 {
+            let (start, rate) = (warmup.start_rate as u128, self.refill_rate as u128);
+            let ramp = warmup.duration.as_nanos();
+            let t = elapsed.as_nanos();
+            if t <= ramp {
+                start * t + (rate - start) * t * t / (2 * ramp)
+            } else {
+                start * ramp + (rate - start) * ramp / 2 + rate * (t - ramp)
+            }
+        };
+
+        // Rounding both ends down keeps fractions of tokens from getting lost between refills
+        let from = self.last_refill.saturating_duration_since(warmup.started);
+        let to = from.saturating_add(self.periods(periods));
+        let period = self.refill_period.as_nanos();
+        let tokens = refilled(to) / period - refilled(from) / period;
+        u64::try_from(tokens).unwrap_or(u64::MAX)
+    }
+
+    /// Returns the maximum number of tokens the bucket can hold.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Returns the number of tokens added to the bucket every
+    /// [refill period](TokenBucket::refill_period).
+    ///
+    /// With a [warmup](TokenBucket::with_warmup), this is the rate the ramp ends at.
+    pub fn refill_rate(&self) -> u64 {
+        self.refill_rate
+    }
+
+    /// Returns the period the refill rate applies to, one second unless set with
+    /// [`TokenBucket::with_refill_period`].
+    pub fn refill_period(&self) -> Duration {
+        self.refill_period
+    }
+
+    /// Returns the current number of tokens available in the bucket.
+    ///
+    /// This is useful for monitoring or logging the current token state. The tokens refilled
+    /// since the last request are included, so the number matches what the next
+    /// [`try_consume`](TokenBucket::try_consume) sees. It is computed as a projection without
+    /// changing the bucket, which is why a shared reference is enough.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(10, 5);
+    /// println!("Available tokens: {}", bucket.available_tokens().await);
+    /// # })
+    /// ```
+    pub async fn available_tokens(&self) -> u64 {
+        self.tokens_at(Instant::now())
+    }
+
+    /// Returns how long until the bucket is back at its capacity at the current refill rate,
+    /// e.g. for a "your limit resets in" hint.
+    ///
+    /// This is zero for a full bucket. Like
+    /// [`TokenBucket::available_tokens`], it is a projection that leaves the bucket unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// use std::time::Duration;
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(10, 5);
+    /// assert_eq!(bucket.time_to_full().await, Duration::ZERO);
+    ///
+    /// assert!(bucket.try_consume(10).await);
+    /// assert!(bucket.time_to_full().await <= Duration::from_secs(2));
+    /// # })
+    /// ```
+    pub async fn time_to_full(&self) -> Duration {
+        self.full_in(Instant::now())
+    }
+
+    /// Returns how long from `now` until `amount` tokens can be consumed, `None` if never.
+    fn wait_at(&self, now: Instant, amount: u64) -> Option<Duration> {
+        let tokens = self.tokens_at(now);
+        let over_capacity_fits = amount > self.capacity
+            && self.over_capacity == OverCapacityPolicy::AllowWhenFull
+            && tokens == self.capacity;
+        if amount <= tokens || over_capacity_fits {
+            return Some(Duration::ZERO);
+        }
+        self.next_available(amount)
+            .ok()
+            .map(|at| at.saturating_duration_since(now))
+    }
+
+    /// Returns `cost` tokens to the bucket, up to its capacity, and takes them back out of the
+    /// admission statistics.
+    fn give_back(&mut self, cost: u64) {
+        self.tokens = self.tokens.saturating_add(cost).min(self.capacity);
+        self.admissions.retract(cost);
+    }
+
+    /// Returns how long from `now` until the bucket is full again.
+    fn full_in(&self, now: Instant) -> Duration {
+        if self.tokens_at(now) >= self.capacity {
+            return Duration::ZERO;
+        }
+        self.next_available(self.capacity)
+            .map_or(Duration::MAX, |at| at.saturating_duration_since(now))
+    }
+}
+
+/// Creates a sibling bucket with the same configuration and current token count.
+///
+/// The clone has completely independent state: consuming from one bucket does not affect the
+/// other. Its refill clock starts at the moment of cloning and it begins with an empty history
+/// of wait times.
+impl Clone for TokenBucket {
+    fn clone(&self) -> Self {
+        Self {
+            capacity: self.capacity,
+            tokens: self.tokens,
+            refill_rate: self.refill_rate,
+            refill_period: self.refill_period,
+            last_refill: Instant::now(),
+            over_capacity: self.over_capacity,
+            wait_stats: WaitRecorder::default(),
+            admissions: RateRecorder::default(),
+            warmup: self.warmup,
+            soft_limit: self.soft_limit,
+        }
+    }
+}
+
+/// Creates a full bucket of 60 tokens refilling 60 tokens per second, a reasonable starting
+/// point for prototypes and tests.
+impl Default for TokenBucket {
+    fn default() -> Self {
+        TokenBucket::new(60, 60)
+    }
+}
+
+/// Shows the configuration along with the tokens available right now, including the refill
+/// pending since the last call.
+impl fmt::Debug for TokenBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenBucket")
+            .field("capacity", &self.capacity)
+            .field("refill_rate", &self.refill_rate)
+            .field("refill_period", &self.refill_period)
+            .field("available_tokens", &self.tokens_at(Instant::now()))
+            .field("over_capacity", &self.over_capacity)
+            .field("soft_limit", &self.soft_limit)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RateLimiter for TokenBucket {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        Box::pin(self.try_consume(cost))
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        Box::pin(TokenBucket::check(self, cost))
+    }
+
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        Box::pin(async move { self.give_back(cost) })
+    }
+
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        Box::pin(async move { self.wait_at(Instant::now(), cost) })
+    }
+
+    fn acquire(&mut self, cost: u64) -> BoxFuture<'_, Result<(), LimitrError>> {
+        Box::pin(TokenBucket::acquire(self, cost))
+    }
+}
+
+/// A handle to a [`TokenBucket`] that many tasks can use at once.
+///
+/// All clones of the handle use the same bucket, so it can be cloned into every task or
+/// request handler that needs to be limited. Create one with [`TokenBucket::shared`], or share
+/// a configured bucket with [`SharedTokenBucket::from`].
+///
+/// All methods take `&self`. Each request refills and consumes tokens as one step under a
+/// short, synchronous lock that is never held across an `.await`, the same as for a
+/// [`SharedLeakyBucket`](crate::bucket::SharedLeakyBucket). Waiting in
+/// [`SharedTokenBucket::acquire`] happens outside of the lock, so a task waiting for tokens
+/// does not hold up the others.
+///
+/// ## Example
+///
+/// ```rust
+/// use limitr::bucket::TokenBucket;
+///
+/// # tokio_test::block_on(async {
+/// let bucket = TokenBucket::shared(10, 2);
+///
+/// let tasks: Vec<_> = (0..4)
+///     .map(|_| {
+///         let bucket = bucket.clone();
+///         tokio::spawn(async move { bucket.try_consume(3).await })
+///     })
+///     .collect();
+///
+/// let mut admitted = 0;
+/// for task in tasks {
+///     admitted += task.await.unwrap() as usize;
+/// }
+/// assert_eq!(admitted, 3);
+/// # })
+/// ```
+#[derive(Clone)]
+pub struct SharedTokenBucket {
+    bucket: Arc<Mutex<TokenBucket>>,
+}
+
+impl SharedTokenBucket {
+    /// Attempts to consume `amount` tokens, see [`TokenBucket::try_consume`].
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume(&self, amount: u64) -> bool {
+        task::coop::consume_budget().await;
+        self.lock().consume_at(Instant::now(), amount)
+    }
+
+    /// Attempts to consume `amount` tokens, reporting the state of the bucket along with the
+    /// outcome, see [`TokenBucket::check`].
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&self, amount: u64) -> Decision {
+        task::coop::consume_budget().await;
+        self.lock().decide(Instant::now(), amount)
+    }
+
+    /// Waits until `amount` tokens are available and consumes them, see
+    /// [`TokenBucket::acquire`].
+    ///
+    /// The bucket is not locked while waiting, so other tasks may take the refilled tokens
+    /// first, in which case this keeps waiting.
+    ///
+    /// # Cancel safety
+    ///
+    /// Tokens are only consumed right before the call returns, so dropping the future while it
+    /// waits leaves the bucket untouched.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the same errors as [`TokenBucket::acquire`] for requests that can never be
+    /// satisfied.
+    pub async fn acquire(&self, amount: u64) -> Result<(), LimitrError> {
+        let start = Instant::now();
+
+        loop {
+            task::coop::consume_budget().await;
+            let at = {
+                let mut bucket = self.lock();
+                if bucket.consume_at(Instant::now(), amount) {
+                    bucket.wait_stats.record(start.elapsed());
+                    return Ok(());
+                }
+                bucket.next_available(amount)?
+            };
+            trace!("Waiting for {} tokens until {:?}", amount, at);
+            sleep_until(at).await;
+        }
+    }
+
+    /// Returns a future that waits until `amount` tokens are available and consumes them,
+    /// resolving to a permit for them.
+    ///
+    /// Unlike [`SharedTokenBucket::acquire`], the future holds its own handle to the bucket
+    /// instead of borrowing this one, so it is `'static` and can be spawned or stored, like
+    /// `tokio::sync::Semaphore::acquire_owned`.
+    ///
+    /// # Cancel safety
+    ///
+    /// The same as [`SharedTokenBucket::acquire`]: dropping the future before it resolves leaves
+    /// the bucket untouched.
+    ///
+    /// # Errors
+    ///
+    /// The future fails with the same errors as [`TokenBucket::acquire`] for requests that can
+    /// never be satisfied.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let bucket = TokenBucket::shared(10, 2);
+    ///
+    /// let task = tokio::spawn(bucket.reserve_owned(4));
+    /// let permit = task.await.unwrap().unwrap();
+    ///
+    /// assert_eq!(permit.amount(), 4);
+    /// assert_eq!(bucket.available_tokens().await, 6);
+    /// # })
+    /// ```
+    pub fn reserve_owned(&self, amount: u64) -> ReservationFuture {
+        let bucket = self.clone();
+        ReservationFuture {
+            amount,
+            inner: Box::pin(async move {
+                bucket.acquire(amount).await?;
+                Ok(OwnedTokenPermit { bucket, amount })
+            }),
+        }
+    }
+
+    /// Returns the current number of tokens available in the bucket, see
+    /// [`TokenBucket::available_tokens`].
+    pub async fn available_tokens(&self) -> u64 {
+        self.lock().tokens_at(Instant::now())
+    }
+
+    /// Returns how long until the bucket is back at its capacity, see
+    /// [`TokenBucket::time_to_full`].
+    pub async fn time_to_full(&self) -> Duration {
+        self.lock().full_in(Instant::now())
+    }
+
+    /// Returns the maximum number of tokens the bucket can hold.
+    pub fn capacity(&self) -> u64 {
+        self.lock().capacity
+    }
+
+    /// Returns the number of tokens added to the bucket every refill period, see
+    /// [`TokenBucket::refill_rate`].
+    pub fn refill_rate(&self) -> u64 {
+        self.lock().refill_rate
+    }
+
+    /// Returns the period the refill rate applies to, see [`TokenBucket::refill_period`].
+    pub fn refill_period(&self) -> Duration {
+        self.lock().refill_period
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TokenBucket> {
+        self.bucket.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A future waiting for tokens, returned by [`SharedTokenBucket::reserve_owned`].
+///
+/// It owns a handle to the bucket, so it can be spawned or kept around on its own. Nothing is
+/// consumed until it resolves.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReservationFuture {
+    amount: u64,
+    inner: BoxFuture<'static, Result<OwnedTokenPermit, LimitrError>>,
+}
+
+impl ReservationFuture {
+    /// Returns the number of tokens the future waits for.
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+}
+
+impl Future for ReservationFuture {
+    type Output = Result<OwnedTokenPermit, LimitrError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.as_mut().poll(cx)
+    }
+}
+
+impl fmt::Debug for ReservationFuture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReservationFuture")
+            .field("amount", &self.amount)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Tokens taken from a [`SharedTokenBucket`] by [`SharedTokenBucket::reserve_owned`].
+///
+/// The tokens are already consumed when the permit is handed out, and they stay consumed when
+/// it is dropped, as the bucket limits how often work starts rather than how much of it runs at
+/// once. Give them back with [`OwnedTokenPermit::refund`] if the work is abandoned.
+#[derive(Debug)]
+pub struct OwnedTokenPermit {
+    bucket: SharedTokenBucket,
+    amount: u64,
+}
+
+impl OwnedTokenPermit {
+    /// Returns the number of tokens the permit was granted.
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    /// Returns the tokens to the bucket, up to its capacity.
+    pub fn refund(self) {
+        self.bucket.lock().give_back(self.amount)
+    }
+}
+
+/// Shows the shared bucket, or `<locked>` while another task holds it.
+impl fmt::Debug for SharedTokenBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("SharedTokenBucket");
+        match self.bucket.try_lock() {
+            Ok(bucket) => debug.field("bucket", &*bucket),
+            Err(TryLockError::Poisoned(poisoned)) => debug.field("bucket", &*poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => debug.field("bucket", &format_args!("<locked>")),
+        };
+        debug.finish()
+    }
+}
+
+/// Shares an existing bucket, keeping its configuration and remaining tokens.
+impl From<TokenBucket> for SharedTokenBucket {
+    fn from(bucket: TokenBucket) -> Self {
+        SharedTokenBucket {
+            bucket: Arc::new(Mutex::new(bucket)),
+        }
+    }
+}
+
+impl RateLimiter for SharedTokenBucket {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        SharedRateLimiter::try_acquire(self, cost)
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        SharedRateLimiter::check(self, cost)
+    }
+
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        SharedRateLimiter::refund(self, cost)
+    }
+
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        SharedRateLimiter::retry_after(self, cost)
+    }
+
+    fn acquire(&mut self, cost: u64) -> BoxFuture<'_, Result<(), LimitrError>> {
+        SharedRateLimiter::acquire(self, cost)
+    }
+}
+
+impl SharedRateLimiter for SharedTokenBucket {
+    fn try_acquire(&self, cost: u64) -> BoxFuture<'_, bool> {
+        Box::pin(self.try_consume(cost))
+    }
+
+    fn check(&self, cost: u64) -> BoxFuture<'_, Decision> {
+        Box::pin(SharedTokenBucket::check(self, cost))
+    }
+
+    fn refund(&self, cost: u64) -> BoxFuture<'_, ()> {
+        Box::pin(async move { self.lock().give_back(cost) })
+    }
+
+    fn retry_after(&self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        Box::pin(async move { self.lock().wait_at(Instant::now(), cost) })
+    }
+
+    fn acquire(&self, cost: u64) -> BoxFuture<'_, Result<(), LimitrError>> {
+        Box::pin(SharedTokenBucket::acquire(self, cost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bucket::{OverCapacityPolicy, SharedTokenBucket, TokenBucket};
+    use crate::test_util::paused_runtime;
+    use crate::{Decision, InvalidConfigReason, LimitrError};
+    use proptest::prelude::*;
+    use tokio::time::{sleep, Duration, Instant};
+
+    #[tokio::test]
+    async fn test_new_token_bucket() {
+        let bucket = TokenBucket::new(10, 5);
+        assert_eq!(bucket.available_tokens().await, 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_default_bucket_refills() {
+        let mut bucket = TokenBucket::default();
+        assert!(bucket.try_consume(60).await);
+        assert!(!bucket.try_consume(1).await);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(bucket.available_tokens().await, 60);
+    }
+
+    #[tokio::test]
+    async fn test_try_consume_at_uses_given_instant() {
+        // Real time, the outcome only depends on the instants passed in
+        let mut bucket = TokenBucket::new(3, 1);
+        let start = Instant::now() + Duration::from_secs(3600);
+
+        assert!(bucket.try_consume_at(start, 3).await);
+        assert!(
+            !bucket
+                .try_consume_at(start + Duration::from_millis(999), 1)
+                .await
+        );
+        assert!(
+            bucket
+                .try_consume_at(start + Duration::from_secs(2), 2)
+                .await
+        );
+
+        // Earlier instants refill nothing
+        assert!(!bucket.try_consume_at(start, 1).await);
+        assert!(
+            bucket
+                .try_consume_at(start + Duration::from_secs(3), 1)
+                .await
+        );
+    }
+
+    #[test]
+    fn test_getters_return_configuration() {
+        let bucket = TokenBucket::new(10, 5);
+        assert_eq!(bucket.capacity(), 10);
+        assert_eq!(bucket.refill_rate(), 5);
+
+        let bucket = TokenBucket::from_quota("120/min".parse().unwrap()).unwrap();
+        assert_eq!((bucket.capacity(), bucket.refill_rate()), (120, 1));
+        assert_eq!(bucket.refill_period(), Duration::from_millis(500));
+
+        let bucket = TokenBucket::from_quota("1000/min".parse().unwrap()).unwrap();
+        assert_eq!((bucket.capacity(), bucket.refill_rate()), (1000, 1));
+        assert_eq!(bucket.refill_period(), Duration::from_millis(60));
+    }
+
+    #[tokio::test]
+    async fn test_consume_success() {
+        let mut bucket = TokenBucket::new(10, 5);
+        assert!(bucket.try_consume(3).await);
+        assert_eq!(bucket.available_tokens().await, 7);
+    }
+
+    #[tokio::test]
+    async fn test_consume_failure() {
+        let mut bucket = TokenBucket::new(10, 5);
+        assert!(!bucket.try_consume(11).await);
+        assert_eq!(bucket.available_tokens().await, 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_available_tokens_projects_refill() {
+        let mut bucket = TokenBucket::new(10, 3);
+        assert!(bucket.try_consume(10).await);
+
+        tokio::time::advance(Duration::from_secs(2)).await;
+        let shared = &bucket;
+        assert_eq!(shared.available_tokens().await, 6);
+        // Reading doesn't refill, so it doesn't restart the partial second either
+        tokio::time::advance(Duration::from_millis(999)).await;
+        assert_eq!(shared.available_tokens().await, 6);
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert_eq!(shared.available_tokens().await, 9);
+
+        assert!(bucket.try_consume(9).await);
+        assert!(!bucket.try_consume(1).await);
+    }
+
+    #[tokio::test]
+    async fn test_refill() {
+        let mut bucket = TokenBucket::new(10, 5);
+        assert!(bucket.try_consume(10).await);
+        assert_eq!(bucket.available_tokens().await, 0);
+
+        sleep(Duration::from_secs(1)).await;
+        assert!(bucket.try_consume(5).await);
+        assert_eq!(bucket.available_tokens().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_refill_up_to_capacity() {
+        let mut bucket = TokenBucket::new(10, 5);
+        assert!(bucket.try_consume(10).await);
+
+        sleep(Duration::from_secs(2)).await;
+        assert!(bucket.try_consume(10).await);
+
+        sleep(Duration::from_secs(2)).await;
+        assert_eq!(bucket.available_tokens().await, 10);
+    }
+
+    #[tokio::test]
+    async fn test_multiple_consume_and_refill() {
+        let mut bucket = TokenBucket::new(10, 2);
+
+        for _ in 0..5 {
+            assert!(bucket.try_consume(2).await);
+            sleep(Duration::from_millis(500)).await;
+        }
+
+        assert!(!bucket.try_consume(5).await);
         assert!(bucket.try_consume(4).await);
         assert!(!bucket.try_consume(1).await);
         assert_eq!(bucket.available_tokens().await, 0);
@@ -253,4 +1452,432 @@ mod tests {
         }
         assert!(!bucket.try_consume(1).await);
     }
+
+    #[tokio::test]
+    async fn test_over_capacity_rejected_by_default() {
+        let mut bucket = TokenBucket::new(10, 5);
+        assert!(!bucket.try_consume(11).await);
+        assert_eq!(bucket.available_tokens().await, 10);
+    }
+
+    #[tokio::test]
+    async fn test_over_capacity_allowed_when_full() {
+        let mut bucket =
+            TokenBucket::new(10, 5).with_over_capacity_policy(OverCapacityPolicy::AllowWhenFull);
+        assert!(bucket.try_consume(11).await);
+        assert_eq!(bucket.available_tokens().await, 0);
+        assert!(!bucket.try_consume(11).await);
+    }
+
+    #[tokio::test]
+    async fn test_over_capacity_requires_full_bucket() {
+        let mut bucket =
+            TokenBucket::new(10, 5).with_over_capacity_policy(OverCapacityPolicy::AllowWhenFull);
+        assert!(bucket.try_consume(1).await);
+        assert!(!bucket.try_consume(11).await);
+        assert_eq!(bucket.available_tokens().await, 9);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_consume_or_deficit_reports_missing_tokens() {
+        let mut bucket = TokenBucket::new(10, 5);
+        assert_eq!(bucket.consume_or_deficit(8).await, Ok(()));
+        assert_eq!(bucket.consume_or_deficit(5).await, Err(3));
+        assert_eq!(bucket.consume_or_deficit(11).await, Err(9));
+
+        // Over-capacity requests only need a full bucket
+        let mut bucket =
+            TokenBucket::new(10, 5).with_over_capacity_policy(OverCapacityPolicy::AllowWhenFull);
+        assert_eq!(bucket.consume_or_deficit(4).await, Ok(()));
+        assert_eq!(bucket.consume_or_deficit(20).await, Err(4));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_debug_shows_projected_tokens() {
+        let mut bucket = TokenBucket::new(10, 2);
+        assert!(bucket.try_consume(6).await);
+        sleep(Duration::from_secs(1)).await;
+
+        let debug = format!("{:?}", bucket);
+        assert!(debug.starts_with("TokenBucket {"), "{}", debug);
+        assert!(debug.contains("capacity: 10"), "{}", debug);
+        assert!(debug.contains("refill_rate: 2"), "{}", debug);
+        assert!(debug.contains("available_tokens: 6"), "{}", debug);
+
+        let shared = SharedTokenBucket::from(bucket);
+        assert!(format!("{:?}", shared).contains("available_tokens: 6"));
+        let _guard = shared.lock();
+        assert_eq!(
+            format!("{:?}", shared),
+            "SharedTokenBucket { bucket: <locked> }"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_time_to_full() {
+        let mut bucket = TokenBucket::new(10, 2);
+        assert_eq!(bucket.time_to_full().await, Duration::ZERO);
+
+        assert!(bucket.try_consume(7).await);
+        assert_eq!(bucket.time_to_full().await, Duration::from_secs(4));
+        sleep(Duration::from_secs(3)).await;
+        assert_eq!(bucket.time_to_full().await, Duration::from_secs(1));
+        sleep(Duration::from_secs(1)).await;
+        assert_eq!(bucket.time_to_full().await, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_waits_for_refill() {
+        let mut bucket = TokenBucket::new(2, 1);
+        let start = Instant::now();
+
+        assert_eq!(bucket.acquire(2).await, Ok(()));
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        assert_eq!(bucket.acquire(2).await, Ok(()));
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        assert_eq!(bucket.available_tokens().await, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_fails_fast_when_impossible() {
+        let start = Instant::now();
+
+        let mut bucket = TokenBucket::new(2, 1);
+        assert_eq!(
+            bucket.acquire(3).await,
+            Err(LimitrError::AmountExceedsCapacity {
+                requested: 3,
+                capacity: 2
+            })
+        );
+
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_over_capacity_waits_for_full_bucket() {
+        let mut bucket =
+            TokenBucket::new(4, 2).with_over_capacity_policy(OverCapacityPolicy::AllowWhenFull);
+        let start = Instant::now();
+
+        assert!(bucket.try_consume(3).await);
+        assert_eq!(bucket.acquire(10).await, Ok(()));
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        assert_eq!(bucket.available_tokens().await, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_time_stats() {
+        let mut bucket = TokenBucket::new(1, 1);
+        assert_eq!(bucket.wait_time_stats().await.count, 0);
+
+        for _ in 0..4 {
+            assert_eq!(bucket.acquire(1).await, Ok(()));
+        }
+
+        let stats = bucket.wait_time_stats().await;
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.min, Duration::ZERO);
+        assert_eq!(stats.max, Duration::from_secs(1));
+        assert_eq!(stats.p50, Duration::from_secs(1));
+        assert_eq!(stats.mean, Duration::from_millis(750));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_clone_has_independent_state() {
+        let mut bucket = TokenBucket::new(10, 1);
+        assert!(bucket.try_consume(4).await);
+
+        let mut sibling = bucket.clone();
+        assert_eq!(sibling.available_tokens().await, 6);
+
+        assert!(sibling.try_consume(6).await);
+        assert_eq!(sibling.available_tokens().await, 0);
+        assert_eq!(bucket.available_tokens().await, 6);
+
+        sleep(Duration::from_secs(1)).await;
+        assert_eq!(sibling.available_tokens().await, 1);
+        assert_eq!(bucket.available_tokens().await, 7);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shared_handles_use_one_bucket() {
+        let bucket = TokenBucket::shared(4, 2);
+        let handle = bucket.clone();
+
+        assert!(handle.try_consume(3).await);
+        assert_eq!(bucket.available_tokens().await, 1);
+
+        // Waiting for tokens does not block the other handle
+        let start = Instant::now();
+        let waiting = tokio::spawn(async move { handle.acquire(3).await });
+        tokio::task::yield_now().await;
+        assert!(bucket.try_consume(1).await);
+        assert_eq!(waiting.await.unwrap(), Ok(()));
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        assert_eq!(bucket.available_tokens().await, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reserve_owned() {
+        let bucket = TokenBucket::shared(4, 2);
+        assert!(bucket.try_consume(4).await);
+
+        // The futures own their handle, so they can be spawned and outlive `bucket`
+        let start = Instant::now();
+        let first = bucket.reserve_owned(2);
+        assert_eq!(first.amount(), 2);
+        let first = tokio::spawn(first);
+        let second = tokio::spawn(bucket.reserve_owned(2));
+
+        let permit = first.await.unwrap().unwrap();
+        assert_eq!(permit.amount(), 2);
+        assert!(second.await.unwrap().is_ok());
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        assert_eq!(bucket.available_tokens().await, 0);
+
+        // Refunding hands the tokens back, dropping the future leaves the bucket alone
+        permit.refund();
+        assert_eq!(bucket.available_tokens().await, 2);
+        drop(bucket.reserve_owned(1));
+        assert_eq!(bucket.available_tokens().await, 2);
+
+        assert_eq!(
+            bucket.reserve_owned(5).await.unwrap_err(),
+            LimitrError::AmountExceedsCapacity {
+                requested: 5,
+                capacity: 4,
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_observed_rate() {
+        let mut bucket = TokenBucket::new(100, 100);
+        for _ in 0..10 {
+            sleep(Duration::from_millis(100)).await;
+            assert!(bucket.try_consume(2).await);
+        }
+        assert!(!bucket.try_consume(1000).await);
+
+        assert_eq!(bucket.observed_rate(Duration::from_secs(1)).await, 20.0);
+        assert_eq!(bucket.observed_rate(Duration::from_secs(4)).await, 5.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refund_undoes_observed_rate() {
+        let mut bucket = TokenBucket::new(10, 1);
+        assert!(bucket.try_consume(4).await);
+        crate::RateLimiter::refund(&mut bucket, 3).await;
+
+        assert_eq!(bucket.available_tokens().await, 9);
+        assert_eq!(bucket.observed_rate(Duration::from_secs(1)).await, 1.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_check_reports_bucket_state() {
+        let mut bucket = TokenBucket::new(4, 2);
+
+        assert_eq!(
+            bucket.check(3).await,
+            Decision::Allowed {
+                limit: 4,
+                remaining: 1,
+                reset_in: Duration::from_secs(2),
+            }
+        );
+        assert_eq!(
+            bucket.check(3).await,
+            Decision::Denied {
+                limit: 4,
+                remaining: 1,
+                reset_in: Duration::from_secs(2),
+                retry_after: Some(Duration::from_secs(1)),
+            }
+        );
+        assert_eq!(bucket.check(5).await.retry_after(), None);
+
+        sleep(Duration::from_secs(1)).await;
+        assert!(bucket.check(3).await.is_allowed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_check_flags_soft_limit() {
+        let mut bucket = TokenBucket::new(10, 1).with_soft_limit(6);
+        assert_eq!(bucket.soft_limit(), Some(6));
+
+        assert!(!bucket.check(6).await.is_soft_exceeded());
+        assert_eq!(
+            bucket.check(2).await,
+            Decision::AllowedSoftExceeded {
+                limit: 10,
+                remaining: 2,
+                reset_in: Duration::from_secs(8),
+            }
+        );
+        // Denials stay denials, and refilled tokens lift the flag again
+        assert!(!bucket.check(3).await.is_allowed());
+        sleep(Duration::from_secs(3)).await;
+        assert!(!bucket.check(1).await.is_soft_exceeded());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_by_deadline() {
+        let mut bucket = TokenBucket::new(4, 2);
+        let start = Instant::now();
+
+        // A deadline in the past still gets one attempt
+        sleep(Duration::from_secs(1)).await;
+        assert_eq!(bucket.acquire_by(4, start).await, Ok(()));
+        assert_eq!(
+            bucket.acquire_by(1, start).await,
+            Err(LimitrError::DeadlineExceeded)
+        );
+
+        // Tokens that cannot arrive in time fail without waiting
+        let deadline = Instant::now() + Duration::from_secs(1);
+        assert_eq!(
+            bucket.acquire_by(3, deadline).await,
+            Err(LimitrError::DeadlineExceeded)
+        );
+        assert_eq!(Instant::now(), start + Duration::from_secs(1));
+
+        assert_eq!(bucket.acquire_by(2, deadline).await, Ok(()));
+        assert_eq!(Instant::now(), deadline);
+        assert_eq!(
+            bucket.acquire_by(5, deadline).await,
+            Err(LimitrError::AmountExceedsCapacity {
+                requested: 5,
+                capacity: 4
+            })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_by_is_cancel_safe() {
+        let mut bucket = TokenBucket::new(4, 1);
+        assert!(bucket.try_consume(4).await);
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        tokio::select! {
+            _ = bucket.acquire_by(3, deadline) => panic!("tokens acquired too early"),
+            _ = sleep(Duration::from_millis(2500)) => {}
+        }
+
+        // The dropped call consumed nothing
+        assert_eq!(bucket.available_tokens().await, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_warmup_ramps_refill_rate() {
+        let mut bucket = TokenBucket::new(100, 10).with_warmup(2, Duration::from_secs(4));
+        assert!(bucket.try_consume(100).await);
+
+        // The rate grows from 2 to 10 tokens per second over 4 seconds: 3, 5, 7 and 9 tokens
+        for expected in [3, 5, 7, 9, 10, 10] {
+            sleep(Duration::from_secs(1)).await;
+            assert_eq!(bucket.available_tokens().await, expected);
+            assert!(bucket.try_consume(expected).await);
+        }
+
+        // Waiting for tokens follows the ramp as well
+        bucket.reset().await;
+        assert!(bucket.try_consume(100).await);
+        let start = Instant::now();
+        assert_eq!(bucket.acquire(8).await, Ok(()));
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_checked_consume_rejects_invalid_amounts() {
+        let mut bucket = TokenBucket::new(4, 1);
+
+        assert_eq!(
+            bucket.checked_consume(0).await,
+            Err(LimitrError::ZeroAmount)
+        );
+        assert_eq!(
+            bucket.checked_consume(5).await,
+            Err(LimitrError::AmountExceedsCapacity {
+                requested: 5,
+                capacity: 4
+            })
+        );
+        assert_eq!(bucket.checked_consume(3).await, Ok(true));
+        assert_eq!(bucket.checked_consume(3).await, Ok(false));
+
+        // The lenient variant keeps admitting zero amounts
+        assert!(bucket.try_consume(0).await);
+
+        let mut bucket =
+            TokenBucket::new(4, 1).with_over_capacity_policy(OverCapacityPolicy::AllowWhenFull);
+        assert_eq!(bucket.checked_consume(5).await, Ok(true));
+    }
+
+    proptest! {
+        /// Over any interval, the bucket admits at most its capacity plus what it refilled.
+        #[test]
+        fn prop_admits_at_most_capacity_plus_refill(
+            capacity in 1u64..20,
+            refill_rate in 1u64..10,
+            steps in prop::collection::vec((0u64..25, 0u64..2_500), 1..60),
+        ) {
+            let admitted = paused_runtime()
+                .block_on(async {
+                    let start = Instant::now();
+                    let mut bucket = TokenBucket::new(capacity, refill_rate);
+                    let mut admitted = Vec::new();
+                    for (amount, wait) in steps {
+                        if bucket.try_consume(amount).await {
+                            admitted.push((start.elapsed(), amount));
+                        }
+                        tokio::time::advance(Duration::from_millis(wait)).await;
+                    }
+                    admitted
+                });
+
+            for (i, (from, _)) in admitted.iter().enumerate() {
+                let mut total = 0;
+                for (at, amount) in &admitted[i..] {
+                    total += amount;
+                    // Refills only count whole seconds, so partial seconds round up
+                    let elapsed = (*at - *from).as_secs_f64().ceil() as u64;
+                    prop_assert!(total <= capacity + refill_rate * elapsed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_new_rejects_zero_capacity() {
+        for refill_rate in [0, 5] {
+            assert_eq!(
+                TokenBucket::try_new(0, refill_rate).err(),
+                Some(LimitrError::InvalidConfig(
+                    InvalidConfigReason::ZeroCapacity
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_try_new_rejects_zero_rate() {
+        assert_eq!(
+            TokenBucket::try_new(10, 0).err(),
+            Some(LimitrError::InvalidConfig(InvalidConfigReason::ZeroRate))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must not be zero")]
+    fn test_new_panics_on_zero_capacity() {
+        let _ = TokenBucket::new(0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "refill rate is zero")]
+    fn test_new_panics_on_zero_rate() {
+        let _ = TokenBucket::new(10, 0);
+    }
 }
@@ -0,0 +1,65 @@
+//! Background tasks that periodically prune the state of a limiter.
+
+use std::future::Future;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration, MissedTickBehavior};
+
+/// Handle of a background cleanup task, e.g. from
+/// [`FixedWindowCounter::spawn_cleanup`](crate::window::FixedWindowCounter::spawn_cleanup) or
+/// [`KeyedLimiter::spawn_gc`](crate::combinators::KeyedLimiter::spawn_gc).
+///
+/// Dropping the handle stops the task.
+#[derive(Debug)]
+pub struct CleanupHandle {
+    task: JoinHandle<()>,
+}
+
+impl CleanupHandle {
+    /// Spawns a task that runs `cleanup` on `target` every `every`, starting right away. A zero
+    /// interval is treated as one millisecond.
+    ///
+    /// The task only holds a weak reference and stops once `target` is dropped.
+    pub(crate) fn spawn<T, F>(
+        target: &Arc<T>,
+        every: Duration,
+        cleanup: impl Fn(Arc<T>) -> F + Send + 'static,
+    ) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Future<Output = ()> + Send,
+    {
+        // A zero period would make `interval` panic inside the task
+        let every = every.max(Duration::from_millis(1));
+        let target = Arc::downgrade(target);
+        let task = tokio::spawn(async move {
+            let mut ticker = time::interval(every);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let Some(target) = target.upgrade() else {
+                    break;
+                };
+                cleanup(target).await;
+            }
+        });
+
+        CleanupHandle { task }
+    }
+
+    /// Stops the cleanup task.
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+
+    /// Returns whether the cleanup task has stopped, e.g. because its limiter was dropped.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Drop for CleanupHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,178 @@
+//! A cached clock trading accuracy for fewer clock reads.
+
+use crate::cleanup::CleanupHandle;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::runtime::Handle;
+use tokio::time::{Duration, Instant};
+
+/// A clock caching the current instant, refreshed by a background task at a fixed resolution.
+///
+/// Reading the clock is a single atomic load instead of a call into the operating system, which
+/// adds up for limiters deciding millions of requests per second. In exchange, the time it
+/// reports lags behind, usually by up to the resolution, so a limiter on a coarse clock measures
+/// windows and waits about one resolution too short or too late. Keep the resolution well below
+/// the periods the limiter works with, e.g. 1ms for windows of a second or more.
+///
+/// The refresh task is spawned on the current tokio runtime and stops once the clock and all of
+/// its clones are dropped. On a busy runtime it may run late, and a late refresh delays the
+/// following ones instead of catching up, so the lag can grow beyond the resolution for as long
+/// as the runtime is starved.
+///
+/// A clock created outside of a runtime has no refresh task and reads the current instant every
+/// time, just like a precise clock. So does a clock whose refresh task stopped because its
+/// runtime shut down, e.g. one created within a `block_on` call that has returned.
+///
+/// # Example
+///
+/// ```rust
+/// use limitr::CoarseClock;
+/// use tokio::time::{self, Duration, Instant};
+///
+/// # tokio_test::block_on(async {
+/// # time::pause();
+/// let clock = CoarseClock::new(Duration::from_millis(1));
+/// let start = clock.now();
+///
+/// // Reads in between refreshes return the cached instant
+/// for _ in 0..1_000 {
+///     assert_eq!(clock.now(), start);
+/// }
+///
+/// time::sleep(Duration::from_millis(10)).await;
+/// assert!(clock.now() > start);
+/// assert!(clock.reads() < 1_000);
+/// # })
+/// ```
+#[derive(Clone)]
+pub struct CoarseClock {
+    cached: Arc<Cached>,
+    /// Refreshes the cached instant, `None` outside of a runtime
+    refresh: Option<Arc<CleanupHandle>>,
+    resolution: Duration,
+}
+
+/// The cached instant, along with how often the underlying clock was read.
+struct Cached {
+    origin: Instant,
+    /// Nanoseconds from `origin` to the latest refresh
+    elapsed: AtomicU64,
+    reads: AtomicU64,
+}
+
+impl Cached {
+    fn read(&self) -> Instant {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        Instant::now()
+    }
+
+    fn refresh(&self) {
+        let elapsed = self.read().duration_since(self.origin);
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        // Refreshes never move the clock backwards, even if they race
+        self.elapsed.fetch_max(nanos, Ordering::Relaxed);
+    }
+}
+
+impl CoarseClock {
+    /// Creates a new `CoarseClock` refreshing the cached instant every `resolution`. A zero
+    /// resolution is treated as one millisecond.
+    pub fn new(resolution: Duration) -> Self {
+        let resolution = if resolution.is_zero() {
+            Duration::from_millis(1)
+        } else {
+            resolution
+        };
+        let cached = Arc::new(Cached {
+            origin: Instant::now(),
+            elapsed: AtomicU64::new(0),
+            reads: AtomicU64::new(1),
+        });
+
+        let refresh = Handle::try_current().is_ok().then(|| {
+            Arc::new(CleanupHandle::spawn(
+                &cached,
+                resolution,
+                |cached| async move { cached.refresh() },
+            ))
+        });
+        if refresh.is_none() {
+            trace!("No runtime to refresh the coarse clock, reading the precise clock instead");
+        }
+
+        CoarseClock {
+            cached,
+            refresh,
+            resolution,
+        }
+    }
+
+    /// Returns the cached instant, usually at most one resolution behind the current one.
+    ///
+    /// Without a running refresh task, this reads the current instant instead.
+    pub fn now(&self) -> Instant {
+        if !self.is_refreshing() {
+            return self.cached.read();
+        }
+        self.cached.origin + Duration::from_nanos(self.cached.elapsed.load(Ordering::Relaxed))
+    }
+
+    /// Returns whether the refresh task is running, which it is not outside of a runtime or
+    /// once its runtime shut down.
+    fn is_refreshing(&self) -> bool {
+        self.refresh
+            .as_ref()
+            .is_some_and(|refresh| !refresh.is_finished())
+    }
+
+    /// Returns how often the underlying clock was read since the clock was created.
+    pub fn reads(&self) -> u64 {
+        self.cached.reads.load(Ordering::Relaxed)
+    }
+
+    /// Returns how often the cached instant is refreshed.
+    pub fn resolution(&self) -> Duration {
+        self.resolution
+    }
+}
+
+impl fmt::Debug for CoarseClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CoarseClock")
+            .field("resolution", &self.resolution)
+            .field("refreshing", &self.is_refreshing())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CoarseClock;
+    use tokio::time::{Duration, Instant};
+
+    #[test]
+    fn test_reads_precise_clock_outside_runtime() {
+        let clock = CoarseClock::new(Duration::ZERO);
+        assert_eq!(clock.resolution(), Duration::from_millis(1));
+
+        let before = Instant::now();
+        assert!(clock.now() >= before);
+        assert_eq!(clock.reads(), 2);
+    }
+
+    #[test]
+    fn test_reads_precise_clock_once_runtime_shut_down() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let clock = runtime.block_on(async { CoarseClock::new(Duration::from_secs(3600)) });
+        drop(runtime);
+
+        // The cached instant is never refreshed again, so it would stay at the creation time
+        let before = Instant::now();
+        assert!(clock.now() >= before);
+        assert!(format!("{:?}", clock).contains("refreshing: false"));
+    }
+}
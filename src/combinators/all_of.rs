@@ -0,0 +1,282 @@
+use crate::decision::Decision;
+use crate::limiter::{BoxFuture, RateLimiter};
+use std::time::Duration;
+
+/// A limiter that admits a request only if every one of its sub-limiters admits it.
+///
+/// Sub-limiters are asked in order. If one of them denies the request, the ones that already
+/// admitted it are refunded and the later ones are not charged at all, so a denied request
+/// leaves every sub-limiter as it was. This is the
+/// [`AndLimiter`](crate::combinators::AndLimiter) for any number of limiters, such as a per-user
+/// bucket, a global bucket and a per-route window all guarding the same request.
+///
+/// # Example
+///
+/// ```rust
+/// use limitr::bucket::{LeakyBucket, TokenBucket};
+/// use limitr::combinators::AllOf;
+/// use limitr::window::FixedWindowCounter;
+/// use limitr::RateLimiter;
+/// use std::time::Duration;
+///
+/// # tokio_test::block_on(async {
+/// let limiters: Vec<Box<dyn RateLimiter>> = vec![
+///     Box::new(TokenBucket::new(5, 1)),
+///     Box::new(LeakyBucket::new(10, 1)),
+///     Box::new(FixedWindowCounter::new(3, Duration::from_secs(60))),
+/// ];
+/// let mut limiter = AllOf::new(limiters);
+///
+/// assert!(limiter.try_consume(3).await);
+/// // The buckets have room left, but the window is used up, so neither bucket is charged
+/// assert!(!limiter.try_consume(1).await);
+/// # })
+/// ```
+pub struct AllOf<L> {
+    limiters: Vec<L>,
+}
+
+impl<L: RateLimiter> AllOf<L> {
+    /// Creates a new `AllOf` requiring every one of `limiters` to admit a request.
+    ///
+    /// To mix different limiter types, pass them as `Box<dyn RateLimiter>`. Without any
+    /// sub-limiters, every request is admitted.
+    pub fn new(limiters: Vec<L>) -> Self {
+        AllOf { limiters }
+    }
+
+    /// Attempts to admit a request of the given `cost`.
+    ///
+    /// Returns `true` if every sub-limiter admitted the request. Sub-limiters after the first
+    /// one denying it are not asked at all.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume(&mut self, cost: u64) -> bool {
+        for index in 0..self.limiters.len() {
+            if !self.limiters[index].try_acquire(cost).await {
+                trace!("Request of cost {} denied by limiter {}", cost, index);
+                self.roll_back(index, cost).await;
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Attempts to admit a request of the given `cost`, reporting the state of the sub-limiter
+    /// that constrains it the most.
+    ///
+    /// Sub-limiters are charged like with [`AllOf::try_consume`]. A denial reports the state of
+    /// the sub-limiter that denied the request, but its [`Decision::retry_after`] is when all of
+    /// them would admit it: the longest wait of that sub-limiter and the ones after it, which
+    /// are asked through [`RateLimiter::retry_after`] without being charged, or `None` if any of
+    /// them never will. If the request is admitted, the decision with the fewest requests
+    /// remaining is returned.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&mut self, cost: u64) -> Decision {
+        let mut tightest: Option<Decision> = None;
+
+        for index in 0..self.limiters.len() {
+            let decision = self.limiters[index].check(cost).await;
+            if decision.is_allowed() {
+                if tightest
+                    .as_ref()
+                    .map_or(true, |current| decision.remaining() < current.remaining())
+                {
+                    tightest = Some(decision);
+                }
+                continue;
+            }
+
+            trace!("Request of cost {} denied by limiter {}", cost, index);
+            self.roll_back(index, cost).await;
+            let mut retry_after = decision.retry_after();
+            for limiter in self.limiters[index + 1..].iter_mut() {
+                let Some(longest) = retry_after else { break };
+                retry_after = limiter
+                    .retry_after(cost)
+                    .await
+                    .map(|wait| wait.max(longest));
+            }
+            return Decision::Denied {
+                limit: decision.limit(),
+                remaining: decision.remaining(),
+                reset_in: decision.reset_in(),
+                retry_after,
+            };
+        }
+
+        tightest.unwrap_or(Decision::Allowed {
+            limit: u64::MAX,
+            remaining: u64::MAX,
+            reset_in: Duration::ZERO,
+        })
+    }
+
+    /// Returns the sub-limiters in the order they were given.
+    pub fn into_inner(self) -> Vec<L> {
+        self.limiters
+    }
+
+    /// Refunds the sub-limiters before `denied`, which admitted the request.
+    async fn roll_back(&mut self, denied: usize, cost: u64) {
+        for limiter in self.limiters[..denied].iter_mut().rev() {
+            limiter.refund(cost).await;
+        }
+    }
+}
+
+impl<L: RateLimiter> RateLimiter for AllOf<L> {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        Box::pin(self.try_consume(cost))
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        Box::pin(AllOf::check(self, cost))
+    }
+
+    /// Refunds every sub-limiter, as all of them were charged by the most recent admission.
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            for limiter in self.limiters.iter_mut() {
+                limiter.refund(cost).await;
+            }
+        })
+    }
+
+    /// Returns the longest wait of the sub-limiters.
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        Box::pin(async move {
+            let mut longest = Duration::ZERO;
+            for limiter in self.limiters.iter_mut() {
+                longest = longest.max(limiter.retry_after(cost).await?);
+            }
+            Some(longest)
+        })
+    }
+}
+
+#[cfg(all(test, feature = "bucket", feature = "window"))]
+mod tests {
+    use crate::bucket::{LeakyBucket, TokenBucket};
+    use crate::combinators::AllOf;
+    use crate::window::{FixedWindowCounter, SlidingWindowCounter};
+    use crate::RateLimiter;
+    use std::sync::Arc;
+    use tokio::time::{self, Duration};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_denial_charges_no_limiter() {
+        let mut limiter = AllOf::new(vec![
+            TokenBucket::new(5, 1),
+            TokenBucket::new(4, 1),
+            TokenBucket::new(2, 1),
+        ]);
+
+        assert!(limiter.try_consume(2).await);
+        assert!(!limiter.try_consume(1).await);
+        assert!(!limiter.check(1).await.is_allowed());
+        assert!(!limiter.try_consume(6).await);
+
+        let mut remaining = Vec::new();
+        for bucket in limiter.into_inner() {
+            remaining.push(bucket.available_tokens().await);
+        }
+        assert_eq!(remaining, [3, 2, 0]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mixed_limiter_types_roll_back() {
+        let window = Duration::from_secs(60);
+        let token = TokenBucket::shared(10, 1);
+        let leaky = LeakyBucket::shared(10, 1);
+        let sliding = SlidingWindowCounter::shared(10, window);
+        let limiters: Vec<Box<dyn RateLimiter>> = vec![
+            Box::new(token.clone()),
+            Box::new(leaky.clone()),
+            Box::new(sliding.clone()),
+            Box::new(FixedWindowCounter::new(2, window)),
+        ];
+        let mut limiter = AllOf::new(limiters);
+
+        assert!(limiter.try_consume(2).await);
+        for _ in 0..3 {
+            assert!(!limiter.try_consume(1).await);
+            assert!(!limiter.check(1).await.is_allowed());
+        }
+
+        // Only the admitted request is left charged
+        assert_eq!(token.available_tokens().await, 8);
+        assert!(leaky.would_allow_n(8));
+        assert!(!leaky.would_allow_n(9));
+        assert_eq!(sliding.current_usage().await, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_denial_by_first_limiter_charges_the_rest_nothing() {
+        let strict = TokenBucket::shared(1, 1);
+        let global = TokenBucket::shared(10, 1);
+        let mut limiter = AllOf::new(vec![strict.clone(), global.clone()]);
+
+        assert!(limiter.try_consume(1).await);
+        for _ in 0..3 {
+            assert!(!limiter.check(1).await.is_allowed());
+            assert!(!limiter.try_consume(1).await);
+        }
+
+        assert_eq!(strict.available_tokens().await, 0);
+        assert_eq!(global.available_tokens().await, 9);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_denial_never_charges_later_shared_limiter() {
+        let shared = SlidingWindowCounter::shared(5, Duration::from_secs(60));
+        let mut limiter = AllOf::new(vec![
+            Box::new(TokenBucket::new(1, 1)) as Box<dyn RateLimiter>,
+            Box::new(Arc::clone(&shared)),
+        ]);
+
+        assert!(limiter.try_consume(1).await);
+        for _ in 0..3 {
+            let decision = limiter.check(1).await;
+            assert!(!decision.is_allowed());
+            assert_eq!(decision.retry_after(), Some(Duration::from_secs(1)));
+            assert!(!limiter.try_consume(1).await);
+        }
+
+        // Not even a charge that was refunded later shows up in the shared window
+        assert_eq!(shared.current_usage().await, 1);
+        assert_eq!(shared.peak_usage(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_check_reports_longest_retry() {
+        let mut limiter = AllOf::new(vec![
+            Box::new(TokenBucket::new(4, 1)) as Box<dyn RateLimiter>,
+            Box::new(FixedWindowCounter::new(3, Duration::from_secs(60))),
+            Box::new(TokenBucket::new(2, 1)),
+        ]);
+
+        let decision = limiter.check(2).await;
+        assert!(decision.is_allowed());
+        assert_eq!(decision.limit(), 2);
+        assert_eq!(decision.remaining(), 0);
+
+        // The first bucket admits, the second one refills sooner than the window
+        let decision = limiter.check(2).await;
+        assert!(!decision.is_allowed());
+        assert_eq!(decision.retry_after(), Some(Duration::from_secs(60)));
+
+        // A request no limiter can ever admit outweighs any wait
+        assert_eq!(limiter.check(3).await.retry_after(), None);
+
+        time::advance(Duration::from_secs(60)).await;
+        assert!(limiter.try_consume(2).await);
+    }
+
+    #[tokio::test]
+    async fn test_empty_admits_everything() {
+        let mut limiter = AllOf::<TokenBucket>::new(vec![]);
+
+        assert!(limiter.try_consume(u64::MAX).await);
+        assert!(limiter.check(u64::MAX).await.is_allowed());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::decision::Decision;
+use crate::limiter::{BoxFuture, RateLimiter};
+use std::time::Duration;
+
+/// A limiter that admits a request only if both of its sub-limiters admit it.
+///
+/// The first sub-limiter is asked first, and the second one only if the first admits the
+/// request. If the second one denies it, the first one is refunded, so a denied request charges
+/// neither of them. Chain `AndLimiter`s to require more than two limiters.
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::Duration;
+/// use limitr::bucket::TokenBucket;
+/// use limitr::combinators::AndLimiter;
+/// use limitr::window::FixedWindowCounter;
+///
+/// # tokio_test::block_on(async {
+/// let burst = TokenBucket::new(5, 1);
+/// let per_hour = FixedWindowCounter::new(3, Duration::from_secs(3600));
+/// let mut limiter = AndLimiter::new(burst, per_hour);
+///
+/// assert!(limiter.try_consume(3).await);
+/// // The bucket has tokens left, but the hourly window is used up
+/// assert!(!limiter.try_consume(1).await);
+/// # })
+/// ```
+#[derive(Clone)]
+pub struct AndLimiter<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: RateLimiter, B: RateLimiter> AndLimiter<A, B> {
+    /// Creates a new `AndLimiter` requiring both `first` and `second` to admit a request.
+    pub fn new(first: A, second: B) -> Self {
+        AndLimiter { first, second }
+    }
+
+    /// Attempts to admit a request of the given `cost`.
+    ///
+    /// Returns `true` if both sub-limiters admitted the request.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume(&mut self, cost: u64) -> bool {
+        if !self.first.try_acquire(cost).await {
+            return false;
+        }
+        if !self.second.try_acquire(cost).await {
+            trace!("Request of cost {} denied by the second limiter", cost);
+            self.first.refund(cost).await;
+            return false;
+        }
+        true
+    }
+
+    /// Attempts to admit a request of the given `cost`, reporting the state of the sub-limiter
+    /// that constrains it the most.
+    ///
+    /// If the request is admitted, the decision with fewer requests remaining is returned.
+    /// Otherwise the denial of the sub-limiter that turned the request down is returned.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&mut self, cost: u64) -> Decision {
+        let first = self.first.check(cost).await;
+        if !first.is_allowed() {
+            return first;
+        }
+
+        let second = self.second.check(cost).await;
+        if !second.is_allowed() {
+            trace!("Request of cost {} denied by the second limiter", cost);
+            self.first.refund(cost).await;
+            return second;
+        }
+
+        if second.remaining() < first.remaining() {
+            second
+        } else {
+            first
+        }
+    }
+
+    /// Returns the sub-limiters in the order they were given.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A: RateLimiter, B: RateLimiter> RateLimiter for AndLimiter<A, B> {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        Box::pin(self.try_consume(cost))
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        Box::pin(AndLimiter::check(self, cost))
+    }
+
+    /// Refunds both sub-limiters, as both were charged by the most recent admission.
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            self.first.refund(cost).await;
+            self.second.refund(cost).await;
+        })
+    }
+
+    /// Returns the longer wait of the two sub-limiters.
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        Box::pin(async move {
+            let first = self.first.retry_after(cost).await?;
+            let second = self.second.retry_after(cost).await?;
+            Some(first.max(second))
+        })
+    }
+}
+
+#[cfg(all(test, feature = "bucket", feature = "window"))]
+mod tests {
+    use crate::bucket::TokenBucket;
+    use crate::combinators::AndLimiter;
+    use crate::window::FixedWindowCounter;
+    use tokio::time::{self, Duration};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_denial_charges_neither_limiter() {
+        let mut limiter = AndLimiter::new(TokenBucket::new(5, 1), TokenBucket::new(2, 1));
+
+        assert!(limiter.try_consume(2).await);
+        assert!(!limiter.try_consume(1).await);
+        assert!(!limiter.try_consume(4).await);
+
+        let (first, second) = limiter.into_inner();
+        assert_eq!(first.available_tokens().await, 3);
+        assert_eq!(second.available_tokens().await, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_check_reports_tightest_limiter() {
+        let burst = TokenBucket::new(10, 1);
+        let per_minute = FixedWindowCounter::new(4, Duration::from_secs(60));
+        let mut limiter = AndLimiter::new(burst, per_minute);
+
+        let decision = limiter.check(3).await;
+        assert!(decision.is_allowed());
+        assert_eq!(decision.limit(), 4);
+        assert_eq!(decision.remaining(), 1);
+
+        let decision = limiter.check(2).await;
+        assert!(!decision.is_allowed());
+        assert_eq!(decision.retry_after(), Some(Duration::from_secs(60)));
+
+        time::advance(Duration::from_secs(60)).await;
+        assert!(limiter.try_consume(4).await);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::combinators::KeyedLimiter;
+use crate::decision::Decision;
+use crate::limiter::RateLimiter;
+use std::hash::Hash;
+use tokio::sync::Mutex;
+
+/// A limiter that admits a request only if both a global limiter shared by all keys and the
+/// limiter of the request's key admit it, e.g. 10 requests per second per user but at most
+/// 10000 per second overall.
+///
+/// A request is charged to both limiters or to neither. The key's limiter decides first, and the
+/// global limiter is only locked for its own decision, so requests of different keys do not
+/// wait on each other's per-key decisions. If the global limiter denies, the key's charge is
+/// refunded. Until then, concurrent requests of the same key may see that charge and be denied
+/// by their key. Like [`KeyedLimiter`], all methods take `&self`, so a `HierarchicalLimiter`
+/// can be shared between tasks behind an `Arc`.
+///
+/// # Example
+///
+/// ```rust
+/// use limitr::bucket::TokenBucket;
+/// use limitr::combinators::HierarchicalLimiter;
+///
+/// # tokio_test::block_on(async {
+/// let limiter = HierarchicalLimiter::new(TokenBucket::new(3, 1), |_| TokenBucket::new(2, 1));
+///
+/// assert!(limiter.try_consume("alice", 2).await);
+/// assert!(!limiter.try_consume("alice", 1).await);
+/// assert!(limiter.try_consume("bob", 1).await);
+///
+/// // Bob has room left, but the global budget is used up
+/// assert!(!limiter.try_consume("bob", 1).await);
+/// # })
+/// ```
+pub struct HierarchicalLimiter<K, G, L> {
+    global: Mutex<G>,
+    per_key: KeyedLimiter<K, L>,
+}
+
+impl<K: Eq + Hash + Send, G: RateLimiter, L: RateLimiter> HierarchicalLimiter<K, G, L> {
+    /// Creates a new `HierarchicalLimiter` enforcing `global` across all keys, and building the
+    /// limiter of each new key with `factory`, see [`KeyedLimiter::with_key_factory`].
+    pub fn new(global: G, factory: impl Fn(&K) -> L + Send + Sync + 'static) -> Self {
+        HierarchicalLimiter {
+            global: Mutex::new(global),
+            per_key: KeyedLimiter::with_key_factory(factory),
+        }
+    }
+
+    /// Attempts to admit a request of the given `cost` for `key`.
+    ///
+    /// Returns `true` if both the global limiter and the limiter of `key` admitted the request.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume(&self, key: K, cost: u64) -> bool
+    where
+        K: Clone,
+    {
+        self.check(key, cost).await.is_allowed()
+    }
+
+    /// Attempts to admit a request of the given `cost` for `key`, reporting the state of the
+    /// limiter that constrains it the most.
+    ///
+    /// If the request is admitted, the decision with fewer requests remaining is returned.
+    /// Otherwise the denial of the limiter that turned the request down is returned.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&self, key: K, cost: u64) -> Decision
+    where
+        K: Clone,
+    {
+        let key_decision = self.per_key.check(key.clone(), cost).await;
+        if !key_decision.is_allowed() {
+            trace!("Request of cost {} denied by its key", cost);
+            return key_decision;
+        }
+
+        let global_decision = self.global.lock().await.check(cost).await;
+        if !global_decision.is_allowed() {
+            trace!(
+                "Request of cost {} denied by the global limiter, refunding its key",
+                cost
+            );
+            self.per_key.refund(&key, cost).await;
+            return global_decision;
+        }
+
+        if key_decision.remaining() < global_decision.remaining() {
+            key_decision
+        } else {
+            global_decision
+        }
+    }
+
+    /// Returns the per-key limiters, e.g. to evict idle keys with [`KeyedLimiter::evict_idle`].
+    pub fn per_key(&self) -> &KeyedLimiter<K, L> {
+        &self.per_key
+    }
+}
+
+#[cfg(all(test, feature = "bucket", feature = "window"))]
+mod tests {
+    use crate::bucket::TokenBucket;
+    use crate::combinators::HierarchicalLimiter;
+    use crate::window::FixedWindowCounter;
+    use std::sync::Arc;
+    use tokio::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_exhausted_global_charges_no_key() {
+        let limiter = HierarchicalLimiter::new(TokenBucket::new(4, 1), |_| TokenBucket::new(3, 1));
+
+        assert!(limiter.try_consume("alice", 3).await);
+        assert!(limiter.try_consume("bob", 1).await);
+        // Bob's bucket has room for two more, but the global bucket is empty
+        assert!(!limiter.try_consume("bob", 2).await);
+        assert!(!limiter.try_consume("carol", 1).await);
+
+        let decision = limiter.per_key().check("bob", 2).await;
+        assert!(decision.is_allowed());
+        assert_eq!(decision.remaining(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_key_denial_leaves_global_uncharged() {
+        let global = FixedWindowCounter::shared(5, Duration::from_secs(60));
+        let limiter = HierarchicalLimiter::new(Arc::clone(&global), |_| TokenBucket::new(2, 1));
+
+        assert!(limiter.try_consume("alice", 2).await);
+        assert_eq!(global.remaining().await, 3);
+        for _ in 0..10 {
+            assert!(!limiter.try_consume("alice", 1).await);
+        }
+        // Alice's denied requests never reached the global budget
+        assert_eq!(global.remaining().await, 3);
+        let decision = limiter.check("bob", 2).await;
+        assert!(decision.is_allowed());
+        assert_eq!(decision.limit(), 2);
+        assert_eq!(decision.remaining(), 0);
+        assert!(limiter.try_consume("carol", 1).await);
+        assert!(!limiter.try_consume("dave", 1).await);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_requests_respect_both_levels() {
+        // The buckets refill too slowly to matter while the test runs
+        let hourly = Duration::from_secs(3_600);
+        let limiter = Arc::new(HierarchicalLimiter::new(
+            TokenBucket::new(50, 1).with_refill_period(hourly),
+            move |_| TokenBucket::new(10, 1).with_refill_period(hourly),
+        ));
+
+        let tasks: Vec<_> = (0..200)
+            .map(|i| {
+                let limiter = Arc::clone(&limiter);
+                tokio::spawn(async move { limiter.try_consume(i % 8, 1).await as u32 })
+            })
+            .collect();
+        let mut admitted = 0;
+        for task in tasks {
+            admitted += task.await.unwrap();
+        }
+
+        // 8 keys of 10 would admit 80, the global bucket caps them at 50
+        assert_eq!(admitted, 50);
+        let mut per_key_left = 0;
+        for key in 0..8 {
+            per_key_left += limiter.per_key().check(key, 0).await.remaining();
+        }
+        assert_eq!(per_key_left, 80 - 50);
+    }
+}
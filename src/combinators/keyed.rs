@@ -0,0 +1,367 @@
+use crate::cleanup::CleanupHandle;
+use crate::decision::Decision;
+use crate::limiter::RateLimiter;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+/// A limiter that keeps a separate sub-limiter per key, e.g. per client IP or per user.
+///
+/// Sub-limiters are created on first use from the factory passed to [`KeyedLimiter::new`], so
+/// every key starts out with the full budget. A factory passed to
+/// [`KeyedLimiter::with_key_factory`] receives the key instead, so different keys can get
+/// different limits or even different algorithms. All methods take `&self`, so a `KeyedLimiter`
+/// can be shared between tasks behind an `Arc`.
+///
+/// Keys are never dropped on their own. Call [`KeyedLimiter::evict_idle`] periodically, or let
+/// [`KeyedLimiter::spawn_gc`] do so in the background, to release the state of keys that have
+/// gone quiet.
+///
+/// # Example
+///
+/// ```rust
+/// use std::net::{IpAddr, Ipv4Addr};
+/// use std::time::Duration;
+/// use limitr::combinators::KeyedLimiter;
+/// use limitr::window::FixedWindowCounter;
+///
+/// # tokio_test::block_on(async {
+/// let limiter = KeyedLimiter::new(|| FixedWindowCounter::new(2, Duration::from_secs(60)));
+/// let alice = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+/// let bob = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+///
+/// assert!(limiter.try_consume(alice, 2).await);
+/// assert!(!limiter.try_consume(alice, 1).await);
+/// assert!(limiter.try_consume(bob, 1).await);
+/// # })
+/// ```
+pub struct KeyedLimiter<K, L> {
+    limiters: Mutex<HashMap<K, KeyedEntry<L>>>,
+    factory: Box<dyn Fn(&K) -> L + Send + Sync>,
+}
+
+struct KeyedEntry<L> {
+    limiter: L,
+    /// Time the key was last checked
+    last_used: Instant,
+}
+
+impl<K: Eq + Hash + Send, L: RateLimiter> KeyedLimiter<K, L> {
+    /// Creates a new `KeyedLimiter` that builds the sub-limiter of each new key with `factory`.
+    pub fn new(factory: impl Fn() -> L + Send + Sync + 'static) -> Self {
+        Self::with_key_factory(move |_| factory())
+    }
+
+    /// Creates a new `KeyedLimiter` that builds the sub-limiter of each new key by passing the
+    /// key to `factory`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::combinators::KeyedLimiter;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let limiter = KeyedLimiter::with_key_factory(|key: &&str| {
+    ///     let limit = if key.starts_with("admin:") { 100 } else { 2 };
+    ///     FixedWindowCounter::new(limit, Duration::from_secs(60))
+    /// });
+    ///
+    /// assert!(limiter.try_consume("admin:alice", 50).await);
+    /// assert!(!limiter.try_consume("bob", 3).await);
+    /// # })
+    /// ```
+    pub fn with_key_factory(factory: impl Fn(&K) -> L + Send + Sync + 'static) -> Self {
+        KeyedLimiter {
+            limiters: Mutex::new(HashMap::new()),
+            factory: Box::new(factory),
+        }
+    }
+
+    /// Attempts to admit a request of the given `cost` for `key`.
+    ///
+    /// Returns `true` if the sub-limiter of `key` admitted the request.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume(&self, key: K, cost: u64) -> bool {
+        self.check(key, cost).await.is_allowed()
+    }
+
+    /// Attempts to admit a request of the given `cost` for `key`, reporting the state of the
+    /// key's sub-limiter along with the outcome.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::combinators::KeyedLimiter;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let limiter = KeyedLimiter::new(|| FixedWindowCounter::new(10, Duration::from_secs(60)));
+    ///
+    /// let decision = limiter.check("alice", 3).await;
+    /// assert!(decision.is_allowed());
+    /// assert_eq!(decision.remaining(), 7);
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&self, key: K, cost: u64) -> Decision {
+        let mut limiters = self.limiters.lock().await;
+        let entry = self.entry(&mut limiters, key);
+        entry.limiter.check(cost).await
+    }
+
+    /// Gives `cost` back to the sub-limiter of `key`, if the key is still tracked.
+    pub(crate) async fn refund(&self, key: &K, cost: u64) {
+        if let Some(entry) = self.limiters.lock().await.get_mut(key) {
+            entry.limiter.refund(cost).await;
+        }
+    }
+
+    /// Attempts to admit a request of the given `cost` for every key in `keys` at once.
+    ///
+    /// The request is only admitted if the sub-limiters of all keys admit it. If any of them
+    /// denies, the keys charged so far are refunded, so a denied request charges no key at all.
+    /// Unlike separate calls to [`KeyedLimiter::try_consume`], the keys are checked under a
+    /// single lock, so concurrent requests never observe a partially charged request.
+    ///
+    /// A key listed more than once is charged once per occurrence.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::combinators::KeyedLimiter;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let limiter = KeyedLimiter::with_key_factory(|key: &&str| {
+    ///     let limit = if *key == "global" { 100 } else { 2 };
+    ///     FixedWindowCounter::new(limit, Duration::from_secs(60))
+    /// });
+    ///
+    /// assert!(limiter.try_consume_all(&["user:1", "org:1", "global"], 2).await);
+    /// assert!(!limiter.try_consume_all(&["user:2", "org:1", "global"], 1).await);
+    /// assert!(limiter.try_consume_all(&["user:2", "global"], 2).await);
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume_all(&self, keys: &[K], cost: u64) -> bool
+    where
+        K: Clone,
+    {
+        let mut limiters = self.limiters.lock().await;
+
+        for (charged, key) in keys.iter().enumerate() {
+            let entry = self.entry(&mut limiters, key.clone());
+            if entry.limiter.try_acquire(cost).await {
+                continue;
+            }
+
+            trace!(
+                "Request of cost {} denied, refunding {} keys",
+                cost,
+                charged
+            );
+            for key in keys[..charged].iter().rev() {
+                if let Some(entry) = limiters.get_mut(key) {
+                    entry.limiter.refund(cost).await;
+                }
+            }
+            return false;
+        }
+
+        true
+    }
+
+    /// Removes the sub-limiters of all keys that have not been checked for at least `idle`.
+    ///
+    /// An evicted key starts over with a fresh sub-limiter the next time it is used. Choose
+    /// `idle` no shorter than the time a sub-limiter needs to fully recover, such as the window
+    /// duration, so evicting a key never hands out more than its limit.
+    ///
+    /// Returns the number of evicted keys.
+    pub async fn evict_idle(&self, idle: Duration) -> usize {
+        let now = Instant::now();
+        let mut limiters = self.limiters.lock().await;
+        let before = limiters.len();
+
+        limiters.retain(|_, entry| now.duration_since(entry.last_used) < idle);
+
+        let evicted = before - limiters.len();
+        trace!(
+            "Evicted {} idle keys, {} keys left",
+            evicted,
+            limiters.len()
+        );
+        evicted
+    }
+
+    /// Spawns a background task that evicts keys idle for at least `idle`, every `every`.
+    ///
+    /// This runs [`KeyedLimiter::evict_idle`] on a schedule, so state of departed clients is
+    /// released without wiring up a loop by hand. The task only holds a weak reference, so it
+    /// stops once the limiter is dropped, and it is aborted when the returned
+    /// [`CleanupHandle`] is dropped. Must be called from within a Tokio runtime. A zero `every`
+    /// is treated as one millisecond.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    /// use limitr::combinators::KeyedLimiter;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let window = Duration::from_secs(60);
+    /// let limiter: Arc<KeyedLimiter<String, _>> =
+    ///     Arc::new(KeyedLimiter::new(move || FixedWindowCounter::new(100, window)));
+    ///
+    /// // Keep the handle alive for as long as the limiter should be swept
+    /// let _gc = limiter.spawn_gc(Duration::from_secs(30), window);
+    /// # })
+    /// ```
+    pub fn spawn_gc(self: &Arc<Self>, every: Duration, idle: Duration) -> CleanupHandle
+    where
+        K: Sync + 'static,
+        L: 'static,
+    {
+        CleanupHandle::spawn(self, every, move |limiter| async move {
+            limiter.evict_idle(idle).await;
+        })
+    }
+
+    /// Returns the entry of `key`, creating it if needed, and marks it as used.
+    fn entry<'a>(
+        &self,
+        limiters: &'a mut HashMap<K, KeyedEntry<L>>,
+        key: K,
+    ) -> &'a mut KeyedEntry<L> {
+        let entry = limiters.entry(key).or_insert_with_key(|key| KeyedEntry {
+            limiter: (self.factory)(key),
+            last_used: Instant::now(),
+        });
+
+        entry.last_used = Instant::now();
+        entry
+    }
+
+    /// Returns the number of keys currently tracked.
+    pub async fn len(&self) -> usize {
+        self.limiters.lock().await.len()
+    }
+
+    /// Returns `true` if no keys are currently tracked.
+    pub async fn is_empty(&self) -> bool {
+        self.limiters.lock().await.is_empty()
+    }
+}
+
+#[cfg(all(test, feature = "bucket", feature = "window"))]
+mod tests {
+    use crate::bucket::{LeakyBucket, TokenBucket};
+    use crate::combinators::KeyedLimiter;
+    use crate::window::{FixedWindowCounter, SlidingWindowCounter};
+    use crate::RateLimiter;
+    use std::sync::Arc;
+    use tokio::time::{advance, Duration};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keys_have_independent_budgets() {
+        let limiter = KeyedLimiter::new(|| TokenBucket::new(2, 1));
+
+        assert!(limiter.try_consume("a", 2).await);
+        assert!(!limiter.try_consume("a", 1).await);
+        assert!(limiter.try_consume("b", 2).await);
+        assert_eq!(limiter.len().await, 2);
+
+        let decision = limiter.check("a", 1).await;
+        assert_eq!(decision.retry_after(), Some(Duration::from_secs(1)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_evicts_only_idle_keys() {
+        let limiter = KeyedLimiter::new(|| TokenBucket::new(2, 1));
+        assert!(limiter.is_empty().await);
+
+        assert!(limiter.try_consume("a", 1).await);
+        advance(Duration::from_secs(3)).await;
+        assert!(limiter.try_consume("b", 1).await);
+
+        assert_eq!(limiter.evict_idle(Duration::from_secs(3)).await, 1);
+        assert_eq!(limiter.len().await, 1);
+
+        // The remaining key keeps its state
+        assert!(limiter.try_consume("b", 1).await);
+        assert!(!limiter.try_consume("b", 1).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_consume_all_rolls_back_mixed_limiters() {
+        let limiter = KeyedLimiter::with_key_factory(|key: &&str| -> Box<dyn RateLimiter> {
+            match *key {
+                "user" => Box::new(TokenBucket::new(5, 1)),
+                "route" => Box::new(SlidingWindowCounter::new(5, Duration::from_secs(10))),
+                "org" => Box::new(LeakyBucket::new(5, 1)),
+                _ => Box::new(FixedWindowCounter::new(3, Duration::from_secs(10))),
+            }
+        });
+        let keys = ["user", "route", "org", "global"];
+
+        assert!(limiter.try_consume_all(&keys, 2).await);
+        assert!(!limiter.try_consume_all(&keys, 2).await);
+
+        // Every sub-limiter still holds exactly what the first request left
+        for key in ["user", "route", "org"] {
+            assert_eq!(limiter.check(key, 0).await.remaining(), 3, "{}", key);
+        }
+        assert_eq!(limiter.check("global", 0).await.remaining(), 1);
+
+        assert!(limiter.try_consume_all(&keys, 1).await);
+        assert!(!limiter.try_consume_all(&keys, 1).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_consume_all_charges_repeated_keys() {
+        let limiter = KeyedLimiter::new(|| TokenBucket::new(3, 1));
+
+        assert!(!limiter.try_consume_all(&["a", "a"], 2).await);
+        assert_eq!(limiter.check("a", 0).await.remaining(), 3);
+        assert!(limiter.try_consume_all(&["a", "a", "a"], 1).await);
+        assert_eq!(limiter.check("a", 0).await.remaining(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gc_task_evicts_idle_keys() {
+        let limiter = Arc::new(KeyedLimiter::new(|| TokenBucket::new(2, 1)));
+        let gc = limiter.spawn_gc(Duration::from_secs(10), Duration::from_secs(5));
+        assert!(limiter.try_consume("a", 1).await);
+
+        advance(Duration::from_secs(10)).await;
+        tokio::task::yield_now().await;
+        assert!(limiter.is_empty().await);
+
+        // Once the handle is dropped, keys stay around
+        assert!(limiter.try_consume("a", 1).await);
+        drop(gc);
+        advance(Duration::from_secs(20)).await;
+        tokio::task::yield_now().await;
+        assert_eq!(limiter.len().await, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gc_with_zero_interval_keeps_running() {
+        let limiter = Arc::new(KeyedLimiter::new(|| TokenBucket::new(2, 1)));
+        let gc = limiter.spawn_gc(Duration::ZERO, Duration::from_secs(5));
+        assert!(limiter.try_consume("a", 1).await);
+
+        advance(Duration::from_secs(6)).await;
+        tokio::task::yield_now().await;
+        assert!(!gc.is_finished());
+        assert!(limiter.is_empty().await);
+    }
+}
@@ -0,0 +1,60 @@
+//! Combinators composing several rate limiters into one.
+//!
+//! The combinators work with anything implementing [`RateLimiter`](crate::RateLimiter), and all
+//! but [`KeyedLimiter`] and [`HierarchicalLimiter`], which take a key with every request,
+//! implement the trait themselves, so they can be nested.
+//!
+//! - **AndLimiter**: Admits a request only if both of its limiters admit it, charging both.
+//!   Useful for stacking limits, such as a burst allowance on top of an hourly quota.
+//! - **AllOf**: Admits a request only if every one of its limiters admits it, refunding those
+//!   already charged when a later one denies it. The `AndLimiter` for any number of limiters.
+//! - **OrLimiter**: Admits a request if any of its limiters admits it, charging only the first
+//!   one that does. Useful for fallback tiers such as a strict per-user limit backed by a shared
+//!   burst pool.
+//! - **HierarchicalLimiter**: Admits a request only if both a global limiter and the limiter
+//!   of its key admit it, such as 10 requests per second per user but 10000 overall.
+//! - **KeyedLimiter**: Keeps a separate limiter per key, such as a client IP or user id,
+//!   creating them on first use.
+//!
+//! The and, or and keyed combinators can also be built with the fluent methods of
+//! [`RateLimiterExt`](crate::RateLimiterExt).
+//!
+//! # Example
+//!
+//! ```rust
+//! use limitr::bucket::TokenBucket;
+//! use limitr::combinators::OrLimiter;
+//!
+//! # tokio_test::block_on(async {
+//! let per_user = TokenBucket::new(2, 1);
+//! let burst_pool = TokenBucket::new(100, 10);
+//! let mut limiter = OrLimiter::new(vec![per_user, burst_pool]);
+//!
+//! for _ in 0..5 {
+//!     assert!(limiter.try_consume(1).await);
+//! }
+//! # })
+//! ```
+
+mod all_of;
+mod and;
+mod hierarchical;
+mod keyed;
+mod or;
+
+pub use all_of::*;
+pub use and::*;
+pub use hierarchical::*;
+pub use keyed::*;
+pub use or::*;
+
+#[cfg(feature = "bucket")]
+const _: () = {
+    use crate::bucket::TokenBucket;
+
+    crate::assert_send_sync::<AndLimiter<TokenBucket, TokenBucket>>();
+    crate::assert_send_sync::<AllOf<TokenBucket>>();
+    crate::assert_send_sync::<OrLimiter<TokenBucket>>();
+    crate::assert_send_sync::<HierarchicalLimiter<String, TokenBucket, TokenBucket>>();
+    crate::assert_send_sync::<KeyedLimiter<String, TokenBucket>>();
+};
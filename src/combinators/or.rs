@@ -0,0 +1,199 @@
+use crate::decision::Decision;
+use crate::limiter::{BoxFuture, RateLimiter};
+use std::time::Duration;
+
+/// A limiter that admits a request if any of its sub-limiters admits it.
+///
+/// Sub-limiters are tried in priority order and only the first one admitting the request is
+/// charged; the others are left untouched. A request is denied only if every sub-limiter denies
+/// it, in which case nothing is charged at all.
+///
+/// # Example
+///
+/// ```rust
+/// use limitr::bucket::TokenBucket;
+/// use limitr::combinators::OrLimiter;
+///
+/// # tokio_test::block_on(async {
+/// let mut limiter = OrLimiter::new(vec![TokenBucket::new(1, 1), TokenBucket::new(1, 1)]);
+///
+/// assert_eq!(limiter.try_consume_tier(1).await, Some(0));
+/// assert_eq!(limiter.try_consume_tier(1).await, Some(1));
+/// assert_eq!(limiter.try_consume_tier(1).await, None);
+/// # })
+/// ```
+pub struct OrLimiter<L> {
+    limiters: Vec<L>,
+    /// Sub-limiter charged by the latest admission
+    last_tier: Option<usize>,
+}
+
+impl<L: RateLimiter> OrLimiter<L> {
+    /// Creates a new `OrLimiter` from sub-limiters in priority order.
+    ///
+    /// To mix different limiter types, pass them as `Box<dyn RateLimiter>`.
+    pub fn new(limiters: Vec<L>) -> Self {
+        OrLimiter {
+            limiters,
+            last_tier: None,
+        }
+    }
+
+    /// Attempts to admit a request of the given `cost`.
+    ///
+    /// Returns `true` if one of the sub-limiters admitted the request.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume(&mut self, cost: u64) -> bool {
+        self.try_consume_tier(cost).await.is_some()
+    }
+
+    /// Attempts to admit a request of the given `cost`, reporting which sub-limiter was charged.
+    ///
+    /// Returns the index of the sub-limiter that admitted the request, or `None` if all denied.
+    pub async fn try_consume_tier(&mut self, cost: u64) -> Option<usize> {
+        for (index, limiter) in self.limiters.iter_mut().enumerate() {
+            if limiter.try_acquire(cost).await {
+                trace!("Request of cost {} admitted by tier {}", cost, index);
+                self.last_tier = Some(index);
+                return Some(index);
+            }
+        }
+
+        trace!("Request of cost {} denied by all tiers", cost);
+        None
+    }
+
+    /// Attempts to admit a request of the given `cost`, reporting the state of the deciding
+    /// sub-limiter.
+    ///
+    /// If a sub-limiter admits the request, its decision is returned. Otherwise the denial of
+    /// the sub-limiter that can admit the request soonest is returned. Without any sub-limiters,
+    /// every request is denied for good.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&mut self, cost: u64) -> Decision {
+        let mut soonest: Option<Decision> = None;
+
+        for (index, limiter) in self.limiters.iter_mut().enumerate() {
+            let decision = limiter.check(cost).await;
+            if decision.is_allowed() {
+                trace!("Request of cost {} admitted by tier {}", cost, index);
+                self.last_tier = Some(index);
+                return decision;
+            }
+
+            let sooner = match (&soonest, decision.retry_after()) {
+                (None, _) => true,
+                (Some(current), Some(retry_after)) => current
+                    .retry_after()
+                    .map_or(true, |current| retry_after < current),
+                (Some(_), None) => false,
+            };
+            if sooner {
+                soonest = Some(decision);
+            }
+        }
+
+        trace!("Request of cost {} denied by all tiers", cost);
+        soonest.unwrap_or(Decision::Denied {
+            limit: 0,
+            remaining: 0,
+            reset_in: Duration::MAX,
+            retry_after: None,
+        })
+    }
+}
+
+impl<L: RateLimiter> RateLimiter for OrLimiter<L> {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        Box::pin(self.try_consume(cost))
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        Box::pin(OrLimiter::check(self, cost))
+    }
+
+    /// Refunds the sub-limiter that admitted the most recent request.
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(limiter) = self.last_tier.and_then(|tier| self.limiters.get_mut(tier)) {
+                limiter.refund(cost).await;
+            }
+        })
+    }
+
+    /// Returns the shortest wait of the sub-limiters.
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        Box::pin(async move {
+            let mut soonest: Option<Duration> = None;
+            for limiter in self.limiters.iter_mut() {
+                if let Some(wait) = limiter.retry_after(cost).await {
+                    soonest = Some(soonest.map_or(wait, |current| current.min(wait)));
+                }
+            }
+            soonest
+        })
+    }
+}
+
+#[cfg(all(test, feature = "bucket", feature = "window"))]
+mod tests {
+    use crate::bucket::{LeakyBucket, TokenBucket};
+    use crate::combinators::OrLimiter;
+    use crate::window::FixedWindowCounter;
+    use crate::RateLimiter;
+    use tokio::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_charges_first_admitting_limiter_only() {
+        let mut limiter = OrLimiter::new(vec![TokenBucket::new(2, 1), TokenBucket::new(3, 1)]);
+
+        assert_eq!(limiter.try_consume_tier(2).await, Some(0));
+        assert_eq!(limiter.try_consume_tier(2).await, Some(1));
+
+        let [strict, pool] = &mut limiter.limiters[..] else {
+            unreachable!()
+        };
+        assert_eq!(strict.available_tokens().await, 0);
+        assert_eq!(pool.available_tokens().await, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_denial_charges_nothing() {
+        let mut limiter = OrLimiter::new(vec![TokenBucket::new(2, 1), TokenBucket::new(3, 1)]);
+
+        assert!(!limiter.try_consume(4).await);
+        assert_eq!(limiter.limiters[0].available_tokens().await, 2);
+        assert_eq!(limiter.limiters[1].available_tokens().await, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mixed_limiter_types() {
+        let limiters: Vec<Box<dyn RateLimiter>> = vec![
+            Box::new(LeakyBucket::new(1, 1)),
+            Box::new(FixedWindowCounter::new(2, Duration::from_secs(1))),
+        ];
+        let mut limiter = OrLimiter::new(limiters);
+
+        assert_eq!(limiter.try_consume_tier(1).await, Some(0));
+        assert_eq!(limiter.try_consume_tier(1).await, Some(1));
+        assert_eq!(limiter.try_consume_tier(1).await, Some(1));
+        assert_eq!(limiter.try_consume_tier(1).await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_check_reports_soonest_denial() {
+        let mut limiter = OrLimiter::new(vec![TokenBucket::new(2, 1), TokenBucket::new(4, 4)]);
+        assert_eq!(limiter.check(0).await.limit(), 2);
+
+        assert!(limiter.try_consume(2).await);
+        assert!(limiter.try_consume(4).await);
+
+        let decision = limiter.check(2).await;
+        assert!(!decision.is_allowed());
+        assert_eq!(decision.limit(), 4);
+        assert_eq!(decision.retry_after(), Some(Duration::from_secs(1)));
+
+        let mut empty = OrLimiter::<TokenBucket>::new(vec![]);
+        assert_eq!(empty.check(1).await.retry_after(), None);
+    }
+}
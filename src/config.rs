@@ -0,0 +1,215 @@
+//! Limiters described by plain data, e.g. to pick the algorithm from a config file at runtime.
+
+use crate::bucket::{LeakyBucket, TokenBucket};
+use crate::error::LimitrError;
+use crate::limiter::RateLimiter;
+use crate::quota::Quota;
+use crate::window::{FixedWindowCounter, SlidingWindowCounter};
+
+/// The rate limiting algorithms a [`LimiterConfig`] can describe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum LimiterKind {
+    /// A [`TokenBucket`].
+    TokenBucket,
+    /// A [`LeakyBucket`].
+    LeakyBucket,
+    /// A [`FixedWindowCounter`].
+    FixedWindow,
+    /// A [`SlidingWindowCounter`].
+    SlidingWindow,
+}
+
+/// The algorithm and parameters of a rate limiter, built into a boxed [`RateLimiter`] with
+/// [`LimiterConfig::build`].
+///
+/// With the `serde` feature the variant is selected by a `type` field, and the windows take
+/// their rate in the string form of a [`Quota`]:
+///
+/// ```yaml
+/// type: sliding_window
+/// rate: 100/min
+/// ```
+///
+/// # Example
+///
+/// ```rust
+/// use limitr::{LimiterConfig, LimiterKind};
+///
+/// # tokio_test::block_on(async {
+/// let config = LimiterConfig::TokenBucket {
+///     capacity: 2,
+///     refill_rate: 1,
+/// };
+/// assert_eq!(config.kind(), LimiterKind::TokenBucket);
+///
+/// let mut limiter = config.build();
+/// assert!(limiter.try_acquire(2).await);
+/// assert!(!limiter.try_acquire(1).await);
+/// # })
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum LimiterConfig {
+    /// A [`TokenBucket`] holding up to `capacity` tokens and refilling `refill_rate` per second.
+    TokenBucket {
+        /// The maximum number of tokens the bucket can hold.
+        capacity: u64,
+        /// Number of tokens added to the bucket every second.
+        refill_rate: u64,
+    },
+    /// A [`LeakyBucket`] holding up to `capacity` requests and leaking `leak_rate` per second.
+    LeakyBucket {
+        /// The maximum number of requests the bucket can hold.
+        capacity: usize,
+        /// Number of requests leaked from the bucket every second.
+        leak_rate: usize,
+    },
+    /// A [`FixedWindowCounter`] admitting the amount of `rate` per period.
+    FixedWindow {
+        /// The limit and the window duration.
+        #[cfg_attr(feature = "serde", serde(with = "crate::quota::string_form"))]
+        rate: Quota,
+    },
+    /// A [`SlidingWindowCounter`] admitting the amount of `rate` within any one period.
+    SlidingWindow {
+        /// The limit and the window duration.
+        #[cfg_attr(feature = "serde", serde(with = "crate::quota::string_form"))]
+        rate: Quota,
+    },
+}
+
+impl LimiterConfig {
+    /// Returns the algorithm the config describes.
+    pub fn kind(&self) -> LimiterKind {
+        match self {
+            LimiterConfig::TokenBucket { .. } => LimiterKind::TokenBucket,
+            LimiterConfig::LeakyBucket { .. } => LimiterKind::LeakyBucket,
+            LimiterConfig::FixedWindow { .. } => LimiterKind::FixedWindow,
+            LimiterConfig::SlidingWindow { .. } => LimiterKind::SlidingWindow,
+        }
+    }
+
+    /// Builds the described limiter, starting with its full capacity available.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`LimiterConfig::try_build`].
+    pub fn build(self) -> Box<dyn RateLimiter> {
+        match self.try_build() {
+            Ok(limiter) => limiter,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Builds the described limiter, or fails if its parameters are invalid, e.g. a bucket
+    /// with a capacity of zero.
+    ///
+    /// Use this for configs loaded at runtime, which may contain anything.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`LimitrError::InvalidConfig`] if the `try_new` constructor of the limiter
+    /// rejects the parameters.
+    pub fn try_build(self) -> Result<Box<dyn RateLimiter>, LimitrError> {
+        Ok(match self {
+            LimiterConfig::TokenBucket {
+                capacity,
+                refill_rate,
+            } => Box::new(TokenBucket::try_new(capacity, refill_rate)?),
+            LimiterConfig::LeakyBucket {
+                capacity,
+                leak_rate,
+            } => Box::new(LeakyBucket::try_new(capacity, leak_rate)?),
+            LimiterConfig::FixedWindow { rate } => Box::new(FixedWindowCounter::from_quota(rate)?),
+            LimiterConfig::SlidingWindow { rate } => {
+                Box::new(SlidingWindowCounter::from_quota(rate)?)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InvalidConfigReason, LimiterConfig, LimiterKind, LimitrError};
+
+    fn configs() -> [LimiterConfig; 4] {
+        [
+            LimiterConfig::TokenBucket {
+                capacity: 3,
+                refill_rate: 1,
+            },
+            LimiterConfig::LeakyBucket {
+                capacity: 3,
+                leak_rate: 1,
+            },
+            LimiterConfig::FixedWindow {
+                rate: "3/min".parse().unwrap(),
+            },
+            LimiterConfig::SlidingWindow {
+                rate: "3/min".parse().unwrap(),
+            },
+        ]
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_builds_every_kind() {
+        let kinds = [
+            LimiterKind::TokenBucket,
+            LimiterKind::LeakyBucket,
+            LimiterKind::FixedWindow,
+            LimiterKind::SlidingWindow,
+        ];
+        for (config, kind) in configs().into_iter().zip(kinds) {
+            assert_eq!(config.kind(), kind);
+
+            let mut limiter = config.build();
+            assert!(limiter.try_acquire(3).await, "{:?}", kind);
+            assert!(!limiter.try_acquire(1).await, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn test_try_build_rejects_zero_capacity() {
+        let config = LimiterConfig::LeakyBucket {
+            capacity: 0,
+            leak_rate: 1,
+        };
+        assert_eq!(
+            config.try_build().err(),
+            Some(LimitrError::InvalidConfig(
+                InvalidConfigReason::ZeroCapacity
+            ))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserializes_tagged_config() {
+        let config: LimiterConfig =
+            serde_json::from_str(r#"{"type": "sliding_window", "rate": "3/min"}"#).unwrap();
+        assert_eq!(config, configs()[3]);
+
+        let config: LimiterConfig =
+            serde_json::from_str(r#"{"type": "token_bucket", "capacity": 3, "refill_rate": 1}"#)
+                .unwrap();
+        assert_eq!(config, configs()[0]);
+
+        for config in configs() {
+            let json = serde_json::to_string(&config).unwrap();
+            assert_eq!(
+                serde_json::from_str::<LimiterConfig>(&json).unwrap(),
+                config
+            );
+        }
+        assert!(serde_json::from_str::<LimiterConfig>(r#"{"type": "gcra"}"#).is_err());
+    }
+}
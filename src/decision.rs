@@ -0,0 +1,170 @@
+//! The outcome of a rate limiting check, including the state needed for response headers.
+
+use crate::error::LimitrError;
+use std::time::Duration;
+
+/// The outcome of checking a request against a limiter.
+///
+/// Besides telling whether the request was admitted, a decision carries a snapshot of the
+/// limiter taken while checking the request, e.g. to populate `X-RateLimit-*` and
+/// `Retry-After` headers without a second, racy call into the limiter.
+///
+/// The enum is `#[non_exhaustive]`, as outcomes like [`Decision::AllowedSoftExceeded`] may be
+/// added over time. Matches on it need a wildcard arm, so prefer the accessors such as
+/// [`Decision::is_allowed`] and [`Decision::retry_after`] where they suffice.
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::Duration;
+/// use limitr::window::FixedWindowCounter;
+///
+/// # tokio_test::block_on(async {
+/// let counter = FixedWindowCounter::new(1, Duration::from_secs(60));
+///
+/// let decision = counter.check(1).await;
+/// assert!(decision.is_allowed());
+/// assert_eq!(decision.remaining(), 0);
+///
+/// let decision = counter.check(1).await;
+/// assert!(!decision.is_allowed());
+/// assert!(decision.retry_after().unwrap() <= Duration::from_secs(60));
+/// # })
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Decision {
+    /// The request was admitted and its cost consumed.
+    Allowed {
+        /// The most the limiter admits at once.
+        limit: u64,
+        /// What is left after admitting the request.
+        remaining: u64,
+        /// Time until the limiter has fully recovered, `Duration::MAX` if it never does.
+        reset_in: Duration,
+    },
+    /// The request was admitted and its cost consumed, but it left more in use than the soft
+    /// limit of the limiter, e.g. to warn clients approaching their quota.
+    AllowedSoftExceeded {
+        /// The most the limiter admits at once.
+        limit: u64,
+        /// What is left after admitting the request.
+        remaining: u64,
+        /// Time until the limiter has fully recovered, `Duration::MAX` if it never does.
+        reset_in: Duration,
+    },
+    /// The request was denied and nothing was consumed.
+    Denied {
+        /// The most the limiter admits at once.
+        limit: u64,
+        /// What is currently left.
+        remaining: u64,
+        /// Time until the limiter has fully recovered, `Duration::MAX` if it never does.
+        reset_in: Duration,
+        /// Time until the same request would be admitted, `None` if it never will be.
+        retry_after: Option<Duration>,
+    },
+}
+
+impl Decision {
+    /// Returns whether the request was admitted, whether or not it exceeded the soft limit.
+    pub fn is_allowed(&self) -> bool {
+        matches!(
+            self,
+            Decision::Allowed { .. } | Decision::AllowedSoftExceeded { .. }
+        )
+    }
+
+    /// Returns whether the request was admitted beyond the soft limit of the limiter.
+    pub fn is_soft_exceeded(&self) -> bool {
+        matches!(self, Decision::AllowedSoftExceeded { .. })
+    }
+
+    /// Returns the most the limiter admits at once.
+    pub fn limit(&self) -> u64 {
+        match *self {
+            Decision::Allowed { limit, .. }
+            | Decision::AllowedSoftExceeded { limit, .. }
+            | Decision::Denied { limit, .. } => limit,
+        }
+    }
+
+    /// Returns what is left in the limiter after the decision.
+    pub fn remaining(&self) -> u64 {
+        match *self {
+            Decision::Allowed { remaining, .. }
+            | Decision::AllowedSoftExceeded { remaining, .. }
+            | Decision::Denied { remaining, .. } => remaining,
+        }
+    }
+
+    /// Returns the time until the limiter has fully recovered.
+    pub fn reset_in(&self) -> Duration {
+        match *self {
+            Decision::Allowed { reset_in, .. }
+            | Decision::AllowedSoftExceeded { reset_in, .. }
+            | Decision::Denied { reset_in, .. } => reset_in,
+        }
+    }
+
+    /// Returns how long to wait before the request would be admitted.
+    ///
+    /// This is zero for admitted requests and `None` for requests that can never be admitted.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match *self {
+            Decision::Allowed { .. } | Decision::AllowedSoftExceeded { .. } => Some(Duration::ZERO),
+            Decision::Denied { retry_after, .. } => retry_after,
+        }
+    }
+
+    /// Returns the decision if the request was admitted, or [`LimitrError::NotUntil`] with the
+    /// time until it would be admitted.
+    ///
+    /// A request that can never be admitted fails with `NotUntil(Duration::MAX)`, the same
+    /// convention as [`Decision::reset_in`]. This lets handlers reject requests with `?`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    /// use limitr::LimitrError;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(1, Duration::from_secs(60));
+    /// assert!(counter.check(1).await.into_result().is_ok());
+    ///
+    /// match counter.check(1).await.into_result() {
+    ///     Err(LimitrError::NotUntil(wait)) => assert!(wait <= Duration::from_secs(60)),
+    ///     other => panic!("unexpected {:?}", other),
+    /// }
+    /// # })
+    /// ```
+    pub fn into_result(self) -> Result<Self, LimitrError> {
+        if self.is_allowed() {
+            return Ok(self);
+        }
+        let retry_after = self.retry_after().unwrap_or(Duration::MAX);
+        Err(LimitrError::NotUntil(retry_after))
+    }
+
+    /// Turns an admission that left more than `soft_limit` in use into
+    /// [`Decision::AllowedSoftExceeded`], leaving other decisions untouched.
+    #[cfg(any(feature = "bucket", feature = "window"))]
+    pub(crate) fn flag_soft_limit(self, soft_limit: Option<u64>) -> Self {
+        match self {
+            Decision::Allowed {
+                limit,
+                remaining,
+                reset_in,
+            } if soft_limit.is_some_and(|soft| limit.saturating_sub(remaining) > soft) => {
+                Decision::AllowedSoftExceeded {
+                    limit,
+                    remaining,
+                    reset_in,
+                }
+            }
+            decision => decision,
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Error type shared by the fallible operations of this crate.
+
+use std::error;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Errors returned by fallible limiter operations.
+///
+/// The limiters return this type from their validating constructors up to waiting for
+/// admission. Parsing a [`Quota`](crate::Quota) fails with the more specific
+/// [`ParseQuotaError`], which converts into it with `?`. It is `Send + Sync + 'static`, so it
+/// converts into the boxed errors of `tower` and `axum` with `?`. New variants may be added as
+/// the crate grows.
+///
+/// The error is not `Clone`, as [`LimitrError::Backend`] holds an arbitrary error. Errors
+/// compare equal if they are the same variant with equal fields, and backend errors if their
+/// messages are equal.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum LimitrError {
+    /// The wall clock reports a time before a previously observed point, such as the UNIX epoch.
+    #[error("clock went backwards")]
+    ClockWentBackwards,
+    /// A limiter was given a configuration it cannot operate with, for the given reason.
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(InvalidConfigReason),
+    /// A request asked for more than the limiter can ever provide.
+    #[error("requested amount {requested} exceeds capacity {capacity}")]
+    AmountExceedsCapacity {
+        /// The amount that was requested.
+        requested: u64,
+        /// The most the limiter can provide at once.
+        capacity: u64,
+    },
+    /// A request within the limit can never be admitted again, e.g. by a one-off
+    /// [`QuotaLimiter`](crate::window::QuotaLimiter) that is used up.
+    #[error("limiter is exhausted and never admits the request")]
+    Exhausted,
+    /// A request could not be admitted before its deadline.
+    #[error("deadline exceeded")]
+    DeadlineExceeded,
+    /// A request asked for nothing, which is most likely a bug in the caller.
+    #[error("requested amount is zero")]
+    ZeroAmount,
+    /// The limiter is draining for shutdown and admits no further requests, see
+    /// [`SlidingWindowCounter::begin_drain`](crate::window::SlidingWindowCounter::begin_drain).
+    #[error("limiter is shutting down")]
+    ShuttingDown,
+    /// A request was denied and would be admitted after the given time, `Duration::MAX` if
+    /// never, see [`Decision::into_result`](crate::Decision::into_result).
+    #[error("request denied, retry after {0:?}")]
+    NotUntil(Duration),
+    /// A quota could not be parsed.
+    #[error("invalid quota: {0}")]
+    Parse(#[from] ParseQuotaError),
+    /// The storage behind a limiter failed, e.g. for limiters keeping their state in an external
+    /// store.
+    #[error("backend error: {0}")]
+    Backend(#[source] Box<dyn error::Error + Send + Sync>),
+}
+
+impl PartialEq for LimitrError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (LimitrError::ClockWentBackwards, LimitrError::ClockWentBackwards)
+            | (LimitrError::Exhausted, LimitrError::Exhausted)
+            | (LimitrError::DeadlineExceeded, LimitrError::DeadlineExceeded)
+            | (LimitrError::ZeroAmount, LimitrError::ZeroAmount)
+            | (LimitrError::ShuttingDown, LimitrError::ShuttingDown) => true,
+            (LimitrError::InvalidConfig(a), LimitrError::InvalidConfig(b)) => a == b,
+            (
+                LimitrError::AmountExceedsCapacity {
+                    requested,
+                    capacity,
+                },
+                LimitrError::AmountExceedsCapacity {
+                    requested: other_requested,
+                    capacity: other_capacity,
+                },
+            ) => requested == other_requested && capacity == other_capacity,
+            (LimitrError::NotUntil(a), LimitrError::NotUntil(b)) => a == b,
+            (LimitrError::Parse(a), LimitrError::Parse(b)) => a == b,
+            (LimitrError::Backend(a), LimitrError::Backend(b)) => a.to_string() == b.to_string(),
+            _ => false,
+        }
+    }
+}
+
+/// Why a configuration was rejected with [`LimitrError::InvalidConfig`].
+///
+/// New reasons may be added as the crate grows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum InvalidConfigReason {
+    /// A bucket has no capacity, so it could never admit a request.
+    #[error("capacity must not be zero")]
+    ZeroCapacity,
+    /// A bucket is never refilled, so a request waiting for tokens would wait forever.
+    #[error("refill rate is zero, tokens are never replenished")]
+    ZeroRate,
+    /// A rate is too large for the limiter to represent.
+    #[error("rate is too large")]
+    RateTooLarge,
+    /// An amount is too large for the limiter to represent.
+    #[error("amount is too large")]
+    AmountTooLarge,
+    /// A period is zero.
+    #[error("period must not be zero")]
+    ZeroPeriod,
+    /// A window duration is zero.
+    #[error("window duration must not be zero")]
+    ZeroWindow,
+    /// A window duration is longer than `u64::MAX` nanoseconds.
+    #[error("window duration is too large")]
+    WindowTooLarge,
+    /// A window is divided into zero sub-buckets.
+    #[error("window must have at least one sub-bucket")]
+    ZeroSubBuckets,
+}
+
+/// The error type of the crate, an alias of [`LimitrError`].
+pub type Error = LimitrError;
+
+/// Errors returned when parsing a [`Quota`](crate::Quota) fails.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParseQuotaError {
+    /// The input has neither a `/` nor ` per ` between the amount and the period.
+    #[error("quota must have the form `<amount>/<period>` or `<amount> per <period>`")]
+    MissingSeparator,
+    /// The amount is not a whole number.
+    #[error("quota amount must be a whole number")]
+    InvalidAmount,
+    /// The amount is zero, which would deny every request.
+    #[error("quota amount must not be zero")]
+    ZeroAmount,
+    /// The amount does not fit into a `u64`.
+    #[error("quota amount is too large")]
+    AmountTooLarge,
+    /// The multiplier of the period is zero, not a whole number or too large.
+    #[error("quota period must be a positive duration")]
+    InvalidPeriod,
+    /// The period has no unit or one that is not supported.
+    #[error("quota period must end in one of ns, ms, s, min, h or d")]
+    UnknownUnit,
+    /// The burst after the period is not a whole number or does not fit into a `u64`.
+    #[error("quota burst must be a whole number")]
+    InvalidBurst,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::LimitrError;
+    use std::error::Error;
+    #[cfg(all(feature = "bucket", feature = "window"))]
+    use {
+        crate::bucket::TokenBucket,
+        crate::window::FixedWindowCounter,
+        tokio::time::{Duration, Instant},
+    };
+
+    #[cfg(all(feature = "bucket", feature = "window"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_variants_surface_from_the_limiters() {
+        assert!(matches!(
+            TokenBucket::try_new(0, 1),
+            Err(LimitrError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            FixedWindowCounter::try_new(1, Duration::ZERO),
+            Err(LimitrError::InvalidConfig(_))
+        ));
+
+        let mut bucket = TokenBucket::new(2, 1);
+        assert_eq!(
+            bucket.acquire(3).await,
+            Err(LimitrError::AmountExceedsCapacity {
+                requested: 3,
+                capacity: 2
+            })
+        );
+        assert_eq!(
+            bucket.checked_consume(0).await,
+            Err(LimitrError::ZeroAmount)
+        );
+
+        assert!(bucket.try_consume(2).await);
+        let deadline = Instant::now() + Duration::from_millis(100);
+        assert_eq!(
+            bucket.acquire_by(1, deadline).await,
+            Err(LimitrError::DeadlineExceeded)
+        );
+
+        let counter = FixedWindowCounter::new(1, Duration::from_secs(10));
+        assert!(counter.check(1).await.into_result().is_ok());
+        assert_eq!(
+            counter.check(1).await.into_result(),
+            Err(LimitrError::NotUntil(Duration::from_secs(10)))
+        );
+        assert_eq!(
+            counter.check(2).await.into_result(),
+            Err(LimitrError::NotUntil(Duration::MAX))
+        );
+    }
+
+    #[test]
+    fn test_wraps_parse_and_backend_errors() {
+        fn quota(s: &str) -> Result<crate::Quota, LimitrError> {
+            Ok(s.parse()?)
+        }
+        assert_eq!(
+            quota("0/s"),
+            Err(LimitrError::Parse(crate::ParseQuotaError::ZeroAmount))
+        );
+        let err = quota("100/fortnight").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid quota: quota period must end in one of ns, ms, s, min, h or d"
+        );
+        assert_eq!(
+            err.source().unwrap().to_string(),
+            crate::ParseQuotaError::UnknownUnit.to_string()
+        );
+
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "store is down");
+        let err = LimitrError::Backend(Box::new(io));
+        assert_eq!(err.to_string(), "backend error: store is down");
+        assert_eq!(err.source().unwrap().to_string(), "store is down");
+    }
+
+    #[test]
+    fn test_converts_into_boxed_errors() {
+        fn admit() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+            Err(LimitrError::DeadlineExceeded)?
+        }
+
+        let err = admit().unwrap_err();
+        assert_eq!(err.to_string(), "deadline exceeded");
+        assert_eq!(
+            err.downcast_ref::<crate::Error>(),
+            Some(&LimitrError::DeadlineExceeded)
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Helpers to turn a [`Decision`] into HTTP response headers and `429 Too Many Requests`
+//! responses, for servers built on plain [`hyper`](https://docs.rs/hyper) or any other crate
+//! using the [`http`] types.
+//!
+//! The headers carry the state of the limiter as delta-seconds, following the draft IETF
+//! RateLimit header fields:
+//!
+//! - `X-RateLimit-Limit`: the most the limiter admits at once
+//! - `X-RateLimit-Remaining`: what is left after the decision
+//! - `X-RateLimit-Reset`: seconds until the limiter has fully recovered
+//! - `Retry-After`: seconds until a denied request would be admitted, omitted for admitted
+//!   requests and for requests that can never be admitted
+//!
+//! Durations are rounded up to whole seconds, so clients never retry too early.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use limitr::http::rate_limit_response;
+//! use limitr::window::FixedWindowCounter;
+//!
+//! # tokio_test::block_on(async {
+//! let counter = FixedWindowCounter::new(1, Duration::from_secs(60));
+//! counter.try_consume().await;
+//!
+//! let decision = counter.check(1).await;
+//! if !decision.is_allowed() {
+//!     let response: http::Response<String> = rate_limit_response(&decision);
+//!     assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+//!     assert!(response.headers().contains_key(http::header::RETRY_AFTER));
+//! }
+//! # })
+//! ```
+
+use crate::decision::Decision;
+use ::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use ::http::{Response, StatusCode};
+use std::time::Duration;
+
+/// Name of the header carrying [`Decision::limit`].
+pub const X_RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+/// Name of the header carrying [`Decision::remaining`].
+pub const X_RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+/// Name of the header carrying [`Decision::reset_in`].
+pub const X_RATELIMIT_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// Returns the rate limit headers describing `decision`.
+///
+/// See the [module documentation](self) for the headers and their values.
+pub fn rate_limit_headers(decision: &Decision) -> HeaderMap {
+    let mut headers = HeaderMap::with_capacity(4);
+    insert_rate_limit_headers(&mut headers, decision);
+    headers
+}
+
+/// Inserts the rate limit headers describing `decision` into `headers`, replacing any existing
+/// values, e.g. to decorate the response of an admitted request.
+///
+/// See the [module documentation](self) for the headers and their values.
+pub fn insert_rate_limit_headers(headers: &mut HeaderMap, decision: &Decision) {
+    headers.insert(X_RATELIMIT_LIMIT, decision.limit().into());
+    headers.insert(X_RATELIMIT_REMAINING, decision.remaining().into());
+    headers.insert(X_RATELIMIT_RESET, seconds(decision.reset_in()));
+
+    match decision {
+        Decision::Denied {
+            retry_after: Some(retry_after),
+            ..
+        } => {
+            headers.insert(RETRY_AFTER, seconds(*retry_after));
+        }
+        _ => {
+            headers.remove(RETRY_AFTER);
+        }
+    }
+}
+
+/// Builds a `429 Too Many Requests` response carrying the rate limit headers of `decision`.
+///
+/// The body is a short plain-text message, converted into any body type that can be created
+/// from a `&'static str`, such as `String`, `axum::body::Body` or
+/// `http_body_util::Full<Bytes>`.
+pub fn rate_limit_response<B: From<&'static str>>(decision: &Decision) -> Response<B> {
+    let mut response = Response::new(B::from("Too Many Requests"));
+    *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+    insert_rate_limit_headers(response.headers_mut(), decision);
+    response
+}
+
+/// Formats a duration as whole seconds, rounded up.
+fn seconds(duration: Duration) -> HeaderValue {
+    let mut seconds = duration.as_secs();
+    if duration.subsec_nanos() > 0 {
+        seconds = seconds.saturating_add(1);
+    }
+    seconds.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http::{rate_limit_headers, rate_limit_response};
+    use crate::Decision;
+    use http::header::RETRY_AFTER;
+    use http::StatusCode;
+    use std::time::Duration;
+
+    #[test]
+    fn test_headers_describe_decision() {
+        let headers = rate_limit_headers(&Decision::Allowed {
+            limit: 10,
+            remaining: 7,
+            reset_in: Duration::from_millis(2_500),
+        });
+        assert_eq!(headers["x-ratelimit-limit"], "10");
+        assert_eq!(headers["x-ratelimit-remaining"], "7");
+        assert_eq!(headers["x-ratelimit-reset"], "3");
+        assert!(!headers.contains_key(RETRY_AFTER));
+
+        let headers = rate_limit_headers(&Decision::Denied {
+            limit: 10,
+            remaining: 0,
+            reset_in: Duration::from_secs(30),
+            retry_after: None,
+        });
+        assert_eq!(headers["x-ratelimit-remaining"], "0");
+        assert!(!headers.contains_key(RETRY_AFTER));
+    }
+
+    #[test]
+    fn test_response_is_too_many_requests() {
+        let response = rate_limit_response::<String>(&Decision::Denied {
+            limit: 10,
+            remaining: 1,
+            reset_in: Duration::from_secs(30),
+            retry_after: Some(Duration::from_millis(1)),
+        });
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "1");
+        assert_eq!(response.headers()["x-ratelimit-reset"], "30");
+        assert_eq!(response.body(), "Too Many Requests");
+    }
+}
@@ -16,6 +16,44 @@
 //! - **Token Bucket**: For burstable traffic control, where tokens accumulate over time and are consumed by requests.
 //! - **Leaky Bucket**: For smoothing out traffic, where requests are allowed to "leak" out at a fixed rate.
 //!
+//! All limiters implement the [`RateLimiter`] trait, so they can be used interchangeably and
+//! composed with the limiters in [`combinators`].
+//!
+//! ## Thread Safety
+//!
+//! Every limiter is `Send + Sync`, so it can be held across `.await` points, moved into spawned
+//! tasks and stored in shared state such as axum's `State`. Limiters whose methods take `&self`,
+//! like [`FixedWindowCounter`](window::FixedWindowCounter),
+//! [`SlidingWindowCounter`](window::SlidingWindowCounter),
+//! [`SharedTokenBucket`](bucket::SharedTokenBucket),
+//! [`SharedLeakyBucket`](bucket::SharedLeakyBucket) and
+//! [`KeyedLimiter`](combinators::KeyedLimiter), can be shared through an `Arc` directly. The
+//! others take `&mut self` and are shared behind a `tokio::sync::Mutex`. These bounds are
+//! checked at compile time, so they will not silently go away.
+//!
+//! Every limiter also has a `shared` constructor, such as
+//! [`TokenBucket::shared`](bucket::TokenBucket::shared), returning a handle whose clones all
+//! use the same limiter. Cloning a limiter itself, where supported, creates an independent
+//! copy instead.
+//!
+//! Under contention, the limiters keep the runtime responsive. Limiters guarding their state
+//! with a `tokio::sync::Mutex`, like the sliding window and
+//! [`KeyedLimiter`](combinators::KeyedLimiter), queue waiting tasks fairly in the order they
+//! arrived. The lock-free fixed window and the briefly locked shared buckets never wait, but
+//! still count against tokio's per-task cooperative budget, so a task retrying them in a tight loop is
+//! made to yield now and then instead of starving other tasks on the same worker. Admission
+//! itself is not fair: a denied request gets no priority over newcomers once capacity frees up.
+//!
+//! ## Testing
+//!
+//! The limiters measure time with [`tokio::time::Instant`] and wait with tokio's timers, so they
+//! work on a `current_thread` runtime and follow [`tokio::time::pause`] and
+//! [`tokio::time::advance`]. Tests of time-based logic can run deterministically and instantly
+//! with `#[tokio::test(start_paused = true)]`, including background tasks such as
+//! [`FixedWindowCounter::spawn_cleanup`](window::FixedWindowCounter::spawn_cleanup). Only
+//! wall-clock time, e.g. of a [`WindowClock::WallClock`](window::WindowClock::WallClock), is out
+//! of tokio's control, use a [`WindowClock::Custom`](window::WindowClock::Custom) clock there.
+//!
 //! ## Example Usage
 //!
 //! ```rust
@@ -37,8 +75,57 @@
 //! }
 //! ```
 
+#[macro_use]
+mod macros;
+
 #[cfg(feature = "bucket")]
 pub mod bucket;
 
 #[cfg(feature = "window")]
 pub mod window;
+
+#[cfg(any(feature = "bucket", feature = "window"))]
+pub mod stats;
+
+#[cfg(feature = "http")]
+pub mod http;
+
+#[cfg(feature = "simulate")]
+mod simulate;
+
+mod cleanup;
+mod clock;
+pub mod combinators;
+#[cfg(all(feature = "bucket", feature = "window"))]
+mod config;
+mod decision;
+mod error;
+mod limiter;
+mod quota;
+#[cfg(all(test, any(feature = "bucket", feature = "window")))]
+mod test_util;
+
+pub use cleanup::CleanupHandle;
+pub use clock::CoarseClock;
+#[cfg(all(feature = "bucket", feature = "window"))]
+pub use config::*;
+pub use decision::*;
+pub use error::*;
+pub use limiter::*;
+pub use quota::*;
+#[cfg(feature = "simulate")]
+pub use simulate::simulate;
+
+/// Fails to compile unless `T` can be shared between threads, used to pin down the auto traits
+/// of the public types.
+const fn assert_send_sync<T: Send + Sync>() {}
+
+const _: () = {
+    assert_send_sync::<CleanupHandle>();
+    assert_send_sync::<CoarseClock>();
+    assert_send_sync::<Decision>();
+    #[cfg(all(feature = "bucket", feature = "window"))]
+    assert_send_sync::<LimiterConfig>();
+    assert_send_sync::<LimitrError>();
+    assert_send_sync::<Quota>();
+};
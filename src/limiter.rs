@@ -0,0 +1,596 @@
+//! A common interface over the rate limiting algorithms of this crate.
+
+use crate::combinators::{AndLimiter, KeyedLimiter, OrLimiter};
+use crate::decision::Decision;
+use crate::error::LimitrError;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+
+/// A boxed future as returned by the [`RateLimiter`] trait methods.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A rate limiter that can be driven without knowing the concrete algorithm.
+///
+/// The trait is object safe, so limiters of different types can be stored together as
+/// `Box<dyn RateLimiter>`, e.g. to combine them with the limiters in [`crate::combinators`].
+///
+/// Implementations must not charge anything for a denied request. Combinators rely on this to
+/// probe limiters without side effects on denial.
+///
+/// # Example
+///
+/// ```rust
+/// use limitr::bucket::TokenBucket;
+/// use limitr::window::FixedWindowCounter;
+/// use limitr::RateLimiter;
+/// use std::time::Duration;
+///
+/// # tokio_test::block_on(async {
+/// let mut limiters: Vec<Box<dyn RateLimiter>> = vec![
+///     Box::new(TokenBucket::new(10, 5)),
+///     Box::new(FixedWindowCounter::new(10, Duration::from_secs(1))),
+/// ];
+///
+/// for limiter in limiters.iter_mut() {
+///     assert!(limiter.try_acquire(3).await);
+/// }
+/// # })
+/// ```
+///
+/// The methods take `&mut self`, as most limiters update their state in place. Limiters whose
+/// inherent methods take `&self` also implement [`SharedRateLimiter`], so one instance can be
+/// shared between tasks through an `Arc`, while the others are shared behind a `Mutex`. See
+/// `examples/generic_limiter.rs` for code that runs unchanged with every limiter.
+pub trait RateLimiter: Send {
+    /// Attempts to admit a request of the given `cost` without waiting.
+    ///
+    /// Returns `true` if the request was admitted and its cost consumed. A request that can
+    /// never be admitted, e.g. because its cost exceeds the capacity of the limiter, returns
+    /// `false` forever, so callers that retry on `false` should tell it apart through
+    /// [`RateLimiter::check`] or use [`RateLimiter::acquire`].
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool>;
+
+    /// Attempts to admit a request of the given `cost` without waiting, reporting the state of
+    /// the limiter along with the outcome.
+    ///
+    /// The request is charged exactly like with [`RateLimiter::try_acquire`]. A denied request
+    /// that can never be admitted reports no [`Decision::retry_after`], while one that is only
+    /// temporarily denied reports how long to wait. Every limiter of the crate keeps to this
+    /// contract.
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision>;
+
+    /// Gives back `cost` of the most recent admission, e.g. to roll back a request that was
+    /// denied by another limiter.
+    ///
+    /// The limiter never ends up with more capacity than it started with. Refunding a request
+    /// whose cost has already been recovered, such as one from a window that has since rolled
+    /// over, has no effect.
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()>;
+
+    /// Returns how long until a request of the given `cost` would be admitted, without
+    /// charging anything.
+    ///
+    /// This is zero if the request would be admitted right away, and `None` if it never will
+    /// be, e.g. because its cost exceeds the capacity of the limiter.
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>>;
+
+    /// Waits until a request of the given `cost` is admitted, and charges it.
+    ///
+    /// Denied requests are retried after the time reported by [`RateLimiter::check`], so the
+    /// limiter is not polled while it is known to be full.
+    ///
+    /// # Cancel safety
+    ///
+    /// Nothing is charged until the request is admitted, so dropping the future while it waits
+    /// leaves the limiter untouched.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`LimitrError::AmountExceedsCapacity`] if the cost exceeds the limit of the
+    /// limiter, and with [`LimitrError::Exhausted`] if a request within the limit can never be
+    /// admitted again.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// use limitr::RateLimiter;
+    /// use tokio::time::{self, Duration, Instant};
+    ///
+    /// # tokio_test::block_on(async {
+    /// # time::pause();
+    /// let mut limiter: Box<dyn RateLimiter> = Box::new(TokenBucket::new(2, 1));
+    /// let start = Instant::now();
+    ///
+    /// for _ in 0..3 {
+    ///     limiter.acquire(1).await.unwrap();
+    /// }
+    /// assert!(start.elapsed() >= Duration::from_secs(1));
+    /// assert!(limiter.acquire(3).await.is_err());
+    /// # })
+    /// ```
+    fn acquire(&mut self, cost: u64) -> BoxFuture<'_, Result<(), LimitrError>> {
+        Box::pin(async move {
+            loop {
+                let decision = self.check(cost).await;
+                if decision.is_allowed() {
+                    return Ok(());
+                }
+                match decision.retry_after() {
+                    Some(wait) => time::sleep(wait).await,
+                    None => return Err(never_admitted(cost, &decision)),
+                }
+            }
+        })
+    }
+
+    /// Attempts to admit a request of the given `cost` without waiting, calling `f` with the
+    /// time until it would be admitted if it is denied.
+    ///
+    /// Returns `Ok(())` if the request was admitted, otherwise `Err` with the result of `f`,
+    /// e.g. to log the denial, emit an event or build a fallback response in one expression.
+    /// Requests that can never be admitted pass `Duration::MAX`. The request is charged exactly
+    /// like with [`RateLimiter::check`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::bucket::TokenBucket;
+    /// use limitr::RateLimiter;
+    /// use std::time::Duration;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let mut bucket = TokenBucket::new(1, 1);
+    /// assert_eq!(bucket.consume_or_else(1, |_| "busy").await, Ok(()));
+    ///
+    /// let denied = bucket.consume_or_else(1, |retry_after| {
+    ///     println!("denied, retry in {:?}", retry_after);
+    ///     retry_after
+    /// });
+    /// assert!(denied.await.unwrap_err() <= Duration::from_secs(1));
+    ///
+    /// // More than the capacity is never admitted
+    /// assert_eq!(bucket.consume_or_else(2, |wait| wait).await, Err(Duration::MAX));
+    /// # })
+    /// ```
+    fn consume_or_else<'a, T, F>(&'a mut self, cost: u64, f: F) -> BoxFuture<'a, Result<(), T>>
+    where
+        Self: Sized,
+        F: FnOnce(Duration) -> T + Send + 'a,
+    {
+        Box::pin(async move {
+            let decision = self.check(cost).await;
+            if decision.is_allowed() {
+                Ok(())
+            } else {
+                Err(f(decision.retry_after().unwrap_or(Duration::MAX)))
+            }
+        })
+    }
+}
+
+/// Fluent constructors for the [`combinators`](crate::combinators), available on every
+/// [`RateLimiter`].
+///
+/// # Example
+///
+/// ```rust
+/// use std::net::{IpAddr, Ipv4Addr};
+/// use limitr::bucket::TokenBucket;
+/// use limitr::RateLimiterExt;
+///
+/// # tokio_test::block_on(async {
+/// let limiter = RateLimiterExt::keyed(|| TokenBucket::new(5, 5).and(TokenBucket::new(100, 10)));
+///
+/// let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+/// assert!(limiter.try_consume(client, 5).await);
+/// assert!(!limiter.try_consume(client, 1).await);
+/// # })
+/// ```
+pub trait RateLimiterExt: RateLimiter + Sized {
+    /// Combines this limiter with `other` into an [`AndLimiter`], admitting a request only if
+    /// both admit it.
+    fn and<L: RateLimiter>(self, other: L) -> AndLimiter<Self, L> {
+        AndLimiter::new(self, other)
+    }
+
+    /// Combines this limiter with `other` into an [`OrLimiter`], admitting a request if either
+    /// admits it and trying this limiter first.
+    ///
+    /// Both limiters are boxed, so they may be of different types.
+    fn or<L: RateLimiter + 'static>(self, other: L) -> OrLimiter<Box<dyn RateLimiter>>
+    where
+        Self: 'static,
+    {
+        OrLimiter::new(vec![Box::new(self), Box::new(other)])
+    }
+
+    /// Creates a [`KeyedLimiter`] keeping a separate limiter of this type per key, each one
+    /// built by `factory` when its key is first used.
+    ///
+    /// A factory rather than a template limiter is taken, as cloning handles such as
+    /// [`SharedTokenBucket`](crate::bucket::SharedTokenBucket) would give every key the same
+    /// limiter. The key type is usually inferred from how the limiter is used.
+    fn keyed<K, F>(factory: F) -> KeyedLimiter<K, Self>
+    where
+        K: Eq + Hash + Send,
+        F: Fn() -> Self + Send + Sync + 'static,
+        Self: Sync + 'static,
+    {
+        KeyedLimiter::new(factory)
+    }
+}
+
+impl<L: RateLimiter> RateLimiterExt for L {}
+
+/// A rate limiter whose methods take `&self`, so one instance can be shared between tasks.
+///
+/// This is the subset of [`RateLimiter`] that does not need exclusive access. It is
+/// implemented by the limiters that synchronize internally, such as
+/// [`FixedWindowCounter`](crate::window::FixedWindowCounter),
+/// [`SlidingWindowCounter`](crate::window::SlidingWindowCounter),
+/// [`QuotaLimiter`](crate::window::QuotaLimiter) and the shared buckets. The trait is object
+/// safe, so an application can pick the algorithm from its configuration and hand out an
+/// `Arc<dyn SharedRateLimiter>`. Such an `Arc` is itself a [`RateLimiter`], so it also works
+/// with the [`combinators`](crate::combinators).
+///
+/// The methods follow the contract of their [`RateLimiter`] counterparts.
+///
+/// # Example
+///
+/// ```rust
+/// use limitr::bucket::TokenBucket;
+/// use limitr::window::SlidingWindowCounter;
+/// use limitr::SharedRateLimiter;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// # tokio_test::block_on(async {
+/// let sliding = true;
+/// let limiter: Arc<dyn SharedRateLimiter> = if sliding {
+///     Arc::new(SlidingWindowCounter::new(2, Duration::from_secs(1)))
+/// } else {
+///     Arc::new(TokenBucket::shared(2, 2))
+/// };
+///
+/// let handle = Arc::clone(&limiter);
+/// assert!(tokio::spawn(async move { handle.try_acquire(2).await }).await.unwrap());
+/// assert!(!limiter.try_acquire(1).await);
+/// assert!(limiter.retry_after(1).await.unwrap() <= Duration::from_secs(1));
+/// # })
+/// ```
+pub trait SharedRateLimiter: Send + Sync {
+    /// Attempts to admit a request of the given `cost` without waiting, see
+    /// [`RateLimiter::try_acquire`].
+    fn try_acquire(&self, cost: u64) -> BoxFuture<'_, bool>;
+
+    /// Attempts to admit a request of the given `cost` without waiting, reporting the state of
+    /// the limiter along with the outcome, see [`RateLimiter::check`].
+    fn check(&self, cost: u64) -> BoxFuture<'_, Decision>;
+
+    /// Gives back `cost` of the most recent admission, see [`RateLimiter::refund`].
+    fn refund(&self, cost: u64) -> BoxFuture<'_, ()>;
+
+    /// Returns how long until a request of the given `cost` would be admitted, without
+    /// charging anything, see [`RateLimiter::retry_after`].
+    ///
+    /// Other tasks may take the capacity in the meantime, so a request made after waiting this
+    /// long can still be denied.
+    fn retry_after(&self, cost: u64) -> BoxFuture<'_, Option<Duration>>;
+
+    /// Waits until a request of the given `cost` is admitted, and charges it, see
+    /// [`RateLimiter::acquire`].
+    ///
+    /// # Cancel safety
+    ///
+    /// Nothing is charged until the request is admitted, so dropping the future while it waits
+    /// leaves the limiter untouched.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`RateLimiter::acquire`] if the request can never be admitted.
+    fn acquire(&self, cost: u64) -> BoxFuture<'_, Result<(), LimitrError>> {
+        Box::pin(async move {
+            loop {
+                let decision = self.check(cost).await;
+                if decision.is_allowed() {
+                    return Ok(());
+                }
+                match decision.retry_after() {
+                    Some(wait) => time::sleep(wait).await,
+                    None => return Err(never_admitted(cost, &decision)),
+                }
+            }
+        })
+    }
+}
+
+/// Returns the error for a request of the given `cost` that `decision` reports can never be
+/// admitted.
+fn never_admitted(cost: u64, decision: &Decision) -> LimitrError {
+    if cost > decision.limit() {
+        LimitrError::AmountExceedsCapacity {
+            requested: cost,
+            capacity: decision.limit(),
+        }
+    } else {
+        LimitrError::Exhausted
+    }
+}
+
+impl<L: SharedRateLimiter + ?Sized> SharedRateLimiter for Arc<L> {
+    fn try_acquire(&self, cost: u64) -> BoxFuture<'_, bool> {
+        (**self).try_acquire(cost)
+    }
+
+    fn check(&self, cost: u64) -> BoxFuture<'_, Decision> {
+        (**self).check(cost)
+    }
+
+    fn refund(&self, cost: u64) -> BoxFuture<'_, ()> {
+        (**self).refund(cost)
+    }
+
+    fn retry_after(&self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        (**self).retry_after(cost)
+    }
+
+    fn acquire(&self, cost: u64) -> BoxFuture<'_, Result<(), LimitrError>> {
+        (**self).acquire(cost)
+    }
+}
+
+impl<L: SharedRateLimiter + ?Sized> RateLimiter for Arc<L> {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        (**self).try_acquire(cost)
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        (**self).check(cost)
+    }
+
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        (**self).refund(cost)
+    }
+
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        (**self).retry_after(cost)
+    }
+
+    fn acquire(&mut self, cost: u64) -> BoxFuture<'_, Result<(), LimitrError>> {
+        (**self).acquire(cost)
+    }
+}
+
+impl<L: RateLimiter + ?Sized> RateLimiter for Box<L> {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        (**self).try_acquire(cost)
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        (**self).check(cost)
+    }
+
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        (**self).refund(cost)
+    }
+
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        (**self).retry_after(cost)
+    }
+
+    fn acquire(&mut self, cost: u64) -> BoxFuture<'_, Result<(), LimitrError>> {
+        (**self).acquire(cost)
+    }
+}
+
+#[cfg(all(test, feature = "bucket", feature = "window"))]
+mod tests {
+    use crate::bucket::{LeakyBucket, SharedLeakyBucket, SharedTokenBucket, TokenBucket};
+    use crate::combinators::AllOf;
+    use crate::window::{FixedWindowCounter, QuotaLimiter, SlidingWindowCounter};
+    use crate::{LimitrError, Quota, RateLimiter, RateLimiterExt, SharedRateLimiter};
+    use std::sync::Arc;
+    use tokio::time::{self, Duration, Instant};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ext_chains_combinators() {
+        let limiter = RateLimiterExt::keyed(|| TokenBucket::new(3, 1).and(LeakyBucket::new(2, 1)));
+
+        assert!(limiter.try_consume("alice", 2).await);
+        assert!(!limiter.try_consume("alice", 1).await);
+        // Every key starts out with its own pair of buckets
+        assert!(limiter.try_consume("bob", 2).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keyed_handles_get_a_limiter_per_key() {
+        let token = SharedTokenBucket::keyed(|| TokenBucket::shared(1, 1));
+        let leaky = SharedLeakyBucket::keyed(|| SharedLeakyBucket::new(1, 1));
+        let sliding = Arc::<SlidingWindowCounter>::keyed(|| {
+            SlidingWindowCounter::shared(1, Duration::from_secs(60))
+        });
+
+        for key in ["alice", "bob"] {
+            assert!(token.try_consume(key, 1).await);
+            assert!(leaky.try_consume(key, 1).await);
+            assert!(sliding.try_consume(key, 1).await);
+        }
+        assert!(!token.try_consume("alice", 1).await);
+        assert!(!leaky.try_consume("alice", 1).await);
+        assert!(!sliding.try_consume("alice", 1).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ext_or_mixes_limiter_types() {
+        let mut limiter =
+            TokenBucket::new(1, 1).or(FixedWindowCounter::new(2, Duration::from_secs(60)));
+
+        for _ in 0..3 {
+            assert!(limiter.try_acquire(1).await);
+        }
+        assert!(!limiter.try_acquire(1).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_waits_with_every_limiter() {
+        let second = Duration::from_secs(1);
+        let limiters: Vec<Box<dyn RateLimiter>> = vec![
+            Box::new(TokenBucket::new(2, 2)),
+            Box::new(LeakyBucket::new(2, 2)),
+            Box::new(FixedWindowCounter::new(2, second)),
+            Box::new(SlidingWindowCounter::new(2, second)),
+        ];
+
+        for mut limiter in limiters {
+            let start = Instant::now();
+            for _ in 0..3 {
+                limiter.acquire(1).await.unwrap();
+            }
+            assert!(start.elapsed() > Duration::ZERO);
+            assert!(start.elapsed() < 2 * second);
+            assert!(matches!(
+                limiter.acquire(3).await,
+                Err(LimitrError::AmountExceedsCapacity { requested: 3, .. })
+            ));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_takes_the_buckets_own_path() {
+        let mut token = TokenBucket::new(1, 1);
+        for _ in 0..2 {
+            RateLimiter::acquire(&mut token, 1).await.unwrap();
+        }
+        // Waiting through the trait is recorded like any other acquire
+        let stats = token.wait_time_stats().await;
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.max, Duration::from_secs(1));
+
+        let mut leaky = LeakyBucket::new(1, 1);
+        let start = Instant::now();
+        for _ in 0..2 {
+            RateLimiter::acquire(&mut leaky, 1).await.unwrap();
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert_eq!(
+            RateLimiter::acquire(&mut leaky, u64::MAX).await,
+            Err(LimitrError::AmountExceedsCapacity {
+                requested: u64::MAX,
+                capacity: 1
+            })
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn test_paused_current_thread_runtime_drives_every_limiter() {
+        let hour = Duration::from_secs(3600);
+        let limiters: Vec<Box<dyn RateLimiter>> = vec![
+            Box::new(TokenBucket::from_quota(Quota::per(3600, hour).allow_burst(1)).unwrap()),
+            Box::new(LeakyBucket::new(1, 1).with_leak_period(hour)),
+            Box::new(FixedWindowCounter::new(1, hour).with_coarse_clock(Duration::from_secs(1))),
+            Box::new(SlidingWindowCounter::new(1, hour).with_coarse_clock(Duration::from_secs(1))),
+        ];
+
+        for (i, mut limiter) in limiters.into_iter().enumerate() {
+            limiter.acquire(1).await.unwrap();
+            assert!(!limiter.try_acquire(1).await, "limiter {}", i);
+
+            // Waiting auto-advances the paused clock instead of sleeping
+            let start = Instant::now();
+            limiter.acquire(1).await.unwrap();
+            assert!(start.elapsed() > Duration::ZERO, "limiter {}", i);
+            assert!(
+                start.elapsed() <= hour + Duration::from_secs(2),
+                "limiter {}",
+                i
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_impossible_requests_are_never_retried() {
+        let second = Duration::from_secs(1);
+        let limiters: Vec<Box<dyn RateLimiter>> = vec![
+            Box::new(TokenBucket::new(2, 2)),
+            Box::new(LeakyBucket::new(2, 2)),
+            Box::new(FixedWindowCounter::new(2, second)),
+            Box::new(SlidingWindowCounter::new(2, second)),
+            Box::new(TokenBucket::new(4, 2).and(LeakyBucket::new(2, 2))),
+        ];
+
+        for (i, mut limiter) in limiters.into_iter().enumerate() {
+            let decision = limiter.check(3).await;
+            assert!(!decision.is_allowed(), "limiter {}", i);
+            assert_eq!(decision.retry_after(), None, "limiter {}", i);
+
+            // A request that only has to wait reports when to retry
+            assert!(limiter.try_acquire(2).await, "limiter {}", i);
+            let decision = limiter.check(1).await;
+            assert!(!decision.is_allowed(), "limiter {}", i);
+            assert!(decision.retry_after().is_some(), "limiter {}", i);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shared_limiters_behind_arc_dyn() {
+        let second = Duration::from_secs(1);
+        let limiters: Vec<Arc<dyn SharedRateLimiter>> = vec![
+            Arc::new(TokenBucket::shared(2, 2)),
+            Arc::new(SharedLeakyBucket::new(2, 2)),
+            Arc::new(FixedWindowCounter::new(2, second)),
+            Arc::new(SlidingWindowCounter::new(2, second)),
+            Arc::new(QuotaLimiter::recurring(2, second)),
+        ];
+
+        for (i, limiter) in limiters.into_iter().enumerate() {
+            assert_eq!(
+                limiter.retry_after(2).await,
+                Some(Duration::ZERO),
+                "limiter {}",
+                i
+            );
+            assert_eq!(limiter.retry_after(3).await, None, "limiter {}", i);
+
+            let handle = Arc::clone(&limiter);
+            assert!(tokio::spawn(async move { handle.try_acquire(2).await })
+                .await
+                .unwrap());
+            assert!(!limiter.try_acquire(1).await, "limiter {}", i);
+
+            // Asking does not charge anything, and the wait is enough for the next request
+            let wait = limiter.retry_after(1).await.unwrap();
+            assert!(wait > Duration::ZERO, "limiter {}", i);
+            assert_eq!(limiter.retry_after(1).await, Some(wait), "limiter {}", i);
+            time::advance(wait).await;
+            assert_eq!(
+                limiter.retry_after(1).await,
+                Some(Duration::ZERO),
+                "limiter {}",
+                i
+            );
+            assert!(limiter.try_acquire(1).await, "limiter {}", i);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shared_limiters_combine_through_arc() {
+        let global: Arc<dyn SharedRateLimiter> = Arc::new(TokenBucket::shared(3, 1));
+        let mut first = AllOf::new(vec![
+            Arc::clone(&global),
+            Arc::new(FixedWindowCounter::new(2, Duration::from_secs(60))),
+        ]);
+        let mut second = AllOf::new(vec![Arc::clone(&global)]);
+
+        assert!(first.try_consume(2).await);
+        assert!(!first.try_consume(1).await);
+        // The denial of the window was rolled back on the shared bucket
+        assert!(second.try_consume(1).await);
+        assert!(!second.try_consume(1).await);
+        assert_eq!(global.retry_after(1).await, Some(Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,29 @@
+//! Logging macros that forward to [`tracing`] when the `tracing` feature is enabled and compile
+//! to nothing otherwise.
+//!
+//! The disabled variants still type check their arguments inside a dead branch, so variables
+//! only used for logging do not trigger warnings and nothing is evaluated at runtime.
+
+macro_rules! trace {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::trace!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    }};
+}
+
+// Only used by the window limiters
+#[allow(unused_macros)]
+macro_rules! warn {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::warn!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    }};
+}
@@ -0,0 +1,486 @@
+//! A rate with an optional burst size, accepted by the `from_quota` constructors of every
+//! limiter.
+
+use crate::error::ParseQuotaError;
+#[cfg(feature = "bucket")]
+use crate::error::{InvalidConfigReason, LimitrError};
+use std::fmt;
+use std::num::IntErrorKind;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A number of requests allowed per period, along with the largest burst to admit at once.
+///
+/// Every limiter takes its parameters in a different form, e.g. a capacity and a refill rate
+/// per second for a [`TokenBucket`](crate::bucket::TokenBucket), or a limit and a window
+/// duration for a [`FixedWindowCounter`](crate::window::FixedWindowCounter). A `Quota` describes
+/// the intended rate once, and each limiter's `from_quota` constructor translates it:
+///
+/// | Limiter | Translation |
+/// |---------|-------------|
+/// | `TokenBucket` | capacity of the burst, refilled by the amount per period |
+/// | `LeakyBucket` | capacity of the burst, leaking the amount per period |
+/// | `FixedWindowCounter` | limit of the amount per window, carrying the burst beyond it over |
+/// | `SlidingWindowCounter` | limit of the amount per window, the burst does not apply |
+///
+/// The burst defaults to the amount, which gives every limiter the same behavior for a client
+/// that starts out idle: the full amount at once, then the amount per period.
+///
+/// Quotas also parse from strings like `"5/s"`, `"10_000/hour"` or `"20 per 250ms"`, e.g. for
+/// limits loaded from config files or environment variables. The period is an optional positive
+/// multiplier followed by a unit: `ns`, `ms`, `s`, `sec`, `second(s)`, `m`, `min`, `minute(s)`,
+/// `h`, `hr`, `hour(s)`, `d` or `day(s)`. The amount may group its digits with underscores and
+/// must not be zero. Whitespace around both is ignored. A burst other than the amount follows
+/// the period, as in `"10/s burst 50"`. A quota displays in the same form, so it parses back
+/// into an equal quota.
+///
+/// # Example
+///
+/// ```rust
+/// use limitr::bucket::TokenBucket;
+/// use limitr::Quota;
+/// use std::time::Duration;
+///
+/// # tokio_test::block_on(async {
+/// let quota = Quota::per_second(10).allow_burst(50);
+/// assert_eq!(quota.amount(), 10);
+/// assert_eq!(quota.period(), Duration::from_secs(1));
+///
+/// let mut bucket = TokenBucket::from_quota(quota).unwrap();
+/// assert!(bucket.try_consume(50).await);
+///
+/// let quota: Quota = "100/min".parse().unwrap();
+/// assert_eq!(quota, Quota::per_minute(100));
+/// assert_eq!(quota.to_string(), "100/min");
+///
+/// let quota: Quota = "10/s burst 50".parse().unwrap();
+/// assert_eq!(quota, Quota::per_second(10).allow_burst(50));
+/// # })
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quota {
+    amount: u64,
+    period: Duration,
+    burst: u64,
+}
+
+impl Quota {
+    /// Creates a new `Quota` allowing `amount` requests per `period`, with a burst of `amount`.
+    ///
+    /// The quota is validated when a limiter is built from it, so a zero period only fails
+    /// there.
+    pub const fn per(amount: u64, period: Duration) -> Self {
+        Quota {
+            amount,
+            period,
+            burst: amount,
+        }
+    }
+
+    /// Creates a new `Quota` allowing `amount` requests per second.
+    pub const fn per_second(amount: u64) -> Self {
+        Quota::per(amount, Duration::from_secs(1))
+    }
+
+    /// Creates a new `Quota` allowing `amount` requests per minute.
+    pub const fn per_minute(amount: u64) -> Self {
+        Quota::per(amount, Duration::from_secs(60))
+    }
+
+    /// Creates a new `Quota` allowing `amount` requests per hour.
+    pub const fn per_hour(amount: u64) -> Self {
+        Quota::per(amount, Duration::from_secs(60 * 60))
+    }
+
+    /// Creates a new `Quota` sustaining `bytes` bytes per second, e.g. for a
+    /// [`ByteRateLimiter`](crate::bucket::ByteRateLimiter).
+    pub const fn bytes_per_second(bytes: u64) -> Self {
+        Quota::per_second(bytes)
+    }
+
+    /// Sets the largest number of requests admitted at once, instead of the amount.
+    ///
+    /// A burst above the amount lets an idle client catch up, one below smooths traffic out.
+    pub const fn allow_burst(mut self, burst: u64) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    /// Returns the number of requests allowed per period.
+    pub const fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    /// Returns the period the amount applies to.
+    pub const fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Returns the largest number of requests admitted at once.
+    pub const fn burst(&self) -> u64 {
+        self.burst
+    }
+
+    /// Returns the amount and the period divided by their greatest common divisor, the smallest
+    /// whole steps to refill the quota in, e.g. one request every 600ms for 100 per minute.
+    #[cfg(feature = "bucket")]
+    pub(crate) fn refill_step(&self) -> Result<(u64, Duration), LimitrError> {
+        let period = self.nonzero_period()?.as_nanos();
+        let (mut a, mut b) = (self.amount as u128, period);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        // The divisor is at most the amount, so the amount still fits
+        let (amount, period) = ((self.amount as u128 / a) as u64, period / a);
+        let period = Duration::new(
+            (period / 1_000_000_000) as u64,
+            (period % 1_000_000_000) as u32,
+        );
+        Ok((amount, period))
+    }
+
+    /// Returns the period, failing if it is zero.
+    #[cfg(feature = "bucket")]
+    pub(crate) fn nonzero_period(&self) -> Result<Duration, LimitrError> {
+        if self.period.is_zero() {
+            return Err(LimitrError::InvalidConfig(InvalidConfigReason::ZeroPeriod));
+        }
+        Ok(self.period)
+    }
+}
+
+impl FromStr for Quota {
+    type Err = ParseQuotaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rate, burst) = match split_burst(s) {
+            Some((rate, burst)) => {
+                let burst = parse_amount(burst).map_err(|_| ParseQuotaError::InvalidBurst)?;
+                (rate, Some(burst))
+            }
+            None => (s, None),
+        };
+
+        let (amount, period) = parse_rate(rate)?;
+        if amount == 0 {
+            return Err(ParseQuotaError::ZeroAmount);
+        }
+        Ok(Quota::per(amount, period).allow_burst(burst.unwrap_or(amount)))
+    }
+}
+
+/// Parses `"<amount>/<period>"` or `"<amount> per <period>"` into the amount and the period,
+/// which may be zero.
+fn parse_rate(s: &str) -> Result<(u64, Duration), ParseQuotaError> {
+    let (amount, period) = s
+        .split_once('/')
+        .or_else(|| split_per(s))
+        .ok_or(ParseQuotaError::MissingSeparator)?;
+
+    Ok((parse_amount(amount)?, parse_period(period)?))
+}
+
+/// Parses a whole number whose digits may be grouped with underscores.
+fn parse_amount(amount: &str) -> Result<u64, ParseQuotaError> {
+    let amount = amount.trim();
+    if amount.split('_').any(str::is_empty) {
+        return Err(ParseQuotaError::InvalidAmount);
+    }
+    amount
+        .replace('_', "")
+        .parse()
+        .map_err(|err: std::num::ParseIntError| match err.kind() {
+            IntErrorKind::PosOverflow => ParseQuotaError::AmountTooLarge,
+            _ => ParseQuotaError::InvalidAmount,
+        })
+}
+
+/// Units accepted after the multiplier of a period.
+const UNITS: &[(&str, Duration)] = &[
+    ("ns", Duration::from_nanos(1)),
+    ("ms", Duration::from_millis(1)),
+    ("seconds", Duration::from_secs(1)),
+    ("second", Duration::from_secs(1)),
+    ("sec", Duration::from_secs(1)),
+    ("s", Duration::from_secs(1)),
+    ("minutes", Duration::from_secs(60)),
+    ("minute", Duration::from_secs(60)),
+    ("min", Duration::from_secs(60)),
+    ("m", Duration::from_secs(60)),
+    ("hours", Duration::from_secs(60 * 60)),
+    ("hour", Duration::from_secs(60 * 60)),
+    ("hr", Duration::from_secs(60 * 60)),
+    ("h", Duration::from_secs(60 * 60)),
+    ("days", Duration::from_secs(24 * 60 * 60)),
+    ("day", Duration::from_secs(24 * 60 * 60)),
+    ("d", Duration::from_secs(24 * 60 * 60)),
+];
+
+/// Parses a period made of an optional multiplier and a unit, e.g. `"10s"` or `"min"`.
+fn parse_period(period: &str) -> Result<Duration, ParseQuotaError> {
+    let period = period.trim();
+    let unit_start = period
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or(ParseQuotaError::UnknownUnit)?;
+    let (multiplier, unit) = period.split_at(unit_start);
+    let unit = UNITS
+        .iter()
+        .find(|(name, _)| unit.trim_start().eq_ignore_ascii_case(name))
+        .map(|(_, unit)| *unit)
+        .ok_or(ParseQuotaError::UnknownUnit)?;
+
+    let multiplier = match multiplier {
+        "" => 1,
+        multiplier => multiplier
+            .parse()
+            .map_err(|_| ParseQuotaError::InvalidPeriod)?,
+    };
+    unit.checked_mul(multiplier)
+        .filter(|period| !period.is_zero())
+        .ok_or(ParseQuotaError::InvalidPeriod)
+}
+
+/// Writes `period` in the largest unit it is a whole multiple of, in the form [`parse_period`]
+/// accepts.
+fn write_period(f: &mut fmt::Formatter<'_>, period: Duration) -> fmt::Result {
+    let nanos = period.as_nanos();
+    let (count, unit) = [
+        (24 * 60 * 60 * 1_000_000_000, "d"),
+        (60 * 60 * 1_000_000_000, "h"),
+        (60 * 1_000_000_000, "min"),
+        (1_000_000_000, "s"),
+        (1_000_000, "ms"),
+    ]
+    .into_iter()
+    .find(|(unit, _)| nanos % *unit == 0)
+    .map_or((nanos, "ns"), |(unit, name)| (nanos / unit, name));
+
+    if count == 1 {
+        f.write_str(unit)
+    } else {
+        write!(f, "{}{}", count, unit)
+    }
+}
+
+/// (De)serializes a quota in its string form, e.g. for [`LimiterConfig`](crate::LimiterConfig).
+#[cfg(all(feature = "serde", feature = "bucket", feature = "window"))]
+pub(crate) mod string_form {
+    use super::Quota;
+
+    pub(crate) fn serialize<S: serde::Serializer>(
+        quota: &Quota,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(quota)
+    }
+
+    pub(crate) fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Quota, D::Error> {
+        let quota = <String as serde::Deserialize>::deserialize(deserializer)?;
+        quota.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Splits `"<amount> per <period>"` into the amount and the period.
+fn split_per(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim();
+    let (amount, rest) = s.split_at(s.find(char::is_whitespace)?);
+    let period = rest.trim_start().strip_prefix("per")?;
+    period
+        .starts_with(char::is_whitespace)
+        .then_some((amount, period))
+}
+
+/// Splits `"<rate> burst <burst>"` into the rate and the burst.
+fn split_burst(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_end();
+    let (rest, burst) = s.split_at(s.rfind(char::is_whitespace)?);
+    let rate = rest.trim_end().strip_suffix("burst")?;
+    rate.ends_with(char::is_whitespace).then_some((rate, burst))
+}
+
+impl fmt::Display for Quota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/", self.amount)?;
+        write_period(f, self.period)?;
+        if self.burst != self.amount {
+            write!(f, " burst {}", self.burst)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ParseQuotaError, Quota};
+    use std::time::Duration;
+    #[cfg(all(feature = "bucket", feature = "window"))]
+    use {
+        crate::bucket::{LeakyBucket, TokenBucket},
+        crate::window::{FixedWindowCounter, SlidingWindowCounter},
+        crate::{LimitrError, RateLimiter},
+        tokio::time,
+    };
+
+    #[test]
+    fn test_constructors() {
+        assert_eq!(
+            Quota::per_minute(600),
+            Quota::per(600, Duration::from_secs(60))
+        );
+        assert_eq!(Quota::per_hour(1).period(), Duration::from_secs(3600));
+        assert_eq!(Quota::per_second(10).burst(), 10);
+        assert_eq!(Quota::bytes_per_second(1024).allow_burst(0).burst(), 0);
+    }
+
+    #[test]
+    fn test_parses_quotas() {
+        for (input, amount, period) in [
+            ("5/s", 5, Duration::from_secs(1)),
+            ("100/min", 100, Duration::from_secs(60)),
+            ("10_000/hour", 10_000, Duration::from_secs(3600)),
+            ("20 per 250ms", 20, Duration::from_millis(250)),
+            ("  7 /\t2 Days ", 7, Duration::from_secs(2 * 86_400)),
+            ("3\tper  10s", 3, Duration::from_secs(10)),
+            ("1/500ns", 1, Duration::from_nanos(500)),
+        ] {
+            assert_eq!(
+                input.parse::<Quota>(),
+                Ok(Quota::per(amount, period)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_malformed_quotas() {
+        for (input, error) in [
+            ("", ParseQuotaError::MissingSeparator),
+            ("fast", ParseQuotaError::MissingSeparator),
+            ("100 per", ParseQuotaError::MissingSeparator),
+            ("100 perminute", ParseQuotaError::MissingSeparator),
+            ("0/s", ParseQuotaError::ZeroAmount),
+            ("0_000 per minute", ParseQuotaError::ZeroAmount),
+            ("/s", ParseQuotaError::InvalidAmount),
+            ("-1/s", ParseQuotaError::InvalidAmount),
+            ("1.5/s", ParseQuotaError::InvalidAmount),
+            ("_1/s", ParseQuotaError::InvalidAmount),
+            ("1__000/s", ParseQuotaError::InvalidAmount),
+            ("lots/s", ParseQuotaError::InvalidAmount),
+            ("18446744073709551616/s", ParseQuotaError::AmountTooLarge),
+            ("100/fortnight", ParseQuotaError::UnknownUnit),
+            ("100/", ParseQuotaError::UnknownUnit),
+            ("100 per 10", ParseQuotaError::UnknownUnit),
+            ("100/s/s", ParseQuotaError::UnknownUnit),
+            ("100/0s", ParseQuotaError::InvalidPeriod),
+            ("100/99999999999999999999d", ParseQuotaError::InvalidPeriod),
+            ("10/s burst", ParseQuotaError::UnknownUnit),
+            ("10/s burst lots", ParseQuotaError::InvalidBurst),
+            ("10/s burst -5", ParseQuotaError::InvalidBurst),
+        ] {
+            assert_eq!(input.parse::<Quota>(), Err(error), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for (input, display) in [
+            ("5/s", "5/s"),
+            ("10_000 per hour", "10000/h"),
+            ("20 per 250ms", "20/250ms"),
+            ("1/90s", "1/90s"),
+            ("1/1500ns", "1/1500ns"),
+            ("10/s burst 50", "10/s burst 50"),
+            ("10 per s  burst 1_000", "10/s burst 1000"),
+            ("10/s burst 10", "10/s"),
+            ("5/min burst 0", "5/min burst 0"),
+        ] {
+            let quota: Quota = input.parse().unwrap();
+            assert_eq!(quota.to_string(), display);
+            assert_eq!(display.parse::<Quota>(), Ok(quota));
+        }
+    }
+
+    #[cfg(all(feature = "bucket", feature = "window"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_every_limiter_follows_the_quota() {
+        let quota = Quota::per_second(2);
+        let limiters: Vec<Box<dyn RateLimiter>> = vec![
+            Box::new(TokenBucket::from_quota(quota).unwrap()),
+            Box::new(LeakyBucket::from_quota(quota).unwrap()),
+            Box::new(FixedWindowCounter::from_quota(quota).unwrap()),
+            Box::new(SlidingWindowCounter::from_quota(quota).unwrap()),
+        ];
+
+        for (i, mut limiter) in limiters.into_iter().enumerate() {
+            // The full amount at once, then the amount again once the period has passed. The
+            // sliding window still counts requests that are exactly one period old.
+            assert!(limiter.try_acquire(2).await, "limiter {}", i);
+            assert!(!limiter.try_acquire(1).await, "limiter {}", i);
+            time::advance(Duration::from_millis(1_001)).await;
+            assert!(limiter.try_acquire(2).await, "limiter {}", i);
+            assert!(!limiter.try_acquire(1).await, "limiter {}", i);
+        }
+    }
+
+    #[cfg(all(feature = "bucket", feature = "window"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_burst_translation() {
+        let quota = Quota::per_second(2).allow_burst(4);
+
+        let mut bucket = TokenBucket::from_quota(quota).unwrap();
+        assert!(bucket.try_consume(4).await);
+        let mut bucket = LeakyBucket::from_quota(quota).unwrap();
+        assert!(bucket.try_consume_n(4).await);
+
+        // The window carries the unused part of a window over, up to the burst
+        let counter = FixedWindowCounter::from_quota(quota).unwrap();
+        assert!(counter.try_consume().await);
+        time::advance(Duration::from_secs(1)).await;
+        assert!(counter.try_consume_n(3).await);
+        assert!(!counter.try_consume().await);
+
+        let counter = SlidingWindowCounter::from_quota(quota).unwrap();
+        assert_eq!(counter.limit(), 2);
+    }
+
+    #[cfg(all(feature = "bucket", feature = "window"))]
+    #[tokio::test(start_paused = true)]
+    async fn test_token_bucket_refills_uneven_quotas() {
+        let mut bucket = TokenBucket::from_quota(Quota::per_minute(100)).unwrap();
+        assert_eq!(bucket.refill_period(), Duration::from_millis(600));
+        assert!(bucket.try_consume(100).await);
+
+        // Time short of a whole step carries over to the next one
+        for _ in 0..3 {
+            time::advance(Duration::from_millis(1_000)).await;
+            assert!(bucket.try_consume(1).await);
+        }
+        // Five steps fit into three seconds
+        assert!(bucket.try_consume(2).await);
+        assert!(!bucket.try_consume(1).await);
+        time::advance(Duration::from_millis(600)).await;
+        assert!(bucket.try_consume(1).await);
+
+        // A whole minute refills the whole amount
+        time::advance(Duration::from_secs(60)).await;
+        assert!(bucket.try_consume(100).await);
+        assert!(!bucket.try_consume(1).await);
+    }
+
+    #[cfg(all(feature = "bucket", feature = "window"))]
+    #[test]
+    fn test_rejects_untranslatable_quotas() {
+        assert!(matches!(
+            TokenBucket::from_quota(Quota::per(1, Duration::ZERO)),
+            Err(LimitrError::InvalidConfig(_))
+        ));
+        assert!(TokenBucket::from_quota(Quota::per(1, Duration::ZERO)).is_err());
+        assert!(LeakyBucket::from_quota(Quota::per(1, Duration::ZERO)).is_err());
+        assert!(FixedWindowCounter::from_quota(Quota::per(1, Duration::ZERO)).is_err());
+        assert!(SlidingWindowCounter::from_quota(Quota::per_second(u64::MAX)).is_err());
+    }
+}
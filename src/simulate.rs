@@ -0,0 +1,118 @@
+//! Offline replay of recorded request traces, e.g. to tune the capacity and rate of a limiter
+//! against production traffic before deploying it.
+
+use crate::decision::Decision;
+use crate::limiter::RateLimiter;
+use tokio::time::{self, Instant};
+
+/// Replays a trace of `(timestamp, cost)` request events against a limiter and returns the
+/// decision it would have made for each of them.
+///
+/// The limiter is built by `config` on a paused tokio clock that jumps from one event to the
+/// next, so the replay takes no real time no matter how long the trace spans. Timestamps only
+/// matter relative to the first event. Events recorded out of order are replayed at the time of
+/// the latest event so far, as the clock never goes backwards. Limiters running on a wall
+/// clock, such as [`FixedWindowCounter::calendar`], do not see the simulated time.
+///
+/// # Panics
+///
+/// Panics if called from within an async runtime, use `tokio::task::spawn_blocking` there.
+///
+/// # Example
+///
+/// ```rust
+/// use limitr::bucket::TokenBucket;
+/// use limitr::simulate;
+/// use tokio::time::{Duration, Instant};
+///
+/// let start = Instant::now();
+/// let trace = [0, 0, 0, 500, 1_000]
+///     .map(|millis| (start + Duration::from_millis(millis), 1));
+///
+/// let decisions = simulate(|| TokenBucket::new(2, 1), trace);
+/// let allowed: Vec<bool> = decisions.iter().map(|d| d.is_allowed()).collect();
+/// assert_eq!(allowed, [true, true, false, false, true]);
+/// ```
+///
+/// [`FixedWindowCounter::calendar`]: crate::window::FixedWindowCounter::calendar
+pub fn simulate<L, F, I>(config: F, events: I) -> Vec<Decision>
+where
+    L: RateLimiter,
+    F: FnOnce() -> L,
+    I: IntoIterator<Item = (Instant, u64)>,
+{
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .start_paused(true)
+        .build()
+        .expect("failed to build the simulation runtime");
+
+    runtime.block_on(async move {
+        let mut limiter = config();
+        let start = Instant::now();
+        let mut events = events.into_iter().peekable();
+        let Some(&(first, _)) = events.peek() else {
+            return Vec::new();
+        };
+
+        let mut decisions = Vec::with_capacity(events.size_hint().0);
+        for (at, cost) in events {
+            let due = start + at.saturating_duration_since(first);
+            if let Some(ahead) = due.checked_duration_since(Instant::now()) {
+                time::advance(ahead).await;
+            }
+            decisions.push(limiter.check(cost).await);
+        }
+        decisions
+    })
+}
+
+#[cfg(all(test, feature = "window"))]
+mod tests {
+    use crate::simulate;
+    use crate::window::{FixedWindowCounter, SlidingWindowCounter};
+    use tokio::time::{Duration, Instant};
+
+    fn trace(events: &[(u64, u64)]) -> Vec<(Instant, u64)> {
+        let start = Instant::now();
+        events
+            .iter()
+            .map(|&(millis, cost)| (start + Duration::from_millis(millis), cost))
+            .collect()
+    }
+
+    fn allowed(decisions: &[crate::Decision]) -> Vec<bool> {
+        decisions.iter().map(|d| d.is_allowed()).collect()
+    }
+
+    #[test]
+    fn test_replays_trace_without_sleeping() {
+        let started = std::time::Instant::now();
+        let events = trace(&[(0, 2), (10, 1), (59_000, 1), (60_000, 3), (3_600_000, 4)]);
+
+        let decisions = simulate(
+            || FixedWindowCounter::new(3, Duration::from_secs(60)),
+            events,
+        );
+        assert_eq!(allowed(&decisions), [true, true, false, true, false]);
+        assert_eq!(decisions[1].remaining(), 0);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_out_of_order_events_do_not_rewind() {
+        let events = trace(&[(1_000, 1), (0, 1), (1_500, 1), (2_001, 1)]);
+
+        let decisions = simulate(
+            || SlidingWindowCounter::new(2, Duration::from_secs(1)),
+            events,
+        );
+        assert_eq!(allowed(&decisions), [true, true, false, true]);
+    }
+
+    #[test]
+    fn test_empty_trace() {
+        let decisions = simulate(|| SlidingWindowCounter::new(1, Duration::from_secs(1)), []);
+        assert!(decisions.is_empty());
+    }
+}
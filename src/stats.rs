@@ -0,0 +1,245 @@
+//! Runtime statistics collected by the limiters.
+//!
+//! Limiters that can wait for capacity (such as [`TokenBucket::acquire`]) record how long each
+//! successful call had to wait, and limiters keep track of recent admissions to estimate the
+//! observed rate. The most recent samples are kept in small fixed-size reservoirs, so recording
+//! stays cheap and memory stays bounded no matter how busy the limiter is.
+//!
+//! # Observed rate
+//!
+//! The buckets and the fixed window counter estimate their `observed_rate` from a small buffer
+//! of recent admissions. If more requests were admitted within the requested period than the
+//! buffer holds, the rate is computed over the shorter span the buffer still covers. This keeps
+//! the cost per admission constant at the price of describing only the most recent traffic of
+//! very busy limiters. Refunds are taken back out of the buffer.
+//!
+//! [`TokenBucket::acquire`]: crate::bucket::TokenBucket::acquire
+
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Number of recent wait samples kept for computing statistics.
+const WAIT_SAMPLES: usize = 128;
+
+/// Number of recent admissions kept for estimating the observed rate.
+const RATE_SAMPLES: usize = 256;
+
+/// Summary of recent wait durations.
+///
+/// All values are computed over the most recent samples only, so they describe the
+/// current behaviour of the limiter rather than its whole lifetime.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WaitStats {
+    /// Number of samples the statistics were computed from.
+    pub count: usize,
+    /// Shortest observed wait.
+    pub min: Duration,
+    /// Longest observed wait.
+    pub max: Duration,
+    /// Average wait.
+    pub mean: Duration,
+    /// Median wait.
+    pub p50: Duration,
+    /// 95th percentile wait.
+    pub p95: Duration,
+}
+
+/// Rolling reservoir of wait durations.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(not(feature = "bucket"), allow(dead_code))]
+pub(crate) struct WaitRecorder {
+    samples: VecDeque<Duration>,
+}
+
+#[cfg_attr(not(feature = "bucket"), allow(dead_code))]
+impl WaitRecorder {
+    /// Records a single wait, evicting the oldest sample once the reservoir is full.
+    pub(crate) fn record(&mut self, wait: Duration) {
+        if self.samples.len() == WAIT_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(wait);
+    }
+
+    /// Computes statistics over the samples currently held.
+    pub(crate) fn stats(&self) -> WaitStats {
+        if self.samples.is_empty() {
+            return WaitStats::default();
+        }
+
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+
+        let count = sorted.len();
+        let total: Duration = sorted.iter().sum();
+
+        WaitStats {
+            count,
+            min: sorted[0],
+            max: sorted[count - 1],
+            mean: total / count as u32,
+            p50: percentile(&sorted, 50),
+            p95: percentile(&sorted, 95),
+        }
+    }
+}
+
+/// Rolling record of recent admissions used to estimate the observed rate.
+///
+/// Only the most recent admissions are kept. If all of them fall into the requested period,
+/// the rate is computed over the time span they cover instead, so the estimate stays accurate
+/// for busy limiters while memory stays bounded.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RateRecorder {
+    admissions: VecDeque<(Instant, u64)>,
+}
+
+impl RateRecorder {
+    /// Records an admission of the given `amount` at `now`.
+    pub(crate) fn record(&mut self, now: Instant, amount: u64) {
+        if let Some((last, total)) = self.admissions.back_mut() {
+            if *last == now {
+                *total += amount;
+                return;
+            }
+        }
+
+        if self.admissions.len() == RATE_SAMPLES {
+            self.admissions.pop_front();
+        }
+        self.admissions.push_back((now, amount));
+    }
+
+    /// Takes back `amount` of the most recent admissions, e.g. when they were refunded.
+    pub(crate) fn retract(&mut self, mut amount: u64) {
+        while amount > 0 {
+            let Some((_, total)) = self.admissions.back_mut() else {
+                break;
+            };
+            let retracted = amount.min(*total);
+            *total -= retracted;
+            amount -= retracted;
+            if *total == 0 {
+                self.admissions.pop_back();
+            }
+        }
+    }
+
+    /// Returns the admitted amount per second over the period `over` ending at `now`.
+    pub(crate) fn rate(&self, now: Instant, over: Duration) -> f64 {
+        if over.is_zero() {
+            return 0.0;
+        }
+
+        let admitted: u64 = self
+            .admissions
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) < over)
+            .map(|(_, amount)| amount)
+            .sum();
+
+        let mut span = over;
+        if self.admissions.len() == RATE_SAMPLES {
+            // Older admissions within the period were evicted, only rely on the retained span
+            let covered = now.duration_since(self.admissions[0].0);
+            if covered < over && !covered.is_zero() {
+                span = covered;
+            }
+        }
+
+        admitted as f64 / span.as_secs_f64()
+    }
+}
+
+/// Returns the nearest-rank percentile of an already sorted, non-empty slice.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (percent * sorted.len()).div_ceil(100);
+    sorted[rank.saturating_sub(1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats::{RateRecorder, WaitRecorder, RATE_SAMPLES, WAIT_SAMPLES};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[test]
+    fn test_empty_stats() {
+        let recorder = WaitRecorder::default();
+        let stats = recorder.stats();
+        assert_eq!(stats.count, 0);
+        assert_eq!(stats.max, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_stats_over_samples() {
+        let mut recorder = WaitRecorder::default();
+        for ms in 1..=100 {
+            recorder.record(Duration::from_millis(ms));
+        }
+
+        let stats = recorder.stats();
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.max, Duration::from_millis(100));
+        assert_eq!(stats.mean, Duration::from_micros(50_500));
+        assert_eq!(stats.p50, Duration::from_millis(50));
+        assert_eq!(stats.p95, Duration::from_millis(95));
+    }
+
+    #[test]
+    fn test_reservoir_is_bounded() {
+        let mut recorder = WaitRecorder::default();
+        for ms in 0..(WAIT_SAMPLES as u64 * 2) {
+            recorder.record(Duration::from_millis(ms));
+        }
+
+        let stats = recorder.stats();
+        assert_eq!(stats.count, WAIT_SAMPLES);
+        assert_eq!(stats.min, Duration::from_millis(WAIT_SAMPLES as u64));
+    }
+
+    #[test]
+    fn test_rate_over_period() {
+        let start = Instant::now();
+        let mut recorder = RateRecorder::default();
+        for ms in 0..10 {
+            recorder.record(start + Duration::from_millis(ms * 100), 2);
+        }
+
+        let now = start + Duration::from_millis(999);
+        assert_eq!(recorder.rate(now, Duration::from_secs(1)), 20.0);
+        assert_eq!(recorder.rate(now, Duration::from_secs(2)), 10.0);
+        assert_eq!(recorder.rate(now, Duration::from_millis(500)), 10.0 / 0.5);
+        assert_eq!(recorder.rate(now, Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn test_retract_takes_back_latest_admissions() {
+        let start = Instant::now();
+        let mut recorder = RateRecorder::default();
+        recorder.record(start, 3);
+        recorder.record(start + Duration::from_millis(500), 2);
+
+        recorder.retract(3);
+        let now = start + Duration::from_millis(999);
+        assert_eq!(recorder.rate(now, Duration::from_secs(1)), 2.0);
+
+        recorder.retract(10);
+        assert_eq!(recorder.rate(now, Duration::from_secs(1)), 0.0);
+    }
+
+    #[test]
+    fn test_rate_with_evicted_admissions() {
+        let start = Instant::now();
+        let mut recorder = RateRecorder::default();
+        for ms in 0..(RATE_SAMPLES as u64 * 2) {
+            recorder.record(start + Duration::from_millis(ms), 1);
+        }
+
+        let now = start + Duration::from_millis(RATE_SAMPLES as u64 * 2);
+        let rate = recorder.rate(now, Duration::from_secs(10));
+        assert!((rate - 1000.0).abs() < 5.0, "rate was {}", rate);
+    }
+}
@@ -0,0 +1,11 @@
+//! Helpers shared by the unit tests.
+
+/// Builds a single-threaded runtime whose clock starts paused, to drive the limiters from
+/// synchronous tests such as the property tests.
+pub(crate) fn paused_runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .start_paused(true)
+        .build()
+        .unwrap()
+}
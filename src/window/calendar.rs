@@ -0,0 +1,65 @@
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Utc};
+use std::time::Duration;
+
+/// A calendar period that [`FixedWindowCounter::calendar`] aligns its windows to.
+///
+/// Boundaries are computed from the UNIX epoch in UTC, or in a fixed UTC offset with
+/// [`FixedWindowCounter::calendar_with_offset`], so windows start at the top of the hour, at
+/// midnight or on the first day of the month respectively. Fixed offsets have no daylight
+/// saving time, so every day has exactly 24 hours.
+///
+/// [`FixedWindowCounter::calendar`]: crate::window::FixedWindowCounter::calendar
+/// [`FixedWindowCounter::calendar_with_offset`]: crate::window::FixedWindowCounter::calendar_with_offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CalendarWindow {
+    /// Windows starting at the top of every hour.
+    Hourly,
+    /// Windows starting at midnight.
+    Daily,
+    /// Windows starting at midnight on the first day of every month.
+    Monthly,
+}
+
+impl CalendarWindow {
+    /// Returns the length of a window.
+    ///
+    /// Months differ in length, so [`CalendarWindow::Monthly`] returns the length of the longest
+    /// month, 31 days.
+    pub fn duration(&self) -> Duration {
+        match self {
+            CalendarWindow::Hourly => Duration::from_secs(60 * 60),
+            CalendarWindow::Daily => Duration::from_secs(24 * 60 * 60),
+            CalendarWindow::Monthly => Duration::from_secs(31 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// Returns the id of the calendar month containing the given time since the UNIX epoch,
+/// counting months in `offset` since January 1970.
+pub(crate) fn month_at(elapsed: Duration, offset: FixedOffset) -> u64 {
+    let seconds = i64::try_from(elapsed.as_secs()).unwrap_or(i64::MAX);
+    let Some(time) = DateTime::<Utc>::from_timestamp(seconds, 0) else {
+        return u64::MAX;
+    };
+
+    let local = time.with_timezone(&offset);
+    let months = (local.year() as i64 - 1970) * 12 + local.month0() as i64;
+    // The last month of 1969 in offsets west of UTC counts as the first window
+    months.max(0) as u64
+}
+
+/// Returns the time since the UNIX epoch at which the calendar month `window` starts in
+/// `offset`, see [`month_at`].
+pub(crate) fn month_start(window: u64, offset: FixedOffset) -> Duration {
+    let start = i32::try_from(window / 12)
+        .ok()
+        .and_then(|years| years.checked_add(1970))
+        .and_then(|year| NaiveDate::from_ymd_opt(year, (window % 12) as u32 + 1, 1))
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|midnight| midnight.and_local_timezone(offset).single());
+
+    match start {
+        Some(start) => Duration::from_secs(start.timestamp().max(0) as u64),
+        None => Duration::MAX,
+    }
+}
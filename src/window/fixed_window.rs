@@ -1,18 +1,76 @@
-use std::collections::HashMap;
+use crate::cleanup::CleanupHandle;
+use crate::clock::CoarseClock;
+use crate::decision::Decision;
+use crate::error::{InvalidConfigReason, LimitrError};
+use crate::limiter::{BoxFuture, RateLimiter, SharedRateLimiter};
+use crate::quota::Quota;
+use crate::stats::RateRecorder;
+#[cfg(feature = "chrono")]
+use crate::window::calendar::{self, CalendarWindow};
+use crate::window::validate_window_duration;
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, FixedOffset, Utc};
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, TryLockError};
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::Mutex;
-use tokio::time::Duration;
+use tokio::task;
+use tokio::time::{Duration, Instant};
+
+/// Most history entries preallocated, so a huge history does not reserve memory up front.
+const MAX_PREALLOCATED_HISTORY: usize = 1024;
+
+/// The time source a [`FixedWindowCounter`] derives its windows from.
+///
+/// By default windows are measured on a monotonic clock starting when the counter is created,
+/// so adjustments of the system clock cannot reopen or skip windows. The wall-clock variants
+/// align windows to the UNIX epoch instead, which is useful when several processes need to
+/// agree on window boundaries. Even then, a clock stepping backwards never moves the counter
+/// back into an earlier window.
+#[derive(Clone, Default)]
+pub enum WindowClock {
+    /// Monotonic time relative to the creation of the counter.
+    #[default]
+    Monotonic,
+    /// System time relative to the UNIX epoch.
+    ///
+    /// Unlike the monotonic clock, system time does not follow [`tokio::time::pause`], so tests
+    /// controlling the time should use a `Custom` clock instead.
+    WallClock,
+    /// A custom wall-clock source, e.g. to simulate clock adjustments in tests.
+    Custom(Arc<dyn Fn() -> SystemTime + Send + Sync>),
+}
+
+impl fmt::Debug for WindowClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowClock::Monotonic => f.write_str("Monotonic"),
+            WindowClock::WallClock => f.write_str("WallClock"),
+            WindowClock::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
 
 /// A Fixed Window Counter rate limiter.
 ///
 /// This implementation uses fixed time windows to limit the number of requests within each window.
 /// It's simple to understand and implement, but can allow twice the rate of requests around window boundaries.
 ///
+/// Windows are measured on a monotonic clock relative to the creation of the counter unless a
+/// different [`WindowClock`] is configured.
+///
 /// # Features
 ///
 /// - Uses fixed time windows for rate limiting.
 /// - Allows a specified number of requests within each time window.
-/// - Automatically clears old windows to prevent memory growth.
+/// - Keeps only the current and the previous window, so memory stays constant.
+/// - Admits and denies requests lock-free, only the first request in a new window takes a lock.
+/// - With the `serde` feature, counts of wall-clock based counters can be persisted and restored
+///   across restarts.
 ///
 /// # Example
 ///
@@ -30,9 +88,40 @@ use tokio::time::Duration;
 /// # })
 /// ```
 pub struct FixedWindowCounter {
-    limit: u32,
-    window_duration: Duration,
-    windows: Mutex<HashMap<u64, u32>>,
+    limit: AtomicU32,
+    timing: RwLock<WindowTiming>,
+    clock: WindowClock,
+    /// Cached monotonic time, if enabled
+    coarse_clock: Option<CoarseClock>,
+    /// Whether the previous window is weighted into decisions
+    sliding_approximation: bool,
+    /// Most unused budget carried over into the next window, if enabled
+    max_carry: Option<u32>,
+    /// Units in use beyond which admissions are flagged, if configured
+    soft_limit: Option<u32>,
+    /// Whether the budget of a window is spread evenly across its duration
+    smoothing: bool,
+    /// Called with the id and final count of every finished window
+    on_rollover: Option<Box<dyn Fn(u64, u32) + Send + Sync>>,
+    /// Most finished windows kept in `history`
+    history_len: usize,
+    /// Ids and final counts of the latest finished windows, oldest first
+    history: Mutex<VecDeque<(u64, u32)>>,
+    /// Reference point for the monotonic clock
+    origin: Instant,
+    /// Highest window id observed so far, windows never move backwards
+    latest_window: AtomicU64,
+    /// Id of the window of the latest admission plus one, zero if there is none
+    last_admitted: AtomicU64,
+    /// Id of the window of the first admission plus one, zero if there is none
+    first_admitted: AtomicU64,
+    windows: WindowCounts,
+    /// Serializes rollovers and other changes of the tracked windows
+    rollover: Mutex<()>,
+    /// Recently admitted requests
+    admissions: Mutex<RateRecorder>,
+    /// Admitted amount not yet recorded because the recorder was busy
+    pending_admissions: AtomicU64,
 }
 
 impl FixedWindowCounter {
@@ -49,17 +138,67 @@ impl FixedWindowCounter {
     ///
     /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60)); // 100 requests per minute
     /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window_duration` is zero or too large, see [`FixedWindowCounter::try_new`].
     pub fn new(limit: u32, window_duration: Duration) -> Self {
-        FixedWindowCounter {
-            limit,
-            window_duration,
-            windows: Mutex::new(HashMap::new()),
+        match FixedWindowCounter::try_new(limit, window_duration) {
+            Ok(counter) => counter,
+            Err(err) => panic!("{}", err),
         }
     }
 
-    /// Attempts to consume a token from the current time window.
+    /// Creates a new `FixedWindowCounter` with the specified `limit` and `window_duration`, or
+    /// fails if windows of that duration cannot be measured.
     ///
-    /// Returns `true` if the request is allowed, and `false` if the limit has been reached for the current window.
+    /// A zero limit is valid and denies every request until it is raised with
+    /// [`FixedWindowCounter::set_limit`].
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`LimitrError::InvalidConfig`] if `window_duration` is zero or longer than
+    /// `u64::MAX` nanoseconds.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// assert!(FixedWindowCounter::try_new(100, Duration::from_secs(60)).is_ok());
+    /// assert!(FixedWindowCounter::try_new(100, Duration::ZERO).is_err());
+    /// ```
+    pub fn try_new(limit: u32, window_duration: Duration) -> Result<Self, LimitrError> {
+        validate_window_duration(window_duration)?;
+        Ok(FixedWindowCounter {
+            limit: AtomicU32::new(limit),
+            timing: RwLock::new(WindowTiming::uniform(window_duration)),
+            clock: WindowClock::default(),
+            coarse_clock: None,
+            sliding_approximation: false,
+            max_carry: None,
+            soft_limit: None,
+            smoothing: false,
+            on_rollover: None,
+            history_len: 0,
+            history: Mutex::new(VecDeque::new()),
+            origin: Instant::now(),
+            latest_window: AtomicU64::new(0),
+            last_admitted: AtomicU64::new(0),
+            first_admitted: AtomicU64::new(0),
+            windows: WindowCounts::default(),
+            rollover: Mutex::new(()),
+            admissions: Mutex::new(RateRecorder::default()),
+            pending_admissions: AtomicU64::new(0),
+        })
+    }
+
+    /// Creates a new `FixedWindowCounter` with the specified `limit` and `window_duration`,
+    /// behind an `Arc` whose clones all use the same counter.
+    ///
+    /// All methods take `&self` and admit requests lock-free, so the `Arc` is all that is
+    /// needed to share the counter between tasks.
     ///
     /// # Example
     ///
@@ -68,31 +207,152 @@ impl FixedWindowCounter {
     /// use limitr::window::FixedWindowCounter;
     ///
     /// # tokio_test::block_on(async {
-    /// let mut counter = FixedWindowCounter::new(5, Duration::from_secs(60));
-    /// for _ in 0..5 {
-    ///     assert!(counter.try_consume().await);
-    /// }
+    /// let counter = FixedWindowCounter::shared(1, Duration::from_secs(60));
+    /// let handle = counter.clone();
     ///
+    /// assert!(handle.try_consume().await);
     /// assert!(!counter.try_consume().await);
     /// # })
     /// ```
-    pub async fn try_consume(&self) -> bool {
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        let current_window = current_time.as_secs() / self.window_duration.as_secs();
-        let mut windows = self.windows.lock().await;
-
-        let count = windows.entry(current_window).or_insert(0);
-        if *count < self.limit {
-            *count += 1;
-            true
-        } else {
-            false
+    pub fn shared(limit: u32, window_duration: Duration) -> Arc<Self> {
+        Arc::new(FixedWindowCounter::new(limit, window_duration))
+    }
+
+    /// Creates a new `FixedWindowCounter` admitting the amount of `quota` in every window of the
+    /// quota's period.
+    ///
+    /// A burst above the amount is translated into [rollover](FixedWindowCounter::with_rollover)
+    /// of up to the difference, so a window following a quiet one admits up to the burst. A
+    /// burst below the amount does not apply.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`LimitrError::InvalidConfig`] if the amount does not fit into a `u32` or the
+    /// period is not a valid window duration.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::window::FixedWindowCounter;
+    /// use limitr::Quota;
+    ///
+    /// let counter = FixedWindowCounter::from_quota(Quota::per_minute(600)).unwrap();
+    /// assert_eq!(counter.limit(), 600);
+    /// ```
+    pub fn from_quota(quota: Quota) -> Result<Self, LimitrError> {
+        let limit = u32::try_from(quota.amount())
+            .map_err(|_| LimitrError::InvalidConfig(InvalidConfigReason::AmountTooLarge))?;
+        let counter = FixedWindowCounter::try_new(limit, quota.period())?;
+
+        let max_carry = quota.burst().saturating_sub(quota.amount());
+        if max_carry == 0 {
+            return Ok(counter);
+        }
+        Ok(counter.with_rollover(u32::try_from(max_carry).unwrap_or(u32::MAX)))
+    }
+
+    /// Creates a new `FixedWindowCounter` whose windows are aligned to calendar boundaries in UTC.
+    ///
+    /// The counter runs on [`WindowClock::WallClock`], so e.g. hourly windows reset at the top of
+    /// every hour no matter when the counter was created. A different wall clock can still be
+    /// set with [`FixedWindowCounter::with_clock`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::window::{CalendarWindow, FixedWindowCounter};
+    ///
+    /// // 10,000 requests per calendar day
+    /// let counter = FixedWindowCounter::calendar(10_000, CalendarWindow::Daily);
+    /// println!("Quota resets at {}", counter.reset_at());
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn calendar(limit: u32, window: CalendarWindow) -> Self {
+        FixedWindowCounter::calendar_with_offset(limit, window, FixedOffset::east_opt(0).unwrap())
+    }
+
+    /// Creates a new `FixedWindowCounter` whose windows are aligned to calendar boundaries in a
+    /// fixed UTC `offset`, e.g. to reset daily quotas at midnight in a customer's time zone.
+    ///
+    /// See [`FixedWindowCounter::calendar`]. Fixed offsets do not follow daylight saving time,
+    /// so pick the offset the quota is defined in.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use chrono::FixedOffset;
+    /// use limitr::window::{CalendarWindow, FixedWindowCounter};
+    ///
+    /// // 1,000,000 requests per calendar month, with months starting in UTC+09:00
+    /// let tokyo = FixedOffset::east_opt(9 * 60 * 60).unwrap();
+    /// let counter =
+    ///     FixedWindowCounter::calendar_with_offset(1_000_000, CalendarWindow::Monthly, tokyo);
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn calendar_with_offset(limit: u32, window: CalendarWindow, offset: FixedOffset) -> Self {
+        let timing = match window {
+            CalendarWindow::Hourly | CalendarWindow::Daily => {
+                // Local boundaries fall on UTC instants that are `offset` before multiples of
+                // the window length
+                let length = window.duration().as_secs() as i64;
+                let first = (-(offset.local_minus_utc() as i64)).rem_euclid(length);
+                WindowTiming {
+                    base_offset: Duration::from_secs(first as u64),
+                    ..WindowTiming::uniform(window.duration())
+                }
+            }
+            CalendarWindow::Monthly => WindowTiming {
+                months: Some(offset),
+                ..WindowTiming::uniform(window.duration())
+            },
+        };
+
+        let counter = FixedWindowCounter::new(limit, timing.duration);
+        *counter
+            .timing
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = timing;
+        counter.with_clock(WindowClock::WallClock)
+    }
+
+    /// Sets the time source the windows are derived from.
+    ///
+    /// Window ids of different clocks are unrelated, so the tracked windows start over on the
+    /// new clock. The count of the current window, e.g. from
+    /// [`with_initial_count`](FixedWindowCounter::with_initial_count), is carried into the
+    /// current window of the new clock.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::{FixedWindowCounter, WindowClock};
+    ///
+    /// // Windows aligned to the UNIX epoch, e.g. to share boundaries across processes
+    /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60))
+    ///     .with_clock(WindowClock::WallClock);
+    /// ```
+    pub fn with_clock(mut self, clock: WindowClock) -> Self {
+        let count = self.windows.count(self.current_window());
+        self.clock = clock;
+        self.latest_window = AtomicU64::new(0);
+        self.last_admitted = AtomicU64::new(0);
+        self.first_admitted = AtomicU64::new(0);
+        self.windows = WindowCounts::default();
+        if count > 0 {
+            self.seed_current_window(count);
         }
+        self
     }
 
-    /// Clears old time windows to prevent unbounded growth of the internal HashMap.
+    /// Reads the monotonic time from a [`CoarseClock`] refreshed every `resolution`, instead of
+    /// reading the current instant for every request.
     ///
-    /// This method should be called periodically to remove data for expired time windows.
+    /// This saves a clock read per request on hot paths at the price of measuring windows up
+    /// to one resolution late, so a request right after a window boundary may still be counted
+    /// in the window before. Wall and custom clocks are still read for every request.
+    ///
+    /// The clock is refreshed by a task on the current tokio runtime, see [`CoarseClock`].
     ///
     /// # Example
     ///
@@ -101,109 +361,3069 @@ impl FixedWindowCounter {
     /// use limitr::window::FixedWindowCounter;
     ///
     /// # tokio_test::block_on(async {
-    /// let mut counter = FixedWindowCounter::new(100, Duration::from_secs(60));
-    /// // ... some time passes ...
-    /// counter.clear_old_windows().await;
+    /// let counter = FixedWindowCounter::new(100_000, Duration::from_secs(1))
+    ///     .with_coarse_clock(Duration::from_millis(1));
+    /// assert!(counter.try_consume().await);
     /// # })
     /// ```
-    pub async fn clear_old_windows(&self) {
-        let current_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        let oldest_valid_window = current_time.as_secs() / self.window_duration.as_secs();
-        let mut windows = self.windows.lock().await;
-
-        windows.retain(|&window, _| window >= oldest_valid_window);
+    pub fn with_coarse_clock(mut self, resolution: Duration) -> Self {
+        self.coarse_clock = Some(CoarseClock::new(resolution));
+        self
     }
-}
-#[cfg(test)]
-mod tests {
-    // assert will mess up codecov report use assert_eg instead
 
-    use crate::window::FixedWindowCounter;
-    use tokio::time::{self, Duration};
+    /// Returns the coarse clock the counter reads its time from, if one is set.
+    pub fn coarse_clock(&self) -> Option<&CoarseClock> {
+        self.coarse_clock.as_ref()
+    }
 
-    #[tokio::test]
-    async fn test_allows_requests_under_limit() {
-        let counter = FixedWindowCounter::new(5, Duration::from_secs(60));
+    /// Returns the current instant, from the coarse clock if one is set.
+    fn now(&self) -> Instant {
+        self.coarse_clock
+            .as_ref()
+            .map_or_else(Instant::now, CoarseClock::now)
+    }
 
-        for _ in 0..5 {
-            assert_eq!(
-                counter.try_consume().await,
-                true,
-                "Request should be allowed"
-            );
-        }
+    /// Approximates a sliding window by also counting the previous window.
+    ///
+    /// A plain fixed window admits up to twice the limit around a window boundary, e.g. a full
+    /// limit at the end of one window and another right after the next window starts. With this
+    /// mode, the count of the previous window is weighted by how much it overlaps the last
+    /// window-long period and added to the current count:
+    ///
+    /// `previous_count * overlap + current_count + cost <= limit`
+    ///
+    /// This closely follows a [`SlidingWindowCounter`](crate::window::SlidingWindowCounter)
+    /// while still only storing two counters. It assumes requests were spread evenly over the
+    /// previous window, so it is an approximation.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60))
+    ///     .with_sliding_approximation();
+    /// ```
+    pub fn with_sliding_approximation(mut self) -> Self {
+        self.sliding_approximation = true;
+        self
+    }
 
-        assert_eq!(
-            counter.try_consume().await,
-            false,
-            "Request should be rate-limited"
-        );
+    /// Carries unused budget of a window over into the next one, up to `max_carry`.
+    ///
+    /// When a window rolls over, `min(limit - used, max_carry)` is added to the limit of the
+    /// next window. Carried budget that goes unused is not carried again, so the effective limit
+    /// never exceeds `limit + max_carry`, no matter how many windows stay idle. The first window
+    /// a request is admitted in has nothing to carry over from.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// // 100 requests per minute, with up to 20 unused requests rolling over
+    /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60)).with_rollover(20);
+    /// ```
+    pub fn with_rollover(mut self, max_carry: u32) -> Self {
+        self.max_carry = Some(max_carry);
+        self
+    }
 
-        let windows = counter.windows.lock().await;
-        let window_key = windows.keys().next().unwrap();
-        assert_eq!(
-            windows[window_key], 5,
-            "Should have recorded 5 requests in the current window"
-        );
+    /// Sets a soft limit of `units` in use within a window, beyond which requests are still
+    /// admitted but reported as [`Decision::AllowedSoftExceeded`] by [`FixedWindowCounter::check`].
+    ///
+    /// This lets clients be warned that they are approaching the limit before they are cut off.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(10, Duration::from_secs(60)).with_soft_limit(8);
+    ///
+    /// assert!(!counter.check(8).await.is_soft_exceeded());
+    /// let decision = counter.check(1).await;
+    /// assert!(decision.is_allowed());
+    /// assert!(decision.is_soft_exceeded());
+    /// # })
+    /// ```
+    pub fn with_soft_limit(mut self, units: u32) -> Self {
+        self.soft_limit = Some(units);
+        self
     }
 
-    #[tokio::test]
-    async fn test_allows_new_window_requests() {
-        let counter = FixedWindowCounter::new(3, Duration::from_secs(2));
+    /// Returns the soft limit of units in use within a window, if one is set.
+    pub fn soft_limit(&self) -> Option<u32> {
+        self.soft_limit
+    }
 
-        for _ in 0..3 {
-            assert_eq!(
-                counter.try_consume().await,
-                true,
-                "Request should be allowed"
-            );
-        }
+    /// Spreads the budget of each window evenly across its duration.
+    ///
+    /// Without smoothing, every client waiting for a window to reset is admitted in the same
+    /// instant, and the backend sees the whole budget as one spike. With smoothing, a window
+    /// only admits up to `limit * elapsed_fraction + 1` units by any point into it, so the
+    /// first request of a window is always admitted and the full budget, including carried
+    /// budget, becomes available just before the window ends. [`remaining`] and the hints of
+    /// [`check`] follow this schedule.
+    ///
+    /// [`remaining`]: FixedWindowCounter::remaining
+    /// [`check`]: FixedWindowCounter::check
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// // 60 requests per minute, admitted at about one per second
+    /// let counter = FixedWindowCounter::new(60, Duration::from_secs(60)).with_smoothing(true);
+    ///
+    /// assert!(counter.try_consume().await);
+    /// assert!(!counter.try_consume().await);
+    /// # })
+    /// ```
+    pub fn with_smoothing(mut self, enabled: bool) -> Self {
+        self.smoothing = enabled;
+        self
+    }
 
-        time::sleep(Duration::from_secs(3)).await;
-        counter.clear_old_windows().await;
+    /// Calls `callback` with the id and final count of every window once it has finished.
+    ///
+    /// A window counts as finished the first time the counter observes a later window, i.e.
+    /// when a request arrives in a new window or [`clear_old_windows`] drops it. The callback
+    /// runs exactly once per window, on the task that observed the rollover and without any of
+    /// the counter's locks held. Windows without any admitted or denied requests are never
+    /// tracked and therefore never reported. A panic in the callback is caught and logged.
+    ///
+    /// [`clear_old_windows`]: FixedWindowCounter::clear_old_windows
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60))
+    ///     .on_rollover(|window, count| println!("window {} closed with {} requests", window, count));
+    /// ```
+    pub fn on_rollover(mut self, callback: impl Fn(u64, u32) + Send + Sync + 'static) -> Self {
+        self.on_rollover = Some(Box::new(callback));
+        self
+    }
 
-        assert_eq!(
-            counter.try_consume().await,
-            true,
-            "Request should be allowed in the new window"
-        );
+    /// Keeps the ids and final counts of the last `windows` finished windows for
+    /// [`history`](FixedWindowCounter::history).
+    ///
+    /// Windows are recorded as they finish, in the same way they are passed to
+    /// [`on_rollover`](FixedWindowCounter::on_rollover), and the oldest entry is dropped once
+    /// `windows` entries are kept, so memory stays bounded. Zero disables the history. Memory
+    /// for at most 1024 entries is reserved up front, the rest grows as windows finish.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// // Remember the counts of the last 60 one-minute windows
+    /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60)).with_history(60);
+    /// ```
+    pub fn with_history(mut self, windows: usize) -> Self {
+        self.history_len = windows;
+        self.history = Mutex::new(VecDeque::with_capacity(
+            windows.min(MAX_PREALLOCATED_HISTORY),
+        ));
+        self
+    }
 
-        let windows = counter.windows.lock().await;
-        assert_eq!(windows.len(), 1, "There should be exactly 1 active window");
+    /// Primes the current window as if `count` units had already been consumed in it.
+    ///
+    /// See [`FixedWindowCounter::seed_current_window`]. The seed is kept if the clock is changed
+    /// afterwards with [`FixedWindowCounter::with_clock`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60)).with_initial_count(80);
+    /// assert_eq!(counter.remaining().await, 20);
+    /// # })
+    /// ```
+    pub fn with_initial_count(self, count: u32) -> Self {
+        self.seed_current_window(count);
+        self
     }
 
-    #[tokio::test]
-    async fn test_clears_old_windows() {
-        let counter = FixedWindowCounter::new(3, Duration::from_secs(2));
+    /// Primes the current window as if `count` units had already been consumed in it.
+    ///
+    /// Useful when traffic moves to a fresh instance mid-window, so clients cannot spend their
+    /// budget a second time. The count is clamped to the limit and only ever raised, so seeding
+    /// never undoes requests admitted in the meantime. The seed applies to the current window
+    /// only and is gone once it rolls over.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60));
+    /// counter.seed_current_window(95);
+    ///
+    /// assert_eq!(counter.remaining().await, 5);
+    /// assert!(!counter.try_consume_n(6).await);
+    /// # })
+    /// ```
+    pub fn seed_current_window(&self, count: u32) {
+        let count = count.min(self.limit());
+        let finished = {
+            let _rollover = lock(&self.rollover);
+            let current_window = self.current_window();
+            let finished = self.windows.roll_to(current_window);
+            self.windows.raise(current_window, count);
+            finished
+        };
 
-        for _ in 0..3 {
-            assert_eq!(
-                counter.try_consume().await,
-                true,
-                "Request should be allowed"
-            );
+        if let Some((window, count)) = finished {
+            self.notify_rollover(window, count);
         }
+    }
 
-        counter.clear_old_windows().await;
+    /// Returns the id of the current time window.
+    ///
+    /// The id never decreases, even if a wall clock is stepped backwards. A wall clock set
+    /// before the UNIX epoch is treated as the epoch itself.
+    fn current_window(&self) -> u64 {
+        self.window_position().0
+    }
 
-        let windows = counter.windows.lock().await;
-        assert_eq!(
-            windows.is_empty(),
-            false,
-            "Windows should contain data after clearing"
-        );
+    /// Returns the id of the current time window, how far into that window the clock is and
+    /// the window duration.
+    fn window_position(&self) -> (u64, Duration, Duration) {
+        self.window_position_at(None)
+    }
 
-        drop(windows);
+    /// Like [`FixedWindowCounter::window_position`], but at the instant `now` if given.
+    fn window_position_at(&self, now: Option<Instant>) -> (u64, Duration, Duration) {
+        let timing = *self.timing();
+        let (window, into_window) = self.position_with(&timing, now);
+        (window, into_window, timing.length_of(window))
+    }
 
-        time::sleep(Duration::from_secs(3)).await;
+    /// Locates the current time, or the instant `now` if given, on the window clock using the
+    /// given window timing.
+    ///
+    /// Wall and custom clocks are read as they are now and shifted by the distance of `now` from
+    /// the current instant.
+    fn position_with(&self, timing: &WindowTiming, now: Option<Instant>) -> (u64, Duration) {
+        let shift = |time: SystemTime| match now {
+            None => time,
+            Some(now) => {
+                let current = Instant::now();
+                if now >= current {
+                    time + (now - current)
+                } else {
+                    time.checked_sub(current - now).unwrap_or(UNIX_EPOCH)
+                }
+            }
+        };
+        let elapsed = match &self.clock {
+            WindowClock::Monotonic => Ok(now
+                .unwrap_or_else(|| self.now())
+                .duration_since(self.origin)),
+            WindowClock::WallClock => since_epoch(shift(SystemTime::now())),
+            WindowClock::Custom(clock) => since_epoch(shift(clock())),
+        }
+        // A clock before the epoch is treated as the epoch, the window watermark below keeps
+        // the counter from moving back into an earlier window
+        .unwrap_or(Duration::ZERO);
+        let window = timing.window_at(elapsed);
 
-        counter.clear_old_windows().await;
+        let latest = self.latest_window.fetch_max(window, Ordering::AcqRel);
+        let window = latest.max(window);
+        (window, elapsed.saturating_sub(timing.start_of(window)))
+    }
 
-        let windows = counter.windows.lock().await;
-        assert_eq!(
-            windows.is_empty(),
-            true,
-            "Windows should be empty after clearing old windows"
-        );
+    fn timing(&self) -> RwLockReadGuard<'_, WindowTiming> {
+        self.timing.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Changes the maximum number of requests allowed in each time window.
+    ///
+    /// The new limit applies to the current window immediately. Raising it allows further
+    /// requests right away, lowering it below the number of requests already admitted in the
+    /// current window simply denies everything until the next window starts.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(1, Duration::from_secs(60));
+    /// assert!(counter.try_consume().await);
+    /// assert!(!counter.try_consume().await);
+    ///
+    /// counter.set_limit(2);
+    /// assert!(counter.try_consume().await);
+    /// # })
+    /// ```
+    pub fn set_limit(&self, limit: u32) {
+        self.limit.store(limit, Ordering::Release);
+    }
+
+    /// Changes the duration of the time windows.
+    ///
+    /// The current window keeps its start and its count, but now ends after the new duration,
+    /// and window boundaries from then on are spaced by the new duration. Window ids are
+    /// re-based onto the current window, so counts recorded so far keep referring to the
+    /// windows they were made in. Counters on a wall clock lose their alignment to the UNIX
+    /// epoch, since boundaries are then measured from the start of the current window.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60));
+    /// counter.set_window_duration(Duration::from_secs(10)).unwrap();
+    /// assert!(counter.reset_in() <= Duration::from_secs(10));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LimitrError::InvalidConfig`] if `window_duration` is zero or too large, like
+    /// [`FixedWindowCounter::try_new`]. The current configuration is left unchanged in that case.
+    pub fn set_window_duration(&self, window_duration: Duration) -> Result<(), LimitrError> {
+        validate_window_duration(window_duration)?;
+
+        let mut timing = self.timing.write().unwrap_or_else(PoisonError::into_inner);
+        let (window, _) = self.position_with(&timing, None);
+
+        *timing = WindowTiming {
+            base_window: window,
+            base_offset: timing.start_of(window),
+            ..WindowTiming::uniform(window_duration)
+        };
+        Ok(())
+    }
+
+    /// Returns how long it takes until the current time window rolls over.
+    ///
+    /// Once this duration has passed, a new window begins and the full limit is available again.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60));
+    /// assert!(counter.reset_in() <= Duration::from_secs(60));
+    /// ```
+    pub fn reset_in(&self) -> Duration {
+        let (_, into_window, duration) = self.window_position();
+        duration.saturating_sub(into_window)
+    }
+
+    /// Returns the instant at which the current time window started.
+    ///
+    /// For wall-clock based counters the instant is derived from the current wall-clock time.
+    /// If the window started before the earliest instant the platform can represent, e.g. a
+    /// calendar month that began before the machine booted, the creation of the counter is
+    /// returned instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    /// use tokio::time::Instant;
+    ///
+    /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60));
+    /// assert!(counter.window_start() <= Instant::now());
+    /// ```
+    pub fn window_start(&self) -> Instant {
+        let (_, into_window, _) = self.window_position();
+        self.now().checked_sub(into_window).unwrap_or(self.origin)
+    }
+
+    /// Returns the wall-clock time at which the current time window started.
+    ///
+    /// For wall-clock based counters this is the exact window boundary, for monotonic counters
+    /// it is derived from the current system time.
+    #[cfg(feature = "chrono")]
+    pub fn window_start_utc(&self) -> DateTime<Utc> {
+        let timing = *self.timing();
+        self.wall_clock_start(&timing).0.into()
+    }
+
+    /// Returns the wall-clock time at which the current time window rolls over.
+    ///
+    /// Useful to report quota resets in civil time, e.g. in logs or billing records.
+    #[cfg(feature = "chrono")]
+    pub fn reset_at(&self) -> DateTime<Utc> {
+        let timing = *self.timing();
+        let (start, window) = self.wall_clock_start(&timing);
+        (start + timing.length_of(window)).into()
+    }
+
+    /// Returns the wall-clock time at which the current window started, along with its id.
+    #[cfg(feature = "chrono")]
+    fn wall_clock_start(&self, timing: &WindowTiming) -> (SystemTime, u64) {
+        let (window, into_window) = self.position_with(timing, None);
+        let start = match &self.clock {
+            WindowClock::Monotonic => SystemTime::now() - into_window,
+            WindowClock::WallClock | WindowClock::Custom(_) => UNIX_EPOCH + timing.start_of(window),
+        };
+        (start, window)
+    }
+
+    /// Returns the maximum number of requests allowed in each time window.
+    pub fn limit(&self) -> u32 {
+        self.limit.load(Ordering::Acquire)
+    }
+
+    /// Returns the duration of each time window.
+    ///
+    /// For windows aligned to calendar months, this is the length of the current month.
+    pub fn window_duration(&self) -> Duration {
+        self.window_position().2
+    }
+
+    /// Returns the number of units consumed in the current time window.
+    ///
+    /// This only inspects the counter, it never consumes anything or starts tracking a new
+    /// window.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(10, Duration::from_secs(60));
+    /// counter.try_consume_n(3).await;
+    /// assert_eq!(counter.current_count().await, 3);
+    /// # })
+    /// ```
+    pub async fn current_count(&self) -> u32 {
+        let _rollover = lock(&self.rollover);
+        self.windows.count(self.current_window())
+    }
+
+    /// Returns how many units can still be consumed in the current time window.
+    ///
+    /// Useful for reporting headers such as `X-RateLimit-Remaining` without consuming anything.
+    /// If the limit was lowered below the current count, zero is returned. With the
+    /// [sliding approximation](FixedWindowCounter::with_sliding_approximation), the weighted
+    /// count of the previous window is taken into account as well, and with
+    /// [rollover](FixedWindowCounter::with_rollover) the budget carried into the current window.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(10, Duration::from_secs(60));
+    /// assert_eq!(counter.remaining().await, 10);
+    ///
+    /// counter.try_consume().await;
+    /// assert_eq!(counter.remaining().await, 9);
+    /// # })
+    /// ```
+    pub async fn remaining(&self) -> u32 {
+        let (limit, usage) = self.usage();
+        limit.saturating_sub(usage).min(u32::MAX as u64) as u32
+    }
+
+    /// Checks whether a single request would currently be admitted, without consuming it.
+    ///
+    /// The current window is only inspected, so probes such as health checks never start
+    /// tracking a new window. The answer agrees with a `try_consume` issued immediately
+    /// afterwards, unless other requests are admitted in between.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(1, Duration::from_secs(60));
+    ///
+    /// assert!(counter.would_allow());
+    /// assert!(counter.try_consume().await);
+    /// assert!(!counter.would_allow());
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub fn would_allow(&self) -> bool {
+        self.would_allow_n(1)
+    }
+
+    /// Checks whether a request of the given `cost` would currently be admitted, without
+    /// consuming it.
+    ///
+    /// See [`FixedWindowCounter::would_allow`] for details.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub fn would_allow_n(&self, cost: u32) -> bool {
+        let (limit, usage) = self.usage();
+        usage.saturating_add(cost as u64) <= limit
+    }
+
+    /// Returns the effective limit of the current window and how much of it is used.
+    fn usage(&self) -> (u64, u64) {
+        let _rollover = lock(&self.rollover);
+        self.usage_locked()
+    }
+
+    /// Like [`FixedWindowCounter::usage`], for callers already holding the rollover lock.
+    fn usage_locked(&self) -> (u64, u64) {
+        let (current_window, into_window, duration) = self.window_position();
+
+        let limit = self.limit() as u64 + self.carry(current_window);
+        let previous = self.previous_weight(current_window, into_window, duration);
+        let budget = self.budget(limit, into_window, duration);
+        (budget, previous + self.windows.count(current_window) as u64)
+    }
+
+    /// Returns how much of `limit` a window admits by `into_window`, which is all of it unless
+    /// smoothing is enabled.
+    fn budget(&self, limit: u64, into_window: Duration, duration: Duration) -> u64 {
+        if !self.smoothing || duration.is_zero() {
+            return limit;
+        }
+
+        let earned = limit as u128 * into_window.as_nanos() / duration.as_nanos();
+        (earned as u64).saturating_add(1).min(limit)
+    }
+
+    /// Returns how far into a window of `duration` the smoothed budget of `limit` covers `usage`.
+    fn budget_covers(usage: u64, limit: u64, duration: Duration) -> Duration {
+        if usage <= 1 || limit == 0 {
+            return Duration::ZERO;
+        }
+
+        let nanos = ((usage - 1) as u128 * duration.as_nanos()).div_ceil(limit as u128);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+
+    /// Attempts to consume a token from the current time window.
+    ///
+    /// Returns `true` if the request is allowed, and `false` if the limit has been reached for the current window.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let mut counter = FixedWindowCounter::new(5, Duration::from_secs(60));
+    /// for _ in 0..5 {
+    ///     assert!(counter.try_consume().await);
+    /// }
+    ///
+    /// assert!(!counter.try_consume().await);
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume(&self) -> bool {
+        self.try_consume_n(1).await
+    }
+
+    /// Attempts to consume `cost` units from the current time window.
+    ///
+    /// The cost is only added if the window stays within the limit; a rejected request does not
+    /// count towards the window at all. A cost larger than the limit can never be admitted, use
+    /// [`FixedWindowCounter::can_ever_admit`] to tell such requests apart from temporary denials.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(10, Duration::from_secs(60));
+    /// assert!(counter.try_consume_n(3).await); // search
+    /// assert!(!counter.try_consume_n(10).await); // write, would exceed the limit
+    /// assert!(counter.try_consume_n(7).await);
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume_n(&self, cost: u32) -> bool {
+        self.decide(cost as u64, None).await.is_allowed()
+    }
+
+    /// Attempts to consume `cost` units from the time window containing `now` instead of the
+    /// current one.
+    ///
+    /// The cost is charged exactly like with [`FixedWindowCounter::try_consume_n`], e.g. to
+    /// test deterministically or to decide a batch of requests against several limiters at the
+    /// same instant. Windows never move backwards, so an instant in a window before the latest
+    /// one observed is decided in the latest one. Counters on a wall or custom clock read their
+    /// clock as usual and shift it by the distance of `now` from the current instant.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::window::FixedWindowCounter;
+    /// use tokio::time::{Duration, Instant};
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(1, Duration::from_secs(60));
+    /// let now = Instant::now();
+    ///
+    /// assert!(counter.try_consume_at(now, 1).await);
+    /// assert!(!counter.try_consume_at(now, 1).await);
+    /// assert!(counter.try_consume_at(now + Duration::from_secs(60), 1).await);
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume_at(&self, now: Instant, cost: u32) -> bool {
+        self.decide(cost as u64, Some(now)).await.is_allowed()
+    }
+
+    /// Attempts to consume `cost` units from the current time window, reporting the state of
+    /// the window along with the outcome.
+    ///
+    /// The cost is charged exactly like with [`FixedWindowCounter::try_consume_n`]. The
+    /// returned [`Decision`] resets once the current window rolls over, which is also when a
+    /// denied request can be retried. Costs larger than the limit are reported as never
+    /// admissible.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(10, Duration::from_secs(60));
+    ///
+    /// let decision = counter.check(4).await;
+    /// assert!(decision.is_allowed());
+    /// assert_eq!(decision.limit(), 10);
+    /// assert_eq!(decision.remaining(), 6);
+    ///
+    /// assert_eq!(counter.check(11).await.retry_after(), None);
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&self, cost: u32) -> Decision {
+        self.decide(cost as u64, None).await
+    }
+
+    /// Attempts to consume `cost` units from the current time window, returning whether they
+    /// were admitted along with what is left of the window afterwards.
+    ///
+    /// The remaining budget is derived from the same atomic update that decides on the
+    /// request, so concurrent requests cannot skew it, e.g. for an `X-RateLimit-Remaining`
+    /// header. See [`FixedWindowCounter::check`] for the full decision.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(5, Duration::from_secs(60));
+    ///
+    /// assert_eq!(counter.consume_reporting(2).await, (true, 3));
+    /// assert_eq!(counter.consume_reporting(4).await, (false, 3));
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn consume_reporting(&self, cost: u32) -> (bool, u32) {
+        let decision = self.decide(cost as u64, None).await;
+        // The remaining budget is at most the limit plus the carried budget, both `u32`
+        let remaining = decision.remaining().min(u32::MAX as u64) as u32;
+        (decision.is_allowed(), remaining)
+    }
+
+    /// Gives back a request admitted in the current time window.
+    ///
+    /// See [`FixedWindowCounter::refund_n`].
+    pub fn refund(&self) {
+        self.refund_n(1);
+    }
+
+    /// Gives back `cost` units admitted in the current time window, e.g. when the handler
+    /// served a cached response or the client disconnected.
+    ///
+    /// The count saturates at zero. If the window of the latest admission has rolled over or
+    /// ended since, the refund has no effect, so it never touches a window the units were not
+    /// charged to.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(5, Duration::from_secs(60));
+    ///
+    /// assert!(counter.try_consume_n(2).await);
+    /// counter.refund_n(2);
+    /// assert_eq!(counter.remaining().await, 5);
+    /// # })
+    /// ```
+    pub fn refund_n(&self, cost: u32) {
+        let _rollover = lock(&self.rollover);
+        let current_window = self.current_window();
+        if self.last_admitted.load(Ordering::Relaxed) == current_window + 1 {
+            self.windows.subtract(current_window, cost);
+
+            let mut admissions = lock(&self.admissions);
+            let pending = self.pending_admissions.swap(0, Ordering::Relaxed);
+            if pending > 0 {
+                admissions.record(self.now(), pending);
+            }
+            admissions.retract(cost as u64);
+        }
+    }
+
+    /// Returns how long until a request of the given `cost` fits, `None` if it never does,
+    /// without charging it.
+    fn wait_for(&self, cost: u64) -> Option<Duration> {
+        let _rollover = lock(&self.rollover);
+        let (current_window, into_window, duration) = self.window_position();
+
+        let limit = self.limit() as u64 + self.carry(current_window);
+        let previous = self.previous_weight(current_window, into_window, duration);
+        let budget = self.budget(limit, into_window, duration);
+        let count = self.windows.count(current_window);
+        if (previous + count as u64).saturating_add(cost) <= budget {
+            return Some(Duration::ZERO);
+        }
+        self.retry_after(current_window, into_window, duration, count, cost)
+    }
+
+    async fn decide(&self, cost: u64, now: Option<Instant>) -> Decision {
+        // Deciding never waits, so give other tasks a turn once this one used up its budget
+        // rather than starving them in a tight retry loop
+        task::coop::consume_budget().await;
+
+        loop {
+            let (current_window, into_window, duration) = self.window_position_at(now);
+            if let Some(decision) = self.try_decide(current_window, into_window, duration, cost) {
+                return decision.flag_soft_limit(self.soft_limit.map(u64::from));
+            }
+
+            // The window is not tracked yet, roll over to it and decide again. Only this path
+            // takes a lock, once per window.
+            let finished = {
+                let _rollover = lock(&self.rollover);
+                self.windows.roll_to(current_window)
+            };
+            if let Some((window, count)) = finished {
+                self.notify_rollover(window, count);
+            }
+        }
+    }
+
+    /// Decides on a request in `current_window` without taking any lock, or returns `None` if
+    /// that window is not the latest tracked window.
+    fn try_decide(
+        &self,
+        current_window: u64,
+        into_window: Duration,
+        duration: Duration,
+        cost: u64,
+    ) -> Option<Decision> {
+        // The previous window is only settled once the current one is tracked
+        if !self.windows.is_current(current_window) {
+            return None;
+        }
+
+        let limit = self.limit() as u64 + self.carry(current_window);
+        let previous = self.previous_weight(current_window, into_window, duration);
+        let budget = self.budget(limit, into_window, duration);
+        let (allowed, count) = self.windows.try_add(current_window, cost, |count| {
+            (previous + count).saturating_add(cost) <= budget
+        })?;
+
+        if allowed {
+            self.last_admitted
+                .store(current_window + 1, Ordering::Relaxed);
+            if self.first_admitted.load(Ordering::Relaxed) == 0 {
+                let _ = self.first_admitted.compare_exchange(
+                    0,
+                    current_window + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                );
+            }
+            self.record_admission(cost);
+        }
+
+        let reset_in = duration.saturating_sub(into_window);
+        let remaining = budget.saturating_sub(previous + count as u64);
+        Some(if allowed {
+            Decision::Allowed {
+                limit,
+                remaining,
+                reset_in,
+            }
+        } else {
+            Decision::Denied {
+                limit,
+                remaining,
+                reset_in,
+                retry_after: self.retry_after(current_window, into_window, duration, count, cost),
+            }
+        })
+    }
+
+    /// Returns how long a denied request of the given `cost` has to wait, `None` if it never fits.
+    fn retry_after(
+        &self,
+        current_window: u64,
+        into_window: Duration,
+        duration: Duration,
+        count: u32,
+        cost: u64,
+    ) -> Option<Duration> {
+        if !self.smoothing {
+            return self.full_budget_retry_after(
+                current_window,
+                into_window,
+                duration,
+                count,
+                cost,
+            );
+        }
+
+        let limit = self.limit() as u64 + self.carry(current_window);
+        let previous = self.previous_weight(current_window, into_window, duration);
+        let usage = previous + count as u64 + cost;
+        if usage <= limit {
+            // Only the smoothed budget is short, it grows within the current window
+            let covered = Self::budget_covers(usage, limit, duration);
+            return Some(covered.saturating_sub(into_window));
+        }
+
+        // Once the request fits the full budget, the smoothed budget has to catch up as well.
+        // Usage with the sliding approximation keeps decaying, so the hint errs on the late side.
+        let wait =
+            self.full_budget_retry_after(current_window, into_window, duration, count, cost)?;
+        let reset_in = duration.saturating_sub(into_window);
+        Some(if wait < reset_in {
+            let covered = Self::budget_covers(limit, limit, duration);
+            wait.max(covered.saturating_sub(into_window))
+        } else {
+            let into_later = Duration::from_nanos(
+                ((wait - reset_in).as_nanos() % duration.as_nanos().max(1)) as u64,
+            );
+            let covered = Self::budget_covers(cost, self.limit() as u64, duration);
+            wait + covered.saturating_sub(into_later)
+        })
+    }
+
+    /// Returns how long a denied request of the given `cost` has to wait until it fits the full
+    /// budget of a window, `None` if it never fits.
+    fn full_budget_retry_after(
+        &self,
+        current_window: u64,
+        into_window: Duration,
+        duration: Duration,
+        count: u32,
+        cost: u64,
+    ) -> Option<Duration> {
+        let limit = self.limit() as u64 + self.carry(current_window);
+        let count = count as u64;
+        let reset_in = duration.saturating_sub(into_window);
+
+        if cost <= limit && self.sliding_approximation {
+            Some(self.approximate_retry_after(current_window, into_window, duration, count, cost))
+        } else if cost <= self.limit() as u64 + self.next_carry(count) {
+            Some(reset_in)
+        } else if cost <= self.limit() as u64 + self.max_carry.unwrap_or(0) as u64 {
+            // Only fits after an idle window has carried over the full budget
+            Some(reset_in + duration)
+        } else {
+            None
+        }
+    }
+
+    /// Records a finished window in the history and passes it to the rollover callback, if
+    /// either is enabled.
+    ///
+    /// A panicking callback is contained here, so it neither fails the request that happened
+    /// to roll the window over nor leaves the counter in an inconsistent state.
+    fn notify_rollover(&self, window: u64, count: u32) {
+        if self.history_len > 0 {
+            let mut history = lock(&self.history);
+            // Rollovers are reported outside the rollover lock, so they may arrive out of order
+            let at = history.partition_point(|&(recorded, _)| recorded < window);
+            history.insert(at, (window, count));
+            while history.len() > self.history_len {
+                history.pop_front();
+            }
+        }
+
+        let Some(callback) = &self.on_rollover else {
+            return;
+        };
+
+        if panic::catch_unwind(AssertUnwindSafe(|| callback(window, count))).is_err() {
+            warn!("Rollover callback panicked for window {}", window);
+        }
+    }
+
+    /// Records an admission for [`FixedWindowCounter::observed_rate`] without ever waiting.
+    ///
+    /// If the recorder is busy, the amount is left for the next admission or rate query to
+    /// record, so statistics never hold up a request.
+    fn record_admission(&self, amount: u64) {
+        let mut admissions = match self.admissions.try_lock() {
+            Ok(admissions) => admissions,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => {
+                self.pending_admissions.fetch_add(amount, Ordering::Relaxed);
+                return;
+            }
+        };
+
+        let pending = self.pending_admissions.swap(0, Ordering::Relaxed);
+        admissions.record(self.now(), amount + pending);
+    }
+
+    /// Returns the budget carried over into `window` from the window before it.
+    fn carry(&self, window: u64) -> u64 {
+        let Some(max_carry) = self.max_carry else {
+            return 0;
+        };
+
+        // Nothing is carried into the first window with admissions or any window before it
+        let first = self.first_admitted.load(Ordering::Relaxed);
+        if first == 0 || window < first {
+            return 0;
+        }
+
+        let previous = self.windows.count(window - 1);
+        self.limit().saturating_sub(previous).min(max_carry) as u64
+    }
+
+    /// Returns the budget the next window would carry over if the current one ended with `count`.
+    fn next_carry(&self, count: u64) -> u64 {
+        self.max_carry.map_or(0, |max_carry| {
+            (self.limit() as u64)
+                .saturating_sub(count)
+                .min(max_carry as u64)
+        })
+    }
+
+    /// Returns the weighted count of the previous window that still applies to the current one.
+    ///
+    /// Without the sliding approximation the previous window never counts. With it, the previous
+    /// count is weighted by how much of a window-long period ending now overlaps the previous
+    /// window, rounded up so the approximation never admits more than the limit.
+    fn previous_weight(
+        &self,
+        current_window: u64,
+        into_window: Duration,
+        duration: Duration,
+    ) -> u64 {
+        let Some(previous_window) = current_window.checked_sub(1) else {
+            return 0;
+        };
+        if !self.sliding_approximation {
+            return 0;
+        }
+
+        let previous = self.windows.count(previous_window) as u128;
+        let overlap = duration.saturating_sub(into_window).as_nanos();
+        (previous * overlap).div_ceil(duration.as_nanos()) as u64
+    }
+
+    /// Returns how long a denied request of the given `cost` has to wait with the sliding
+    /// approximation, assuming no other requests are admitted meanwhile. `count` is the count
+    /// of the current window and `cost` must not exceed the limit.
+    fn approximate_retry_after(
+        &self,
+        current_window: u64,
+        into_window: Duration,
+        duration: Duration,
+        count: u64,
+        cost: u64,
+    ) -> Duration {
+        let limit = self.limit() as u64 + self.carry(current_window);
+
+        // Offset into a window at which `weighted` requests of the window before it have decayed
+        // enough to leave room for `budget` more requests
+        let decayed_at = |weighted: u64, budget: u64| -> Duration {
+            if weighted <= budget {
+                return Duration::ZERO;
+            }
+            let allowed_overlap = duration.as_nanos() * budget as u128 / weighted as u128;
+            Duration::from_nanos((duration.as_nanos() - allowed_overlap) as u64)
+        };
+
+        if count + cost <= limit {
+            // The request fits once the previous window has decayed far enough
+            let previous = current_window
+                .checked_sub(1)
+                .map_or(0, |window| self.windows.count(window) as u64);
+            decayed_at(previous, limit - count - cost).saturating_sub(into_window)
+        } else {
+            // The current window becomes the previous one, which then has to decay
+            let limit = self.limit() as u64 + self.next_carry(count);
+            duration.saturating_sub(into_window) + decayed_at(count, limit.saturating_sub(cost))
+        }
+    }
+
+    /// Returns the observed number of units admitted per second over the trailing `over` period.
+    ///
+    /// Window counts alone cannot answer this for arbitrary periods, so the rate is estimated
+    /// from recent admissions, see [the `stats` module](crate::stats#observed-rate). For
+    /// unit-cost requests the result is the number of requests per second.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60));
+    /// counter.try_consume().await;
+    ///
+    /// println!("{} requests/s", counter.observed_rate(Duration::from_secs(10)).await);
+    /// # })
+    /// ```
+    pub async fn observed_rate(&self, over: Duration) -> f64 {
+        let now = self.now();
+        let mut admissions = lock(&self.admissions);
+
+        let pending = self.pending_admissions.swap(0, Ordering::Relaxed);
+        if pending > 0 {
+            admissions.record(now, pending);
+        }
+        admissions.rate(now, over)
+    }
+
+    /// Returns the ids and final counts of the latest finished windows, oldest first.
+    ///
+    /// Holds at most as many windows as configured with
+    /// [`with_history`](FixedWindowCounter::with_history) and is always empty without it. Like
+    /// for [`on_rollover`](FixedWindowCounter::on_rollover), windows without any requests are
+    /// never tracked and therefore missing from the history.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = FixedWindowCounter::new(100, Duration::from_secs(60)).with_history(60);
+    /// counter.try_consume().await;
+    ///
+    /// for (window, count) in counter.history() {
+    ///     println!("window {} admitted {} requests", window, count);
+    /// }
+    /// # })
+    /// ```
+    pub fn history(&self) -> Vec<(u64, u32)> {
+        lock(&self.history).iter().copied().collect()
+    }
+
+    /// Returns whether a request of the given `cost` can be admitted in any window.
+    ///
+    /// Costs larger than the limit, plus the most budget that can roll over if
+    /// [`with_rollover`](FixedWindowCounter::with_rollover) is enabled, are denied by
+    /// `try_consume_n` no matter how long the caller waits, so retrying them is pointless.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// let counter = FixedWindowCounter::new(10, Duration::from_secs(60));
+    /// assert!(counter.can_ever_admit(10));
+    /// assert!(!counter.can_ever_admit(11));
+    /// ```
+    pub fn can_ever_admit(&self, cost: u32) -> bool {
+        cost as u64 <= self.limit() as u64 + self.max_carry.unwrap_or(0) as u64
+    }
+
+    /// Clears old time windows.
+    ///
+    /// The counter only ever keeps the current and the previous window, which `try_consume`
+    /// replaces in place whenever a new window starts, so calling this is never required. It
+    /// drops every window before the current one, except for the previous window when the
+    /// sliding approximation or rollover still depends on it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let mut counter = FixedWindowCounter::new(100, Duration::from_secs(60));
+    /// // ... some time passes ...
+    /// counter.clear_old_windows().await;
+    /// # })
+    /// ```
+    pub async fn clear_old_windows(&self) {
+        let mut oldest_valid_window = self.current_window();
+        if self.sliding_approximation || self.max_carry.is_some() {
+            // The previous window still affects the current one
+            oldest_valid_window = oldest_valid_window.saturating_sub(1);
+        }
+
+        let finished = {
+            let _rollover = lock(&self.rollover);
+            self.windows.clear_before(oldest_valid_window)
+        };
+        if let Some((window, count)) = finished {
+            self.notify_rollover(window, count);
+        }
+    }
+
+    /// Spawns a background task that calls [`clear_old_windows`] every `every`.
+    ///
+    /// Pruning already happens as requests come in, but a sweeper also drops the counts of
+    /// counters that went quiet, and makes [`on_rollover`] report finished windows on schedule.
+    /// The task only holds a weak reference, so it stops once the counter is dropped, and it is
+    /// aborted when the returned [`CleanupHandle`] is dropped. Must be called from within a
+    /// Tokio runtime. A zero `every` is treated as one millisecond.
+    ///
+    /// [`clear_old_windows`]: FixedWindowCounter::clear_old_windows
+    /// [`on_rollover`]: FixedWindowCounter::on_rollover
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    /// use limitr::window::FixedWindowCounter;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = Arc::new(FixedWindowCounter::new(100, Duration::from_secs(60)));
+    /// let cleanup = counter.spawn_cleanup(Duration::from_secs(60));
+    ///
+    /// // ... serve requests ...
+    ///
+    /// cleanup.abort();
+    /// # })
+    /// ```
+    pub fn spawn_cleanup(self: &Arc<Self>, every: Duration) -> CleanupHandle {
+        CleanupHandle::spawn(self, every, |counter| async move {
+            counter.clear_old_windows().await;
+        })
+    }
+}
+
+/// Creates a counter admitting 60 requests per one-second window, a reasonable starting point
+/// for prototypes and tests.
+impl Default for FixedWindowCounter {
+    fn default() -> Self {
+        FixedWindowCounter::new(60, Duration::from_secs(1))
+    }
+}
+
+/// Shows the configuration along with the usage of the current window, including the weighted
+/// previous window, out of its effective limit. The usage reads `<locked>` while a window rolls
+/// over.
+impl fmt::Debug for FixedWindowCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("FixedWindowCounter");
+        debug
+            .field("limit", &self.limit())
+            .field("window_duration", &self.window_duration())
+            .field("clock", &self.clock);
+        let rollover = match self.rollover.try_lock() {
+            Ok(rollover) => Some(rollover),
+            Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        };
+        match rollover.map(|_rollover| self.usage_locked()) {
+            Some((limit, usage)) => debug
+                .field("usage", &usage)
+                .field("effective_limit", &limit),
+            None => debug.field("usage", &format_args!("<locked>")),
+        };
+        debug.finish_non_exhaustive()
+    }
+}
+
+impl RateLimiter for FixedWindowCounter {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        SharedRateLimiter::try_acquire(self, cost)
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        SharedRateLimiter::check(self, cost)
+    }
+
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        SharedRateLimiter::refund(self, cost)
+    }
+
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        SharedRateLimiter::retry_after(self, cost)
+    }
+}
+
+impl SharedRateLimiter for FixedWindowCounter {
+    fn try_acquire(&self, cost: u64) -> BoxFuture<'_, bool> {
+        Box::pin(async move { self.decide(cost, None).await.is_allowed() })
+    }
+
+    fn check(&self, cost: u64) -> BoxFuture<'_, Decision> {
+        Box::pin(self.decide(cost, None))
+    }
+
+    fn refund(&self, cost: u64) -> BoxFuture<'_, ()> {
+        Box::pin(async move { self.refund_n(u32::try_from(cost).unwrap_or(u32::MAX)) })
+    }
+
+    fn retry_after(&self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        Box::pin(async move { self.wait_for(cost) })
+    }
+}
+
+/// Returns the sum of the current-window counts of all `counters`, e.g. of per-shard counters.
+///
+/// This only reports usage, it does not enforce any limit across the counters. Each counter
+/// is read while it cannot roll over, for the window that is current on its own clock, so a
+/// count is never mixed up with that of the previous window of the same counter. The counters
+/// are read one after another though, so the sum is no atomic snapshot across all of them.
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::Duration;
+/// use limitr::window::{total_current_usage, FixedWindowCounter};
+///
+/// # tokio_test::block_on(async {
+/// let shards = [
+///     FixedWindowCounter::new(10, Duration::from_secs(60)),
+///     FixedWindowCounter::new(10, Duration::from_secs(60)),
+/// ];
+/// shards[0].try_consume_n(3).await;
+/// shards[1].try_consume_n(4).await;
+///
+/// assert_eq!(total_current_usage(&[&shards[0], &shards[1]]), 7);
+/// # })
+/// ```
+pub fn total_current_usage(counters: &[&FixedWindowCounter]) -> u64 {
+    counters
+        .iter()
+        .map(|counter| {
+            let _rollover = lock(&counter.rollover);
+            counter.windows.count(counter.current_window()) as u64
+        })
+        .sum()
+}
+
+/// Serialized form of a [`FixedWindowCounter`].
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FixedWindowState {
+    limit: u32,
+    window_duration: Duration,
+    base_window: u64,
+    base_offset: Duration,
+    #[serde(default)]
+    sliding_approximation: bool,
+    #[serde(default)]
+    max_carry: Option<u32>,
+    #[serde(default)]
+    smoothing: bool,
+    /// UTC offset in seconds of the calendar months the windows follow, if they do
+    #[serde(default)]
+    month_offset: Option<i32>,
+    /// Checkpoints written before the clock was recorded were all taken on the wall clock
+    #[serde(default)]
+    clock: ClockKind,
+    windows: BTreeMap<u64, u32>,
+}
+
+/// The kind of clock a serialized [`FixedWindowCounter`] derived its windows from.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ClockKind {
+    Monotonic,
+    #[default]
+    Wall,
+}
+
+/// Serializes the limit, the window timing, the kind of clock and the counts of the tracked
+/// windows.
+///
+/// Window ids are only meaningful across restarts for counters on a wall clock, since the
+/// monotonic clock starts over with every process. A [`WindowClock::Custom`] clock cannot be
+/// serialized and is recorded as a wall clock.
+#[cfg(feature = "serde")]
+impl serde::Serialize for FixedWindowCounter {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let timing = *self.timing();
+        let windows = {
+            let _rollover = lock(&self.rollover);
+            self.windows.iter().collect()
+        };
+
+        FixedWindowState {
+            limit: self.limit(),
+            window_duration: timing.duration,
+            base_window: timing.base_window,
+            base_offset: timing.base_offset,
+            sliding_approximation: self.sliding_approximation,
+            max_carry: self.max_carry,
+            smoothing: self.smoothing,
+            #[cfg(feature = "chrono")]
+            month_offset: timing.months.map(|offset| offset.local_minus_utc()),
+            #[cfg(not(feature = "chrono"))]
+            month_offset: None,
+            clock: match self.clock {
+                WindowClock::Monotonic => ClockKind::Monotonic,
+                WindowClock::WallClock | WindowClock::Custom(_) => ClockKind::Wall,
+            },
+            windows,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Restores a counter on the kind of clock it was serialized from.
+///
+/// Counters on a wall clock come back on [`WindowClock::WallClock`], keeping only the counts of
+/// the windows that still affect decisions. Windows that ended while the counter was not
+/// running are dropped. Use [`FixedWindowCounter::with_clock`] to go back to a custom clock.
+///
+/// Counters on [`WindowClock::Monotonic`] only keep their configuration: their windows are
+/// counted from the creation of the counter, so the counts cannot be carried into a new one.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FixedWindowCounter {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = FixedWindowState::deserialize(deserializer)?;
+        let mut counter = FixedWindowCounter::try_new(state.limit, state.window_duration)
+            .map_err(serde::de::Error::custom)?;
+        counter.sliding_approximation = state.sliding_approximation;
+        counter.max_carry = state.max_carry;
+        counter.smoothing = state.smoothing;
+        #[cfg(feature = "chrono")]
+        let months = match state.month_offset {
+            Some(offset) => Some(FixedOffset::east_opt(offset).ok_or_else(|| {
+                serde::de::Error::custom(format!("invalid UTC offset {}", offset))
+            })?),
+            None => None,
+        };
+        #[cfg(not(feature = "chrono"))]
+        if state.month_offset.is_some() {
+            return Err(serde::de::Error::custom(
+                "calendar month windows require the `chrono` feature",
+            ));
+        }
+        let timing = WindowTiming {
+            #[cfg(feature = "chrono")]
+            months,
+            ..WindowTiming::uniform(state.window_duration)
+        };
+        if state.clock == ClockKind::Monotonic {
+            // The new counter starts its windows from scratch
+            *counter
+                .timing
+                .write()
+                .unwrap_or_else(PoisonError::into_inner) = timing;
+            return Ok(counter);
+        }
+
+        counter = counter.with_clock(WindowClock::WallClock);
+        *counter
+            .timing
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = WindowTiming {
+            base_window: state.base_window,
+            base_offset: state.base_offset,
+            ..timing
+        };
+
+        // Keep windows from moving backwards if the clock was stepped back across the restart
+        let latest = state.windows.keys().max().copied().unwrap_or(0);
+        counter.latest_window.store(latest, Ordering::Release);
+        if let Some(first) = state.windows.keys().min() {
+            counter.first_admitted.store(first + 1, Ordering::Relaxed);
+        }
+
+        let mut oldest_valid_window = counter.current_window();
+        if counter.sliding_approximation || counter.max_carry.is_some() {
+            oldest_valid_window = oldest_valid_window.saturating_sub(1);
+        }
+        for (window, count) in state.windows.range(oldest_valid_window..) {
+            counter.windows.restore(*window, *count);
+        }
+
+        Ok(counter)
+    }
+}
+
+/// Counts of the latest two windows requests were made in.
+///
+/// Only the current window decides whether a request is admitted, so older windows are
+/// overwritten in place as soon as a new one starts and memory stays constant.
+///
+/// The count of the latest window is packed together with the low half of its id into a
+/// single atomic, so requests are admitted with a compare-and-swap that fails as soon as the
+/// window rolls over. Everything else only changes while the counter's rollover lock is held.
+#[derive(Debug, Default)]
+struct WindowCounts {
+    /// Id of the latest window plus one, zero if there is none
+    current: AtomicU64,
+    /// Low half of the id and the count of the latest window
+    packed: AtomicU64,
+    /// Id of the window before the latest one plus one, zero if there is none
+    previous: AtomicU64,
+    /// Count of the window before the latest one
+    previous_count: AtomicU32,
+}
+
+impl WindowCounts {
+    /// Returns whether `window` is the latest window.
+    fn is_current(&self, window: u64) -> bool {
+        self.current.load(Ordering::Acquire) == window + 1
+    }
+
+    /// Returns the count of `window`, zero if it is not tracked.
+    fn count(&self, window: u64) -> u32 {
+        if self.is_current(window) {
+            let (id, count) = unpack(self.packed.load(Ordering::Acquire));
+            if id == window as u32 {
+                return count;
+            }
+        }
+
+        if self.previous.load(Ordering::Acquire) == window + 1 {
+            return self.previous_count.load(Ordering::Acquire);
+        }
+        0
+    }
+
+    /// Adds `cost` to the count of the latest window if `fits` accepts its current count.
+    ///
+    /// Returns whether the cost was added along with the resulting count, or `None` if
+    /// `window` is not the latest window.
+    fn try_add(&self, window: u64, cost: u64, fits: impl Fn(u64) -> bool) -> Option<(bool, u32)> {
+        if !self.is_current(window) {
+            return None;
+        }
+
+        let mut packed = self.packed.load(Ordering::Acquire);
+        loop {
+            let (id, count) = unpack(packed);
+            if id != window as u32 {
+                return None;
+            }
+            if !fits(count as u64) {
+                return Some((false, count));
+            }
+
+            let added = (count as u64 + cost).min(u32::MAX as u64) as u32;
+            match self.packed.compare_exchange_weak(
+                packed,
+                pack(window, added),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some((true, added)),
+                Err(actual) => packed = actual,
+            }
+        }
+    }
+
+    /// Starts tracking `window` if it is newer than the latest window.
+    ///
+    /// Returns the latest window and its final count if it was replaced. Must only be called
+    /// under the rollover lock.
+    fn roll_to(&self, window: u64) -> Option<(u64, u32)> {
+        let current = self.current.load(Ordering::Acquire);
+        if current > window {
+            return None;
+        }
+
+        // Swapping the packed count first makes pending additions to the old window fail, so
+        // its final count is exact. Publishing the new window last keeps lock-free readers from
+        // seeing it before the previous window is settled.
+        let (_, count) = unpack(self.packed.swap(pack(window, 0), Ordering::AcqRel));
+        let finished = current.checked_sub(1).map(|id| (id, count));
+        if let Some((id, count)) = finished {
+            self.previous_count.store(count, Ordering::Release);
+            self.previous.store(id + 1, Ordering::Release);
+        }
+        self.current.store(window + 1, Ordering::Release);
+        finished
+    }
+
+    /// Tracks `window` with the given count, rolling over to it if needed.
+    ///
+    /// Must only be called under the rollover lock.
+    #[cfg(feature = "serde")]
+    fn restore(&self, window: u64, count: u32) {
+        self.roll_to(window);
+        self.packed.store(pack(window, count), Ordering::Release);
+    }
+
+    /// Raises the count of the latest window to at least `count` if it is `window`.
+    fn raise(&self, window: u64, count: u32) {
+        let _ = self
+            .packed
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |packed| {
+                let (id, current) = unpack(packed);
+                (id == window as u32).then(|| pack(window, current.max(count)))
+            });
+    }
+
+    /// Subtracts `amount` from the count of `window` if it is tracked.
+    ///
+    /// Must only be called under the rollover lock.
+    fn subtract(&self, window: u64, amount: u32) {
+        if self.is_current(window) {
+            let _ = self
+                .packed
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |packed| {
+                    let (id, count) = unpack(packed);
+                    (id == window as u32).then(|| pack(window, count.saturating_sub(amount)))
+                });
+        } else if self.previous.load(Ordering::Acquire) == window + 1 {
+            let count = self.previous_count.load(Ordering::Acquire);
+            self.previous_count
+                .store(count.saturating_sub(amount), Ordering::Release);
+        }
+    }
+
+    /// Drops the counts of all windows before `window`.
+    ///
+    /// Returns the latest window and its count if it was dropped as well. Must only be called
+    /// under the rollover lock.
+    fn clear_before(&self, window: u64) -> Option<(u64, u32)> {
+        if self.previous.load(Ordering::Acquire) < window + 1 {
+            self.previous.store(0, Ordering::Release);
+            self.previous_count.store(0, Ordering::Release);
+        }
+
+        let current = self.current.load(Ordering::Acquire);
+        if current == 0 || current > window {
+            return None;
+        }
+
+        // Requests still racing for the dropped window only change a count nobody reads anymore
+        self.current.store(0, Ordering::Release);
+        let (_, count) = unpack(self.packed.load(Ordering::Acquire));
+        Some((current - 1, count))
+    }
+
+    /// Returns the tracked windows and their counts, oldest first.
+    #[cfg(any(test, feature = "serde"))]
+    fn iter(&self) -> impl Iterator<Item = (u64, u32)> + '_ {
+        let previous = self.previous.load(Ordering::Acquire).checked_sub(1);
+        let current = self.current.load(Ordering::Acquire).checked_sub(1);
+
+        previous
+            .map(|window| (window, self.previous_count.load(Ordering::Acquire)))
+            .into_iter()
+            .chain(current.map(|window| (window, self.count(window))))
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    #[cfg(test)]
+    fn is_empty(&self) -> bool {
+        self.current.load(Ordering::Acquire) == 0
+    }
+}
+
+/// Packs the low half of a window id and a count into a single word.
+fn pack(window: u64, count: u32) -> u64 {
+    (window as u32 as u64) << 32 | count as u64
+}
+
+/// Splits a word packed by [`pack`] into the low half of the window id and the count.
+fn unpack(packed: u64) -> (u32, u32) {
+    ((packed >> 32) as u32, packed as u32)
+}
+
+/// How window ids map onto the window clock.
+#[derive(Debug, Clone, Copy)]
+struct WindowTiming {
+    duration: Duration,
+    /// Window id starting at `base_offset`
+    base_window: u64,
+    /// Offset on the window clock at which `base_window` starts
+    base_offset: Duration,
+    /// UTC offset of the calendar months the windows follow instead of `duration`, if any
+    #[cfg(feature = "chrono")]
+    months: Option<FixedOffset>,
+}
+
+impl WindowTiming {
+    /// Returns a timing of windows of the given `duration`, starting at the window clock's origin.
+    fn uniform(duration: Duration) -> Self {
+        WindowTiming {
+            duration,
+            base_window: 0,
+            base_offset: Duration::ZERO,
+            #[cfg(feature = "chrono")]
+            months: None,
+        }
+    }
+
+    /// Returns the id of the window containing the given offset on the window clock.
+    fn window_at(&self, elapsed: Duration) -> u64 {
+        #[cfg(feature = "chrono")]
+        if let Some(offset) = self.months {
+            return calendar::month_at(elapsed, offset);
+        }
+
+        let since_base = elapsed.saturating_sub(self.base_offset);
+        self.base_window + (since_base.as_nanos() / self.duration.as_nanos()) as u64
+    }
+
+    /// Returns the offset on the window clock at which `window` starts.
+    fn start_of(&self, window: u64) -> Duration {
+        #[cfg(feature = "chrono")]
+        if let Some(offset) = self.months {
+            return calendar::month_start(window, offset);
+        }
+
+        let windows = window.saturating_sub(self.base_window) as u128;
+        let offset = self.base_offset.as_nanos() + windows * self.duration.as_nanos();
+        Duration::from_nanos(u64::try_from(offset).unwrap_or(u64::MAX))
+    }
+
+    /// Returns the length of `window`.
+    #[cfg_attr(not(feature = "chrono"), allow(unused_variables))]
+    fn length_of(&self, window: u64) -> Duration {
+        #[cfg(feature = "chrono")]
+        if self.months.is_some() {
+            return self
+                .start_of(window + 1)
+                .saturating_sub(self.start_of(window));
+        }
+
+        self.duration
+    }
+}
+
+/// Locks `mutex`, ignoring poisoning since every critical section leaves the state consistent.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the time elapsed since the UNIX epoch.
+fn since_epoch(time: SystemTime) -> Result<Duration, LimitrError> {
+    time.duration_since(UNIX_EPOCH)
+        .map_err(|_| LimitrError::ClockWentBackwards)
+}
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    // assert will mess up codecov report use assert_eg instead
+
+    use crate::test_util::paused_runtime;
+    use crate::window::fixed_window::{WindowCounts, MAX_PREALLOCATED_HISTORY};
+    use crate::window::{total_current_usage, FixedWindowCounter, WindowClock};
+    use crate::{Decision, InvalidConfigReason, LimitrError};
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use tokio::time::Instant;
+    use tokio::time::{self, Duration};
+
+    fn mock_clock(start: SystemTime) -> (Arc<Mutex<SystemTime>>, WindowClock) {
+        let time = Arc::new(Mutex::new(start));
+        let source = time.clone();
+        let clock = WindowClock::Custom(Arc::new(move || *source.lock().unwrap()));
+        (time, clock)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_default_counter_rolls_over() {
+        let counter = FixedWindowCounter::default();
+        assert_eq!(counter.limit(), 60);
+        assert_eq!(counter.window_duration(), Duration::from_secs(1));
+
+        assert_eq!(counter.try_consume_n(60).await, true);
+        assert_eq!(counter.try_consume().await, false);
+        time::advance(Duration::from_secs(1)).await;
+        assert_eq!(counter.try_consume().await, true);
+    }
+
+    #[tokio::test]
+    async fn test_allows_requests_under_limit() {
+        let counter = FixedWindowCounter::new(5, Duration::from_secs(60));
+
+        for _ in 0..5 {
+            assert_eq!(
+                counter.try_consume().await,
+                true,
+                "Request should be allowed"
+            );
+        }
+
+        assert_eq!(
+            counter.try_consume().await,
+            false,
+            "Request should be rate-limited"
+        );
+
+        let windows = &counter.windows;
+        let (_, count) = windows.iter().next().unwrap();
+        assert_eq!(
+            count, 5,
+            "Should have recorded 5 requests in the current window"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_allows_new_window_requests() {
+        let counter = FixedWindowCounter::new(3, Duration::from_secs(2));
+
+        for _ in 0..3 {
+            assert_eq!(
+                counter.try_consume().await,
+                true,
+                "Request should be allowed"
+            );
+        }
+
+        time::sleep(Duration::from_secs(3)).await;
+        counter.clear_old_windows().await;
+
+        assert_eq!(
+            counter.try_consume().await,
+            true,
+            "Request should be allowed in the new window"
+        );
+
+        let windows = &counter.windows;
+        assert_eq!(windows.len(), 1, "There should be exactly 1 active window");
+    }
+
+    #[tokio::test]
+    async fn test_clears_old_windows() {
+        let counter = FixedWindowCounter::new(3, Duration::from_secs(2));
+
+        for _ in 0..3 {
+            assert_eq!(
+                counter.try_consume().await,
+                true,
+                "Request should be allowed"
+            );
+        }
+
+        counter.clear_old_windows().await;
+
+        let windows = &counter.windows;
+        assert_eq!(
+            windows.is_empty(),
+            false,
+            "Windows should contain data after clearing"
+        );
+
+        time::sleep(Duration::from_secs(3)).await;
+
+        counter.clear_old_windows().await;
+
+        let windows = &counter.windows;
+        assert_eq!(
+            windows.is_empty(),
+            true,
+            "Windows should be empty after clearing old windows"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_monotonic_windows_start_at_creation() {
+        let counter = FixedWindowCounter::new(1, Duration::from_millis(250));
+        assert_eq!(counter.try_consume().await, true);
+
+        time::advance(Duration::from_millis(249)).await;
+        assert_eq!(
+            counter.try_consume().await,
+            false,
+            "Request should be rate-limited within the first window"
+        );
+
+        time::advance(Duration::from_millis(1)).await;
+        assert_eq!(
+            counter.try_consume().await,
+            true,
+            "Request should be allowed in the next window"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backwards_clock_does_not_reopen_windows() {
+        let (time, clock) = mock_clock(UNIX_EPOCH + Duration::from_secs(100));
+        let counter = FixedWindowCounter::new(2, Duration::from_secs(1)).with_clock(clock);
+
+        for _ in 0..2 {
+            assert_eq!(counter.try_consume().await, true);
+        }
+        assert_eq!(counter.try_consume().await, false);
+
+        *time.lock().unwrap() = UNIX_EPOCH + Duration::from_secs(50);
+        assert_eq!(
+            counter.try_consume().await,
+            false,
+            "A backwards clock step must not reopen an old window"
+        );
+
+        *time.lock().unwrap() = UNIX_EPOCH - Duration::from_secs(10);
+        assert_eq!(
+            counter.try_consume().await,
+            false,
+            "A clock before the epoch must neither panic nor admit"
+        );
+        counter.clear_old_windows().await;
+
+        *time.lock().unwrap() = UNIX_EPOCH + Duration::from_secs(101);
+        for _ in 0..2 {
+            assert_eq!(counter.try_consume().await, true);
+        }
+        assert_eq!(counter.try_consume().await, false);
+    }
+
+    #[tokio::test]
+    async fn test_backwards_clock_grants_no_extra_capacity() {
+        let (time, clock) = mock_clock(UNIX_EPOCH + Duration::from_millis(100_500));
+        let counter = FixedWindowCounter::new(4, Duration::from_secs(1))
+            .with_clock(clock)
+            .with_sliding_approximation();
+
+        assert_eq!(counter.try_consume_n(4).await, true);
+        *time.lock().unwrap() = UNIX_EPOCH + Duration::from_millis(101_500);
+        assert_eq!(counter.try_consume_n(2).await, true);
+        assert_eq!(counter.try_consume().await, false);
+
+        // An NTP step back into the previous window keeps counting against the latest one,
+        // as if no time had passed in it
+        *time.lock().unwrap() = UNIX_EPOCH + Duration::from_millis(100_200);
+        let decision = counter.check(1).await;
+        assert_eq!(decision.is_allowed(), false);
+        assert_eq!(decision.reset_in(), Duration::from_secs(1));
+        assert_eq!(counter.remaining().await, 0);
+
+        // Time only counts again once the clock is past the latest window start
+        *time.lock().unwrap() = UNIX_EPOCH + Duration::from_millis(101_750);
+        assert_eq!(counter.try_consume().await, true);
+        assert_eq!(counter.try_consume().await, false);
+    }
+
+    #[tokio::test]
+    async fn test_clock_before_epoch() {
+        let (_time, clock) = mock_clock(UNIX_EPOCH - Duration::from_secs(10));
+        let counter = FixedWindowCounter::new(1, Duration::from_secs(1)).with_clock(clock);
+
+        assert_eq!(counter.try_consume().await, true);
+        assert_eq!(counter.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_prunes_windows_without_manual_cleanup() {
+        let counter = FixedWindowCounter::new(3, Duration::from_millis(10));
+
+        for _ in 0..5000 {
+            assert_eq!(counter.try_consume().await, true);
+            time::advance(Duration::from_millis(10)).await;
+        }
+
+        let windows = &counter.windows;
+        assert_eq!(
+            windows.len() <= 2,
+            true,
+            "Only the current and previous window should be retained"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_weighted_consume_never_exceeds_limit() {
+        let counter = FixedWindowCounter::new(20, Duration::from_secs(1));
+
+        let mut admitted = 0;
+        for cost in [3, 10, 3, 10, 1, 3] {
+            if counter.try_consume_n(cost).await {
+                admitted += cost;
+            }
+        }
+        assert_eq!(admitted, 20, "Costs 3 + 10 + 3 + 1 + 3 should be admitted");
+
+        let windows = &counter.windows;
+        let (_, count) = windows.iter().next().unwrap();
+        assert_eq!(count, 20);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rejected_cost_does_not_partially_count() {
+        let counter = FixedWindowCounter::new(10, Duration::from_secs(1));
+
+        assert_eq!(counter.try_consume_n(8).await, true);
+        assert_eq!(counter.try_consume_n(3).await, false);
+        assert_eq!(counter.try_consume_n(2).await, true);
+        assert_eq!(counter.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cost_above_limit_is_never_possible() {
+        let counter = FixedWindowCounter::new(10, Duration::from_secs(1));
+
+        assert_eq!(counter.can_ever_admit(11), false);
+        assert_eq!(counter.try_consume_n(11).await, false);
+        assert_eq!(counter.try_consume_n(u32::MAX).await, false);
+
+        time::advance(Duration::from_secs(1)).await;
+        assert_eq!(counter.try_consume_n(11).await, false);
+        assert_eq!(counter.try_consume_n(10).await, true);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reset_in_counts_down_to_rollover() {
+        let counter = FixedWindowCounter::new(1, Duration::from_secs(1));
+        let start = time::Instant::now();
+
+        assert_eq!(counter.try_consume().await, true);
+        assert_eq!(counter.reset_in(), Duration::from_secs(1));
+        assert_eq!(counter.window_start(), start);
+
+        time::advance(Duration::from_millis(400)).await;
+        assert_eq!(counter.reset_in(), Duration::from_millis(600));
+
+        time::advance(Duration::from_millis(599)).await;
+        assert_eq!(counter.reset_in(), Duration::from_millis(1));
+        assert_eq!(counter.try_consume().await, false);
+
+        time::advance(Duration::from_millis(1)).await;
+        assert_eq!(counter.reset_in(), Duration::from_secs(1));
+        assert_eq!(counter.window_start(), start + Duration::from_secs(1));
+        assert_eq!(counter.try_consume().await, true);
+    }
+
+    #[tokio::test]
+    async fn test_reset_in_with_wall_clock() {
+        let (time, clock) = mock_clock(UNIX_EPOCH + Duration::from_millis(10_250));
+        let counter = FixedWindowCounter::new(1, Duration::from_secs(1)).with_clock(clock);
+
+        assert_eq!(counter.reset_in(), Duration::from_millis(750));
+
+        *time.lock().unwrap() = UNIX_EPOCH + Duration::from_millis(10_999);
+        assert_eq!(counter.reset_in(), Duration::from_millis(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_observed_rate() {
+        let counter = FixedWindowCounter::new(5, Duration::from_secs(1));
+        for _ in 0..19 {
+            time::advance(Duration::from_millis(100)).await;
+            let _ = counter.try_consume().await;
+        }
+
+        // Both windows admit the first 5 of their requests
+        assert_eq!(counter.observed_rate(Duration::from_secs(2)).await, 5.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_lowering_limit_below_usage() {
+        let counter = FixedWindowCounter::new(5, Duration::from_secs(1));
+        for _ in 0..4 {
+            assert_eq!(counter.try_consume().await, true);
+        }
+
+        counter.set_limit(2);
+        assert_eq!(
+            counter.try_consume().await,
+            false,
+            "No further grants once usage exceeds the new limit"
+        );
+
+        time::advance(Duration::from_secs(1)).await;
+        for _ in 0..2 {
+            assert_eq!(counter.try_consume().await, true);
+        }
+        assert_eq!(counter.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_raising_limit_mid_window() {
+        let counter = FixedWindowCounter::new(2, Duration::from_secs(1));
+        for _ in 0..2 {
+            assert_eq!(counter.try_consume().await, true);
+        }
+        assert_eq!(counter.try_consume().await, false);
+
+        counter.set_limit(4);
+        for _ in 0..2 {
+            assert_eq!(counter.try_consume().await, true);
+        }
+        assert_eq!(counter.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_set_window_duration_rebases_windows() {
+        let counter = FixedWindowCounter::new(2, Duration::from_secs(10));
+        time::advance(Duration::from_secs(25)).await;
+        for _ in 0..2 {
+            assert_eq!(counter.try_consume().await, true);
+        }
+
+        // The current window started at 20s and now ends at 24s, which already passed
+        counter.set_window_duration(Duration::from_secs(4)).unwrap();
+        assert_eq!(counter.reset_in(), Duration::from_secs(3));
+        assert_eq!(counter.try_consume().await, true);
+
+        time::advance(Duration::from_secs(2)).await;
+        assert_eq!(counter.try_consume().await, true);
+        assert_eq!(counter.try_consume().await, false);
+        assert_eq!(counter.reset_in(), Duration::from_secs(1));
+
+        time::advance(Duration::from_secs(1)).await;
+        assert_eq!(counter.try_consume().await, true);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_set_window_duration_keeps_current_count() {
+        let counter = FixedWindowCounter::new(2, Duration::from_secs(10));
+        time::advance(Duration::from_secs(2)).await;
+        for _ in 0..2 {
+            assert_eq!(counter.try_consume().await, true);
+        }
+
+        counter
+            .set_window_duration(Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(counter.try_consume().await, false);
+        assert_eq!(counter.reset_in(), Duration::from_secs(58));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_set_zero_window_duration_is_rejected() {
+        let counter = FixedWindowCounter::new(2, Duration::from_secs(10));
+
+        assert_eq!(
+            counter.set_window_duration(Duration::ZERO),
+            Err(LimitrError::InvalidConfig(InvalidConfigReason::ZeroWindow))
+        );
+        assert_eq!(counter.reset_in(), Duration::from_secs(10));
+        assert_eq!(counter.try_consume().await, true);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_remaining_tracks_current_window() {
+        let counter = FixedWindowCounter::new(3, Duration::from_secs(10));
+        assert_eq!(counter.limit(), 3);
+        assert_eq!(counter.window_duration(), Duration::from_secs(10));
+
+        assert_eq!(counter.remaining().await, 3);
+        assert_eq!(counter.current_count().await, 0);
+        assert!(counter.windows.is_empty());
+
+        assert!(counter.try_consume().await);
+        assert!(counter.try_consume().await);
+        assert_eq!(counter.remaining().await, 1);
+        assert_eq!(counter.current_count().await, 2);
+
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(counter.remaining().await, 3);
+        assert_eq!(counter.current_count().await, 0);
+        assert_eq!(counter.windows.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_remaining_after_lowering_limit() {
+        let counter = FixedWindowCounter::new(3, Duration::from_secs(10));
+        assert!(counter.try_consume_n(3).await);
+
+        counter.set_limit(1);
+        assert_eq!(counter.limit(), 1);
+        assert_eq!(counter.remaining().await, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_check_reports_window_state() {
+        let counter = FixedWindowCounter::new(3, Duration::from_secs(10));
+        time::advance(Duration::from_secs(4)).await;
+
+        assert_eq!(
+            counter.check(2).await,
+            Decision::Allowed {
+                limit: 3,
+                remaining: 1,
+                reset_in: Duration::from_secs(6),
+            }
+        );
+        assert_eq!(
+            counter.check(2).await,
+            Decision::Denied {
+                limit: 3,
+                remaining: 1,
+                reset_in: Duration::from_secs(6),
+                retry_after: Some(Duration::from_secs(6)),
+            }
+        );
+        assert_eq!(counter.check(4).await.retry_after(), None);
+        assert_eq!(counter.current_count().await, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_check_flags_soft_limit() {
+        let counter = FixedWindowCounter::new(5, Duration::from_secs(10)).with_soft_limit(3);
+        assert_eq!(counter.soft_limit(), Some(3));
+
+        assert_eq!(counter.check(3).await.is_soft_exceeded(), false);
+        let decision = counter.check(1).await;
+        assert_eq!(decision.is_allowed(), true);
+        assert_eq!(decision.is_soft_exceeded(), true);
+        assert_eq!(decision.remaining(), 1);
+        assert_eq!(counter.check(2).await.is_allowed(), false);
+
+        // The usage starts over with the next window
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(counter.check(3).await.is_soft_exceeded(), false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_debug_shows_window_usage() {
+        let counter = FixedWindowCounter::new(5, Duration::from_secs(10)).with_rollover(2);
+        assert_eq!(counter.try_consume_n(3).await, true);
+
+        let debug = format!("{:?}", counter);
+        assert_eq!(debug.starts_with("FixedWindowCounter {"), true, "{}", debug);
+        assert_eq!(debug.contains("limit: 5"), true, "{}", debug);
+        assert_eq!(debug.contains("window_duration: 10s"), true, "{}", debug);
+        assert_eq!(debug.contains("usage: 3"), true, "{}", debug);
+
+        // The unused budget carries over into the effective limit of the next window
+        time::advance(Duration::from_secs(10)).await;
+        let debug = format!("{:?}", counter);
+        assert_eq!(
+            debug.contains("usage: 0, effective_limit: 7"),
+            true,
+            "{}",
+            debug
+        );
+
+        let _rollover = counter.rollover.lock().unwrap();
+        assert_eq!(format!("{:?}", counter).contains("usage: <locked>"), true);
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_serde_restores_current_window() {
+        let counter = FixedWindowCounter::new(5, Duration::from_secs(3600))
+            .with_clock(WindowClock::WallClock);
+        if counter.reset_in() < Duration::from_secs(10) {
+            // Keep the test from straddling a window boundary
+            time::sleep(counter.reset_in()).await;
+        }
+
+        assert!(counter.try_consume_n(3).await);
+        let checkpoint = serde_json::to_string(&counter).unwrap();
+        drop(counter);
+
+        let restored: FixedWindowCounter = serde_json::from_str(&checkpoint).unwrap();
+        assert_eq!(restored.limit(), 5);
+        assert_eq!(restored.window_duration(), Duration::from_secs(3600));
+        assert_eq!(restored.remaining().await, 2);
+        assert!(restored.try_consume_n(2).await);
+        assert!(!restored.try_consume().await);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_drops_stale_windows() {
+        let checkpoint = r#"{
+            "limit": 5,
+            "window_duration": { "secs": 60, "nanos": 0 },
+            "base_window": 0,
+            "base_offset": { "secs": 0, "nanos": 0 },
+            "windows": { "1": 5, "2": 4 }
+        }"#;
+
+        let restored: FixedWindowCounter = serde_json::from_str(checkpoint).unwrap();
+        assert!(restored.windows.is_empty());
+
+        let invalid = checkpoint.replace(r#""secs": 60"#, r#""secs": 0"#);
+        assert!(serde_json::from_str::<FixedWindowCounter>(&invalid).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test(start_paused = true)]
+    async fn test_serde_keeps_monotonic_clock() {
+        let counter = FixedWindowCounter::new(5, Duration::from_secs(60));
+        assert!(counter.try_consume_n(3).await);
+        let checkpoint = serde_json::to_string(&counter).unwrap();
+        assert!(checkpoint.contains(r#""clock":"monotonic""#));
+
+        // Monotonic window ids are meaningless to a new counter, so only the configuration is kept
+        let restored: FixedWindowCounter = serde_json::from_str(&checkpoint).unwrap();
+        assert!(matches!(restored.clock, WindowClock::Monotonic));
+        assert_eq!(restored.limit(), 5);
+        assert_eq!(restored.remaining().await, 5);
+
+        let custom = FixedWindowCounter::new(5, Duration::from_secs(60))
+            .with_clock(WindowClock::Custom(Arc::new(SystemTime::now)));
+        let checkpoint = serde_json::to_string(&custom).unwrap();
+        let restored: FixedWindowCounter = serde_json::from_str(&checkpoint).unwrap();
+        assert!(matches!(restored.clock, WindowClock::WallClock));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_consumers_never_exceed_limit() {
+        let counter = Arc::new(FixedWindowCounter::new(500, Duration::from_secs(3600)));
+
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                tokio::spawn(async move {
+                    let mut admitted = 0;
+                    for _ in 0..100 {
+                        if counter.try_consume().await {
+                            admitted += 1;
+                        }
+                    }
+                    admitted
+                })
+            })
+            .collect();
+
+        let mut admitted = 0;
+        for task in tasks {
+            admitted += task.await.unwrap();
+        }
+
+        assert_eq!(admitted, 500);
+        assert_eq!(counter.current_count().await, 500);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_full_window_denies_until_rollover_or_raised_limit() {
+        let counter = FixedWindowCounter::new(2, Duration::from_secs(10));
+        assert_eq!(counter.try_consume_n(2).await, true);
+        assert_eq!(counter.try_consume().await, false);
+        assert_eq!(counter.try_consume_n(0).await, true);
+
+        counter.set_limit(3);
+        assert_eq!(counter.try_consume().await, true);
+        assert_eq!(counter.try_consume().await, false);
+
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(counter.try_consume_n(3).await, true);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[tokio::test]
+    async fn test_calendar_windows_align_to_utc_hours() {
+        use crate::window::CalendarWindow;
+        use chrono::{TimeZone, Utc};
+
+        // 2024-05-01T10:59:59Z
+        let (time, clock) = mock_clock(UNIX_EPOCH + Duration::from_secs(1_714_561_199));
+        let counter = FixedWindowCounter::calendar(2, CalendarWindow::Hourly).with_clock(clock);
+
+        assert_eq!(counter.reset_in(), Duration::from_secs(1));
+        assert_eq!(
+            counter.window_start_utc(),
+            Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap()
+        );
+        assert_eq!(
+            counter.reset_at(),
+            Utc.with_ymd_and_hms(2024, 5, 1, 11, 0, 0).unwrap()
+        );
+        assert_eq!(counter.try_consume_n(2).await, true);
+        assert_eq!(counter.try_consume().await, false);
+
+        *time.lock().unwrap() = UNIX_EPOCH + Duration::from_secs(1_714_561_200);
+        assert_eq!(counter.reset_in(), Duration::from_secs(3600));
+        assert_eq!(counter.try_consume_n(2).await, true);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[tokio::test]
+    async fn test_calendar_months_roll_over_on_the_first() {
+        use crate::window::CalendarWindow;
+        use chrono::{TimeZone, Utc};
+
+        let at = |y, m, d, h, min, s| {
+            SystemTime::from(Utc.with_ymd_and_hms(y, m, d, h, min, s).unwrap())
+        };
+        let (time, clock) = mock_clock(at(2024, 1, 31, 23, 59, 59));
+        let counter = FixedWindowCounter::calendar(2, CalendarWindow::Monthly).with_clock(clock);
+
+        assert_eq!(counter.reset_in(), Duration::from_secs(1));
+        assert_eq!(counter.window_duration(), Duration::from_secs(31 * 86_400));
+        assert_eq!(
+            counter.window_start_utc(),
+            Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(counter.try_consume_n(2).await, true);
+        assert_eq!(counter.try_consume().await, false);
+
+        // February 2024 is a leap month
+        *time.lock().unwrap() = at(2024, 2, 1, 0, 0, 0);
+        assert_eq!(counter.window_duration(), Duration::from_secs(29 * 86_400));
+        assert_eq!(counter.reset_in(), Duration::from_secs(29 * 86_400));
+        assert_eq!(
+            counter.reset_at(),
+            Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(counter.try_consume_n(2).await, true);
+
+        // December rolls over into the next year
+        *time.lock().unwrap() = at(2024, 12, 31, 12, 0, 0);
+        assert_eq!(counter.try_consume_n(2).await, true);
+        assert_eq!(
+            counter.reset_at(),
+            Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(counter.reset_in(), Duration::from_secs(12 * 3600));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[tokio::test]
+    async fn test_calendar_windows_follow_fixed_offset() {
+        use crate::window::CalendarWindow;
+        use chrono::{FixedOffset, TimeZone, Utc};
+
+        let at = |y, m, d, h, min, s| {
+            SystemTime::from(Utc.with_ymd_and_hms(y, m, d, h, min, s).unwrap())
+        };
+
+        // Days in UTC+02:00 start at 22:00 UTC
+        let plus_two = FixedOffset::east_opt(2 * 3600).unwrap();
+        let (time, clock) = mock_clock(at(2024, 5, 1, 21, 30, 0));
+        let daily = FixedWindowCounter::calendar_with_offset(1, CalendarWindow::Daily, plus_two)
+            .with_clock(clock);
+
+        assert_eq!(daily.reset_in(), Duration::from_secs(30 * 60));
+        assert_eq!(
+            daily.window_start_utc(),
+            Utc.with_ymd_and_hms(2024, 4, 30, 22, 0, 0).unwrap()
+        );
+        assert_eq!(daily.try_consume().await, true);
+        assert_eq!(daily.try_consume().await, false);
+
+        *time.lock().unwrap() = at(2024, 5, 1, 22, 0, 0);
+        assert_eq!(daily.reset_in(), Duration::from_secs(86_400));
+        assert_eq!(daily.try_consume().await, true);
+
+        // Months in UTC-05:00 start at 05:00 UTC on the first
+        let minus_five = FixedOffset::west_opt(5 * 3600).unwrap();
+        let (time, clock) = mock_clock(at(2024, 3, 1, 4, 0, 0));
+        let monthly =
+            FixedWindowCounter::calendar_with_offset(1, CalendarWindow::Monthly, minus_five)
+                .with_clock(clock);
+
+        assert_eq!(monthly.reset_in(), Duration::from_secs(3600));
+        assert_eq!(
+            monthly.window_start_utc(),
+            Utc.with_ymd_and_hms(2024, 2, 1, 5, 0, 0).unwrap()
+        );
+        assert_eq!(monthly.try_consume().await, true);
+        assert_eq!(monthly.try_consume().await, false);
+
+        *time.lock().unwrap() = at(2024, 3, 1, 5, 0, 0);
+        assert_eq!(monthly.try_consume().await, true);
+        assert_eq!(
+            monthly.reset_at(),
+            Utc.with_ymd_and_hms(2024, 4, 1, 5, 0, 0).unwrap()
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_memory_stays_constant_across_windows() {
+        let counter = FixedWindowCounter::new(3, Duration::from_millis(10));
+
+        for i in 0..10_000u64 {
+            assert_eq!(counter.try_consume_n(1 + (i % 3) as u32).await, true);
+            assert_eq!(counter.try_consume_n(3).await, false);
+            time::advance(Duration::from_millis(10)).await;
+        }
+
+        let windows = &counter.windows;
+        assert_eq!(windows.len(), 2);
+        assert_eq!(
+            windows.iter().map(|(window, _)| window).collect::<Vec<_>>(),
+            [9_998, 9_999]
+        );
+        assert_eq!(size_of::<WindowCounts>(), 4 * size_of::<u64>());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sliding_approximation_prevents_boundary_burst() {
+        let plain = FixedWindowCounter::new(10, Duration::from_secs(10));
+        let approximate =
+            FixedWindowCounter::new(10, Duration::from_secs(10)).with_sliding_approximation();
+
+        time::advance(Duration::from_secs(9)).await;
+        assert_eq!(plain.try_consume_n(10).await, true);
+        assert_eq!(approximate.try_consume_n(10).await, true);
+
+        // Right after the boundary the plain counter admits another full limit
+        time::advance(Duration::from_secs(1)).await;
+        assert_eq!(plain.try_consume_n(10).await, true);
+        assert_eq!(approximate.try_consume().await, false);
+        assert_eq!(approximate.remaining().await, 0);
+
+        // Halfway through, half of the previous window still counts
+        time::advance(Duration::from_secs(5)).await;
+        assert_eq!(approximate.remaining().await, 5);
+        assert_eq!(approximate.try_consume_n(5).await, true);
+        assert_eq!(approximate.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sliding_approximation_retry_after() {
+        let counter =
+            FixedWindowCounter::new(10, Duration::from_secs(10)).with_sliding_approximation();
+        assert_eq!(counter.try_consume_n(10).await, true);
+
+        // The current window is full, the request fits once the window rolled over and 10% of
+        // the previous window has decayed
+        let retry_after = counter.check(1).await.retry_after().unwrap();
+        assert_eq!(retry_after, Duration::from_secs(11));
+
+        time::advance(Duration::from_secs(10)).await;
+        let retry_after = counter.check(1).await.retry_after().unwrap();
+        assert_eq!(retry_after, Duration::from_secs(1));
+
+        time::advance(retry_after - Duration::from_millis(1)).await;
+        assert_eq!(counter.try_consume().await, false);
+        time::advance(Duration::from_millis(1)).await;
+        assert_eq!(counter.try_consume().await, true);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rollover_carries_unused_budget() {
+        let counter = FixedWindowCounter::new(10, Duration::from_secs(10)).with_rollover(5);
+
+        // Nothing is carried into the first window
+        assert_eq!(counter.try_consume_n(2).await, true);
+        assert_eq!(counter.remaining().await, 8);
+        assert_eq!(
+            counter.check(9).await.retry_after(),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(counter.check(16).await.retry_after(), None);
+        assert_eq!(counter.can_ever_admit(15), true);
+
+        // 8 requests went unused, 5 of them carry over
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(counter.remaining().await, 15);
+        let decision = counter.check(15).await;
+        assert_eq!(decision.is_allowed(), true);
+        assert_eq!(decision.limit(), 15);
+        assert_eq!(counter.try_consume().await, false);
+
+        // A busy window leaves nothing to carry over
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(counter.remaining().await, 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rollover_does_not_compound() {
+        let counter = FixedWindowCounter::new(10, Duration::from_secs(10)).with_rollover(20);
+        assert_eq!(counter.try_consume_n(10).await, true);
+
+        // A full window needs an idle one before a request above the limit fits
+        let retry_after = counter.check(15).await.retry_after();
+        assert_eq!(retry_after, Some(Duration::from_secs(20)));
+
+        // Two idle windows carry over at most one unused limit
+        time::advance(Duration::from_secs(30)).await;
+        assert_eq!(counter.remaining().await, 20);
+        assert_eq!(counter.try_consume_n(21).await, false);
+        assert_eq!(counter.try_consume_n(20).await, true);
+
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(counter.remaining().await, 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_total_current_usage() {
+        let short = FixedWindowCounter::new(10, Duration::from_secs(5));
+        let long = FixedWindowCounter::new(10, Duration::from_secs(60));
+        assert_eq!(total_current_usage(&[]), 0);
+
+        assert_eq!(short.try_consume_n(4).await, true);
+        assert_eq!(long.try_consume_n(6).await, true);
+        assert_eq!(total_current_usage(&[&short, &long]), 10);
+
+        // Counts of windows that ended are not part of the usage
+        time::advance(Duration::from_secs(5)).await;
+        assert_eq!(short.try_consume().await, true);
+        assert_eq!(total_current_usage(&[&short, &long]), 7);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_on_rollover_reports_each_window_once() {
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&finished);
+        let counter = FixedWindowCounter::new(10, Duration::from_secs(10))
+            .on_rollover(move |window, count| log.lock().unwrap().push((window, count)));
+
+        for requests in [3, 12, 0, 1] {
+            for _ in 0..requests {
+                let _ = counter.try_consume().await;
+            }
+            time::advance(Duration::from_secs(10)).await;
+        }
+        assert_eq!(*finished.lock().unwrap(), [(0, 3), (1, 10)]);
+
+        // Dropping the latest window reports it as well
+        counter.clear_old_windows().await;
+        counter.clear_old_windows().await;
+        assert_eq!(*finished.lock().unwrap(), [(0, 3), (1, 10), (3, 1)]);
+    }
+    #[tokio::test(start_paused = true)]
+    async fn test_smoothing_spreads_admissions_across_window() {
+        let smoothed = FixedWindowCounter::new(10, Duration::from_secs(10)).with_smoothing(true);
+        let plain = FixedWindowCounter::new(10, Duration::from_secs(10));
+
+        let mut smoothed_at = Vec::new();
+        let mut plain_at = Vec::new();
+        for tick in 0..100u64 {
+            while smoothed.try_consume().await {
+                smoothed_at.push(tick);
+            }
+            while plain.try_consume().await {
+                plain_at.push(tick);
+            }
+            time::advance(Duration::from_millis(100)).await;
+        }
+
+        // One admission per second instead of the whole budget up front
+        assert_eq!(
+            smoothed_at,
+            (0..10).map(|second| second * 10).collect::<Vec<_>>()
+        );
+        assert_eq!(plain_at, [0; 10]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_smoothing_hints_follow_schedule() {
+        let counter = FixedWindowCounter::new(10, Duration::from_secs(10)).with_smoothing(true);
+
+        assert_eq!(counter.try_consume().await, true);
+        assert_eq!(counter.remaining().await, 0);
+        assert_eq!(counter.would_allow(), false);
+        assert_eq!(
+            counter.check(3).await.retry_after(),
+            Some(Duration::from_secs(3))
+        );
+
+        time::advance(Duration::from_millis(3_500)).await;
+        assert_eq!(counter.remaining().await, 3);
+        assert_eq!(counter.try_consume_n(3).await, true);
+        assert_eq!(
+            counter.check(1).await.retry_after(),
+            Some(Duration::from_millis(500))
+        );
+
+        // A request beyond this window's budget waits for the next one to earn it
+        assert_eq!(
+            counter.check(7).await.retry_after(),
+            Some(Duration::from_millis(6_500 + 6_000))
+        );
+        assert_eq!(counter.check(11).await.retry_after(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_consume_reporting_is_consistent_under_concurrency() {
+        let counter = Arc::new(FixedWindowCounter::new(10, Duration::from_secs(60)));
+
+        let tasks: Vec<_> = (0..20)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                tokio::spawn(async move { counter.consume_reporting(1).await })
+            })
+            .collect();
+
+        let mut remaining = Vec::new();
+        for task in tasks {
+            if let (true, left) = task.await.unwrap() {
+                remaining.push(left);
+            }
+        }
+
+        // Every admission saw its own count, none were skewed by concurrent ones
+        remaining.sort_unstable();
+        assert_eq!(remaining, (0..10).collect::<Vec<_>>());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refund_restores_current_window() {
+        let counter = FixedWindowCounter::new(5, Duration::from_secs(10));
+
+        assert_eq!(counter.try_consume_n(2).await, true);
+        let remaining = counter.remaining().await;
+        assert_eq!(counter.try_consume().await, true);
+        counter.refund();
+        assert_eq!(counter.remaining().await, remaining);
+
+        // Refunds saturate at zero
+        counter.refund_n(10);
+        assert_eq!(counter.current_count().await, 0);
+        assert_eq!(counter.try_consume_n(5).await, true);
+        assert_eq!(counter.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refund_after_rollover_is_a_no_op() {
+        let counter = FixedWindowCounter::new(5, Duration::from_secs(10)).with_rollover(5);
+
+        assert_eq!(counter.try_consume_n(4).await, true);
+        time::advance(Duration::from_secs(10)).await;
+
+        // The window ended before any request rolled it over
+        counter.refund_n(4);
+        assert_eq!(counter.remaining().await, 6);
+
+        assert_eq!(counter.try_consume().await, true);
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(counter.try_consume_n(2).await, true);
+        counter.refund_n(3);
+        assert_eq!(counter.current_count().await, 0);
+        assert_eq!(counter.remaining().await, 9);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_history_keeps_latest_finished_windows() {
+        let counter = FixedWindowCounter::new(20, Duration::from_secs(10)).with_history(4);
+        assert!(counter.history().is_empty());
+
+        for window in 0..10u32 {
+            assert!(counter.try_consume_n(window + 1).await);
+            time::advance(Duration::from_secs(10)).await;
+        }
+        // Window 9 only finishes once a later window is observed
+        assert_eq!(counter.history(), [(5, 6), (6, 7), (7, 8), (8, 9)]);
+
+        assert!(counter.try_consume().await);
+        assert_eq!(counter.history(), [(6, 7), (7, 8), (8, 9), (9, 10)]);
+
+        let untracked = FixedWindowCounter::new(20, Duration::from_secs(10));
+        assert!(untracked.try_consume().await);
+        time::advance(Duration::from_secs(10)).await;
+        assert!(untracked.try_consume().await);
+        assert!(untracked.history().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unbounded_history_reserves_bounded_memory() {
+        let counter = FixedWindowCounter::new(20, Duration::from_secs(10)).with_history(usize::MAX);
+        assert!(counter.history.lock().unwrap().capacity() <= 2 * MAX_PREALLOCATED_HISTORY);
+
+        assert!(counter.try_consume().await);
+        time::advance(Duration::from_secs(10)).await;
+        assert!(counter.try_consume().await);
+        assert_eq!(counter.history(), [(0, 1)]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_on_rollover_fires_once_under_concurrency() {
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&finished);
+        let counter = Arc::new(
+            FixedWindowCounter::new(u32::MAX, Duration::from_millis(20))
+                .on_rollover(move |window, count| log.lock().unwrap().push((window, count))),
+        );
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                tokio::spawn(async move {
+                    for _ in 0..200 {
+                        assert!(counter.try_consume().await);
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        time::sleep(Duration::from_millis(20)).await;
+        counter.clear_old_windows().await;
+
+        let finished = finished.lock().unwrap();
+        let windows: Vec<_> = finished.iter().map(|(window, _)| *window).collect();
+        let mut unique = windows.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(windows.len(), unique.len());
+        assert_eq!(finished.iter().map(|(_, count)| count).sum::<u32>(), 1_600);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_on_rollover_panic_does_not_break_counter() {
+        let counter = FixedWindowCounter::new(2, Duration::from_secs(10))
+            .on_rollover(|_, _| panic!("callback failed"));
+
+        assert_eq!(counter.try_consume_n(2).await, true);
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(counter.try_consume().await, true);
+        assert_eq!(counter.try_consume().await, true);
+        assert_eq!(counter.try_consume().await, false);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_lock_free_counts_are_exact_per_window() {
+        const LIMIT: u32 = 500;
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&finished);
+        let counter = Arc::new(
+            FixedWindowCounter::new(LIMIT, Duration::from_millis(25))
+                .on_rollover(move |window, count| log.lock().unwrap().push((window, count))),
+        );
+
+        let tasks: Vec<_> = (0..16)
+            .map(|task| {
+                let counter = Arc::clone(&counter);
+                tokio::spawn(async move {
+                    let end = time::Instant::now() + Duration::from_millis(150);
+                    let mut admitted = 0;
+                    for i in 0u32.. {
+                        if time::Instant::now() >= end {
+                            break;
+                        }
+                        let cost = 1 + (task + i) % 3;
+                        if counter.try_consume_n(cost).await {
+                            admitted += cost;
+                        }
+                        if i % 64 == 0 {
+                            tokio::task::yield_now().await;
+                        }
+                    }
+                    admitted
+                })
+            })
+            .collect();
+
+        let mut admitted = 0;
+        for task in tasks {
+            admitted += task.await.unwrap();
+        }
+        time::sleep(Duration::from_millis(25)).await;
+        counter.clear_old_windows().await;
+
+        // Every admission is counted in exactly one window, and no window exceeds the limit
+        let finished = finished.lock().unwrap();
+        assert!(finished.len() > 1);
+        assert!(finished.iter().all(|&(_, count)| count <= LIMIT));
+        assert_eq!(
+            finished.iter().map(|&(_, count)| count).sum::<u32>(),
+            admitted
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_consume_in_tracked_window_takes_no_lock() {
+        let counter = FixedWindowCounter::new(2, Duration::from_secs(10));
+        assert_eq!(counter.try_consume().await, true);
+
+        // With the rollover lock held elsewhere, requests are still decided on the first poll
+        let _rollover = counter.rollover.lock().unwrap();
+        let mut consume = tokio_test::task::spawn(counter.try_consume());
+        assert_eq!(consume.poll(), std::task::Poll::Ready(true));
+        let mut consume = tokio_test::task::spawn(counter.try_consume());
+        assert_eq!(consume.poll(), std::task::Poll::Ready(false));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_seeded_window_admits_only_the_rest() {
+        let counter = FixedWindowCounter::new(100, Duration::from_secs(10));
+        time::advance(Duration::from_secs(5)).await;
+
+        counter.seed_current_window(80);
+        assert_eq!(counter.remaining().await, 20);
+        assert_eq!(counter.current_count().await, 80);
+
+        for _ in 0..20 {
+            assert_eq!(counter.try_consume().await, true);
+        }
+        assert_eq!(counter.try_consume().await, false);
+
+        // Seeding never lowers the count, and the seed ends with the window
+        counter.seed_current_window(50);
+        assert_eq!(counter.remaining().await, 0);
+        time::advance(Duration::from_secs(5)).await;
+        assert_eq!(counter.remaining().await, 100);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_initial_count_is_clamped_to_limit() {
+        let counter = FixedWindowCounter::new(10, Duration::from_secs(10)).with_initial_count(25);
+        assert_eq!(counter.current_count().await, 10);
+        assert_eq!(counter.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_initial_count_survives_clock_change() {
+        let wall = WindowClock::Custom(Arc::new(|| UNIX_EPOCH + Duration::from_secs(1_000_005)));
+        let counter = FixedWindowCounter::new(10, Duration::from_secs(10))
+            .with_initial_count(7)
+            .with_clock(wall)
+            .with_clock(WindowClock::Monotonic);
+        assert_eq!(counter.current_count().await, 7);
+        assert_eq!(counter.remaining().await, 3);
+
+        // The windows of the wall clock are forgotten, so the monotonic windows still roll over
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(counter.remaining().await, 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_would_allow_agrees_with_consume_at_boundary() {
+        let counter = FixedWindowCounter::new(3, Duration::from_secs(10));
+        assert_eq!(counter.would_allow_n(3), true);
+        assert_eq!(counter.would_allow_n(4), false);
+        assert_eq!(counter.try_consume_n(3).await, true);
+
+        // Right before the boundary the window is full, right after it is empty again
+        time::advance(Duration::from_millis(9_999)).await;
+        assert_eq!(counter.would_allow(), false);
+        assert_eq!(counter.try_consume().await, false);
+        time::advance(Duration::from_millis(1)).await;
+        assert_eq!(counter.would_allow(), true);
+        assert_eq!(counter.windows.len(), 1);
+
+        for _ in 0..3 {
+            assert_eq!(counter.would_allow(), true);
+            assert_eq!(counter.try_consume().await, true);
+        }
+        assert_eq!(counter.would_allow(), false);
+        assert_eq!(counter.try_consume().await, false);
+    }
+
+    #[tokio::test]
+    async fn test_tight_retry_loops_do_not_starve_other_tasks() {
+        let counter = Arc::new(FixedWindowCounter::new(1_000, Duration::from_secs(60)));
+        let started = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..50)
+            .map(|_| {
+                let counter = counter.clone();
+                let started = started.clone();
+                tokio::spawn(async move {
+                    started.fetch_add(1, Ordering::SeqCst);
+                    // Neither admissions nor denials wait, so nothing here yields explicitly
+                    for _ in 0..5_000 {
+                        let _ = counter.try_consume().await;
+                    }
+                    started.load(Ordering::SeqCst)
+                })
+            })
+            .collect();
+
+        // On a single thread, every task only gets going if the spinning ones yield
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), 50);
+        }
+        assert_eq!(counter.current_count().await, 1_000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_would_allow_tracks_no_windows() {
+        let counter = FixedWindowCounter::new(3, Duration::from_secs(1))
+            .with_sliding_approximation()
+            .with_rollover(1);
+
+        for _ in 0..100 {
+            assert_eq!(counter.would_allow_n(3), true);
+            time::advance(Duration::from_millis(500)).await;
+        }
+        assert_eq!(counter.windows.is_empty(), true);
+
+        // With the previous window weighted in, the peek still matches the outcome
+        assert_eq!(counter.try_consume_n(3).await, true);
+        time::advance(Duration::from_millis(1_500)).await;
+        for cost in [2, 1, 1] {
+            let expected = counter.would_allow_n(cost);
+            assert_eq!(counter.try_consume_n(cost).await, expected);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cleanup_task_prunes_on_schedule() {
+        let counter = Arc::new(FixedWindowCounter::new(10, Duration::from_secs(10)));
+        let cleanup = counter.spawn_cleanup(Duration::from_secs(15));
+        assert_eq!(counter.try_consume().await, true);
+
+        // The first sweep runs right away, the next one after 15 seconds
+        time::advance(Duration::from_secs(14)).await;
+        assert_eq!(counter.windows.is_empty(), false);
+        time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+        assert_eq!(counter.windows.is_empty(), true);
+
+        // Dropping the handle stops the task along with its reference to the counter
+        assert_eq!(counter.try_consume().await, true);
+        drop(cleanup);
+        tokio::task::yield_now().await;
+        assert_eq!(Arc::weak_count(&counter), 0);
+        time::advance(Duration::from_secs(30)).await;
+        assert_eq!(counter.windows.is_empty(), false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cleanup_task_stops_with_counter() {
+        let counter = Arc::new(FixedWindowCounter::new(10, Duration::from_secs(10)));
+        let cleanup = counter.spawn_cleanup(Duration::from_secs(1));
+        tokio::task::yield_now().await;
+        assert_eq!(cleanup.is_finished(), false);
+
+        drop(counter);
+        time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+        assert_eq!(cleanup.is_finished(), true);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cleanup_with_zero_interval_keeps_running() {
+        let counter = Arc::new(FixedWindowCounter::new(10, Duration::from_secs(1)));
+        let cleanup = counter.spawn_cleanup(Duration::ZERO);
+        assert_eq!(counter.try_consume().await, true);
+
+        time::advance(Duration::from_secs(2)).await;
+        tokio::task::yield_now().await;
+        assert_eq!(cleanup.is_finished(), false);
+        assert_eq!(counter.windows.is_empty(), true);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_after_lands_on_boundary() {
+        let counter = FixedWindowCounter::new(1, Duration::from_millis(700));
+        time::advance(Duration::from_millis(1_399)).await;
+        assert_eq!(counter.try_consume().await, true);
+
+        let retry_after = counter.check(1).await.retry_after().unwrap();
+        assert_eq!(retry_after, Duration::from_millis(1));
+        assert_eq!(retry_after, counter.reset_in());
+
+        time::advance(retry_after - Duration::from_nanos(1)).await;
+        assert_eq!(counter.try_consume().await, false);
+        time::advance(Duration::from_nanos(1)).await;
+        assert_eq!(counter.try_consume().await, true);
+    }
+
+    proptest! {
+        /// A window ends one nanosecond before the next one starts, whatever its duration.
+        #[test]
+        fn prop_windows_roll_over_exactly_at_boundary(
+            window_nanos in 1u64..10_000_000_000,
+            windows in 1u32..1_000,
+        ) {
+            let window = Duration::from_nanos(window_nanos);
+            let admitted = paused_runtime()
+                .block_on(async {
+                    let counter = FixedWindowCounter::new(1, window);
+                    let boundary = Instant::now() + window * windows;
+                    let last_tick = boundary - Duration::from_nanos(1);
+                    [
+                        counter.try_consume_at(last_tick, 1).await,
+                        counter.try_consume_at(last_tick, 1).await,
+                        counter.try_consume_at(boundary, 1).await,
+                        counter.try_consume_at(boundary, 1).await,
+                    ]
+                });
+
+            prop_assert_eq!(admitted, [true, false, true, false]);
+        }
+
+        /// No window admits more than the limit.
+        #[test]
+        fn prop_admits_at_most_limit_per_window(
+            limit in 1u32..20,
+            window_ms in 1u64..3_000,
+            steps in prop::collection::vec((0u32..25, 0u64..1_000), 1..60),
+        ) {
+            let window = Duration::from_millis(window_ms);
+            let admitted = paused_runtime()
+                .block_on(async {
+                    let start = Instant::now();
+                    let counter = FixedWindowCounter::new(limit, window);
+                    let mut admitted = HashMap::<u128, u32>::new();
+                    for (amount, wait) in steps {
+                        if counter.try_consume_n(amount).await {
+                            let id = start.elapsed().as_nanos() / window.as_nanos();
+                            *admitted.entry(id).or_default() += amount;
+                        }
+                        time::advance(Duration::from_millis(wait)).await;
+                    }
+                    admitted
+                });
+
+            for total in admitted.values() {
+                prop_assert!(*total <= limit);
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_coarse_clock_rolls_over_on_refresh() {
+        let window = Duration::from_secs(1);
+        let counter =
+            FixedWindowCounter::new(2, window).with_coarse_clock(Duration::from_millis(10));
+        let reads = counter.coarse_clock().unwrap().reads();
+
+        assert!(counter.try_consume_n(2).await);
+        assert!(!counter.try_consume().await);
+        assert_eq!(counter.coarse_clock().unwrap().reads(), reads);
+
+        // The cached time reaches the next window up to one resolution late
+        time::sleep(window + Duration::from_millis(10)).await;
+        assert!(counter.try_consume().await);
+    }
+
+    #[test]
+    fn test_try_new_validates_window_duration() {
+        for limit in [0, 100] {
+            assert_eq!(
+                FixedWindowCounter::try_new(limit, Duration::ZERO).err(),
+                Some(LimitrError::InvalidConfig(InvalidConfigReason::ZeroWindow))
+            );
+            assert_eq!(
+                FixedWindowCounter::try_new(limit, Duration::MAX).err(),
+                Some(LimitrError::InvalidConfig(
+                    InvalidConfigReason::WindowTooLarge
+                ))
+            );
+        }
+
+        // A zero limit denies everything, but can still be raised later
+        assert!(FixedWindowCounter::try_new(0, Duration::from_secs(1)).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "window duration must not be zero")]
+    fn test_new_panics_on_zero_window() {
+        let _ = FixedWindowCounter::new(100, Duration::ZERO);
     }
 }
@@ -4,6 +4,7 @@
 //!
 //! - Fixed Window Counter: Limits requests within fixed time windows.
 //! - Sliding Window Counter: Provides a smoother rate limiting approach using a sliding time window.
+//! - Quota Limiter: Limits requests until a scheduled reset, such as a monthly API quota.
 //!
 //! These algorithms are useful for controlling the rate of requests or operations in a system,
 //! helping to prevent overload and ensure fair resource usage.
@@ -34,7 +35,7 @@
 //! use limitr::window::SlidingWindowCounter;
 //! use std::time::Duration;
 //!
-//! let mut counter = SlidingWindowCounter::new(5, Duration::from_secs(1));
+//! let counter = SlidingWindowCounter::new(5, Duration::from_secs(1));
 //! # tokio_test::block_on(async {
 //! for i in 0..7 {
 //!     if counter.try_consume().await {
@@ -46,8 +47,38 @@
 //! # })
 //! ```
 
+use crate::error::{InvalidConfigReason, LimitrError};
+use std::time::Duration;
+
+#[cfg(feature = "chrono")]
+mod calendar;
 mod fixed_window;
+mod quota;
 mod sliding_window;
 
+#[cfg(feature = "chrono")]
+pub use calendar::*;
 pub use fixed_window::*;
+pub use quota::*;
 pub use sliding_window::*;
+
+/// Checks that windows of `duration` can be measured, i.e. that it is neither zero nor longer
+/// than `u64::MAX` nanoseconds.
+fn validate_window_duration(duration: Duration) -> Result<(), LimitrError> {
+    if duration.is_zero() {
+        return Err(LimitrError::InvalidConfig(InvalidConfigReason::ZeroWindow));
+    }
+    if duration.as_nanos() > u64::MAX as u128 {
+        return Err(LimitrError::InvalidConfig(
+            InvalidConfigReason::WindowTooLarge,
+        ));
+    }
+    Ok(())
+}
+
+const _: () = {
+    crate::assert_send_sync::<FixedWindowCounter>();
+    crate::assert_send_sync::<SlidingWindowCounter>();
+    crate::assert_send_sync::<QuotaLimiter>();
+    crate::assert_send_sync::<WindowClock>();
+};
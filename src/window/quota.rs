@@ -0,0 +1,334 @@
+use crate::decision::Decision;
+use crate::limiter::{BoxFuture, RateLimiter, SharedRateLimiter};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use tokio::task;
+use tokio::time::{Duration, Instant};
+
+/// A quota of requests that is used up over time and renewed in full at a scheduled instant,
+/// such as the monthly quota of an API plan.
+///
+/// Unlike a window, the quota does not recover gradually: every admitted request takes from
+/// it until it is exhausted, and everything is denied from then on until the reset instant
+/// passes. The quota is then refilled to its limit, and with a period set, the next reset is
+/// scheduled one period after the previous one. Resets that were missed while the limiter was
+/// not used are skipped rather than stacked.
+///
+/// All methods take `&self` and only hold a short, synchronous lock, so the limiter can be
+/// shared across tasks through an `Arc` directly.
+///
+/// # Example
+///
+/// ```rust
+/// use limitr::window::QuotaLimiter;
+/// use tokio::time::{self, Duration};
+///
+/// # tokio_test::block_on(async {
+/// # time::pause();
+/// let day = Duration::from_secs(24 * 60 * 60);
+/// let quota = QuotaLimiter::recurring(1_000, day);
+///
+/// assert!(quota.try_consume_n(1_000).await);
+/// assert!(!quota.try_consume().await);
+///
+/// time::advance(day).await;
+/// assert_eq!(quota.remaining(), 1_000);
+/// assert_eq!(quota.reset_in(), Some(day));
+/// # })
+/// ```
+pub struct QuotaLimiter {
+    state: Mutex<QuotaState>,
+}
+
+/// The quota along with its reset schedule.
+struct QuotaState {
+    limit: u64,
+    remaining: u64,
+    /// Instant of the next reset, `None` once a one-off reset has happened
+    reset_at: Option<Instant>,
+    /// Time between resets, never zero
+    period: Option<Duration>,
+}
+
+impl QuotaState {
+    /// Refills the quota if its reset instant has passed at `now`, scheduling the next reset.
+    fn renew(&mut self, now: Instant) {
+        let Some(reset_at) = self.reset_at.filter(|&reset_at| now >= reset_at) else {
+            return;
+        };
+        trace!("Renewing quota of {} requests", self.limit);
+        self.remaining = self.limit;
+
+        self.reset_at = self.period.and_then(|period| {
+            // The first reset after `now`, skipping those that were missed
+            let missed = now.duration_since(reset_at).as_nanos() / period.as_nanos();
+            let until_next = period.as_nanos().saturating_mul(missed + 1);
+            reset_at.checked_add(Duration::from_nanos(u64::try_from(until_next).ok()?))
+        });
+    }
+
+    /// Returns `cost` requests to the quota, up to its limit.
+    fn give_back(&mut self, cost: u64) {
+        self.remaining = self.remaining.saturating_add(cost).min(self.limit);
+    }
+
+    fn reset_in(&self, now: Instant) -> Option<Duration> {
+        self.reset_at
+            .map(|reset_at| reset_at.saturating_duration_since(now))
+    }
+
+    fn decide(&mut self, now: Instant, n: u64) -> Decision {
+        self.renew(now);
+
+        let allowed = n <= self.remaining;
+        if allowed {
+            self.remaining -= n;
+        }
+        let reset_in = if self.remaining == self.limit {
+            Duration::ZERO
+        } else {
+            self.reset_in(now).unwrap_or(Duration::MAX)
+        };
+
+        if allowed {
+            return Decision::Allowed {
+                limit: self.limit,
+                remaining: self.remaining,
+                reset_in,
+            };
+        }
+        Decision::Denied {
+            limit: self.limit,
+            remaining: self.remaining,
+            reset_in,
+            // A request above the limit never fits, not even into a renewed quota
+            retry_after: (n <= self.limit).then_some(self.reset_in(now)).flatten(),
+        }
+    }
+}
+
+impl QuotaLimiter {
+    /// Creates a new `QuotaLimiter` admitting `limit` requests until `reset_at`, when the quota
+    /// is renewed once.
+    ///
+    /// Renew the quota periodically with [`QuotaLimiter::with_period`], or create a quota that
+    /// is renewed every period from now on with [`QuotaLimiter::recurring`].
+    pub fn new(limit: u64, reset_at: Instant) -> Self {
+        QuotaLimiter {
+            state: Mutex::new(QuotaState {
+                limit,
+                remaining: limit,
+                reset_at: Some(reset_at),
+                period: None,
+            }),
+        }
+    }
+
+    /// Creates a new `QuotaLimiter` admitting `limit` requests per `period`, renewed every
+    /// `period` from now on.
+    pub fn recurring(limit: u64, period: Duration) -> Self {
+        QuotaLimiter::new(limit, Instant::now() + period).with_period(period)
+    }
+
+    /// Renews the quota every `period` after the first reset, instead of only once. A zero
+    /// period is treated as one nanosecond.
+    pub fn with_period(self, period: Duration) -> Self {
+        self.lock().period = Some(period.max(Duration::from_nanos(1)));
+        self
+    }
+
+    /// Attempts to take one request from the quota.
+    ///
+    /// Returns `true` if the quota had a request left.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume(&self) -> bool {
+        self.try_consume_n(1).await
+    }
+
+    /// Attempts to take `n` requests from the quota at once.
+    ///
+    /// Either all of them are taken or none.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume_n(&self, n: u64) -> bool {
+        self.check(n).await.is_allowed()
+    }
+
+    /// Attempts to take `n` requests from the quota, reporting the state of the quota along
+    /// with the outcome.
+    ///
+    /// A denied request can be retried once the quota is renewed, unless it exceeds the limit
+    /// or the quota is never renewed again.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&self, n: u64) -> Decision {
+        task::coop::consume_budget().await;
+        self.lock().decide(Instant::now(), n)
+    }
+
+    /// Returns how many requests are left in the quota.
+    ///
+    /// A reset that is due is applied first, so this reports the renewed quota even if no
+    /// request was made since.
+    pub fn remaining(&self) -> u64 {
+        let mut state = self.lock();
+        state.renew(Instant::now());
+        state.remaining
+    }
+
+    /// Returns how long until the quota is renewed, or `None` if it is never renewed again.
+    pub fn reset_in(&self) -> Option<Duration> {
+        let now = Instant::now();
+        let mut state = self.lock();
+        state.renew(now);
+        state.reset_in(now)
+    }
+
+    /// Returns the number of requests the quota is renewed to.
+    pub fn limit(&self) -> u64 {
+        self.lock().limit
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QuotaState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl RateLimiter for QuotaLimiter {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        SharedRateLimiter::try_acquire(self, cost)
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        SharedRateLimiter::check(self, cost)
+    }
+
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        SharedRateLimiter::refund(self, cost)
+    }
+
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        SharedRateLimiter::retry_after(self, cost)
+    }
+}
+
+impl SharedRateLimiter for QuotaLimiter {
+    fn try_acquire(&self, cost: u64) -> BoxFuture<'_, bool> {
+        Box::pin(self.try_consume_n(cost))
+    }
+
+    fn check(&self, cost: u64) -> BoxFuture<'_, Decision> {
+        Box::pin(QuotaLimiter::check(self, cost))
+    }
+
+    fn refund(&self, cost: u64) -> BoxFuture<'_, ()> {
+        Box::pin(async move { self.lock().give_back(cost) })
+    }
+
+    fn retry_after(&self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        Box::pin(async move {
+            let now = Instant::now();
+            let mut state = self.lock();
+            state.renew(now);
+            if cost <= state.remaining {
+                Some(Duration::ZERO)
+            } else if cost <= state.limit {
+                state.reset_in(now)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::window::QuotaLimiter;
+    use crate::{Decision, LimitrError, RateLimiter};
+    use tokio::time::{self, Duration, Instant};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_one_off_reset() {
+        let start = Instant::now();
+        let quota = QuotaLimiter::new(3, start + Duration::from_secs(60));
+
+        assert!(quota.try_consume_n(2).await);
+        assert!(quota.try_consume().await);
+        assert!(!quota.try_consume().await);
+        assert_eq!(quota.remaining(), 0);
+
+        time::advance(Duration::from_secs(59)).await;
+        assert!(!quota.try_consume().await);
+        assert_eq!(quota.reset_in(), Some(Duration::from_secs(1)));
+
+        time::advance(Duration::from_secs(1)).await;
+        assert_eq!(quota.remaining(), 3);
+        assert_eq!(quota.reset_in(), None);
+
+        // Without a period, the renewed quota is the last one
+        assert!(quota.try_consume_n(3).await);
+        let decision = quota.check(1).await;
+        assert_eq!(decision.retry_after(), None);
+        assert_eq!(decision.reset_in(), Duration::MAX);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_fails_once_exhausted() {
+        let start = Instant::now();
+        let mut quota = QuotaLimiter::new(3, start + Duration::from_secs(60));
+        assert!(quota.try_consume_n(3).await);
+
+        // The one reset is still ahead, so acquiring waits for it
+        quota.acquire(3).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(60));
+
+        assert_eq!(quota.acquire(1).await, Err(LimitrError::Exhausted));
+        assert_eq!(
+            quota.acquire(4).await,
+            Err(LimitrError::AmountExceedsCapacity {
+                requested: 4,
+                capacity: 3
+            })
+        );
+        assert_eq!(
+            crate::SharedRateLimiter::acquire(&quota, 1).await,
+            Err(LimitrError::Exhausted)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_recurring_reset_skips_missed_periods() {
+        let period = Duration::from_secs(10);
+        let quota = QuotaLimiter::recurring(5, period);
+        assert!(quota.try_consume_n(5).await);
+
+        let decision = quota.check(1).await;
+        assert!(!decision.is_allowed());
+        assert_eq!(decision.retry_after(), Some(period));
+        assert_eq!(quota.check(6).await.retry_after(), None);
+
+        // Three and a half periods later the quota was renewed once, not three times
+        time::advance(Duration::from_secs(35)).await;
+        assert_eq!(quota.reset_in(), Some(Duration::from_secs(5)));
+        assert!(quota.try_consume_n(5).await);
+        assert!(!quota.try_consume().await);
+
+        time::advance(Duration::from_secs(5)).await;
+        assert_eq!(
+            quota.check(2).await,
+            Decision::Allowed {
+                limit: 5,
+                remaining: 3,
+                reset_in: period,
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refund_never_exceeds_limit() {
+        let mut quota = QuotaLimiter::recurring(4, Duration::from_secs(60));
+        assert!(quota.try_acquire(3).await);
+
+        quota.refund(2).await;
+        assert_eq!(quota.remaining(), 3);
+        quota.refund(10).await;
+        assert_eq!(quota.remaining(), 4);
+    }
+}
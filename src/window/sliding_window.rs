@@ -1,7 +1,16 @@
+use crate::clock::CoarseClock;
+use crate::decision::Decision;
+use crate::error::{InvalidConfigReason, LimitrError};
+use crate::limiter::{BoxFuture, RateLimiter, SharedRateLimiter};
+use crate::quota::Quota;
+use crate::window::validate_window_duration;
 use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tokio::time::{Duration, Instant};
+use std::time::SystemTime;
+use tokio::sync::{Mutex, Notify};
+use tokio::time::{sleep_until, Duration, Instant};
 
 /// A sliding window rate limiter
 ///
@@ -11,7 +20,9 @@ use tokio::time::{Duration, Instant};
 /// request count reflects only those within the current window.
 ///
 /// The `SlidingWindowCounter` is safe for use in multithreaded applications, as
-/// it leverages an `Arc` and `Mutex` to protect the internal queue of requests.
+/// it leverages a `Mutex` to protect the internal queue of requests. All methods
+/// take `&self`, so the counter can be shared across tasks through an `Arc` directly, without
+/// wrapping it in another `Mutex`. [`SlidingWindowCounter::shared`] creates it that way.
 ///
 /// ## Example
 ///
@@ -20,7 +31,7 @@ use tokio::time::{Duration, Instant};
 /// use limitr::window::SlidingWindowCounter;
 /// # tokio_test::block_on(async {
 ///  // Create a rate limiter that allows 5 requests per 10-second window
-///  let mut limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+///  let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
 ///
 ///  // Attempt to consume a request
 ///  if limiter.try_consume().await {
@@ -32,15 +43,499 @@ use tokio::time::{Duration, Instant};
 /// # })
 /// ```
 ///
+/// A request made exactly one window duration ago still counts by default, so with a one-second
+/// window a request made exactly one second after the first is still limited by it. This can be
+/// changed with [`SlidingWindowCounter::with_boundary`]. Requests count fully until they leave
+/// the window, unless they are set to fade out with [`SlidingWindowCounter::with_decay`].
+///
+/// Every admitted request is logged by default. For large limits the counter can instead keep
+/// only two counts and approximate the window from them, see [`SlidingWindowMode`].
+///
+/// With the `serde` feature, the request log can be persisted across restarts through
+/// [`SlidingWindowCounter::snapshot`] and [`SlidingWindowCounter::from_snapshot`].
+///
 /// # Fields
 /// - `limit`: The maximum number of requests allowed in the time window.
-/// - `window_duration`: The duration of the sliding window.
-/// - `requests`: A deque of `Instant` timestamps, protected by a mutex,
-///   representing when requests were made.
+/// - `window_duration`: The duration of the sliding window, in nanoseconds.
+/// - `max_entries`: An optional cap on the number of stored timestamps.
+/// - `sub_buckets`: The number of sub-buckets requests are merged into, if enabled.
+/// - `origin`: The instant sub-buckets are aligned to.
+/// - `boundary`: Whether a request exactly one window old still counts.
+/// - `decay`: How much requests count as they age.
+/// - `mode`: Whether the window is computed from a log of requests or approximated.
+/// - `min_gap`: The shortest time allowed between two admitted requests, if any.
+/// - `peak`: The highest number of requests the window held at once.
+/// - `requests`: A log of request timestamps and weights, protected by a mutex,
+///   representing when requests were made and how much they count.
+/// - `drain`: Whether the counter is draining for shutdown, and what it still waits for.
 pub struct SlidingWindowCounter {
-    limit: u32,
-    window_duration: Duration,
-    requests: Arc<Mutex<VecDeque<Instant>>>,
+    // Only changed while holding the `requests` lock, so decisions see a consistent configuration
+    limit: AtomicU32,
+    window_duration: AtomicU64,
+    max_entries: Option<usize>,
+    sub_buckets: Option<u32>,
+    origin: Instant,
+    boundary: Boundary,
+    decay: Decay,
+    mode: SlidingWindowMode,
+    min_gap: Option<Duration>,
+    coarse_clock: Option<CoarseClock>,
+    /// Requests in the window beyond which admissions are flagged, if configured
+    soft_limit: Option<u32>,
+    /// Most requests ever in the window at once
+    peak: AtomicU32,
+    requests: Mutex<RequestLog>,
+    /// Shared with the reservations handed out, which report back when they are dropped
+    drain: Arc<Drain>,
+}
+
+/// Shutdown state of a [`SlidingWindowCounter`], see [`SlidingWindowCounter::begin_drain`].
+#[derive(Debug, Default)]
+struct Drain {
+    draining: AtomicBool,
+    /// Number of `acquire` calls in flight
+    waiting: AtomicUsize,
+    /// Notified when a drain begins, and when a waiter or a reservation goes away
+    changed: Notify,
+}
+
+impl Drain {
+    fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts an `acquire` call as in flight until it is dropped, whether it returned or not.
+struct Waiting<'a>(&'a Drain);
+
+impl<'a> Waiting<'a> {
+    fn new(drain: &'a Drain) -> Self {
+        drain.waiting.fetch_add(1, Ordering::Relaxed);
+        Waiting(drain)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.waiting.fetch_sub(1, Ordering::Relaxed);
+        self.0.changed.notify_waiters();
+    }
+}
+
+/// Identifies requests admitted by [`SlidingWindowCounter::consume_tracked`], to give them back
+/// with [`SlidingWindowCounter::refund_entry`].
+///
+/// An id can only be used once, so it is neither `Clone` nor `Copy`. It is only meaningful to
+/// the counter that issued it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct EntryId {
+    seq: u64,
+    n: u32,
+}
+
+/// A request booked for a later instant by [`SlidingWindowCounter::reserve`].
+///
+/// The reserved request counts towards the limit of the window from the moment it is booked, so
+/// neither later reservations nor other requests can take its slot. Run the request after
+/// [`Reservation::wait`] returns. Dropping the reservation without waiting for it cancels it and
+/// gives the slot back, unless the counter has already logged the request because its instant
+/// has passed.
+#[derive(Debug)]
+#[must_use = "dropping a reservation cancels it"]
+pub struct Reservation {
+    due: Instant,
+    /// Flag shared with the pending entry, `None` once the reservation is fulfilled
+    cancelled: Option<Arc<AtomicBool>>,
+    drain: Arc<Drain>,
+}
+
+impl Reservation {
+    /// Returns the instant the reserved request may run at.
+    pub fn due(&self) -> Instant {
+        self.due
+    }
+
+    /// Returns how long until the reserved request may run, which is zero once it is due.
+    pub fn wait_time(&self) -> Duration {
+        self.due.saturating_duration_since(Instant::now())
+    }
+
+    /// Waits until the reserved request may run.
+    ///
+    /// # Cancel safety
+    ///
+    /// Dropping the future while it waits drops the reservation along with it, cancelling it.
+    pub async fn wait(mut self) {
+        sleep_until(self.due).await;
+        self.cancelled = None;
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(cancelled) = &self.cancelled {
+            // Cancelled entries are removed the next time the counter is used, so dropping a
+            // reservation never has to wait for the lock
+            cancelled.store(true, Ordering::Relaxed);
+            self.drain.changed.notify_waiters();
+        }
+    }
+}
+
+/// Determines whether a request made exactly one window duration ago still counts towards the
+/// window of a [`SlidingWindowCounter`].
+///
+/// With a one-second window and a request made at `t`, the window covers `[t, t + 1s]` with
+/// [`Boundary::Inclusive`] and `[t, t + 1s)` with [`Boundary::Exclusive`]. The request is
+/// therefore still counted at exactly `t + 1s` with the former and no longer with the latter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Boundary {
+    /// A request counts until it is strictly older than the window. This is the default.
+    #[default]
+    Inclusive,
+    /// A request stops counting as soon as it is exactly one window old.
+    Exclusive,
+}
+
+/// How much a request counts towards the window of a [`SlidingWindowCounter`] as it ages.
+///
+/// With a decay, the window admits requests while the sum of the weights of the requests in it,
+/// rounded up, stays within the limit. A burst then frees up capacity gradually instead of all
+/// at once when it leaves the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Decay {
+    /// A request counts fully until it leaves the window. This is the default.
+    #[default]
+    None,
+    /// A request counts by the share of the window it has left, from fully when it is made down
+    /// to nothing when it leaves the window.
+    Linear,
+    /// A request counts half as much with every given half-life that passes, until it leaves
+    /// the window. A zero half-life is treated as one nanosecond.
+    Exponential(Duration),
+}
+
+/// How a [`SlidingWindowCounter`] keeps track of the requests in its window.
+///
+/// | Mode                         | Memory                           | Accuracy                   |
+/// |------------------------------|----------------------------------|----------------------------|
+/// | [`SlidingWindowMode::Log`]    | One entry per admitted request   | Exact                      |
+/// | [`SlidingWindowMode::Approx`] | Two counts, regardless of limit  | Assumes evenly spread load |
+///
+/// The log can be bounded as well, with [`SlidingWindowCounter::with_max_entries`] or
+/// [`SlidingWindowCounter::with_sub_buckets`], which only ever makes it deny more.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SlidingWindowMode {
+    /// Every admitted request is logged with its time, so the window is exact. This is the
+    /// default.
+    #[default]
+    Log,
+    /// Requests are counted per fixed window, and the sliding window is approximated by the
+    /// current count plus the previous count weighted by how much the previous fixed window
+    /// still overlaps the sliding one.
+    ///
+    /// This assumes the requests of the previous fixed window were spread evenly over it. With
+    /// steady traffic the error is small, but a burst at the very end of one fixed window
+    /// followed by a steady stream in the next can let up to twice the limit through within a
+    /// window. The [`Boundary`] and [`Decay`] of the counter are ignored in this mode.
+    Approx,
+}
+
+impl Decay {
+    /// Returns how much a request of the given `age` counts in a window of length `window`.
+    fn weight(self, age: Duration, window: Duration) -> f64 {
+        match self {
+            Decay::None => 1.0,
+            Decay::Linear => 1.0 - (age.as_secs_f64() / window.as_secs_f64()).min(1.0),
+            Decay::Exponential(half_life) => {
+                let half_life = half_life.max(Duration::from_nanos(1));
+                0.5f64.powf(age.as_secs_f64() / half_life.as_secs_f64())
+            }
+        }
+    }
+}
+
+/// Time-ordered log of admitted requests.
+///
+/// Requests recorded at the same instant or in the same sub-bucket share one entry, and entries
+/// may be merged further when the log is capped, so each entry carries the number of requests
+/// it stands for.
+#[derive(Default)]
+struct RequestLog {
+    entries: VecDeque<Entry>,
+    /// Sum of all entry counts
+    total: u64,
+    /// Sequence number of the latest recorded batch of requests
+    recorded: u64,
+    /// Capacity the log was created with, which it never shrinks below
+    base_capacity: usize,
+    /// Time since which the log has used less than a quarter of its capacity
+    underused_since: Option<Instant>,
+    /// Reserved requests that are not due yet, ordered by when they are due
+    reserved: VecDeque<Reserved>,
+    /// Time the latest request was admitted at, which entries may not show once merged
+    last_admitted: Option<Instant>,
+}
+
+/// Requests booked through [`SlidingWindowCounter::reserve`] that are not logged yet.
+struct Reserved {
+    /// Instant the requests may run at
+    due: Instant,
+    /// Instant the requests are logged at once due
+    at: Instant,
+    count: u32,
+    /// Set when the reservation is dropped before it is fulfilled
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Most entries preallocated for a new counter, so huge limits do not reserve memory up front.
+const MAX_PREALLOCATED_ENTRIES: usize = 1024;
+
+/// Requests counted at the same instant.
+///
+/// Every batch of requests recorded together gets the next sequence number, and an entry stands
+/// for the consecutive batches `first..=last`, so single batches can be found again for refunds.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    at: Instant,
+    count: u32,
+    first: u64,
+    last: u64,
+}
+
+impl RequestLog {
+    /// Creates an empty log with room for `capacity` entries.
+    fn with_capacity(capacity: usize) -> Self {
+        RequestLog {
+            entries: VecDeque::with_capacity(capacity),
+            base_capacity: capacity,
+            ..RequestLog::default()
+        }
+    }
+
+    /// Records `n` requests made at `now`, keeping at most `max_entries` entries, and returns the
+    /// sequence number of the batch.
+    ///
+    /// The latest entry absorbs the requests if it was recorded at or after `merge_since`.
+    fn record(
+        &mut self,
+        now: Instant,
+        n: u32,
+        max_entries: Option<usize>,
+        merge_since: Option<Instant>,
+    ) -> u64 {
+        self.recorded += 1;
+        let seq = self.recorded;
+        if n == 0 {
+            return seq;
+        }
+        self.total += n as u64;
+
+        if let Some(last) = self.entries.back_mut() {
+            if last.at == now || merge_since.is_some_and(|since| last.at >= since) {
+                // The merged requests count as made now, so none of them expires too early
+                last.at = now;
+                last.count += n;
+                last.last = seq;
+                return seq;
+            }
+        }
+        self.entries.push_back(Entry {
+            at: now,
+            count: n,
+            first: seq,
+            last: seq,
+        });
+        self.cap(max_entries);
+        seq
+    }
+
+    /// Inserts `n` requests made at `at` among the logged requests, keeping at most
+    /// `max_entries` entries.
+    ///
+    /// The requests get no sequence number of their own, so they are never found as a batch.
+    /// The entry logged before them absorbs them if it was recorded at or after `merge_since`.
+    fn insert(
+        &mut self,
+        at: Instant,
+        n: u32,
+        max_entries: Option<usize>,
+        merge_since: Option<Instant>,
+    ) {
+        if n == 0 {
+            return;
+        }
+        self.total += n as u64;
+
+        let index = self.entries.partition_point(|entry| entry.at <= at);
+        if let Some(previous) = index.checked_sub(1).and_then(|i| self.entries.get_mut(i)) {
+            if previous.at == at || merge_since.is_some_and(|since| previous.at >= since) {
+                // The merged requests count as made at the later time, as when recording
+                previous.at = at;
+                previous.count += n;
+                return;
+            }
+        }
+
+        // Sharing the first sequence number of the next entry keeps the entries ordered by it,
+        // while batches are still found in the next entry, which comes later
+        let first = self
+            .entries
+            .get(index)
+            .map_or(self.recorded + 1, |next| next.first);
+        self.entries.insert(
+            index,
+            Entry {
+                at,
+                count: n,
+                first,
+                last: first - 1,
+            },
+        );
+        self.cap(max_entries);
+    }
+
+    /// Folds the oldest entries together until at most `max_entries` are left.
+    fn cap(&mut self, max_entries: Option<usize>) {
+        let Some(max) = max_entries else {
+            return;
+        };
+        while self.entries.len() > max.max(1) {
+            // Fold the oldest entry into the next one. Its requests now expire later than
+            // they should, so the approximation only ever errs on the side of denying.
+            let Some(oldest) = self.entries.pop_front() else {
+                break;
+            };
+            if let Some(next) = self.entries.front_mut() {
+                next.count += oldest.count;
+                next.first = oldest.first;
+            }
+        }
+    }
+
+    /// Removes the requests that are at least `lifetime` old at `now`.
+    ///
+    /// This is called on every decision, so only requests within the current window are
+    /// counted.
+    fn evict_expired(&mut self, now: Instant, lifetime: Duration) {
+        self.settle_reserved(now);
+
+        // Entries are ordered by time, so a burst of expired entries after a quiet period is
+        // found with a binary search and dropped at once instead of popped one by one
+        let expired = self
+            .entries
+            .partition_point(|entry| now.duration_since(entry.at) >= lifetime);
+        if expired > 0 {
+            let evicted: u64 = self
+                .entries
+                .range(..expired)
+                .map(|entry| entry.count as u64)
+                .sum();
+            self.total -= evicted;
+            self.entries.drain(..expired);
+        }
+        self.release_unused(now, lifetime);
+    }
+
+    /// Drops cancelled reservations and logs the requests of the reservations due at `now`.
+    fn settle_reserved(&mut self, now: Instant) {
+        self.reserved
+            .retain(|reserved| !reserved.cancelled.load(Ordering::Relaxed));
+        while self.reserved.front().is_some_and(|next| next.due <= now) {
+            let Some(reserved) = self.reserved.pop_front() else {
+                break;
+            };
+            let at = self
+                .entries
+                .back()
+                .map_or(reserved.at, |latest| reserved.at.max(latest.at));
+            self.record(at, reserved.count, None, None);
+            self.last_admitted = self.last_admitted.max(Some(reserved.due));
+        }
+    }
+
+    /// Returns the logged requests followed by the reserved ones, as their time and count.
+    fn counted(&self) -> impl Iterator<Item = (Instant, u32)> + '_ {
+        let logged = self.entries.iter().map(|entry| (entry.at, entry.count));
+        let reserved = self
+            .reserved
+            .iter()
+            .map(|reserved| (reserved.at, reserved.count));
+        logged.chain(reserved)
+    }
+
+    /// Returns the number of logged and reserved requests.
+    fn counted_total(&self) -> u64 {
+        let reserved: u64 = self
+            .reserved
+            .iter()
+            .map(|reserved| reserved.count as u64)
+            .sum();
+        self.total + reserved
+    }
+
+    /// Gives back memory left over from a spike once the log has used less than a quarter of
+    /// its capacity for `lifetime`, so it does not shrink and grow again with every burst.
+    fn release_unused(&mut self, now: Instant, lifetime: Duration) {
+        let capacity = self.entries.capacity();
+        if capacity <= self.base_capacity || self.entries.len() * 4 >= capacity {
+            self.underused_since = None;
+            return;
+        }
+
+        let since = *self.underused_since.get_or_insert(now);
+        if now.duration_since(since) >= lifetime {
+            self.entries
+                .shrink_to((self.entries.len() * 2).max(self.base_capacity));
+            self.underused_since = None;
+        }
+    }
+
+    /// Removes `n` of the most recently recorded requests.
+    fn remove_latest(&mut self, mut n: u64) {
+        while n > 0 {
+            let Some(latest) = self.entries.back_mut() else {
+                break;
+            };
+
+            let removed = n.min(latest.count as u64);
+            // `removed` is at most `count`, so it fits into a u32
+            latest.count -= removed as u32;
+            self.total -= removed;
+            n -= removed;
+
+            if latest.count == 0 {
+                self.entries.pop_back();
+            } else {
+                // Which batches the removed requests belonged to is unknown, so none of the
+                // batches recorded so far can be found in this entry any more
+                latest.first = self.recorded + 1;
+                latest.last = self.recorded;
+            }
+        }
+    }
+
+    /// Removes the `n` requests of the batch `seq`, returning whether they were still logged.
+    fn remove_batch(&mut self, seq: u64, n: u32) -> bool {
+        let index = self.entries.partition_point(|entry| entry.first <= seq);
+        let Some(entry) = index.checked_sub(1).and_then(|i| self.entries.get_mut(i)) else {
+            return false;
+        };
+        // Batches that left the log, or whose requests were already removed, are not found
+        if seq > entry.last || entry.count < n || n == 0 {
+            return false;
+        }
+
+        entry.count -= n;
+        self.total -= n as u64;
+        if entry.count == 0 {
+            self.entries.remove(index - 1);
+        }
+        true
+    }
 }
 
 impl SlidingWindowCounter {
@@ -52,188 +547,3115 @@ impl SlidingWindowCounter {
     ///
     /// # Returns
     /// A new instance of `SlidingWindowCounter`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window_duration` is zero or too large, see [`SlidingWindowCounter::try_new`].
     pub fn new(limit: u32, window_duration: Duration) -> Self {
-        SlidingWindowCounter {
-            limit,
-            window_duration,
-            requests: Arc::new(Mutex::new(VecDeque::new())),
+        match SlidingWindowCounter::try_new(limit, window_duration) {
+            Ok(counter) => counter,
+            Err(err) => panic!("{}", err),
         }
     }
 
-    /// Attempts to consume a request from the rate limiter.
-    ///
-    /// If the current number of requests within the time window is less than the
-    /// limit, this method will allow the request and return `true`.
-    /// If the limit has been reached, it will return `false`.
-    ///
-    /// This function clears out any requests that have expired based on the
-    /// window duration.
-    ///
-    /// # Returns
-    /// - `true` if the request is allowed.
-    /// - `false` if the request is rate-limited.
-    pub async fn try_consume(&mut self) -> bool {
-        let now = Instant::now();
-        let request = self.requests.clone();
-        let mut requests = request.lock().await;
+    /// Creates a new `SlidingWindowCounter` with the specified request limit and window
+    /// duration, or fails if a window of that duration cannot be measured.
+    ///
+    /// A zero limit is valid and denies every request until it is raised with
+    /// [`SlidingWindowCounter::set_limit`].
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`LimitrError::InvalidConfig`] if `window_duration` is zero or longer than
+    /// `u64::MAX` nanoseconds.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::window::SlidingWindowCounter;
+    /// use std::time::Duration;
+    ///
+    /// assert!(SlidingWindowCounter::try_new(100, Duration::from_secs(60)).is_ok());
+    /// assert!(SlidingWindowCounter::try_new(100, Duration::MAX).is_err());
+    /// ```
+    pub fn try_new(limit: u32, window_duration: Duration) -> Result<Self, LimitrError> {
+        validate_window_duration(window_duration)?;
+        Ok(SlidingWindowCounter {
+            limit: AtomicU32::new(limit),
+            window_duration: AtomicU64::new(saturating_nanos(window_duration)),
+            max_entries: None,
+            sub_buckets: None,
+            origin: Instant::now(),
+            boundary: Boundary::default(),
+            decay: Decay::default(),
+            mode: SlidingWindowMode::default(),
+            min_gap: None,
+            coarse_clock: None,
+            soft_limit: None,
+            peak: AtomicU32::new(0),
+            requests: Mutex::new(RequestLog::with_capacity(
+                (limit as usize).min(MAX_PREALLOCATED_ENTRIES),
+            )),
+            drain: Arc::default(),
+        })
+    }
+
+    /// Creates a new `SlidingWindowCounter` with the specified request limit and window
+    /// duration, behind an `Arc` whose clones all use the same counter.
+    ///
+    /// All methods take `&self`, so the `Arc` is all that is needed to share the counter
+    /// between tasks. A counter configured through the `with_*` builders is shared the same way
+    /// by wrapping it in an `Arc` by hand.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::window::SlidingWindowCounter;
+    /// use std::time::Duration;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = SlidingWindowCounter::shared(1, Duration::from_secs(60));
+    /// let handle = counter.clone();
+    ///
+    /// assert_eq!(handle.try_consume().await, true);
+    /// assert_eq!(counter.try_consume().await, false);
+    /// # })
+    /// ```
+    pub fn shared(limit: u32, window_duration: Duration) -> Arc<Self> {
+        Arc::new(SlidingWindowCounter::new(limit, window_duration))
+    }
+
+    /// Creates a new `SlidingWindowCounter` admitting the amount of `quota` within any window
+    /// as long as the quota's period.
+    ///
+    /// The burst of the quota does not apply: the window never admits more than the amount in
+    /// any period, and admits all of it at once if it has been idle.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`LimitrError::InvalidConfig`] if the amount does not fit into a `u32` or the
+    /// period is not a valid window duration.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::window::SlidingWindowCounter;
+    /// use limitr::Quota;
+    ///
+    /// let limiter = SlidingWindowCounter::from_quota(Quota::per_minute(600)).unwrap();
+    /// assert_eq!(limiter.limit(), 600);
+    /// ```
+    pub fn from_quota(quota: Quota) -> Result<Self, LimitrError> {
+        let limit = u32::try_from(quota.amount())
+            .map_err(|_| LimitrError::InvalidConfig(InvalidConfigReason::AmountTooLarge))?;
+        SlidingWindowCounter::try_new(limit, quota.period())
+    }
+
+    /// Caps the number of timestamps stored by the counter.
+    ///
+    /// By default every admitted request is stored, so memory grows with the limit. With a cap,
+    /// the oldest stored timestamps are merged once the cap is exceeded and their requests are
+    /// counted as if they happened at the later timestamp. Memory stays bounded by `max_entries`,
+    /// at the cost of some requests staying in the window longer than they should. The counter
+    /// may therefore deny slightly more than the exact algorithm, but never admits more.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    ///
+    /// // Allow a million requests per minute while storing at most 1024 timestamps
+    /// let limiter = SlidingWindowCounter::new(1_000_000, Duration::from_secs(60))
+    ///     .with_max_entries(1024);
+    /// ```
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self.size_log();
+        self
+    }
+
+    /// Divides the window into `buckets` sub-buckets and stores one count per sub-bucket
+    /// instead of one timestamp per request.
+    ///
+    /// Memory then stays at `buckets + 1` entries no matter the limit, e.g. for limits of
+    /// millions of requests per window. Requests within a sub-bucket count as if they were all
+    /// made at the latest of them, so they may stay in the window for up to one sub-bucket
+    /// longer than they should. The counter therefore never admits more than the limit within
+    /// any window, but may deny up to one sub-bucket's worth of requests that the exact
+    /// algorithm admits. More sub-buckets make the approximation tighter. Zero is treated as
+    /// one sub-bucket.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    ///
+    /// // A million requests per minute, counted in one-second sub-buckets
+    /// let limiter = SlidingWindowCounter::new(1_000_000, Duration::from_secs(60))
+    ///     .with_sub_buckets(60);
+    /// ```
+    pub fn with_sub_buckets(mut self, buckets: u32) -> Self {
+        self.sub_buckets = Some(buckets.max(1));
+        self.size_log();
+        self
+    }
+
+    /// Sets whether a request made exactly one window duration ago still counts.
+    ///
+    /// By default it does, see [`Boundary`]. All decisions and wait times, such as
+    /// [`SlidingWindowCounter::next_available_in`], follow the chosen boundary.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::{Boundary, SlidingWindowCounter};
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(1, Duration::from_secs(1))
+    ///     .with_boundary(Boundary::Exclusive);
+    ///
+    /// assert!(limiter.try_consume().await);
+    /// assert!(limiter.next_available_in().await.unwrap() <= Duration::from_secs(1));
+    /// # })
+    /// ```
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    /// Sets how much requests count towards the limit as they age.
+    ///
+    /// By default every request counts fully until it leaves the window, see [`Decay`]. With
+    /// sub-buckets, requests age from the latest request of their sub-bucket, like they leave
+    /// the window.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::{self, Duration};
+    /// use limitr::window::{Decay, SlidingWindowCounter};
+    /// # tokio_test::block_on(async {
+    /// # time::pause();
+    /// let limiter =
+    ///     SlidingWindowCounter::new(10, Duration::from_secs(10)).with_decay(Decay::Linear);
+    /// assert!(limiter.try_consume_n(10).await);
+    ///
+    /// // Halfway through the window the burst only counts half
+    /// time::advance(Duration::from_secs(5)).await;
+    /// assert!(limiter.would_allow_n(5).await);
+    /// assert!(!limiter.would_allow_n(6).await);
+    /// # })
+    /// ```
+    pub fn with_decay(mut self, decay: Decay) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    /// Sets whether the window is computed exactly from a log of the requests or approximated
+    /// from two counts, see [`SlidingWindowMode`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::{SlidingWindowCounter, SlidingWindowMode};
+    /// # tokio_test::block_on(async {
+    /// // Ten million requests per minute in constant memory
+    /// let limiter = SlidingWindowCounter::new(10_000_000, Duration::from_secs(60))
+    ///     .with_mode(SlidingWindowMode::Approx);
+    ///
+    /// assert!(limiter.try_consume_n(1_000).await);
+    /// assert_eq!(limiter.remaining().await, 9_999_000);
+    /// # })
+    /// ```
+    pub fn with_mode(mut self, mode: SlidingWindowMode) -> Self {
+        self.mode = mode;
+        self.size_log();
+        self
+    }
+
+    /// Sizes the memory preallocated for the log to the most entries it can hold, which the log
+    /// never shrinks below.
+    fn size_log(&mut self) {
+        let most = match (self.mode, self.sub_buckets) {
+            // One count for the current fixed window and one for the previous
+            (SlidingWindowMode::Approx, _) => 2,
+            // A request exactly one window old may still count, one sub-bucket beyond
+            (SlidingWindowMode::Log, Some(buckets)) => buckets as usize + 1,
+            (SlidingWindowMode::Log, None) => self.limit() as usize,
+        };
+        // The log briefly holds one entry above the cap before folding
+        let most = self
+            .max_entries
+            .map_or(most, |max_entries| most.min(max_entries.max(1) + 1));
+
+        let capacity = most
+            .min(self.limit() as usize)
+            .min(MAX_PREALLOCATED_ENTRIES);
+        let requests = self.requests.get_mut();
+        requests.base_capacity = capacity;
+        requests.entries.shrink_to(capacity);
+    }
+
+    /// Additionally requires at least `min_gap` between two admitted requests, e.g. at most 100
+    /// requests per minute and no two of them within 200ms.
+    ///
+    /// Both constraints are checked under the same lock and a request is only recorded if it
+    /// passes both, so a request denied by either one is not counted by the other. Wait times
+    /// such as [`SlidingWindowCounter::next_available_in`] are the longer of the two waits.
+    /// Requests admitted at once through `try_consume_n` count as one request for the gap.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::{self, Duration};
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// # time::pause();
+    /// let limiter = SlidingWindowCounter::new(100, Duration::from_secs(60))
+    ///     .with_min_gap(Duration::from_millis(200));
+    ///
+    /// assert!(limiter.try_consume().await);
+    /// assert!(!limiter.try_consume().await);
+    /// assert_eq!(limiter.next_available_in().await, Some(Duration::from_millis(200)));
+    ///
+    /// time::advance(Duration::from_millis(200)).await;
+    /// assert!(limiter.try_consume().await);
+    /// # })
+    /// ```
+    pub fn with_min_gap(mut self, min_gap: Duration) -> Self {
+        self.min_gap = Some(min_gap);
+        self
+    }
+
+    /// Sets a soft limit of `requests` in the window, beyond which requests are still admitted
+    /// but reported as [`Decision::AllowedSoftExceeded`] by [`SlidingWindowCounter::check`].
+    ///
+    /// This lets clients be warned that they are approaching the limit before they are cut off.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(10, Duration::from_secs(60)).with_soft_limit(8);
+    ///
+    /// assert!(!limiter.check(8).await.is_soft_exceeded());
+    /// let decision = limiter.check(1).await;
+    /// assert!(decision.is_allowed());
+    /// assert!(decision.is_soft_exceeded());
+    /// # })
+    /// ```
+    pub fn with_soft_limit(mut self, requests: u32) -> Self {
+        self.soft_limit = Some(requests);
+        self
+    }
+
+    /// Returns the soft limit of requests in the window, if one is set.
+    pub fn soft_limit(&self) -> Option<u32> {
+        self.soft_limit
+    }
+
+    /// Reads the time from a [`CoarseClock`] refreshed every `resolution`, instead of reading
+    /// the current instant for every request.
+    ///
+    /// This saves a clock read per request on hot paths at the price of timestamping requests
+    /// up to one resolution early, so they also leave the window up to one resolution early.
+    ///
+    /// The clock is refreshed by a task on the current tokio runtime, see [`CoarseClock`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use limitr::window::SlidingWindowCounter;
+    /// use std::time::Duration;
+    ///
+    /// # tokio_test::block_on(async {
+    /// let counter = SlidingWindowCounter::new(100_000, Duration::from_secs(1))
+    ///     .with_coarse_clock(Duration::from_millis(1));
+    /// assert_eq!(counter.try_consume().await, true);
+    /// # })
+    /// ```
+    pub fn with_coarse_clock(mut self, resolution: Duration) -> Self {
+        self.coarse_clock = Some(CoarseClock::new(resolution));
+        self
+    }
+
+    /// Returns the coarse clock the counter reads its time from, if one is set.
+    pub fn coarse_clock(&self) -> Option<&CoarseClock> {
+        self.coarse_clock.as_ref()
+    }
+
+    /// Returns the current instant, from the coarse clock if one is set.
+    fn now(&self) -> Instant {
+        self.coarse_clock
+            .as_ref()
+            .map_or_else(Instant::now, CoarseClock::now)
+    }
+
+    /// Changes the maximum number of requests allowed within the window.
+    ///
+    /// The new limit applies immediately. Raising it allows further requests right away,
+    /// lowering it below the number of requests already in the window denies everything until
+    /// enough of them have left the window.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(1, Duration::from_secs(60));
+    /// assert!(limiter.try_consume().await);
+    /// assert!(!limiter.try_consume().await);
+    ///
+    /// limiter.set_limit(2).await;
+    /// assert!(limiter.try_consume().await);
+    /// # })
+    /// ```
+    pub async fn set_limit(&self, limit: u32) {
+        let _requests = self.requests.lock().await;
+        self.limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// Changes the duration of the sliding window.
+    ///
+    /// Stored requests are re-evaluated against the new duration right away: shrinking the
+    /// window evicts the requests that are now older than it, widening it keeps counting the
+    /// stored requests for longer. Requests evicted before the window was widened stay evicted.
+    /// Sub-buckets keep their number and scale with the window.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(1, Duration::from_secs(60));
+    /// assert!(limiter.try_consume().await);
+    ///
+    /// limiter.set_window_duration(Duration::from_secs(10)).await.unwrap();
+    /// assert!(limiter.next_available_in().await.unwrap() <= Duration::from_secs(11));
+    /// # })
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LimitrError::InvalidConfig`] if `window_duration` is zero or too large, like
+    /// [`SlidingWindowCounter::try_new`]. The current configuration is left unchanged in that
+    /// case.
+    pub async fn set_window_duration(&self, window_duration: Duration) -> Result<(), LimitrError> {
+        validate_window_duration(window_duration)?;
+
+        let mut requests = self.requests.lock().await;
+        self.window_duration
+            .store(saturating_nanos(window_duration), Ordering::Relaxed);
+        requests.evict_expired(self.now(), self.lifetime());
+        Ok(())
+    }
+
+    /// Returns the maximum number of requests allowed within the window.
+    pub fn limit(&self) -> u32 {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    /// Returns the duration of the sliding window.
+    pub fn window_duration(&self) -> Duration {
+        Duration::from_nanos(self.window_duration.load(Ordering::Relaxed))
+    }
+
+    /// Returns the number of requests currently counted in the window.
+    ///
+    /// With [`SlidingWindowMode::Approx`] this is the estimated number, rounded up.
+    ///
+    /// Requests that have left the window are evicted first, so the count is up to date even if
+    /// no request was made for a while. Nothing is consumed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(10, Duration::from_secs(60));
+    /// assert!(limiter.try_consume_n(3).await);
+    /// assert_eq!(limiter.current_usage().await, 3);
+    /// # })
+    /// ```
+    pub async fn current_usage(&self) -> u32 {
+        let now = self.now();
+        let mut requests = self.requests.lock().await;
+        self.usage_at(&mut requests, now)
+    }
+
+    /// Clears the requests that left the window by `now` and returns the usage of the rest.
+    fn usage_at(&self, requests: &mut RequestLog, now: Instant) -> u32 {
+        requests.evict_expired(now, self.lifetime());
+        let usage = match self.mode {
+            SlidingWindowMode::Log => requests.total,
+            SlidingWindowMode::Approx => self.used(requests, now),
+        };
+        u32::try_from(usage).unwrap_or(u32::MAX)
+    }
+
+    /// Returns the usage at `now` like [`SlidingWindowCounter::usage_at`], but without
+    /// evicting or settling anything, so that merely inspecting the counter leaves it as is.
+    fn peek_usage(&self, requests: &RequestLog, now: Instant) -> u32 {
+        let usage = match self.mode {
+            SlidingWindowMode::Log => {
+                let lifetime = self.lifetime();
+                let due = requests
+                    .reserved
+                    .iter()
+                    .filter(|reserved| {
+                        reserved.due <= now && !reserved.cancelled.load(Ordering::Relaxed)
+                    })
+                    .map(|reserved| (reserved.at, reserved.count));
+                requests
+                    .entries
+                    .iter()
+                    .map(|entry| (entry.at, entry.count))
+                    .chain(due)
+                    .filter(|&(at, _)| now.saturating_duration_since(at) < lifetime)
+                    .map(|(_, count)| count as u64)
+                    .sum()
+            }
+            SlidingWindowMode::Approx => self.used(requests, now),
+        };
+        u32::try_from(usage).unwrap_or(u32::MAX)
+    }
+
+    /// Returns how many more requests the window currently admits, without consuming any.
+    ///
+    /// This is zero while the usage is at or above the limit, e.g. after the limit was lowered.
+    /// With a [`Decay`], the usage is the weighted sum of the requests in the window rather than
+    /// their number.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(10, Duration::from_secs(60));
+    /// assert_eq!(limiter.remaining().await, 10);
+    ///
+    /// assert!(limiter.try_consume().await);
+    /// assert_eq!(limiter.remaining().await, 9);
+    /// # })
+    /// ```
+    pub async fn remaining(&self) -> u32 {
+        let now = self.now();
+        let mut requests = self.requests.lock().await;
+        requests.evict_expired(now, self.lifetime());
+
+        let used = self.used(&requests, now);
+        u32::try_from((self.limit() as u64).saturating_sub(used)).unwrap_or(u32::MAX)
+    }
+
+    /// Checks whether a single request would currently be admitted, without consuming it.
+    ///
+    /// Expired requests are cleared out, but nothing is recorded, e.g. to pre-check a request
+    /// before doing expensive work for it. The answer agrees with a `try_consume` issued
+    /// immediately afterwards, unless other requests are admitted in between.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(1, Duration::from_secs(60));
+    ///
+    /// assert!(limiter.would_allow().await);
+    /// assert!(limiter.try_consume().await);
+    /// assert!(!limiter.would_allow().await);
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn would_allow(&self) -> bool {
+        self.would_allow_n(1).await
+    }
+
+    /// Checks whether `n` requests would currently be admitted at once, without consuming them.
+    ///
+    /// `n` is counted like with [`SlidingWindowCounter::try_consume_n`]. See
+    /// [`SlidingWindowCounter::would_allow`] for details.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn would_allow_n(&self, n: u32) -> bool {
+        let now = self.now();
+        let mut requests = self.requests.lock().await;
+        // Clear out requests the same way a consume would
+        let now = requests
+            .entries
+            .back()
+            .map_or(now, |latest| now.max(latest.at));
+        requests.evict_expired(now, self.lifetime());
+
+        !self.drain.is_draining()
+            && self.used(&requests, now).saturating_add(n as u64) <= self.limit() as u64
+            && self.gap_wait(&requests, now).is_zero()
+    }
+
+    /// Attempts to consume a request from the rate limiter.
+    ///
+    /// If the current number of requests within the time window is less than the
+    /// limit, this method will allow the request and return `true`.
+    /// If the limit has been reached, it will return `false`.
+    ///
+    /// This function clears out any requests that have expired based on the
+    /// window duration.
+    ///
+    /// # Returns
+    /// - `true` if the request is allowed.
+    /// - `false` if the request is rate-limited.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume(&self) -> bool {
+        self.try_consume_n(1).await
+    }
+
+    /// Attempts to consume `n` requests from the rate limiter at once.
+    ///
+    /// The requests are only recorded if all `n` fit into the current window,
+    /// otherwise nothing is recorded.
+    ///
+    /// This also serves as a weighted consume: `n` is stored alongside the timestamp as a single
+    /// entry, so an expensive request can count as several units and leaves the window again
+    /// as a whole, freeing all of its weight at once.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(10, Duration::from_secs(1));
+    ///
+    /// // An expensive search counts as 8 units, a cheap lookup as 1
+    /// assert!(limiter.try_consume_n(8).await);
+    /// assert!(limiter.try_consume_n(1).await);
+    /// assert!(!limiter.try_consume_n(8).await);
+    /// # })
+    /// ```
+    ///
+    /// # Returns
+    /// - `true` if the requests are allowed.
+    /// - `false` if the requests are rate-limited.
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume_n(&self, n: u32) -> bool {
+        self.decide(n as u64, self.now()).await.is_allowed()
+    }
+
+    /// Attempts to consume `n` requests at the instant `now` instead of the current one.
+    ///
+    /// The requests are recorded exactly like with [`SlidingWindowCounter::try_consume_n`], e.g.
+    /// to test deterministically or to decide a batch of requests against several limiters at
+    /// the same instant. The log never moves backwards, so an instant before the latest recorded
+    /// request is decided at the time of that request.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::{Duration, Instant};
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(1, Duration::from_secs(10));
+    /// let now = Instant::now();
+    ///
+    /// assert!(limiter.try_consume_at(now, 1).await);
+    /// assert!(!limiter.try_consume_at(now + Duration::from_secs(10), 1).await);
+    /// assert!(limiter.try_consume_at(now + Duration::from_millis(10_001), 1).await);
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn try_consume_at(&self, now: Instant, n: u32) -> bool {
+        self.decide(n as u64, now).await.is_allowed()
+    }
+
+    /// Attempts to consume `n` requests, reporting the state of the window along with the
+    /// outcome.
+    ///
+    /// Requests are recorded exactly like with [`SlidingWindowCounter::try_consume_n`]. The
+    /// returned [`Decision`] resets once every stored request has left the window. A denied
+    /// request can be retried once enough of the oldest requests have left it, requests for more
+    /// than the limit are reported as never admissible.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+    ///
+    /// assert_eq!(limiter.check(3).await.remaining(), 2);
+    ///
+    /// let decision = limiter.check(3).await;
+    /// assert!(!decision.is_allowed());
+    /// assert!(decision.retry_after().unwrap() <= Duration::from_secs(11));
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn check(&self, n: u32) -> Decision {
+        self.decide(n as u64, self.now()).await
+    }
+
+    /// Waits until a request fits into the window and records it.
+    ///
+    /// Unlike `try_consume`, a request that does not fit right away is delayed until enough of
+    /// the oldest requests have left the window. Waiting requests are not queued, so a request
+    /// made in the meantime may take the freed slot first, see
+    /// [`SlidingWindowCounter::reserve`] for a fair queue.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe, e.g. when used as a branch of `tokio::select!` or when the
+    /// client of a request handler disconnects. The request is recorded in the same step that
+    /// finds room for it, right before the call returns, so dropping the future while it waits
+    /// leaves no trace in the window.
+    ///
+    /// # Errors
+    ///
+    /// - [`LimitrError::AmountExceedsCapacity`] without waiting if the limit is zero.
+    /// - [`LimitrError::ShuttingDown`] once [`SlidingWindowCounter::begin_drain`] was called,
+    ///   also for calls that were already waiting.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::{self, Duration};
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// # time::pause();
+    /// let limiter = SlidingWindowCounter::new(1, Duration::from_secs(1));
+    /// limiter.acquire().await.unwrap();
+    ///
+    /// // Gives up on the second request, which is not counted
+    /// tokio::select! {
+    ///     _ = limiter.acquire() => unreachable!(),
+    ///     _ = time::sleep(Duration::from_millis(100)) => {}
+    /// }
+    /// assert_eq!(limiter.current_usage().await, 1);
+    /// # })
+    /// ```
+    pub async fn acquire(&self) -> Result<(), LimitrError> {
+        self.acquire_n(1).await
+    }
+
+    /// Waits until `n` requests fit into the window at once and records them, see
+    /// [`SlidingWindowCounter::acquire`].
+    ///
+    /// # Errors
+    ///
+    /// Fails with the same errors as [`SlidingWindowCounter::acquire`], and with
+    /// [`LimitrError::AmountExceedsCapacity`] without waiting if `n` exceeds the limit.
+    pub async fn acquire_n(&self, n: u32) -> Result<(), LimitrError> {
+        let _waiting = Waiting::new(&self.drain);
+        loop {
+            // Listen before checking, so a drain beginning in between still wakes this call
+            let drain_began = self.drain.changed.notified();
+            tokio::pin!(drain_began);
+            drain_began.as_mut().enable();
+            if self.drain.is_draining() {
+                return Err(LimitrError::ShuttingDown);
+            }
+
+            let decision = self.check(n).await;
+            if decision.is_allowed() {
+                return Ok(());
+            }
+
+            match decision.retry_after() {
+                Some(wait) => {
+                    trace!("Waiting {:?} for {} requests to fit", wait, n);
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {}
+                        _ = drain_began => {}
+                    }
+                }
+                // Denied because a drain began after the check above
+                None if self.drain.is_draining() => return Err(LimitrError::ShuttingDown),
+                None => {
+                    trace!("{} requests never fit into the window", n);
+                    return Err(LimitrError::AmountExceedsCapacity {
+                        requested: n as u64,
+                        capacity: decision.limit(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Stops admitting requests, e.g. while shutting down a service.
+    ///
+    /// From now on `try_consume` and `check` deny every request as never admissible, and
+    /// [`SlidingWindowCounter::acquire`] and [`SlidingWindowCounter::reserve`] fail with
+    /// [`LimitrError::ShuttingDown`]. `acquire` calls that are already waiting fail right away
+    /// instead of waiting for room. Reservations made before the drain stay valid. The drain
+    /// cannot be undone.
+    ///
+    /// Drain mode is specific to `SlidingWindowCounter`, the only limiter that hands out
+    /// [`Reservation`]s. The other limiters have no drain mode. To drain a service limited by
+    /// several limiters, stop calling them and wait for their permits yourself.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// use limitr::LimitrError;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(10, Duration::from_secs(1));
+    ///
+    /// limiter.begin_drain().await;
+    /// assert!(!limiter.try_consume().await);
+    /// assert_eq!(limiter.acquire().await, Err(LimitrError::ShuttingDown));
+    /// limiter.drained().await;
+    /// # })
+    /// ```
+    pub async fn begin_drain(&self) {
+        // Taking the lock orders the drain after decisions already in progress
+        let _requests = self.requests.lock().await;
+        self.drain.draining.store(true, Ordering::Relaxed);
+        self.drain.changed.notify_waiters();
+        trace!("Draining, no further requests are admitted");
+    }
+
+    /// Returns whether [`SlidingWindowCounter::begin_drain`] was called.
+    pub fn is_draining(&self) -> bool {
+        self.drain.is_draining()
+    }
+
+    /// Waits until the counter is drained: a drain has begun, no `acquire` call is in flight
+    /// anymore and every reservation is either due or dropped.
+    ///
+    /// Only the waiters and [`Reservation`]s of this counter are tracked. Work admitted by
+    /// `try_consume` or `acquire` holds no permit, so it may still be running once the counter
+    /// is drained.
+    pub async fn drained(&self) {
+        loop {
+            // Listen before checking, so changes in between are not missed
+            let changed = self.drain.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+
+            let next_due = {
+                let now = self.now();
+                let mut requests = self.requests.lock().await;
+                requests.evict_expired(now, self.lifetime());
+                let idle = self.drain.waiting.load(Ordering::Relaxed) == 0;
+                if self.drain.is_draining() && idle && requests.reserved.is_empty() {
+                    return;
+                }
+                requests.reserved.front().map(|next| next.due)
+            };
+
+            match next_due {
+                Some(due) => {
+                    tokio::select! {
+                        _ = sleep_until(due) => {}
+                        _ = changed => {}
+                    }
+                }
+                None => changed.await,
+            }
+        }
+    }
+
+    /// Attempts to consume `n` requests like [`SlidingWindowCounter::try_consume_n`], returning
+    /// an id to [`refund_entry`](SlidingWindowCounter::refund_entry) them with if they were admitted.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(2, Duration::from_secs(10));
+    ///
+    /// let id = limiter.consume_tracked(2).await.unwrap();
+    /// assert!(limiter.consume_tracked(1).await.is_none());
+    ///
+    /// // The request was aborted before doing any work
+    /// assert!(limiter.refund_entry(id).await);
+    /// assert_eq!(limiter.remaining().await, 2);
+    /// # })
+    /// ```
+    #[must_use = "ignoring this means the rate limit isn't enforced"]
+    pub async fn consume_tracked(&self, n: u32) -> Option<EntryId> {
+        self.decide_tracked(n as u64, self.now()).await.1
+    }
+
+    /// Gives back the requests admitted by [`SlidingWindowCounter::consume_tracked`] under `id`.
+    ///
+    /// Exactly those requests are removed, no matter how many requests were recorded since.
+    /// Returns `false` without changing anything if they have already left the window, or if
+    /// [`RateLimiter::refund`] gave back any request recorded together with them: it takes
+    /// the latest requests without knowing which batches they belong to, so the batches
+    /// sharing their time with them can no longer be told apart.
+    pub async fn refund_entry(&self, id: EntryId) -> bool {
+        let mut requests = self.requests.lock().await;
+        requests.evict_expired(self.now(), self.lifetime());
+        requests.remove_batch(id.seq, id.n)
+    }
+
+    /// Books a slot for one request at the earliest instant the window has room for it.
+    ///
+    /// If the window has room, the reservation is due right away. Otherwise it is due once
+    /// enough requests have left the window, and never before an earlier reservation, so
+    /// reservations are served in the order they were made. The reserved request counts towards
+    /// the limit from now on, so requests that would take its slot are denied, but it is only
+    /// logged once due, e.g. by [`SlidingWindowCounter::current_usage`].
+    ///
+    /// # Errors
+    ///
+    /// - [`LimitrError::AmountExceedsCapacity`] if the limit is zero.
+    /// - [`LimitrError::ShuttingDown`] once [`SlidingWindowCounter::begin_drain`] was called.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::{self, Duration, Instant};
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// # time::pause();
+    /// let limiter = SlidingWindowCounter::new(1, Duration::from_secs(1));
+    /// let start = Instant::now();
+    ///
+    /// let first = limiter.reserve().await.unwrap();
+    /// let second = limiter.reserve().await.unwrap();
+    /// assert!(first.due() < second.due());
+    ///
+    /// first.wait().await;
+    /// second.wait().await;
+    /// assert!(start.elapsed() >= Duration::from_secs(1));
+    /// # })
+    /// ```
+    pub async fn reserve(&self) -> Result<Reservation, LimitrError> {
+        self.reserve_n(1).await
+    }
+
+    /// Books a slot for `n` requests at once, see [`SlidingWindowCounter::reserve`].
+    ///
+    /// # Errors
+    ///
+    /// - [`LimitrError::AmountExceedsCapacity`] if `n` exceeds the limit.
+    /// - [`LimitrError::ShuttingDown`] once [`SlidingWindowCounter::begin_drain`] was called.
+    pub async fn reserve_n(&self, n: u32) -> Result<Reservation, LimitrError> {
+        let now = self.now();
+        let mut requests = self.requests.lock().await;
+        if self.drain.is_draining() {
+            return Err(LimitrError::ShuttingDown);
+        }
+        requests.evict_expired(now, self.lifetime());
+
+        let limit = self.limit();
+        let wait = self.available_in(&requests, now, n as u64).ok_or(
+            LimitrError::AmountExceedsCapacity {
+                requested: n as u64,
+                capacity: limit as u64,
+            },
+        )?;
+        // Never jump ahead of an earlier reservation
+        let due = requests
+            .reserved
+            .back()
+            .map_or(now + wait, |last| (now + wait).max(last.due));
+        trace!("Reserved {} requests in {:?}", n, due - now);
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        requests.reserved.push_back(Reserved {
+            due,
+            at: self.log_time(due),
+            count: n,
+            cancelled: Arc::clone(&cancelled),
+        });
+        Ok(Reservation {
+            due,
+            cancelled: Some(cancelled),
+            drain: Arc::clone(&self.drain),
+        })
+    }
+
+    async fn decide(&self, n: u64, now: Instant) -> Decision {
+        let (decision, _) = self.decide_tracked(n, now).await;
+        decision.flag_soft_limit(self.soft_limit.map(u64::from))
+    }
+
+    /// Decides on `n` requests at `now`, returning the id of the recorded batch if admitted.
+    async fn decide_tracked(&self, n: u64, now: Instant) -> (Decision, Option<EntryId>) {
+        let mut requests = self.requests.lock().await;
+        // Keep the log ordered by time, even if `now` was taken before a concurrent request
+        let now = requests
+            .entries
+            .back()
+            .map_or(now, |latest| now.max(latest.at));
+
+        // Remove old requests outside the window duration
+        requests.evict_expired(now, self.lifetime());
+
+        let limit = self.limit() as u64;
+        let draining = self.drain.is_draining();
+        let allowed = !draining
+            && self.used(&requests, now).saturating_add(n) <= limit
+            && self.gap_wait(&requests, now).is_zero();
+        let mut id = None;
+        if allowed {
+            // allow the requests if they fit under the limit, `n` is at most `limit` here
+            let n = n as u32;
+            let at = self.log_time(now);
+            let seq = requests.record(at, n, self.max_entries, self.sub_bucket_start(now));
+            requests.last_admitted = Some(now);
+            id = Some(EntryId { seq, n });
+            self.raise_peak(requests.total);
+        }
+
+        let reset_in = requests.entries.back().map_or(Duration::ZERO, |latest| {
+            self.expiry(latest.at).saturating_duration_since(now)
+        });
+
+        if allowed {
+            let decision = Decision::Allowed {
+                limit,
+                remaining: limit.saturating_sub(self.used(&requests, now)),
+                reset_in,
+            };
+            return (decision, id);
+        }
+
+        let decision = Decision::Denied {
+            limit,
+            remaining: limit.saturating_sub(self.used(&requests, now)),
+            reset_in,
+            retry_after: if draining {
+                None
+            } else {
+                self.available_in(&requests, now, n)
+            },
+        };
+        (decision, None)
+    }
+
+    /// Returns how long until `n` requests fit into the window of the already cleared
+    /// `requests` and are far enough from the latest request, or `None` if they never fit.
+    fn available_in(&self, requests: &RequestLog, now: Instant, n: u64) -> Option<Duration> {
+        let fits_in = self.fits_in(requests, now, n)?;
+        Some(fits_in.max(self.gap_wait(requests, now)))
+    }
+
+    /// Returns how long until another request is far enough from the latest admitted or
+    /// reserved one, which is zero without a minimum gap.
+    fn gap_wait(&self, requests: &RequestLog, now: Instant) -> Duration {
+        let Some(min_gap) = self.min_gap else {
+            return Duration::ZERO;
+        };
+        let reserved = requests.reserved.back().map(|reserved| reserved.due);
+        let Some(latest) = requests.last_admitted.max(reserved) else {
+            return Duration::ZERO;
+        };
+        latest
+            .checked_add(min_gap)
+            .map_or(Duration::MAX, |next| next.saturating_duration_since(now))
+    }
+
+    /// Returns how long until `n` requests fit into the window of the already cleared
+    /// `requests`, or `None` if they never do.
+    fn fits_in(&self, requests: &RequestLog, now: Instant, n: u64) -> Option<Duration> {
+        let limit = self.limit() as u64;
+        if n > limit {
+            return None;
+        }
+
+        let fits = |at: Instant| self.used(requests, at) + n <= limit;
+        if fits(now) {
+            return Some(Duration::ZERO);
+        }
+
+        // The requests fit once enough of the oldest requests have left the window
+        let mut total = requests.counted_total();
+        let cutoff = requests.counted().find_map(|(at, count)| {
+            total -= count as u64;
+            (total + n <= limit).then(|| self.expiry(at).saturating_duration_since(now))
+        })?;
+        if self.counts_fully() {
+            return Some(cutoff);
+        }
+
+        // Decayed or approximated requests only count less over time, so search for the
+        // earliest instant the requests fit, which is at the latest when enough requests have
+        // left the window
+        let (mut low, mut high) = (0, saturating_nanos(cutoff));
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if fits(now + Duration::from_nanos(mid)) {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        Some(Duration::from_nanos(low))
+    }
+
+    /// Returns how much of the limit the already cleared `requests` use at `now`, which is their
+    /// number unless they decay or are approximated.
+    ///
+    /// Weighted usage is rounded up, so fractions of a request are never admitted. Requests that
+    /// have left the window by `now` do not count, so this can look ahead in time.
+    fn used(&self, requests: &RequestLog, now: Instant) -> u64 {
+        if self.counts_fully() {
+            return requests.counted_total();
+        }
+
+        let window = self.window_duration();
+        let lifetime = self.lifetime();
+        let (mut total, mut weighted) = (0, 0.0);
+        for (at, count) in requests.counted() {
+            let age = now.saturating_duration_since(at);
+            if age < lifetime {
+                let weight = match self.mode {
+                    SlidingWindowMode::Log => self.decay.weight(age, window),
+                    // Entries are logged at the start of their fixed window, which counts fully
+                    // while current and by its overlap with the sliding window once previous
+                    SlidingWindowMode::Approx => {
+                        (2.0 - age.as_secs_f64() / window.as_secs_f64()).min(1.0)
+                    }
+                };
+                total += count as u64;
+                weighted += count as f64 * weight;
+            }
+        }
+        // Ignore rounding errors in the sum, which would otherwise round a whole usage up
+        ((weighted * (1.0 - 1e-12)).ceil() as u64).min(total)
+    }
+
+    /// Returns how long until the next request would be admitted, or `None` if no request is
+    /// ever admitted.
+    ///
+    /// This is zero while the window has room. Otherwise it is the time until enough of the
+    /// oldest requests have left the window, so after sleeping exactly that long the next
+    /// [`try_consume`](SlidingWindowCounter::try_consume) succeeds unless other requests got
+    /// there first.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(1, Duration::from_secs(10));
+    /// assert_eq!(limiter.next_available_in().await, Some(Duration::ZERO));
+    ///
+    /// limiter.try_consume().await;
+    /// assert!(limiter.next_available_in().await.unwrap() > Duration::from_secs(9));
+    /// # })
+    /// ```
+    pub async fn next_available_in(&self) -> Option<Duration> {
+        let now = self.now();
+        let mut requests = self.requests.lock().await;
+        requests.evict_expired(now, self.lifetime());
+
+        self.available_in(&requests, now, 1)
+    }
+
+    /// Returns the start of the sub-bucket containing `now`, if sub-buckets are enabled.
+    fn sub_bucket_start(&self, now: Instant) -> Option<Instant> {
+        Some(self.align(now, self.window_duration() / self.sub_buckets?))
+    }
+
+    /// Returns the start of the interval of length `width` containing `now`, with intervals
+    /// aligned to the creation of the counter.
+    fn align(&self, now: Instant, width: Duration) -> Instant {
+        let width = width.max(Duration::from_nanos(1)).as_nanos();
+        let into_interval = now.duration_since(self.origin).as_nanos() % width;
+        // The remainder is below the width of the interval, which is a `Duration`
+        now - Duration::from_nanos(into_interval as u64)
+    }
+
+    /// Returns the instant requests made at `at` are logged at, which is the start of their
+    /// fixed window when the window is approximated.
+    fn log_time(&self, at: Instant) -> Instant {
+        match self.mode {
+            SlidingWindowMode::Log => at,
+            SlidingWindowMode::Approx => self.align(at, self.window_duration()),
+        }
+    }
+
+    /// Returns whether every logged request counts fully until it leaves the window.
+    fn counts_fully(&self) -> bool {
+        self.mode == SlidingWindowMode::Log && self.decay == Decay::None
+    }
+
+    /// Returns how long a request counts towards the window.
+    fn lifetime(&self) -> Duration {
+        let window = self.window_duration();
+        if self.mode == SlidingWindowMode::Approx {
+            // A fixed window counts while current and while previous
+            return window.saturating_mul(2);
+        }
+        match self.boundary {
+            // Requests leave the window once they are strictly older than it
+            Boundary::Inclusive => window.saturating_add(Duration::from_nanos(1)),
+            Boundary::Exclusive => window,
+        }
+    }
+
+    /// Returns the instant at which a request made at `at` no longer counts towards the window.
+    fn expiry(&self, at: Instant) -> Instant {
+        at + self.lifetime()
+    }
+
+    /// Records requests made at the given wall-clock times, e.g. handed over by the previous
+    /// instance of a service during a rolling deploy.
+    ///
+    /// The requests were already admitted elsewhere, so they are recorded as they are, even if
+    /// that takes the window above its limit. Requests that have already left the window are
+    /// dropped, and times in the future count as now. Imported requests cannot be given back
+    /// through [`SlidingWindowCounter::refund_entry`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::time::SystemTime;
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let now = SystemTime::now();
+    /// let handed_over = [now - Duration::from_secs(2), now - Duration::from_secs(1), now];
+    ///
+    /// let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+    /// limiter.import(handed_over).await;
+    /// assert_eq!(limiter.remaining().await, 2);
+    /// # })
+    /// ```
+    pub async fn import(&self, entries: impl IntoIterator<Item = SystemTime>) {
+        let wall_now = SystemTime::now();
+        let now = Instant::now();
+        let mut ages: Vec<Duration> = entries
+            .into_iter()
+            .map(|at| wall_now.duration_since(at).unwrap_or(Duration::ZERO))
+            .collect();
+        // Oldest first, with requests made at the same time next to each other
+        ages.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut requests = self.requests.lock().await;
+        let lifetime = self.lifetime();
+        requests.evict_expired(now, lifetime);
+        for same_time in ages.chunk_by(|a, b| a == b) {
+            let age = same_time[0];
+            if age >= lifetime {
+                continue;
+            }
+            // Requests older than the clock count as made now, which errs on denying
+            let at = now.checked_sub(age).unwrap_or(now);
+            let count = u32::try_from(same_time.len()).unwrap_or(u32::MAX);
+            let log_at = self.log_time(at);
+            requests.insert(log_at, count, self.max_entries, self.sub_bucket_start(at));
+            requests.last_admitted = requests.last_admitted.max(Some(at));
+        }
+        self.raise_peak(requests.total);
+    }
+
+    /// Releases the memory of the request log that is not needed for the requests currently in
+    /// the window.
+    ///
+    /// Memory left over from a spike is released on its own once the log has used less than a
+    /// quarter of it for a whole window. This releases it right away instead, e.g. after a
+    /// known burst, at the cost of reallocating should the traffic pick up again.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::{self, Duration};
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// # time::pause();
+    /// let limiter = SlidingWindowCounter::new(10_000, Duration::from_secs(1));
+    /// for _ in 0..5_000 {
+    ///     assert!(limiter.try_consume().await);
+    ///     time::advance(Duration::from_micros(1)).await;
+    /// }
+    ///
+    /// time::advance(Duration::from_secs(1)).await;
+    /// limiter.shrink_to_fit().await;
+    /// # })
+    /// ```
+    pub async fn shrink_to_fit(&self) {
+        let mut requests = self.requests.lock().await;
+        requests.evict_expired(self.now(), self.lifetime());
+        requests.entries.shrink_to_fit();
+        requests.underused_since = None;
+    }
+
+    /// Records `usage` as the peak usage if it is the highest seen so far.
+    fn raise_peak(&self, usage: u64) {
+        let usage = u32::try_from(usage).unwrap_or(u32::MAX);
+        self.peak.fetch_max(usage, Ordering::Relaxed);
+    }
+
+    /// Returns the highest number of requests the window has held at once since the counter was
+    /// created, e.g. to chart how close a client came to its limit.
+    ///
+    /// The peak is not lowered when requests leave the window or are refunded.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::{self, Duration};
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// # time::pause();
+    /// let limiter = SlidingWindowCounter::new(10, Duration::from_secs(1));
+    /// assert!(limiter.try_consume_n(7).await);
+    ///
+    /// time::advance(Duration::from_secs(2)).await;
+    /// assert!(limiter.try_consume_n(2).await);
+    /// assert_eq!(limiter.current_usage().await, 2);
+    /// assert_eq!(limiter.peak_usage(), 7);
+    /// # })
+    /// ```
+    pub fn peak_usage(&self) -> u32 {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Returns the observed number of requests admitted per second over the trailing `over` period.
+    ///
+    /// The counter already stores every request within the window, so this is computed exactly
+    /// from the stored timestamps without any extra bookkeeping. Requests older than the window
+    /// are no longer stored, therefore `over` is capped at the window duration, and passing
+    /// [`window_duration`](SlidingWindowCounter::window_duration) reports the rate over the
+    /// whole window. With a
+    /// [`max_entries`](SlidingWindowCounter::with_max_entries) cap, merged requests count as
+    /// having happened at their later timestamp.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+    /// limiter.try_consume().await;
+    ///
+    /// println!("{} requests/s", limiter.observed_rate(Duration::from_secs(5)).await);
+    /// # })
+    /// ```
+    pub async fn observed_rate(&self, over: Duration) -> f64 {
+        let now = self.now();
+        let requests = self.requests.lock().await;
+        let over = over.min(self.window_duration());
+        if over.is_zero() {
+            return 0.0;
+        }
+
+        let admitted: u64 = requests
+            .entries
+            .iter()
+            .rev()
+            .take_while(|entry| now.duration_since(entry.at) < over)
+            .map(|entry| entry.count as u64)
+            .sum();
+
+        admitted as f64 / over.as_secs_f64()
+    }
+
+    /// Returns the ages of the requests currently counted in the window with the number of
+    /// requests of each age, oldest first, e.g. to show why a client is being throttled.
+    ///
+    /// Requests that have left the window are evicted first. There is one pair per logged
+    /// entry rather than per request, so the list stays short however many requests are
+    /// counted, and its counts add up to [`SlidingWindowCounter::current_usage`] in the default
+    /// [`SlidingWindowMode::Log`]. Requests merged through
+    /// [`max_entries`](SlidingWindowCounter::with_max_entries) or
+    /// [`sub_buckets`](SlidingWindowCounter::with_sub_buckets) report the age of the later
+    /// timestamp they were merged into, and with [`SlidingWindowMode::Approx`] requests report
+    /// the age of their fixed window. The lock is only held while the ages are copied.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::{self, Duration};
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// # time::pause();
+    /// let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+    /// assert!(limiter.try_consume().await);
+    /// time::advance(Duration::from_secs(3)).await;
+    /// assert!(limiter.try_consume_n(2).await);
+    ///
+    /// let ages = limiter.request_ages().await;
+    /// assert_eq!(ages, [(Duration::from_secs(3), 1), (Duration::ZERO, 2)]);
+    /// # })
+    /// ```
+    pub async fn request_ages(&self) -> Vec<(Duration, u32)> {
+        let now = self.now();
+        let mut requests = self.requests.lock().await;
+        requests.evict_expired(now, self.lifetime());
+
+        requests
+            .entries
+            .iter()
+            .map(|entry| (now.saturating_duration_since(entry.at), entry.count))
+            .collect()
+    }
+
+    /// Captures the configuration and the request log of the counter, e.g. to persist it across
+    /// a restart.
+    ///
+    /// `Instant`s are meaningless outside the process, so requests are stored by their age at
+    /// the wall-clock time the snapshot was taken.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::SlidingWindowCounter;
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+    /// assert!(limiter.try_consume_n(3).await);
+    ///
+    /// let checkpoint = serde_json::to_string(&limiter.snapshot().await).unwrap();
+    /// let restored = SlidingWindowCounter::from_snapshot(serde_json::from_str(&checkpoint).unwrap());
+    /// assert!(restored.try_consume_n(2).await);
+    /// assert!(!restored.try_consume().await);
+    /// # })
+    /// ```
+    #[cfg(feature = "serde")]
+    pub async fn snapshot(&self) -> SlidingWindowState {
+        let requests = self.requests.lock().await;
+        let now = Instant::now();
+        SlidingWindowState {
+            limit: self.limit(),
+            window_duration: self.window_duration(),
+            max_entries: self.max_entries,
+            sub_buckets: self.sub_buckets,
+            boundary: self.boundary,
+            decay: self.decay,
+            mode: self.mode,
+            min_gap: self.min_gap,
+            soft_limit: self.soft_limit,
+            taken_at: SystemTime::now(),
+            entries: requests
+                .entries
+                .iter()
+                .map(|entry| (now.duration_since(entry.at), entry.count))
+                .collect(),
+        }
+    }
+
+    /// Restores a counter from a [`SlidingWindowCounter::snapshot`].
+    ///
+    /// The wall-clock time passed since the snapshot was taken counts towards the age of the
+    /// requests, so requests that left the window while the counter was not running are dropped.
+    /// A wall clock that was stepped back is treated as no time having passed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the snapshot holds an invalid configuration, which a snapshot deserialized from
+    /// untrusted or edited input may do. Use [`SlidingWindowCounter::try_from_snapshot`] to
+    /// handle that case.
+    #[cfg(feature = "serde")]
+    pub fn from_snapshot(state: SlidingWindowState) -> Self {
+        match SlidingWindowCounter::try_from_snapshot(state) {
+            Ok(counter) => counter,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Restores a counter from a [`SlidingWindowCounter::snapshot`], or fails if the snapshot
+    /// holds an invalid configuration.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`LimitrError::InvalidConfig`] if the window duration of the snapshot is zero
+    /// or longer than `u64::MAX` nanoseconds, or if it divides the window into zero sub-buckets.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use tokio::time::Duration;
+    /// use limitr::window::{SlidingWindowCounter, SlidingWindowState};
+    /// # tokio_test::block_on(async {
+    /// let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+    /// let checkpoint = serde_json::to_string(&limiter.snapshot().await).unwrap();
+    /// let state: SlidingWindowState = serde_json::from_str(&checkpoint).unwrap();
+    /// assert!(SlidingWindowCounter::try_from_snapshot(state).is_ok());
+    ///
+    /// let edited = checkpoint.replace("\"secs\":10", "\"secs\":0");
+    /// let state: SlidingWindowState = serde_json::from_str(&edited).unwrap();
+    /// assert!(SlidingWindowCounter::try_from_snapshot(state).is_err());
+    /// # })
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn try_from_snapshot(state: SlidingWindowState) -> Result<Self, LimitrError> {
+        if state.sub_buckets == Some(0) {
+            return Err(LimitrError::InvalidConfig(
+                InvalidConfigReason::ZeroSubBuckets,
+            ));
+        }
+        let mut counter = SlidingWindowCounter::try_new(state.limit, state.window_duration)?;
+        counter.max_entries = state.max_entries;
+        counter.sub_buckets = state.sub_buckets;
+        counter.boundary = state.boundary;
+        counter.decay = state.decay;
+        counter.mode = state.mode;
+        counter.min_gap = state.min_gap;
+        counter.soft_limit = state.soft_limit;
+        counter.size_log();
+        let lifetime = counter.lifetime();
+
+        let downtime = SystemTime::now()
+            .duration_since(state.taken_at)
+            .unwrap_or(Duration::ZERO);
+        let now = Instant::now();
+        let entries: Vec<_> = state
+            .entries
+            .into_iter()
+            .map(|(age, count)| (age.saturating_add(downtime), count))
+            .filter(|&(age, _)| age < lifetime)
+            // Requests older than the clock count as made now, which errs on denying
+            .map(|(age, count)| (counter.log_time(now.checked_sub(age).unwrap_or(now)), count))
+            .collect();
+        let requests = counter.requests.get_mut();
+        for (at, count) in entries {
+            // The log stays ordered by time
+            let at = requests
+                .entries
+                .back()
+                .map_or(at, |latest| at.max(latest.at));
+            requests.record(at, count, None, None);
+        }
+        let usage = requests.total;
+        counter.raise_peak(usage);
+        Ok(counter)
+    }
+}
+
+/// Persistable state of a [`SlidingWindowCounter`], see [`SlidingWindowCounter::snapshot`].
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SlidingWindowState {
+    limit: u32,
+    window_duration: Duration,
+    #[serde(default)]
+    max_entries: Option<usize>,
+    #[serde(default)]
+    sub_buckets: Option<u32>,
+    #[serde(default)]
+    boundary: Boundary,
+    #[serde(default)]
+    decay: Decay,
+    #[serde(default)]
+    mode: SlidingWindowMode,
+    #[serde(default)]
+    min_gap: Option<Duration>,
+    #[serde(default)]
+    soft_limit: Option<u32>,
+    /// Wall-clock time the snapshot was taken at
+    taken_at: SystemTime,
+    /// Ages of the logged requests at `taken_at` along with their counts, oldest first
+    entries: Vec<(Duration, u32)>,
+}
+
+/// Converts `duration` to nanoseconds, saturating at about 584 years.
+fn saturating_nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// Creates a counter admitting 60 requests within any one-second window, a reasonable starting
+/// point for prototypes and tests.
+impl Default for SlidingWindowCounter {
+    fn default() -> Self {
+        SlidingWindowCounter::new(60, Duration::from_secs(1))
+    }
+}
+
+/// Shows the configuration along with the current usage and the number of stored log entries.
+/// Both read `<locked>` while another task holds the log, instead of waiting for it.
+impl fmt::Debug for SlidingWindowCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("SlidingWindowCounter");
+        debug
+            .field("limit", &self.limit())
+            .field("window_duration", &self.window_duration())
+            .field("mode", &self.mode);
+        match self.requests.try_lock() {
+            Ok(requests) => debug
+                .field("usage", &self.peek_usage(&requests, self.now()))
+                .field("stored_entries", &requests.entries.len()),
+            Err(_) => debug
+                .field("usage", &format_args!("<locked>"))
+                .field("stored_entries", &format_args!("<locked>")),
+        };
+        debug.finish_non_exhaustive()
+    }
+}
+
+impl RateLimiter for SlidingWindowCounter {
+    fn try_acquire(&mut self, cost: u64) -> BoxFuture<'_, bool> {
+        SharedRateLimiter::try_acquire(self, cost)
+    }
+
+    fn check(&mut self, cost: u64) -> BoxFuture<'_, Decision> {
+        SharedRateLimiter::check(self, cost)
+    }
+
+    fn refund(&mut self, cost: u64) -> BoxFuture<'_, ()> {
+        SharedRateLimiter::refund(self, cost)
+    }
+
+    fn retry_after(&mut self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        SharedRateLimiter::retry_after(self, cost)
+    }
+}
+
+impl SharedRateLimiter for SlidingWindowCounter {
+    fn try_acquire(&self, cost: u64) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            match u32::try_from(cost) {
+                Ok(n) => self.try_consume_n(n).await,
+                Err(_) => false,
+            }
+        })
+    }
+
+    fn check(&self, cost: u64) -> BoxFuture<'_, Decision> {
+        Box::pin(self.decide(cost, self.now()))
+    }
+
+    fn refund(&self, cost: u64) -> BoxFuture<'_, ()> {
+        Box::pin(async move { self.requests.lock().await.remove_latest(cost) })
+    }
+
+    fn retry_after(&self, cost: u64) -> BoxFuture<'_, Option<Duration>> {
+        Box::pin(async move {
+            let now = self.now();
+            let mut requests = self.requests.lock().await;
+            let now = requests
+                .entries
+                .back()
+                .map_or(now, |latest| now.max(latest.at));
+            requests.evict_expired(now, self.lifetime());
+
+            if self.drain.is_draining() {
+                return None;
+            }
+            self.available_in(&requests, now, cost)
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    // assert will mess up codecov report use assert_eg instead
+
+    use crate::test_util::paused_runtime;
+    use crate::window::{Boundary, Decay, SlidingWindowCounter, SlidingWindowMode};
+    use crate::{Decision, InvalidConfigReason, LimitrError};
+    use proptest::prelude::*;
+    use std::sync::Arc;
+    use std::time::SystemTime;
+    use tokio::time::Instant;
+    use tokio::time::{self, Duration};
+
+    #[tokio::test]
+    async fn test_allows_requests_under_limit() {
+        let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+
+        for _ in 0..5 {
+            assert_eq!(
+                limiter.try_consume().await,
+                true,
+                "Request should be allowed"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_when_limit_exceeded() {
+        let limiter = SlidingWindowCounter::new(3, Duration::from_secs(10));
+
+        for _ in 0..3 {
+            assert_eq!(
+                limiter.try_consume().await,
+                true,
+                "Request should be allowed"
+            );
+        }
+
+        // 4th request should be denied since the limit is 3
+        assert_eq!(
+            limiter.try_consume().await,
+            false,
+            "Request should be rate-limited"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_eviction_of_old_requests() {
+        let limiter = SlidingWindowCounter::new(3, Duration::from_secs(2));
+
+        for _ in 0..3 {
+            assert_eq!(
+                limiter.try_consume().await,
+                true,
+                "Request should be allowed"
+            );
+        }
+
+        time::sleep(Duration::from_secs(3)).await;
+
+        assert_eq!(
+            limiter.try_consume().await,
+            true,
+            "Request should be allowed after window expiration"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mixed_behavior() {
+        let limiter = SlidingWindowCounter::new(5, Duration::from_secs(5));
+
+        for _ in 0..4 {
+            assert_eq!(
+                limiter.try_consume().await,
+                true,
+                "Request should be allowed"
+            );
+        }
+
+        assert_eq!(
+            limiter.try_consume().await,
+            true,
+            "Request should be allowed"
+        );
+
+        assert_eq!(
+            limiter.try_consume().await,
+            false,
+            "Request should be rate-limited"
+        );
+
+        time::sleep(Duration::from_secs(6)).await;
+
+        assert_eq!(
+            limiter.try_consume().await,
+            true,
+            "Request should be allowed after window expiration"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_approx_weighs_previous_window() {
+        let limiter = SlidingWindowCounter::new(10, Duration::from_secs(10))
+            .with_mode(SlidingWindowMode::Approx);
+        assert_eq!(limiter.try_consume_n(10).await, true);
+
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(limiter.try_consume().await, false);
+        assert_eq!(
+            limiter.next_available_in().await,
+            Some(Duration::from_secs(1))
+        );
+
+        time::advance(Duration::from_secs(5)).await;
+        assert_eq!(limiter.current_usage().await, 5);
+        assert_eq!(limiter.try_consume_n(5).await, true);
+        assert_eq!(limiter.try_consume().await, false);
+
+        time::advance(Duration::from_secs(15)).await;
+        assert_eq!(limiter.remaining().await, 10);
+    }
+
+    /// Returns the most requests admitted within any `window` among the sorted `admitted` times.
+    fn max_in_any_window(admitted: &[Instant], window: Duration) -> usize {
+        admitted
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| admitted[i..].partition_point(|&at| at - start < window))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_approx_bounds_admissions_at_window_boundaries() {
+        let window = Duration::from_secs(1);
+        for burst_at_end in [false, true] {
+            let limiter =
+                SlidingWindowCounter::new(100, window).with_mode(SlidingWindowMode::Approx);
+            let mut admitted = vec![];
+            if burst_at_end {
+                // The worst case: a burst at the end of a fixed window, then a steady stream
+                time::advance(Duration::from_millis(990)).await;
+                assert_eq!(limiter.try_consume_n(100).await, true);
+                admitted.extend([Instant::now(); 100]);
+                time::advance(Duration::from_millis(10)).await;
+            }
+            // Five times the limit is offered
+            for _ in 0..500 {
+                for _ in 0..5 {
+                    if limiter.try_consume().await {
+                        admitted.push(Instant::now());
+                    }
+                }
+                time::advance(Duration::from_millis(10)).await;
+            }
+
+            let max = max_in_any_window(&admitted, window);
+            if burst_at_end {
+                assert!(max < 200, "{}", max);
+            } else {
+                assert!(max <= 110, "{}", max);
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_request_ages_oldest_first_after_eviction() {
+        let limiter = SlidingWindowCounter::new(4, Duration::from_secs(10));
+        assert_eq!(limiter.request_ages().await, []);
+
+        for n in 1..=3 {
+            assert_eq!(limiter.try_consume_n(n).await, n < 3);
+            time::advance(Duration::from_secs(4)).await;
+        }
+        // The first request has left the window and the third one was denied
+        assert_eq!(limiter.try_consume().await, true);
+        assert_eq!(
+            limiter.request_ages().await,
+            [(Duration::from_secs(8), 2), (Duration::ZERO, 1)]
+        );
+
+        // The second batch leaves the window without any other request clearing it out
+        time::advance(Duration::from_secs(3)).await;
+        assert_eq!(limiter.request_ages().await, [(Duration::from_secs(3), 1)]);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_request_ages_stay_within_limit(
+            limit in 1u32..20,
+            steps in prop::collection::vec((1u32..5, 0u64..3_000), 1..40),
+        ) {
+            paused_runtime().block_on(async {
+                let limiter = SlidingWindowCounter::new(limit, Duration::from_secs(10));
+                for (n, wait) in steps {
+                    let _ = limiter.try_consume_n(n).await;
+                    time::advance(Duration::from_millis(wait)).await;
+
+                    let ages = limiter.request_ages().await;
+                    let counted: u32 = ages.iter().map(|&(_, count)| count).sum();
+                    prop_assert!(counted <= limit);
+                    prop_assert!(ages.iter().all(|&(_, count)| count > 0));
+                    prop_assert!(ages.windows(2).all(|pair| pair[0].0 > pair[1].0));
+                    prop_assert!(ages.iter().all(|&(age, _)| age <= Duration::from_secs(10)));
+                }
+                Ok(())
+            })?;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reservations_run_staggered() {
+        let limiter =
+            SlidingWindowCounter::new(2, Duration::from_secs(1)).with_boundary(Boundary::Exclusive);
+        let start = Instant::now();
+
+        let mut tasks = vec![];
+        for _ in 0..5 {
+            let reservation = limiter.reserve().await.unwrap();
+            tasks.push(tokio::spawn(async move {
+                reservation.wait().await;
+                start.elapsed()
+            }));
+        }
+        // The reserved slots are taken, so nothing else gets in before them
+        assert_eq!(limiter.try_consume().await, false);
+
+        let mut ran_at = vec![];
+        for task in tasks {
+            ran_at.push(task.await.unwrap());
+        }
+        let secs = Duration::from_secs;
+        assert_eq!(ran_at, [secs(0), secs(0), secs(1), secs(1), secs(2)]);
+        assert_eq!(limiter.current_usage().await, 1);
+        assert_eq!(limiter.try_consume().await, true);
+        assert_eq!(limiter.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dropped_reservation_frees_its_slot() {
+        let limiter =
+            SlidingWindowCounter::new(1, Duration::from_secs(1)).with_boundary(Boundary::Exclusive);
+        let start = Instant::now();
+
+        let first = limiter.reserve().await.unwrap();
+        let second = limiter.reserve().await.unwrap();
+        assert_eq!(second.due() - start, Duration::from_secs(1));
+
+        // The third reservation takes the slot given back by the second one
+        drop(second);
+        let third = limiter.reserve().await.unwrap();
+        assert_eq!(third.due() - start, Duration::from_secs(1));
+
+        // The first reservation was already due and logged, so dropping it changes nothing
+        drop(first);
+        assert_eq!(limiter.current_usage().await, 1);
+        assert_eq!(limiter.try_consume().await, false);
+
+        third.wait().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert_eq!(limiter.current_usage().await, 1);
+
+        assert!(matches!(
+            limiter.reserve_n(2).await,
+            Err(LimitrError::AmountExceedsCapacity {
+                requested: 2,
+                capacity: 1
+            })
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_waits_for_room() {
+        let limiter = SlidingWindowCounter::new(2, Duration::from_secs(1));
+        let start = Instant::now();
+
+        for _ in 0..5 {
+            assert_eq!(limiter.acquire().await, Ok(()));
+        }
+        // Two per window, so the fifth request made it into the third window
+        assert!(start.elapsed() > Duration::from_secs(2));
+        assert!(start.elapsed() < Duration::from_secs(3));
+        assert_eq!(
+            limiter.acquire_n(3).await,
+            Err(LimitrError::AmountExceedsCapacity {
+                requested: 3,
+                capacity: 2,
+            })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dropped_acquire_leaves_no_trace() {
+        let limiter = SlidingWindowCounter::shared(2, Duration::from_secs(10));
+        assert_eq!(limiter.try_consume_n(2).await, true);
+
+        // Every waiter times out long before the window has room
+        let racers: Vec<_> = (0..4)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move {
+                    tokio::select! {
+                        result = limiter.acquire() => result.is_ok(),
+                        _ = time::sleep(Duration::from_secs(1)) => false,
+                    }
+                })
+            })
+            .collect();
+        for racer in racers {
+            assert_eq!(racer.await.unwrap(), false);
+        }
+        assert_eq!(limiter.current_usage().await, 2);
+
+        // Once the window has room two waiters win, the others wait on and time out
+        let racers: Vec<_> = (0..4)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move {
+                    tokio::select! {
+                        result = limiter.acquire() => result.is_ok(),
+                        _ = time::sleep(Duration::from_secs(15)) => false,
+                    }
+                })
+            })
+            .collect();
+        let mut winners = 0;
+        for racer in racers {
+            winners += racer.await.unwrap() as u32;
+        }
+        assert_eq!(winners, 2);
+        assert_eq!(limiter.current_usage().await, winners);
+        let ages = limiter.request_ages().await;
+        assert_eq!(ages.iter().map(|&(_, count)| count).sum::<u32>(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_drain_fails_waiting_acquires() {
+        let limiter = SlidingWindowCounter::shared(1, Duration::from_secs(10));
+        assert_eq!(limiter.try_consume().await, true);
+
+        let waiters: Vec<_> = (0..3)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.acquire().await })
+            })
+            .collect();
+        time::advance(Duration::from_secs(1)).await;
+
+        let start = Instant::now();
+        limiter.begin_drain().await;
+        for waiter in waiters {
+            assert_eq!(waiter.await.unwrap(), Err(LimitrError::ShuttingDown));
+        }
+        limiter.drained().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // Even once the window has room, nothing is admitted anymore
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(limiter.is_draining(), true);
+        assert_eq!(limiter.would_allow().await, false);
+        assert_eq!(limiter.try_consume().await, false);
+        assert_eq!(limiter.check(1).await.retry_after(), None);
+        assert!(matches!(
+            limiter.reserve().await,
+            Err(LimitrError::ShuttingDown)
+        ));
+        assert_eq!(limiter.current_usage().await, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_drained_waits_for_reservations() {
+        let limiter = SlidingWindowCounter::shared(1, Duration::from_secs(10));
+        let start = Instant::now();
+        let kept = limiter.reserve().await.unwrap();
+        let dropped = limiter.reserve().await.unwrap();
+        let last = limiter.reserve().await.unwrap();
+
+        limiter.begin_drain().await;
+        let drained = tokio::spawn({
+            let limiter = limiter.clone();
+            async move {
+                limiter.drained().await;
+                start.elapsed()
+            }
+        });
+
+        // Reservations made before the drain are still honored
+        kept.wait().await;
+        drop(dropped);
+        time::advance(Duration::from_secs(15)).await;
+        assert_eq!(drained.is_finished(), false);
+
+        // The last reservation is given up on, so nothing is left to wait for
+        drop(last);
+        assert_eq!(drained.await.unwrap(), Duration::from_secs(15));
+        assert_eq!(limiter.current_usage().await, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_min_gap_denies_without_recording() {
+        let limiter = SlidingWindowCounter::new(100, Duration::from_secs(60))
+            .with_min_gap(Duration::from_millis(200));
+        assert_eq!(limiter.try_consume().await, true);
+
+        for _ in 0..10 {
+            assert_eq!(limiter.try_consume().await, false);
+        }
+        let decision = limiter.check(1).await;
+        assert_eq!(decision.remaining(), 99);
+        assert_eq!(decision.retry_after(), Some(Duration::from_millis(200)));
+
+        time::advance(Duration::from_millis(199)).await;
+        assert_eq!(limiter.try_consume().await, false);
+        time::advance(Duration::from_millis(1)).await;
+        assert_eq!(limiter.try_consume().await, true);
+        assert_eq!(limiter.current_usage().await, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_would_allow_respects_min_gap() {
+        let limiter = SlidingWindowCounter::new(100, Duration::from_secs(60))
+            .with_min_gap(Duration::from_millis(200));
+        assert_eq!(limiter.would_allow().await, true);
+        assert_eq!(limiter.try_consume().await, true);
+
+        assert_eq!(limiter.would_allow().await, false);
+        assert_eq!(limiter.try_consume().await, false);
+        time::advance(Duration::from_millis(199)).await;
+        assert_eq!(limiter.would_allow().await, false);
+        time::advance(Duration::from_millis(1)).await;
+        assert_eq!(limiter.would_allow().await, true);
+        assert_eq!(limiter.try_consume().await, true);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_min_gap_waits_for_both_constraints() {
+        let window = Duration::from_secs(1);
+        let ms = Duration::from_millis;
+
+        // The window is the tighter constraint
+        let limiter = SlidingWindowCounter::new(2, window)
+            .with_boundary(Boundary::Exclusive)
+            .with_min_gap(ms(100));
+        assert_eq!(limiter.try_consume().await, true);
+        time::advance(ms(100)).await;
+        assert_eq!(limiter.try_consume().await, true);
+        time::advance(ms(100)).await;
+        assert_eq!(limiter.try_consume().await, false);
+        assert_eq!(limiter.next_available_in().await, Some(ms(800)));
+        assert_eq!(limiter.current_usage().await, 2);
+
+        // The gap is the tighter constraint
+        let limiter = SlidingWindowCounter::new(2, window)
+            .with_boundary(Boundary::Exclusive)
+            .with_min_gap(ms(700));
+        assert_eq!(limiter.try_consume().await, true);
+        time::advance(ms(700)).await;
+        assert_eq!(limiter.try_consume().await, true);
+        time::advance(ms(100)).await;
+        assert_eq!(limiter.check(1).await.retry_after(), Some(ms(600)));
+        assert_eq!(limiter.next_available_in().await, Some(ms(600)));
+
+        // Waiting the reported time admits the request
+        let start = Instant::now();
+        assert_eq!(limiter.acquire().await, Ok(()));
+        assert_eq!(start.elapsed(), ms(600));
+        assert_eq!(limiter.current_usage().await, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_coarse_clock_reads_cached_time() {
+        let window = Duration::from_secs(1);
+        let limiter = SlidingWindowCounter::new(3, window)
+            .with_boundary(Boundary::Exclusive)
+            .with_coarse_clock(Duration::from_millis(10));
+        let clock = limiter.coarse_clock().unwrap().clone();
+
+        let reads = clock.reads();
+        for _ in 0..3 {
+            assert_eq!(limiter.try_consume().await, true);
+        }
+        assert_eq!(limiter.try_consume().await, false);
+        assert_eq!(clock.reads(), reads);
+
+        // Requests leave the window once the cached time has caught up, up to one resolution
+        // late
+        time::sleep(window + Duration::from_millis(10)).await;
+        assert_eq!(limiter.try_consume().await, true);
+        assert!(clock.reads() <= reads + 101);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_shared_handles_share_the_window() {
+        let limiter = SlidingWindowCounter::shared(20, Duration::from_secs(60));
+        let other = Arc::clone(&limiter);
+
+        let tasks: Vec<_> = [Arc::clone(&limiter), Arc::clone(&other)]
+            .into_iter()
+            .map(|handle| {
+                tokio::spawn(async move {
+                    let mut admitted = 0;
+                    for _ in 0..30 {
+                        admitted += handle.try_consume().await as u32;
+                    }
+                    admitted
+                })
+            })
+            .collect();
+        let mut admitted = 0;
+        for task in tasks {
+            admitted += task.await.unwrap();
+        }
+
+        assert_eq!(admitted, 20);
+        assert_eq!(limiter.remaining().await, 0);
+        other.set_limit(21).await;
+        assert!(limiter.try_consume().await);
+        assert_eq!(other.peak_usage(), 21);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_access() {
+        let limiter = Arc::new(SlidingWindowCounter::new(5, Duration::from_secs(10)));
+
+        let mut handles = vec![];
+
+        for _ in 0..10 {
+            let limiter_clone = limiter.clone();
+            let handle = tokio::spawn(async move { limiter_clone.try_consume().await });
+            handles.push(handle);
+        }
+
+        let mut allowed_count = 0;
+        for handle in handles {
+            if handle.await.unwrap() {
+                allowed_count += 1;
+            }
+        }
+
+        assert_eq!(
+            allowed_count, 5,
+            "Only 5 requests should be allowed due to the limit"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_consume_n_is_all_or_nothing() {
+        let limiter = SlidingWindowCounter::new(5, Duration::from_secs(1));
+
+        assert_eq!(limiter.try_consume_n(3).await, true);
+        assert_eq!(limiter.try_consume_n(3).await, false);
+        assert_eq!(limiter.try_consume_n(2).await, true);
+        assert_eq!(limiter.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mixed_weights_leave_window_as_a_whole() {
+        let limiter = SlidingWindowCounter::new(10, Duration::from_secs(10));
+
+        assert_eq!(limiter.try_consume_n(6).await, true);
+        time::advance(Duration::from_secs(4)).await;
+        assert_eq!(limiter.try_consume_n(1).await, true);
+        time::advance(Duration::from_secs(4)).await;
+        assert_eq!(limiter.try_consume_n(3).await, true);
+        assert_eq!(limiter.try_consume_n(1).await, false);
+
+        // The heavy request leaves the window first and frees all of its weight
+        time::advance(Duration::from_millis(2_001)).await;
+        assert_eq!(limiter.try_consume_n(7).await, false);
+        assert_eq!(limiter.try_consume_n(6).await, true);
+        assert_eq!(limiter.requests.lock().await.total, 10);
+
+        // Then the light one, while the later requests still count
+        time::advance(Duration::from_secs(4)).await;
+        assert_eq!(limiter.try_consume_n(2).await, false);
+        assert_eq!(limiter.try_consume_n(1).await, true);
+
+        let requests = limiter.requests.lock().await;
+        assert_eq!(requests.total, 10);
+        assert_eq!(
+            requests
+                .entries
+                .iter()
+                .map(|entry| entry.count as u64)
+                .sum::<u64>(),
+            requests.total
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_next_available_in_is_boundary_exact() {
+        let limiter = SlidingWindowCounter::new(3, Duration::from_secs(10));
+        assert_eq!(limiter.next_available_in().await, Some(Duration::ZERO));
+
+        // Two requests at the same instant expire together
+        assert_eq!(limiter.try_consume_n(2).await, true);
+        time::advance(Duration::from_secs(2)).await;
+        assert_eq!(limiter.try_consume().await, true);
+
+        let wait = limiter.next_available_in().await.unwrap();
+        assert_eq!(wait, Duration::from_secs(8) + Duration::from_nanos(1));
+
+        // One nanosecond early, the oldest requests are still in the window
+        time::advance(wait - Duration::from_nanos(1)).await;
+        assert_eq!(
+            limiter.next_available_in().await,
+            Some(Duration::from_nanos(1))
+        );
+        assert_eq!(limiter.try_consume().await, false);
+
+        time::advance(Duration::from_nanos(1)).await;
+        assert_eq!(limiter.next_available_in().await, Some(Duration::ZERO));
+        assert_eq!(limiter.try_consume_n(2).await, true);
+
+        // Now the request from two seconds in has to leave first
+        assert_eq!(
+            limiter.next_available_in().await,
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            SlidingWindowCounter::new(0, Duration::from_secs(1))
+                .next_available_in()
+                .await,
+            None
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sub_buckets_bound_memory_and_stay_near_limit() {
+        let window = Duration::from_secs(10);
+        let limiter = SlidingWindowCounter::new(1_000, window).with_sub_buckets(10);
+        let start = Instant::now();
 
-        // Remove old requests outside the window duration
-        self.clear_old_requests(&mut requests, now).await;
+        // Ten times more traffic than the limit, spread over three windows
+        let mut admitted = Vec::new();
+        for _ in 0..30_000 {
+            if limiter.try_consume().await {
+                admitted.push(start.elapsed());
+            }
+            time::advance(Duration::from_millis(1)).await;
+        }
+        assert_eq!(limiter.requests.lock().await.entries.len() <= 11, true);
 
-        if requests.len() < self.limit as usize {
-            // allow the request if under the limit
-            requests.push_back(now);
-            true
-        } else {
-            // reject request if limit is reached
-            false
+        let mut oldest = 0;
+        for (newest, at) in admitted.iter().enumerate() {
+            while *at - admitted[oldest] > window {
+                oldest += 1;
+            }
+            assert_eq!(newest - oldest < 1_000, true, "Window exceeded the limit");
+        }
+
+        // Sub-buckets only delay requests, each window still admits nearly the whole limit
+        for second in [10, 20] {
+            let in_window = admitted
+                .iter()
+                .filter(|at| **at >= Duration::from_secs(second - 10))
+                .filter(|at| **at < Duration::from_secs(second))
+                .count();
+            assert_eq!(in_window >= 1_000 - 1_000 / 10, true, "{}", in_window);
         }
+
+        let wait = limiter.next_available_in().await.unwrap();
+        time::advance(wait).await;
+        assert_eq!(limiter.try_consume().await, true);
     }
 
-    /// Clears out requests that are older than the window duration.
-    ///
-    /// This function removes requests from the front of the deque that
-    /// occurred before the current window. It is called every time
-    /// `try_consume` is invoked to ensure that only requests within the
-    /// valid window are counted.
-    ///
-    /// - `requests`: A mutable reference to the request deque.
-    /// - `now`: The current time used for comparison with request timestamps.
-    async fn clear_old_requests(&mut self, requests: &mut VecDeque<Instant>, now: Instant) {
-        while let Some(request_time) = requests.front() {
-            if now.duration_since(*request_time) > self.window_duration {
-                requests.pop_front();
-            } else {
-                break;
+    #[tokio::test(start_paused = true)]
+    async fn test_evicts_large_backlog_at_once() {
+        let limiter = SlidingWindowCounter::new(100_000, Duration::from_secs(10));
+        let start = Instant::now();
+        time::advance(Duration::from_secs(5)).await;
+        {
+            let mut requests = limiter.requests.lock().await;
+            for i in 0..100_000 {
+                requests.record(start + Duration::from_micros(50 * i), 1, None, None);
             }
         }
+        assert_eq!(limiter.try_consume().await, false);
+
+        // Half of the backlog has expired, the request made exactly a window ago has not
+        time::advance(Duration::from_millis(7_500)).await;
+        assert_eq!(limiter.try_consume_n(50_000).await, true);
+        assert_eq!(limiter.try_consume().await, false);
+
+        let requests = limiter.requests.lock().await;
+        assert_eq!(requests.entries.len(), 50_001);
+        assert_eq!(requests.total, 100_000);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    // assert will mess up codecov report use assert_eg instead
+    #[cfg(feature = "serde")]
+    #[tokio::test(start_paused = true)]
+    async fn test_snapshot_survives_restart() {
+        let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+        assert_eq!(limiter.try_consume_n(2).await, true);
+        time::advance(Duration::from_secs(7)).await;
+        assert_eq!(limiter.try_consume_n(2).await, true);
 
-    use crate::window::SlidingWindowCounter;
-    use std::sync::Arc;
-    use tokio::sync::Mutex;
-    use tokio::time::{self, Duration};
+        // A restart taking 2 seconds
+        let mut state = limiter.snapshot().await;
+        state.taken_at -= Duration::from_secs(2);
+        let checkpoint = serde_json::to_string(&state).unwrap();
+        drop(limiter);
+
+        let restored =
+            SlidingWindowCounter::from_snapshot(serde_json::from_str(&checkpoint).unwrap());
+        assert_eq!(restored.check(2).await.remaining(), 1);
+
+        // The first requests are 9 seconds old after the restart
+        time::advance(Duration::from_millis(1_001)).await;
+        assert_eq!(restored.try_consume_n(3).await, true);
+        assert_eq!(restored.try_consume().await, false);
+
+        // Requests that aged out during a longer downtime are dropped
+        state.taken_at -= Duration::from_secs(10);
+        let restored = SlidingWindowCounter::from_snapshot(state);
+        assert_eq!(restored.requests.lock().await.total, 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test(start_paused = true)]
+    async fn test_invalid_snapshot_is_rejected() {
+        let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10)).with_sub_buckets(4);
+
+        let mut state = limiter.snapshot().await;
+        state.window_duration = Duration::ZERO;
+        assert_eq!(
+            SlidingWindowCounter::try_from_snapshot(state).err(),
+            Some(LimitrError::InvalidConfig(InvalidConfigReason::ZeroWindow))
+        );
+
+        let mut state = limiter.snapshot().await;
+        state.sub_buckets = Some(0);
+        assert_eq!(
+            SlidingWindowCounter::try_from_snapshot(state).err(),
+            Some(LimitrError::InvalidConfig(
+                InvalidConfigReason::ZeroSubBuckets
+            ))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_set_limit_in_both_directions() {
+        let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+        assert_eq!(limiter.try_consume_n(2).await, true);
+        time::advance(Duration::from_secs(4)).await;
+        assert_eq!(limiter.try_consume_n(3).await, true);
+
+        // Below the current usage, nothing is admitted until enough requests have left
+        limiter.set_limit(2).await;
+        assert_eq!(limiter.try_consume().await, false);
+        assert_eq!(
+            limiter.check(1).await.remaining(),
+            0,
+            "Usage above the limit has nothing remaining"
+        );
+        assert_eq!(
+            limiter.next_available_in().await,
+            Some(Duration::from_nanos(10_000_000_001))
+        );
+
+        time::advance(Duration::from_nanos(6_000_000_001)).await;
+        assert_eq!(limiter.try_consume().await, false);
+        time::advance(Duration::from_secs(4)).await;
+        assert_eq!(limiter.try_consume_n(2).await, true);
+
+        limiter.set_limit(4).await;
+        assert_eq!(limiter.try_consume_n(2).await, true);
+        assert_eq!(limiter.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_set_window_duration_in_both_directions() {
+        let limiter = SlidingWindowCounter::new(3, Duration::from_secs(10));
+        assert_eq!(limiter.try_consume().await, true);
+        time::advance(Duration::from_secs(6)).await;
+        assert_eq!(limiter.try_consume_n(2).await, true);
+
+        // Shrinking evicts the request that is older than the new window right away
+        limiter
+            .set_window_duration(Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(limiter.requests.lock().await.total, 2);
+        assert_eq!(limiter.next_available_in().await, Some(Duration::ZERO));
+        assert_eq!(limiter.try_consume().await, true);
+        assert_eq!(
+            limiter.next_available_in().await,
+            Some(Duration::from_nanos(5_000_000_001))
+        );
+
+        // Widening keeps the stored requests for longer
+        limiter
+            .set_window_duration(Duration::from_secs(20))
+            .await
+            .unwrap();
+        time::advance(Duration::from_secs(6)).await;
+        assert_eq!(limiter.try_consume().await, false);
+        assert_eq!(
+            limiter.next_available_in().await,
+            Some(Duration::from_nanos(14_000_000_001))
+        );
+
+        // Widen then shrink again
+        limiter
+            .set_window_duration(Duration::from_secs(3))
+            .await
+            .unwrap();
+        assert_eq!(limiter.requests.lock().await.total, 0);
+        assert_eq!(limiter.try_consume_n(3).await, true);
+        assert_eq!(
+            limiter.next_available_in().await,
+            Some(Duration::from_nanos(3_000_000_001))
+        );
+
+        assert!(limiter.set_window_duration(Duration::ZERO).await.is_err());
+        assert_eq!(limiter.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_usage_decreases_as_requests_age_out() {
+        let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+        assert_eq!(limiter.limit(), 5);
+        assert_eq!(limiter.window_duration(), Duration::from_secs(10));
+
+        assert_eq!(limiter.try_consume_n(2).await, true);
+        time::advance(Duration::from_secs(4)).await;
+        assert_eq!(limiter.try_consume_n(3).await, true);
+        assert_eq!(limiter.current_usage().await, 5);
+        assert_eq!(limiter.remaining().await, 0);
+
+        time::advance(Duration::from_millis(6_001)).await;
+        assert_eq!(limiter.current_usage().await, 3);
+        assert_eq!(limiter.remaining().await, 2);
+
+        time::advance(Duration::from_secs(4)).await;
+        assert_eq!(limiter.current_usage().await, 0);
+        assert_eq!(limiter.remaining().await, 5);
+
+        limiter.set_limit(1).await;
+        assert_eq!(limiter.limit(), 1);
+        assert_eq!(limiter.remaining().await, 1);
+    }
 
     #[tokio::test]
-    async fn test_allows_requests_under_limit() {
-        let mut limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+    async fn test_try_consume_at_never_moves_backwards() {
+        let limiter = SlidingWindowCounter::new(2, Duration::from_secs(10));
+        let start = Instant::now();
 
-        for _ in 0..5 {
+        assert_eq!(
+            limiter
+                .try_consume_at(start + Duration::from_secs(5), 1)
+                .await,
+            true
+        );
+        // Counted as made at the latest request, so it leaves the window together with it
+        assert_eq!(limiter.try_consume_at(start, 1).await, true);
+        assert_eq!(
+            limiter
+                .try_consume_at(start + Duration::from_secs(11), 1)
+                .await,
+            false
+        );
+        assert_eq!(
+            limiter
+                .try_consume_at(start + Duration::from_millis(15_001), 2)
+                .await,
+            true
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_boundary_modes_at_exactly_one_window() {
+        for (boundary, wait) in [
+            (Boundary::Inclusive, Duration::from_nanos(1_000_000_001)),
+            (Boundary::Exclusive, Duration::from_secs(1)),
+        ] {
+            let limiter =
+                SlidingWindowCounter::new(1, Duration::from_secs(1)).with_boundary(boundary);
+            assert_eq!(limiter.try_consume().await, true);
+            assert_eq!(limiter.next_available_in().await, Some(wait));
+            assert_eq!(limiter.check(1).await.retry_after(), Some(wait));
+
+            time::advance(Duration::from_secs(1)).await;
             assert_eq!(
                 limiter.try_consume().await,
-                true,
-                "Request should be allowed"
+                boundary == Boundary::Exclusive,
+                "{:?}",
+                boundary
             );
+            if boundary == Boundary::Inclusive {
+                assert_eq!(
+                    limiter.next_available_in().await,
+                    Some(Duration::from_nanos(1))
+                );
+                time::advance(Duration::from_nanos(1)).await;
+                assert_eq!(limiter.try_consume().await, true);
+            }
         }
     }
 
-    #[tokio::test]
-    async fn test_rate_limits_when_limit_exceeded() {
-        let mut limiter = SlidingWindowCounter::new(3, Duration::from_secs(10));
+    #[tokio::test(start_paused = true)]
+    async fn test_would_allow_agrees_with_try_consume() {
+        let limiter = SlidingWindowCounter::new(3, Duration::from_secs(1));
 
-        for _ in 0..3 {
-            assert_eq!(
-                limiter.try_consume().await,
-                true,
-                "Request should be allowed"
-            );
+        for step in 0..40 {
+            let cost = step % 3 + 1;
+            let expected = limiter.would_allow_n(cost).await;
+            assert_eq!(limiter.would_allow_n(cost).await, expected);
+            assert_eq!(limiter.try_consume_n(cost).await, expected, "step {}", step);
+            time::advance(Duration::from_millis(150)).await;
         }
 
-        // 4th request should be denied since the limit is 3
+        let limiter = SlidingWindowCounter::new(1, Duration::from_secs(1));
+        assert_eq!(limiter.try_consume().await, true);
+        time::advance(Duration::from_secs(1)).await;
+        // exactly one window later the request still counts, see `Boundary::Inclusive`
+        assert_eq!(limiter.would_allow().await, false);
+        time::advance(Duration::from_nanos(1)).await;
+        assert_eq!(limiter.would_allow().await, true);
+        assert_eq!(limiter.try_consume().await, true);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_would_allow_never_consumes() {
+        let limiter = Arc::new(SlidingWindowCounter::new(50, Duration::from_secs(60)));
+
+        let peekers: Vec<_> = (0..8)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move {
+                    for _ in 0..100 {
+                        let _ = limiter.would_allow_n(5).await;
+                    }
+                })
+            })
+            .collect();
+        let consumers: Vec<_> = (0..4)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move {
+                    let mut admitted = 0;
+                    for _ in 0..10 {
+                        if limiter.try_consume().await {
+                            admitted += 1;
+                        }
+                    }
+                    admitted
+                })
+            })
+            .collect();
+
+        for peeker in peekers {
+            peeker.await.unwrap();
+        }
+        let mut admitted = 0;
+        for consumer in consumers {
+            admitted += consumer.await.unwrap();
+        }
+        assert_eq!(admitted, 40);
+        assert_eq!(limiter.current_usage().await, 40);
+        assert_eq!(limiter.would_allow_n(10).await, true);
+        assert_eq!(limiter.would_allow_n(11).await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_linear_decay_recovers_gradually() {
+        let decayed =
+            SlidingWindowCounter::new(10, Duration::from_secs(10)).with_decay(Decay::Linear);
+        let cutoff = SlidingWindowCounter::new(10, Duration::from_secs(10));
+        assert_eq!(decayed.try_consume_n(10).await, true);
+        assert_eq!(cutoff.try_consume_n(10).await, true);
+
+        for second in 1..10 {
+            time::advance(Duration::from_secs(1)).await;
+            // one request's worth of capacity comes back every second
+            assert_eq!(decayed.remaining().await, second, "after {}s", second);
+            assert_eq!(decayed.would_allow_n(second).await, true);
+            assert_eq!(decayed.would_allow_n(second + 1).await, false);
+            assert_eq!(cutoff.remaining().await, 0);
+        }
+        assert_eq!(decayed.current_usage().await, 10);
+
+        // the burst no longer counts at the end of the window, even though it is still in it
+        time::advance(Duration::from_secs(1)).await;
+        assert_eq!(decayed.remaining().await, 10);
+        assert_eq!(cutoff.remaining().await, 0);
+        time::advance(Duration::from_nanos(1)).await;
+        assert_eq!(cutoff.remaining().await, 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_linear_decay_retry_after() {
+        let limiter =
+            SlidingWindowCounter::new(10, Duration::from_secs(10)).with_decay(Decay::Linear);
+        assert_eq!(limiter.try_consume_n(10).await, true);
+
+        let decision = limiter.check(3).await;
+        assert_eq!(decision.is_allowed(), false);
+        let retry_after = decision.retry_after().unwrap();
+        assert!(
+            retry_after.abs_diff(Duration::from_secs(3)) <= Duration::from_micros(1),
+            "{:?}",
+            retry_after
+        );
         assert_eq!(
-            limiter.try_consume().await,
-            false,
-            "Request should be rate-limited"
+            limiter.next_available_in().await,
+            Some(Duration::from_secs(1))
         );
+
+        time::advance(retry_after - Duration::from_nanos(1)).await;
+        assert_eq!(limiter.try_consume_n(3).await, false);
+        time::advance(Duration::from_nanos(1)).await;
+        assert_eq!(limiter.try_consume_n(3).await, true);
+        assert_eq!(limiter.remaining().await, 0);
     }
 
-    #[tokio::test]
-    async fn test_eviction_of_old_requests() {
-        let mut limiter = SlidingWindowCounter::new(3, Duration::from_secs(2));
+    #[tokio::test(start_paused = true)]
+    async fn test_exponential_decay_halves_weight() {
+        let limiter = SlidingWindowCounter::new(8, Duration::from_secs(10))
+            .with_decay(Decay::Exponential(Duration::from_secs(1)));
+        assert_eq!(limiter.try_consume_n(8).await, true);
+        assert_eq!(limiter.try_consume().await, false);
 
-        for _ in 0..3 {
+        time::advance(Duration::from_secs(1)).await;
+        assert_eq!(limiter.remaining().await, 4);
+        time::advance(Duration::from_secs(1)).await;
+        assert_eq!(limiter.remaining().await, 6);
+        assert_eq!(limiter.try_consume_n(6).await, true);
+        assert_eq!(limiter.try_consume().await, false);
+
+        // the requests still leave the window at its end
+        time::advance(Duration::from_secs(11)).await;
+        assert_eq!(limiter.current_usage().await, 0);
+        assert_eq!(limiter.remaining().await, 8);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_import_half_full_window() {
+        let now = SystemTime::now();
+        let ago = |millis| now - Duration::from_millis(millis);
+
+        let limiter = SlidingWindowCounter::new(10, Duration::from_secs(10));
+        limiter
+            .import([
+                ago(4_000),
+                ago(1_000),
+                ago(3_000),
+                ago(2_000),
+                ago(1_000),
+                ago(60_000),
+            ])
+            .await;
+
+        // the request from a minute ago has already left the window
+        assert_eq!(limiter.current_usage().await, 5);
+        assert_eq!(limiter.try_consume_n(6).await, false);
+        assert_eq!(limiter.try_consume_n(5).await, true);
+        assert_eq!(limiter.try_consume().await, false);
+
+        // the imported requests leave the window in the order they were made
+        time::advance(Duration::from_millis(6_001)).await;
+        assert_eq!(limiter.current_usage().await, 9);
+        time::advance(Duration::from_secs(1)).await;
+        assert_eq!(limiter.current_usage().await, 8);
+        time::advance(Duration::from_secs(2)).await;
+        assert_eq!(limiter.current_usage().await, 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_import_beyond_limit_is_recorded() {
+        let limiter = SlidingWindowCounter::new(3, Duration::from_secs(10));
+        let id = limiter.consume_tracked(2).await.unwrap();
+
+        let now = SystemTime::now();
+        limiter
+            .import([
+                now - Duration::from_secs(1),
+                now,
+                now + Duration::from_secs(5),
+            ])
+            .await;
+        assert_eq!(limiter.current_usage().await, 5);
+        assert_eq!(limiter.peak_usage(), 5);
+        assert_eq!(limiter.remaining().await, 0);
+        assert_eq!(limiter.try_consume().await, false);
+
+        // requests logged around the imported ones can still be refunded
+        assert_eq!(limiter.refund_entry(id).await, true);
+        assert_eq!(limiter.current_usage().await, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_preallocates_bounded_capacity() {
+        let limiter = SlidingWindowCounter::new(100, Duration::from_secs(1));
+        assert!(limiter.requests.lock().await.entries.capacity() >= 100);
+
+        let limiter = SlidingWindowCounter::new(u32::MAX, Duration::from_secs(1));
+        assert!(limiter.requests.lock().await.entries.capacity() < 2 * 1024);
+
+        let limiter =
+            SlidingWindowCounter::new(u32::MAX, Duration::from_secs(1)).with_max_entries(10);
+        assert!(limiter.requests.lock().await.entries.capacity() < 2 * 11);
+
+        let limiter =
+            SlidingWindowCounter::new(u32::MAX, Duration::from_secs(1)).with_sub_buckets(10);
+        assert!(limiter.requests.lock().await.entries.capacity() < 2 * 11);
+
+        let limiter = SlidingWindowCounter::new(u32::MAX, Duration::from_secs(1))
+            .with_mode(SlidingWindowMode::Approx);
+        assert!(limiter.requests.lock().await.entries.capacity() < 2 * 2);
+        // The log stays at its size once used, instead of growing towards the limit
+        for _ in 0..5 {
+            assert_eq!(limiter.try_consume_n(1_000).await, true);
+            time::advance(Duration::from_millis(700)).await;
+        }
+        assert!(limiter.requests.lock().await.entries.capacity() < 2 * 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_capacity_shrinks_after_spike() {
+        let window = Duration::from_secs(1);
+        let limiter = SlidingWindowCounter::new(100_000, window);
+        let steady = limiter.requests.lock().await.entries.capacity();
+
+        let start = Instant::now();
+        for i in 0..20_000 {
+            let at = start + Duration::from_micros(i);
+            assert_eq!(limiter.try_consume_at(at, 1).await, true);
+        }
+        assert!(limiter.requests.lock().await.entries.capacity() >= 20_000);
+
+        // the memory is kept while the window drains, then released after a full idle window
+        time::advance(window * 2).await;
+        assert_eq!(limiter.current_usage().await, 0);
+        assert!(limiter.requests.lock().await.entries.capacity() >= 20_000);
+        time::advance(window * 2).await;
+        assert_eq!(limiter.try_consume().await, true);
+        assert!(limiter.requests.lock().await.entries.capacity() < 2 * steady);
+
+        // a busy window keeps its memory
+        for i in 0..10_000 {
             assert_eq!(
-                limiter.try_consume().await,
-                true,
-                "Request should be allowed"
+                limiter
+                    .try_consume_at(Instant::now() + Duration::from_micros(i), 1)
+                    .await,
+                true
             );
         }
+        let busy = limiter.requests.lock().await.entries.capacity();
+        time::advance(window / 2).await;
+        assert_eq!(limiter.current_usage().await, 10_001);
+        time::advance(window).await;
+        assert_eq!(limiter.current_usage().await, 0);
+        assert_eq!(limiter.requests.lock().await.entries.capacity(), busy);
 
-        time::sleep(Duration::from_secs(3)).await;
+        limiter.shrink_to_fit().await;
+        assert_eq!(limiter.requests.lock().await.entries.capacity(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refund_tracked_out_of_order() {
+        let limiter = SlidingWindowCounter::new(10, Duration::from_secs(10));
+
+        let first = limiter.consume_tracked(2).await.unwrap();
+        time::advance(Duration::from_secs(1)).await;
+        let second = limiter.consume_tracked(3).await.unwrap();
+        time::advance(Duration::from_secs(1)).await;
+        let third = limiter.consume_tracked(4).await.unwrap();
+        assert_eq!(limiter.current_usage().await, 9);
+        assert_eq!(limiter.consume_tracked(2).await, None);
+
+        assert_eq!(limiter.refund_entry(second).await, true);
+        assert_eq!(limiter.current_usage().await, 6);
+        assert_eq!(limiter.refund_entry(first).await, true);
+        assert_eq!(limiter.current_usage().await, 4);
+
+        // the remaining requests still expire on their own schedule
+        time::advance(Duration::from_secs(11)).await;
+        assert_eq!(limiter.current_usage().await, 0);
+        assert_eq!(limiter.refund_entry(third).await, false);
+        assert_eq!(limiter.current_usage().await, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refund_expired_is_noop() {
+        let limiter = SlidingWindowCounter::new(3, Duration::from_secs(1));
 
+        let expired = limiter.consume_tracked(1).await.unwrap();
+        time::advance(Duration::from_secs(2)).await;
+        let _ = limiter.consume_tracked(3).await.unwrap();
+
+        assert_eq!(limiter.refund_entry(expired).await, false);
+        assert_eq!(limiter.current_usage().await, 3);
+        assert_eq!(limiter.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refund_within_sub_bucket() {
+        let limiter = SlidingWindowCounter::new(10, Duration::from_secs(10)).with_sub_buckets(10);
+
+        let first = limiter.consume_tracked(2).await.unwrap();
+        time::advance(Duration::from_millis(100)).await;
+        let second = limiter.consume_tracked(3).await.unwrap();
+        assert_eq!(limiter.current_usage().await, 5);
+
+        assert_eq!(limiter.refund_entry(first).await, true);
+        assert_eq!(limiter.current_usage().await, 3);
+        assert_eq!(limiter.refund_entry(second).await, true);
+        assert_eq!(limiter.current_usage().await, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refund_entry_after_partial_refund() {
+        let limiter = SlidingWindowCounter::new(10, Duration::from_secs(10));
+
+        let first = limiter.consume_tracked(2).await.unwrap();
+        let second = limiter.consume_tracked(3).await.unwrap();
+        crate::SharedRateLimiter::refund(&limiter, 1).await;
+        assert_eq!(limiter.current_usage().await, 4);
+
+        // the batches were merged, so it is unknown which of them lost a request
+        assert_eq!(limiter.refund_entry(first).await, false);
+        assert_eq!(limiter.refund_entry(second).await, false);
+        assert_eq!(limiter.current_usage().await, 4);
+
+        // batches recorded afterwards can still be given back
+        let third = limiter.consume_tracked(2).await.unwrap();
+        assert_eq!(limiter.refund_entry(third).await, true);
+        assert_eq!(limiter.current_usage().await, 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_entries_bounds_memory() {
+        let limiter =
+            SlidingWindowCounter::new(10_000, Duration::from_secs(10)).with_max_entries(16);
+
+        for _ in 0..10_000 {
+            assert_eq!(limiter.try_consume().await, true);
+            time::advance(Duration::from_micros(100)).await;
+        }
         assert_eq!(
             limiter.try_consume().await,
-            true,
-            "Request should be allowed after window expiration"
+            false,
+            "Request should be rate-limited"
         );
+
+        let requests = limiter.requests.lock().await;
+        assert_eq!(requests.entries.len(), 16);
+        assert_eq!(requests.total, 10_000);
     }
 
-    #[tokio::test]
-    async fn test_mixed_behavior() {
-        let mut limiter = SlidingWindowCounter::new(5, Duration::from_secs(5));
+    #[tokio::test(start_paused = true)]
+    async fn test_max_entries_never_over_admits() {
+        let limiter = SlidingWindowCounter::new(4, Duration::from_secs(4)).with_max_entries(2);
 
         for _ in 0..4 {
-            assert_eq!(
-                limiter.try_consume().await,
-                true,
-                "Request should be allowed"
-            );
+            assert_eq!(limiter.try_consume().await, true);
+            time::advance(Duration::from_secs(1)).await;
         }
 
+        // The exact window would let the first request expire here, the capped one may not
+        time::advance(Duration::from_millis(1)).await;
+        let mut admitted = 0;
+        while limiter.try_consume().await {
+            admitted += 1;
+        }
         assert_eq!(
-            limiter.try_consume().await,
+            admitted <= 1,
             true,
-            "Request should be allowed"
+            "Capped log must not admit extra requests"
+        );
+
+        time::advance(Duration::from_secs(4)).await;
+        for _ in 0..4 {
+            assert_eq!(limiter.try_consume().await, true);
+        }
+        assert_eq!(limiter.try_consume().await, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_same_instant_requests_share_an_entry() {
+        let limiter = SlidingWindowCounter::new(5, Duration::from_secs(1));
+
+        for _ in 0..5 {
+            assert_eq!(limiter.try_consume().await, true);
+        }
+
+        let requests = limiter.requests.lock().await;
+        assert_eq!(requests.entries.len(), 1);
+        assert_eq!(requests.total, 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_observed_rate() {
+        let limiter = SlidingWindowCounter::new(100, Duration::from_secs(2));
+        for _ in 0..30 {
+            time::advance(Duration::from_millis(100)).await;
+            assert_eq!(limiter.try_consume().await, true);
+        }
+
+        assert_eq!(limiter.observed_rate(Duration::from_secs(1)).await, 10.0);
+        assert_eq!(
+            limiter.observed_rate(Duration::from_secs(10)).await,
+            10.0,
+            "Period should be capped at the window duration"
         );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_observed_rate_and_peak_follow_pattern() {
+        let limiter = SlidingWindowCounter::new(100, Duration::from_secs(2));
+        assert_eq!(limiter.try_consume_n(6).await, true);
+        for _ in 0..16 {
+            time::advance(Duration::from_millis(250)).await;
+            assert_eq!(limiter.try_consume().await, true);
+        }
+
+        // the burst counted until exactly one window later, along with 8 steady requests
+        assert_eq!(limiter.peak_usage(), 14);
+        let rate = limiter.observed_rate(limiter.window_duration()).await;
+        assert!((rate - 4.0).abs() < 1e-9, "{}", rate);
+        let rate = limiter.observed_rate(Duration::from_millis(600)).await;
+        assert!((rate - 5.0).abs() < 1e-9, "{}", rate);
+
+        time::advance(Duration::from_secs(10)).await;
+        assert_eq!(limiter.current_usage().await, 0);
+        assert_eq!(limiter.observed_rate(Duration::from_secs(2)).await, 0.0);
+        assert_eq!(limiter.peak_usage(), 14);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_check_reports_window_state() {
+        let limiter = SlidingWindowCounter::new(3, Duration::from_secs(10));
+        let expiry = Duration::from_secs(10) + Duration::from_nanos(1);
+
+        assert!(limiter.check(1).await.is_allowed());
+        time::advance(Duration::from_secs(4)).await;
 
         assert_eq!(
-            limiter.try_consume().await,
-            false,
-            "Request should be rate-limited"
+            limiter.check(1).await,
+            Decision::Allowed {
+                limit: 3,
+                remaining: 1,
+                reset_in: expiry,
+            }
         );
+        assert_eq!(
+            limiter.check(2).await,
+            Decision::Denied {
+                limit: 3,
+                remaining: 1,
+                reset_in: expiry,
+                retry_after: Some(expiry - Duration::from_secs(4)),
+            }
+        );
+        assert_eq!(limiter.check(4).await.retry_after(), None);
 
-        time::sleep(Duration::from_secs(6)).await;
+        time::advance(expiry - Duration::from_secs(4)).await;
+        assert!(limiter.check(2).await.is_allowed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_check_flags_soft_limit() {
+        let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10)).with_soft_limit(3);
+        assert_eq!(limiter.soft_limit(), Some(3));
+
+        assert_eq!(limiter.check(3).await.is_soft_exceeded(), false);
+        let decision = limiter.check(1).await;
+        assert_eq!(decision.is_allowed(), true);
+        assert_eq!(decision.is_soft_exceeded(), true);
+        assert_eq!(decision.remaining(), 1);
+        assert_eq!(limiter.check(2).await.is_allowed(), false);
+
+        // The flag clears once the requests have left the window
+        time::advance(Duration::from_secs(11)).await;
+        assert_eq!(limiter.check(3).await.is_soft_exceeded(), false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_debug_shows_usage_and_entries() {
+        let limiter = SlidingWindowCounter::new(5, Duration::from_secs(10));
+        assert_eq!(limiter.try_consume_n(2).await, true);
+        time::advance(Duration::from_secs(1)).await;
+        assert_eq!(limiter.try_consume().await, true);
 
+        let debug = format!("{:?}", limiter);
         assert_eq!(
-            limiter.try_consume().await,
+            debug.starts_with("SlidingWindowCounter {"),
             true,
-            "Request should be allowed after window expiration"
+            "{}",
+            debug
         );
+        assert_eq!(debug.contains("limit: 5"), true, "{}", debug);
+        assert_eq!(debug.contains("usage: 3"), true, "{}", debug);
+        assert_eq!(debug.contains("stored_entries: 2"), true, "{}", debug);
+
+        // Formatting counts expired requests out without evicting them
+        time::advance(Duration::from_secs(10)).await;
+        let debug = format!("{:?}", limiter);
+        assert_eq!(debug.contains("usage: 1"), true, "{}", debug);
+        assert_eq!(debug.contains("stored_entries: 2"), true, "{}", debug);
+
+        let _requests = limiter.requests.lock().await;
+        let debug = format!("{:?}", limiter);
+        assert_eq!(debug.contains("usage: <locked>"), true, "{}", debug);
     }
 
-    #[tokio::test]
-    async fn test_concurrent_access() {
-        let limiter = Arc::new(Mutex::new(SlidingWindowCounter::new(
-            5,
-            Duration::from_secs(10),
-        )));
+    #[tokio::test(start_paused = true)]
+    async fn test_sleeping_retry_after_admits_at_boundary() {
+        for boundary in [Boundary::Inclusive, Boundary::Exclusive] {
+            let limiter =
+                SlidingWindowCounter::new(2, Duration::from_millis(700)).with_boundary(boundary);
+            assert_eq!(limiter.try_consume().await, true);
+            time::advance(Duration::from_millis(300)).await;
+            assert_eq!(limiter.try_consume().await, true);
 
-        let mut handles = vec![];
+            let retry_after = limiter.check(1).await.retry_after().unwrap();
+            assert_eq!(limiter.next_available_in().await, Some(retry_after));
+            time::advance(retry_after - Duration::from_nanos(1)).await;
+            assert_eq!(limiter.would_allow().await, false, "{:?}", boundary);
+            assert_eq!(limiter.try_consume().await, false, "{:?}", boundary);
 
-        for _ in 0..10 {
-            let limiter_clone = limiter.clone();
-            let handle = tokio::spawn(async move {
-                let mut limiter = limiter_clone.lock().await;
-                limiter.try_consume().await
-            });
-            handles.push(handle);
+            time::advance(Duration::from_nanos(1)).await;
+            assert_eq!(limiter.try_consume().await, true, "{:?}", boundary);
+            assert_eq!(limiter.current_usage().await, 2);
         }
+    }
 
-        let mut allowed_count = 0;
-        for handle in handles {
-            if handle.await.unwrap() {
-                allowed_count += 1;
+    proptest! {
+        /// A request stops counting exactly when its window ends, as set by the boundary.
+        #[test]
+        fn prop_requests_expire_exactly_at_boundary(
+            window_nanos in 1u64..10_000_000_000,
+            offset_nanos in 0u64..10_000_000_000,
+            exclusive in any::<bool>(),
+        ) {
+            let window = Duration::from_nanos(window_nanos);
+            let boundary = if exclusive { Boundary::Exclusive } else { Boundary::Inclusive };
+            let admitted = paused_runtime()
+                .block_on(async {
+                    let limiter = SlidingWindowCounter::new(1, window).with_boundary(boundary);
+                    let at = Instant::now() + Duration::from_nanos(offset_nanos);
+                    let tick = Duration::from_nanos(1);
+                    [
+                        limiter.try_consume_at(at, 1).await,
+                        limiter.try_consume_at(at + window - tick, 1).await,
+                        limiter.try_consume_at(at + window, 1).await,
+                        limiter.try_consume_at(at + window + tick, 1).await,
+                    ]
+                });
+
+            // exactly one window later the request only counts with an inclusive boundary
+            prop_assert_eq!(admitted, [true, false, exclusive, !exclusive]);
+        }
+
+        /// Any window-long period ending in an admission holds at most the limit.
+        #[test]
+        fn prop_admits_at_most_limit_per_sliding_window(
+            limit in 1u32..20,
+            window_ms in 1u64..3_000,
+            sub_buckets in prop::option::of(0u32..12),
+            steps in prop::collection::vec((0u32..25, 0u64..1_000), 1..60),
+        ) {
+            let window = Duration::from_millis(window_ms);
+            let admitted = paused_runtime()
+                .block_on(async {
+                    let start = Instant::now();
+                    let mut limiter = SlidingWindowCounter::new(limit, window);
+                    if let Some(buckets) = sub_buckets {
+                        limiter = limiter.with_sub_buckets(buckets);
+                    }
+                    let mut admitted = Vec::new();
+                    for (amount, wait) in steps {
+                        if limiter.try_consume_n(amount).await {
+                            admitted.push((start.elapsed(), amount));
+                        }
+                        time::advance(Duration::from_millis(wait)).await;
+                    }
+                    admitted
+                });
+
+            for (j, (to, _)) in admitted.iter().enumerate() {
+                let total: u32 = admitted[..=j]
+                    .iter()
+                    .filter(|(at, _)| *to - *at <= window)
+                    .map(|(_, amount)| amount)
+                    .sum();
+                prop_assert!(total <= limit);
             }
         }
+    }
 
-        assert_eq!(
-            allowed_count, 5,
-            "Only 5 requests should be allowed due to the limit"
-        );
+    #[test]
+    fn test_try_new_validates_window_duration() {
+        for limit in [0, 100] {
+            assert_eq!(
+                SlidingWindowCounter::try_new(limit, Duration::ZERO).err(),
+                Some(LimitrError::InvalidConfig(InvalidConfigReason::ZeroWindow))
+            );
+            assert_eq!(
+                SlidingWindowCounter::try_new(limit, Duration::MAX).err(),
+                Some(LimitrError::InvalidConfig(
+                    InvalidConfigReason::WindowTooLarge
+                ))
+            );
+        }
+
+        // A zero limit denies everything, but can still be raised later
+        assert!(SlidingWindowCounter::try_new(0, Duration::from_secs(1)).is_ok());
     }
 }